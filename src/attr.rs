@@ -7,14 +7,15 @@ use rustc_span::{symbol::sym, BytePos, Span, Symbol, DUMMY_SP};
 use self::doc_comment::DocCommentFormatter;
 use crate::comment::{contains_comment, rewrite_doc_comment, CommentStyle};
 use crate::config::lists::*;
-use crate::config::IndentStyle;
+use crate::config::{IndentStyle, Version};
 use crate::expr::rewrite_literal;
 use crate::lists::{definitive_tactic, itemize_list, write_list, ListFormatting, Separator};
 use crate::overflow;
 use crate::rewrite::{Rewrite, RewriteContext};
 use crate::shape::Shape;
+use crate::string::{rewrite_string, StringFormat};
 use crate::types::{rewrite_path, PathContext};
-use crate::utils::{count_newlines, mk_sp};
+use crate::utils::{count_newlines, mk_sp, unicode_str_width};
 
 mod doc_comment;
 
@@ -80,7 +81,7 @@ fn argument_shape(
     shape: Shape,
     context: &RewriteContext<'_>,
 ) -> Option<Shape> {
-    match context.config.indent_style() {
+    match context.indent_style() {
         IndentStyle::Block => {
             if combine {
                 shape.offset_left(left)
@@ -229,6 +230,12 @@ impl Rewrite for ast::MetaItem {
             ast::MetaItemKind::Word => {
                 rewrite_path(context, PathContext::Type, None, &self.path, shape)?
             }
+            // 1 = "]"
+            ast::MetaItemKind::List(..)
+                if context.config.normalize_cfg() && self.has_name(sym::cfg) =>
+            {
+                rewrite_cfg_meta(context, self, shape.sub_width(1)?)?
+            }
             ast::MetaItemKind::List(ref list) => {
                 let path = rewrite_path(context, PathContext::Type, None, &self.path, shape)?;
                 let has_trailing_comma = crate::expr::span_ends_with_comma(context, self.span);
@@ -247,6 +254,16 @@ impl Rewrite for ast::MetaItem {
                     }),
                 )?
             }
+            ast::MetaItemKind::NameValue(ref literal) if self.has_name(sym::doc) => {
+                let path = rewrite_path(context, PathContext::Type, None, &self.path, shape)?;
+                // 3 = ` = `
+                let lit_shape = shape.shrink_left(path.len() + 3)?;
+                format!(
+                    "{} = {}",
+                    path,
+                    rewrite_doc_attr_value(context, literal, lit_shape)
+                )
+            }
             ast::MetaItemKind::NameValue(ref literal) => {
                 let path = rewrite_path(context, PathContext::Type, None, &self.path, shape)?;
                 // 3 = ` = `
@@ -265,6 +282,86 @@ impl Rewrite for ast::MetaItem {
     }
 }
 
+/// Rewrites the value of a doc attribute. A string literal exceeding the width is only wrapped
+/// when `format_doc_attributes` is set, with escaped newlines so that the documentation does not
+/// change. Other literals are left as they are.
+fn rewrite_doc_attr_value(
+    context: &RewriteContext<'_>,
+    literal: &ast::Lit,
+    shape: Shape,
+) -> String {
+    let snippet = context.snippet(literal.span);
+    match literal.kind {
+        ast::LitKind::Str(_, ast::StrStyle::Cooked)
+            if context.config.format_doc_attributes()
+                && unicode_str_width(snippet) > shape.width =>
+        {
+            rewrite_string(
+                &snippet[1..snippet.len() - 1],
+                &StringFormat::new(shape.visual_indent(0), context.config),
+                shape.width.saturating_sub(2),
+            )
+            .unwrap_or_else(|| snippet.to_owned())
+        }
+        _ => snippet.to_owned(),
+    }
+}
+
+/// Rewrites a `cfg(..)` predicate, sorting and deduplicating the predicates of `any(..)` and
+/// `all(..)` at any depth.
+fn rewrite_cfg_meta(
+    context: &RewriteContext<'_>,
+    meta: &ast::MetaItem,
+    shape: Shape,
+) -> Option<String> {
+    let is_cfg_operator = [sym::cfg, sym::any, sym::all, sym::not]
+        .iter()
+        .any(|&name| meta.has_name(name));
+    let list = match meta.kind {
+        ast::MetaItemKind::List(ref list) if is_cfg_operator => list,
+        _ => return meta.rewrite(context, shape),
+    };
+    let name = rewrite_path(context, PathContext::Type, None, &meta.path, shape)?;
+
+    // A single predicate, as in `cfg(..)` and `not(..)`, is combined with its parent.
+    let combine = list.len() == 1;
+    // 1 = "(", 1 = ")"
+    let nested_shape = argument_shape(name.len() + 1, 1, combine, shape, context)?;
+    let mut predicates = list
+        .iter()
+        .map(|item| match item {
+            ast::NestedMetaItem::MetaItem(ref meta) => {
+                rewrite_cfg_meta(context, meta, nested_shape)
+            }
+            ast::NestedMetaItem::Literal(..) => item.rewrite(context, nested_shape),
+        })
+        .collect::<Option<Vec<_>>>()?;
+    if meta.has_name(sym::any) || meta.has_name(sym::all) {
+        predicates.sort();
+        predicates.dedup();
+    }
+
+    let item_str = format_arg_list(
+        predicates.iter(),
+        |_| DUMMY_SP.lo(),
+        |_| DUMMY_SP.hi(),
+        |predicate| Some((*predicate).clone()),
+        DUMMY_SP,
+        context,
+        nested_shape,
+        shape.offset_left(name.len() + 1)?.sub_width(1)?,
+        None,
+        combine,
+    )?;
+    let mut result = format!("{}({}", name, item_str);
+    if item_str.starts_with('\n') {
+        result.push(',');
+        result.push_str(&shape.indent.to_string_with_newline(context.config));
+    }
+    result.push(')');
+    Some(result)
+}
+
 fn format_arg_list<I, T, F1, F2, F3>(
     list: I,
     get_lo: F1,
@@ -309,7 +406,7 @@ where
     let item_str = write_list(&item_vec, &fmt)?;
 
     let one_line_budget = one_line_shape.width;
-    if context.config.indent_style() == IndentStyle::Visual
+    if context.indent_style() == IndentStyle::Visual
         || combine
         || (!item_str.contains('\n') && item_str.len() <= one_line_budget)
     {
@@ -364,12 +461,71 @@ impl Rewrite for ast::Attribute {
                         .map_or_else(|| snippet.to_owned(), |rw| format!("{}[{}]", prefix, rw)),
                 )
             } else {
+                // E.g. `#[doc = include_str!("README.md")]`, whose value is not a literal.
                 Some(snippet.to_owned())
             }
         }
     }
 }
 
+/// Rewrites the inner attributes of a module with the feature gates before the other attributes,
+/// as configured by `reorder_inner_attributes`. Leading doc comments stay first. Returns `None` if
+/// the attributes are in that order already, or if reordering them would move doc comments or
+/// comments.
+pub(crate) fn rewrite_reordered_inner_attrs(
+    context: &RewriteContext<'_>,
+    attrs: &[ast::Attribute],
+    shape: Shape,
+) -> Option<String> {
+    let is_feature = |attr: &&ast::Attribute| attr.has_name(sym::feature);
+    let docs_len = attrs
+        .iter()
+        .take_while(|attr| attr.is_doc_comment())
+        .count();
+    let (docs, rest) = attrs.split_at(docs_len);
+    let is_ordered = rest
+        .iter()
+        .skip_while(is_feature)
+        .all(|attr| !is_feature(&attr));
+    if is_ordered || rest.iter().any(ast::Attribute::is_doc_comment) {
+        return None;
+    }
+    let gaps: Vec<_> = attrs
+        .windows(2)
+        .map(|pair| context.snippet(mk_sp(pair[0].span.hi(), pair[1].span.lo())))
+        .collect();
+    if gaps.iter().any(|gap| contains_comment(gap)) {
+        return None;
+    }
+    let is_blank_line = |gap: &&str| count_newlines(gap) > 1;
+    let newline = shape.indent.to_string_with_newline(context.config);
+
+    let mut result = String::new();
+    if !docs.is_empty() {
+        result.push_str(&docs.rewrite(context, shape)?);
+        if is_blank_line(&gaps[docs_len - 1]) {
+            result.push('\n');
+        }
+        result.push_str(&newline);
+    }
+    let (features, others): (Vec<_>, Vec<_>) = rest.iter().partition(is_feature);
+    for (i, attr) in features.iter().enumerate() {
+        if i > 0 {
+            result.push_str(&newline);
+        }
+        result.push_str(&attr.rewrite(context, shape)?);
+    }
+    // Keep the feature gates apart from the other attributes if any attributes were apart.
+    if gaps[docs_len..].iter().any(is_blank_line) {
+        result.push('\n');
+    }
+    for attr in others {
+        result.push_str(&newline);
+        result.push_str(&attr.rewrite(context, shape)?);
+    }
+    Some(result)
+}
+
 impl<'a> Rewrite for [ast::Attribute] {
     fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String> {
         if self.is_empty() {
@@ -421,14 +577,23 @@ impl<'a> Rewrite for [ast::Attribute] {
                 continue;
             }
 
-            // Handle derives if we will merge them.
-            if context.config.merge_derives() && is_derive(&attrs[0]) {
-                let derives = take_while_with_pred(context, attrs, is_derive);
-                let derive_spans: Vec<_> = derives
+            // Handle derives if we will merge or sort them.
+            if (context.config.merge_derives() || context.config.sort_derives())
+                && is_derive(&attrs[0])
+            {
+                let derives = if context.config.merge_derives() {
+                    take_while_with_pred(context, attrs, is_derive)
+                } else {
+                    &attrs[..1]
+                };
+                let mut derive_spans: Vec<_> = derives
                     .iter()
                     .filter_map(get_derive_spans)
                     .flatten()
                     .collect();
+                if context.config.sort_derives() {
+                    derive_spans.sort_by_key(|sp| context.snippet(*sp));
+                }
                 let derive_str =
                     format_derive(&derive_spans, attr_prefix(&attrs[0]), shape, context)?;
                 result.push_str(&derive_str);
@@ -478,7 +643,12 @@ impl<'a> Rewrite for [ast::Attribute] {
                 )?;
                 result.push_str(&comment);
                 if let Some(next) = attrs.get(1) {
-                    if next.is_doc_comment() {
+                    // Blank lines separate groups of inner attributes, e.g. the feature gates of a
+                    // crate from its lints.
+                    let keeps_blank_lines = next.is_doc_comment()
+                        || (next.style == ast::AttrStyle::Inner
+                            && context.config.version() == Version::Two);
+                    if keeps_blank_lines {
                         let snippet = context.snippet(missing_span);
                         let (_, mlb) = has_newlines_before_after_comment(snippet);
                         result.push_str(&mlb);
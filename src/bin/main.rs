@@ -1,34 +1,66 @@
 use anyhow::{format_err, Result};
 use env_logger;
 use io::Error as IoError;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use rustfmt_nightly as rustfmt;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
-use std::fs::File;
-use std::io::{self, stdout, Read, Write};
+use std::fs::{self, File};
+use std::io::{self, stdout, BufRead, Read, Write};
+use std::panic::{self, AssertUnwindSafe};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use getopts::{Matches, Options};
+use ignore::WalkBuilder;
 
 use crate::rustfmt::{
-    load_config, CliOptions, Color, Config, Edition, EmitMode, FileLines, FileName,
-    FormatReportFormatterBuilder, Input, Session, Verbosity,
+    load_config, CliOptions, Color, Config, DiffStyle, Edition, EmitMode, ErrorKind, FileLines,
+    FileName, FileStats, FormatReport, FormatReportFormatterBuilder, Input, ModuleTraversal,
+    RunStats, Session, SymlinkPolicy, Verbosity,
 };
 
+// Exit codes of the rustfmt binary. When several apply, the highest one is used.
+
+/// The input is formatted correctly, or was formatted successfully.
+const SUCCESS: i32 = 0;
+/// Formatting is required (`--check` only).
+const DIFF_FOUND: i32 = 1;
+/// Rustfmt could not do its job, e.g. because of invalid arguments or an io error.
+const OPERATIONAL_ERROR: i32 = 2;
+/// The input could not be parsed.
+const PARSE_ERROR: i32 = 3;
+/// Rustfmt itself failed, e.g. it panicked.
+const INTERNAL_ERROR: i32 = 4;
+
 fn main() {
     env_logger::init();
     let opts = make_opts();
 
-    let exit_code = match execute(&opts) {
-        Ok(code) => code,
-        Err(e) => {
-            eprintln!("{}", e.to_string());
-            1
+    // The error format is needed to report errors that occur while parsing the
+    // other options.
+    let error_format = opts
+        .parse(env::args().skip(1))
+        .ok()
+        .and_then(|matches| matches.opt_str("error-format"))
+        .and_then(|error_format| ErrorFormat::from_str(&error_format).ok())
+        .unwrap_or_default();
+    if error_format == ErrorFormat::Json {
+        panic::set_hook(Box::new(|info| {
+            report_error(ErrorFormat::Json, "internal", &info.to_string())
+        }));
+    }
+
+    let exit_code = match panic::catch_unwind(AssertUnwindSafe(|| execute(&opts))) {
+        Ok(Ok(code)) => code,
+        Ok(Err(e)) => {
+            report_error(error_format, "error", &e.to_string());
+            OPERATIONAL_ERROR
         }
+        Err(_) => INTERNAL_ERROR,
     };
     // Make sure standard output is flushed before we exit.
     std::io::stdout().flush().unwrap();
@@ -53,10 +85,16 @@ enum Operation {
     Version,
     /// Output default config to a file, or stdout if None
     ConfigOutputDefault { path: Option<String> },
+    /// Output a JSON description of every config option to a file, or stdout if None
+    ConfigOutputSchema { path: Option<String> },
     /// Output current config (as if formatting to a file) to stdout
     ConfigOutputCurrent { path: Option<String> },
+    /// Output the options that differ from the defaults when formatting a file to stdout
+    ConfigOutputMinimal { path: String },
     /// No file specified, read from stdin
     Stdin { input: String },
+    /// Format the requests read from stdin one by one, see `format_stdin_batch`
+    StdinBatch,
 }
 
 /// Rustfmt operations errors.
@@ -71,6 +109,9 @@ pub enum OperationError {
     /// Attempt to generate a minimal config from standard input.
     #[error("The `--print-config=minimal` option doesn't work with standard input.")]
     MinimalPathWithStdin,
+    /// A path without a parent directory to look for config files in.
+    #[error("`{0}` has no parent directory to look for config files in.")]
+    NoParentDirectory(PathBuf),
     /// An io error during reading or writing.
     #[error("{0}")]
     IoError(IoError),
@@ -90,6 +131,106 @@ impl From<IoError> for OperationError {
     }
 }
 
+/// How error messages are printed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ErrorFormat {
+    /// Plain text messages.
+    Human,
+    /// One JSON object per message.
+    Json,
+}
+
+impl Default for ErrorFormat {
+    fn default() -> ErrorFormat {
+        ErrorFormat::Human
+    }
+}
+
+impl FromStr for ErrorFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<ErrorFormat> {
+        match s {
+            "human" => Ok(ErrorFormat::Human),
+            "json" => Ok(ErrorFormat::Json),
+            _ => Err(format_err!("Invalid value for `--error-format`")),
+        }
+    }
+}
+
+/// An error message in the JSON error format.
+#[derive(Serialize)]
+struct JsonMessage<'a> {
+    level: &'a str,
+    message: &'a str,
+}
+
+/// Prints a message to stderr in the given error format. `level` is one of `error`,
+/// `warning` or `internal`, and is only part of the JSON output.
+fn report_error(error_format: ErrorFormat, level: &str, message: &str) {
+    match error_format {
+        ErrorFormat::Human => eprintln!("{}", message),
+        ErrorFormat::Json => {
+            let message = JsonMessage { level, message };
+            match serde_json::to_string(&message) {
+                Ok(json) => eprintln!("{}", json),
+                Err(_) => eprintln!("{}", message.message),
+            }
+        }
+    }
+}
+
+/// A request to format a file with `--stdin-batch`, read as a line of JSON.
+#[derive(Deserialize)]
+struct BatchRequest {
+    path: PathBuf,
+    content: String,
+}
+
+/// The response to a `BatchRequest`, written as a line of JSON.
+#[derive(Serialize)]
+struct BatchResponse {
+    path: PathBuf,
+    /// The formatted content, or `None` if the content could not be formatted.
+    formatted: Option<String>,
+    /// The warnings and errors reported while formatting.
+    messages: Vec<String>,
+}
+
+/// The statistics printed by `--timings json`, as one JSON object.
+#[derive(Default, Serialize)]
+struct Timings {
+    files: Vec<FileTimings>,
+    /// The sums of the statistics of all the files.
+    total: RunStats,
+}
+
+#[derive(Serialize)]
+struct FileTimings {
+    name: FileName,
+    #[serde(flatten)]
+    stats: FileStats,
+}
+
+impl Timings {
+    fn record(&mut self, report: &FormatReport) {
+        for (name, stats) in report.file_stats() {
+            self.files.push(FileTimings { name, stats });
+        }
+        let stats = report.run_stats();
+        self.total.parse_time += stats.parse_time;
+        self.total.format_time += stats.format_time;
+        self.total.skipped_items += stats.skipped_items;
+        self.total.items_formatted += stats.items_formatted;
+        self.total.rewrite_fallbacks += stats.rewrite_fallbacks;
+    }
+
+    fn print(&self) -> Result<()> {
+        eprintln!("{}", serde_json::to_string(self)?);
+        Ok(())
+    }
+}
+
 /// Arguments to `--help`
 enum HelpOp {
     None,
@@ -106,14 +247,28 @@ fn make_opts() -> Options {
         "Run in 'check' mode. Exits with 0 if input is formatted correctly. Exits \
          with 1 and prints a diff if formatting is required.",
     );
+    opts.optopt(
+        "",
+        "error-format",
+        "How to print error messages. Rustfmt exits with 2 on operational errors, \
+         3 on parse errors and 4 on internal errors.",
+        "[human|json]",
+    );
     let is_nightly = is_nightly();
     let emit_opts = if is_nightly {
-        "[files|stdout|coverage|checkstyle|json]"
+        "[files|stdout|coverage|checkstyle|json|modified-lines|stats|stats-json]"
     } else {
         "[files|stdout]"
     };
     opts.optopt("", "emit", "What data to emit and how", emit_opts);
     opts.optflag("", "backup", "Backup any modified files.");
+    opts.optopt(
+        "",
+        "out-dir",
+        "Write the formatted files to this directory, mirroring the input tree, instead of \
+         overwriting them. Only valid with `--emit files`",
+        "[Path]",
+    );
     opts.optopt(
         "",
         "config-path",
@@ -122,19 +277,68 @@ fn make_opts() -> Options {
         "[Path for the configuration file]",
     );
     opts.optopt("", "edition", "Rust edition to use", "[2015|2018]");
+    opts.optopt(
+        "",
+        "manifest-path",
+        "Format the targets of the package of the given Cargo.toml, or of every member \
+         of a virtual workspace. Defaults to the nearest Cargo.toml when `--package` is used.",
+        "[Path to Cargo.toml]",
+    );
+    opts.optmulti(
+        "p",
+        "package",
+        "Format the targets (lib, bins, tests, examples and benches) of the given \
+         workspace member",
+        "<package>",
+    );
     opts.optopt(
         "",
         "color",
         "Use colored output (if supported)",
         "[always|never|auto]",
     );
+    opts.optopt(
+        "",
+        "diff-style",
+        "How the diffs printed by --check are laid out",
+        "[unified|side-by-side]",
+    );
+    opts.optopt(
+        "",
+        "diff-context",
+        "Number of unchanged lines printed around the changes in the diffs printed by --check",
+        "N",
+    );
+    opts.optflag(
+        "",
+        "diff-summary",
+        "With --check, print `path: N lines differ` for each misformatted file instead of its \
+         diff. With `--emit json` or `--emit checkstyle`, include the counts in the output",
+    );
     opts.optopt(
         "",
         "print-config",
         "Dumps a default or minimal config to PATH. A minimal config is the \
          subset of the current config file used for formatting the current program. \
-         `current` writes to stdout current config as if formatting the file at PATH.",
-        "[default|minimal|current] PATH",
+         `current` writes to stdout current config as if formatting the file at PATH. \
+         `minimal` with a Rust file at PATH and no files to format writes to stdout \
+         the options that differ from the defaults when formatting that file. `schema` dumps a JSON \
+         description of every option to PATH.",
+        "[default|minimal|current|schema] PATH",
+    );
+    opts.optopt(
+        "",
+        "files-from",
+        "Format the files listed in the given file, or in stdin if it is `-`, in addition to the \
+         files given as arguments. The paths are separated by NULs if the list has any, e.g. \
+         the output of `git diff --name-only -z`, and by newlines otherwise",
+        "<list>",
+    );
+    opts.optflag(
+        "",
+        "crate-roots",
+        "Format the given files as the crate roots of one package, e.g. its library, tests and \
+         examples, in one pass. Modules shared by several roots are formatted once",
     );
     opts.optflag(
         "l",
@@ -142,6 +346,13 @@ fn make_opts() -> Options {
         "Prints the names of mismatched files that were formatted. Prints the names of \
          files that would be formated when used with `--check` mode. ",
     );
+    opts.optopt(
+        "",
+        "explain",
+        "Prints how many lines of each file would change if the given option was set, \
+         without formatting anything",
+        "key=val",
+    );
     opts.optmulti(
         "",
         "config",
@@ -175,8 +386,32 @@ fn make_opts() -> Options {
         );
     }
 
+    opts.optflag(
+        "",
+        "stdin-batch",
+        "Format many files over one process: read lines of JSON requests \
+         `{\"path\": ..., \"content\": ...}` from stdin, and write a line of JSON with the \
+         formatted content for each to stdout",
+    );
+    opts.optopt(
+        "",
+        "timings",
+        "Print the time spent formatting each file and the whole run, and the number of items \
+         formatted and left unformatted, to stderr",
+        "[json]",
+    );
+    opts.optflag(
+        "",
+        "trace-decisions",
+        "Print the layout chosen for every list, chain and unformatted statement or item to \
+         stderr, as lines of JSON",
+    );
     opts.optflag("v", "verbose", "Print verbose output");
-    opts.optflag("q", "quiet", "Print less output");
+    opts.optflag(
+        "q",
+        "quiet",
+        "Print less output. With `--check`, only the names of misformatted files are printed",
+    );
     opts.optflag("V", "version", "Show version information");
     opts.optflagopt(
         "h",
@@ -196,6 +431,10 @@ fn is_nightly() -> bool {
 fn execute(opts: &Options) -> Result<i32> {
     let matches = opts.parse(env::args().skip(1))?;
     let options = GetOptsOptions::from_matches(&matches)?;
+    // Tracing is enabled for the whole process, before anything is formatted.
+    if matches.opt_present("trace-decisions") {
+        env::set_var("RUSTFMT_TRACE", "1");
+    }
 
     match determine_operation(&matches)? {
         Operation::Help(HelpOp::None) => {
@@ -224,6 +463,15 @@ fn execute(opts: &Options) -> Result<i32> {
             }
             Ok(0)
         }
+        Operation::ConfigOutputSchema { path } => {
+            if let Some(path) = path {
+                let mut file = File::create(path)?;
+                Config::print_schema(&mut file);
+            } else {
+                Config::print_schema(&mut stdout());
+            }
+            Ok(0)
+        }
         Operation::ConfigOutputCurrent { path } => {
             let path = match path {
                 Some(path) => path,
@@ -233,13 +481,25 @@ fn execute(opts: &Options) -> Result<i32> {
             let file = PathBuf::from(path);
             let file = file.canonicalize().unwrap_or(file);
 
-            let (config, _) = load_config(Some(file.parent().unwrap()), Some(options.clone()))?;
+            let (config, _) = load_config(Some(parent_dir(&file)?), Some(options.clone()))?;
             let toml = config.all_options().to_toml()?;
             io::stdout().write_all(toml.as_bytes())?;
 
             Ok(0)
         }
+        Operation::ConfigOutputMinimal { path } => {
+            let file = PathBuf::from(path);
+            let file = file.canonicalize().unwrap_or(file);
+
+            let (config, _) = load_config(Some(parent_dir(&file)?), Some(options.clone()))?;
+            let toml = config.non_default_options().to_toml()?;
+            io::stdout().write_all(toml.as_bytes())?;
+
+            Ok(0)
+        }
         Operation::Stdin { input } => format_string(input, options),
+        Operation::StdinBatch => format_stdin_batch(options),
+        Operation::Format { files, .. } if options.explain.is_some() => explain(files, &options),
         Operation::Format {
             files,
             minimal_config_path,
@@ -274,23 +534,145 @@ fn format_string(input: String, options: GetOptsOptions) -> Result<i32> {
 
     let out = &mut stdout();
     let mut session = Session::new(config, Some(out));
-    format_and_emit_report(&mut session, Input::Text(input));
+    let report = format_and_emit_report(&mut session, Input::Text(input), options.error_format);
+    if options.timings {
+        let mut timings = Timings::default();
+        if let Some(report) = report {
+            timings.record(&report);
+        }
+        timings.print()?;
+    }
 
-    let exit_code = if session.has_operational_errors() || session.has_parsing_errors() {
-        1
-    } else {
-        0
+    Ok(exit_code(&session, false))
+}
+
+// Formats the requests read from stdin, one line of JSON each, until stdin is closed. The
+// response to each request is written to stdout as soon as it is formatted. The configs of the
+// directories of the requested paths are loaded once and reused for later requests.
+fn format_stdin_batch(options: GetOptsOptions) -> Result<i32> {
+    if options.check {
+        return Err(OperationError::CheckWithStdin.into());
+    }
+    if let Some(emit_mode) = options.emit_mode {
+        if emit_mode != EmitMode::Stdout {
+            return Err(OperationError::EmitWithStdin.into());
+        }
+    }
+
+    let mut configs: HashMap<PathBuf, Config> = HashMap::new();
+    let stdin = io::stdin();
+    let out = &mut stdout();
+    for line in stdin.lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<BatchRequest>(&line) {
+            Ok(request) => {
+                let dir = match request.path.parent() {
+                    Some(dir) if !dir.as_os_str().is_empty() => dir.to_owned(),
+                    _ => PathBuf::from("."),
+                };
+                let config = match configs.get(&dir) {
+                    Some(config) => Ok(config.clone()),
+                    None => load_config(Some(dir.as_path()), Some(options.clone())).map(
+                        |(config, _)| {
+                            configs.insert(dir, config.clone());
+                            config
+                        },
+                    ),
+                };
+                match config {
+                    Ok(config) => format_batch_request(request, config),
+                    // A broken config only fails the requests of the files it applies to.
+                    Err(e) => BatchResponse {
+                        path: request.path,
+                        formatted: None,
+                        messages: vec![format!("Invalid config: {}", e)],
+                    },
+                }
+            }
+            Err(e) => BatchResponse {
+                path: PathBuf::new(),
+                formatted: None,
+                messages: vec![format!("Invalid request: {}", e)],
+            },
+        };
+        serde_json::to_writer(&mut *out, &response)?;
+        writeln!(out)?;
+        out.flush()?;
+    }
+
+    Ok(SUCCESS)
+}
+
+fn format_batch_request(request: BatchRequest, mut config: Config) -> BatchResponse {
+    // The content is formatted as stdin, so the options applying to the path are checked here.
+    if config.ignore().is_ignored(&request.path) {
+        return BatchResponse {
+            path: request.path,
+            formatted: Some(request.content),
+            messages: vec![],
+        };
+    }
+    let file_lines = config
+        .file_lines()
+        .for_stdin(&FileName::Real(request.path.clone()));
+    config.set().file_lines(file_lines);
+    config.set().emit_mode(EmitMode::Stdout);
+    config.set().verbose(Verbosity::Quiet);
+
+    let mut messages = vec![];
+    let mut out: Vec<u8> = vec![];
+    let formatted = {
+        let mut session = Session::new(config, Some(&mut out));
+        match session.format(Input::Text(request.content)) {
+            Ok(report) => {
+                if report.has_warnings() {
+                    let report = FormatReportFormatterBuilder::new(&report).build();
+                    messages.push(report.to_string());
+                }
+                !session.has_parsing_errors()
+            }
+            Err(e) => {
+                messages.push(e.to_string());
+                false
+            }
+        }
     };
-    Ok(exit_code)
+
+    BatchResponse {
+        path: request.path,
+        formatted: if formatted {
+            String::from_utf8(out).ok()
+        } else {
+            None
+        },
+        messages,
+    }
 }
 
 fn format(
-    files: Vec<PathBuf>,
+    mut files: Vec<PathBuf>,
     minimal_config_path: Option<String>,
     options: &GetOptsOptions,
 ) -> Result<i32> {
-    options.verify_file_lines(&files);
+    // The editions of the Cargo targets, by canonical path.
+    let mut target_editions = HashMap::new();
+    if options.manifest_path.is_some() || !options.packages.is_empty() {
+        let manifest_path = options.manifest_path.as_ref().map(|p| &**p);
+        for (path, edition) in package_targets(manifest_path, &options.packages)? {
+            if let Some(edition) = edition {
+                target_editions.insert(canonical_path(&path), edition);
+            }
+            files.push(path);
+        }
+    }
+
     let (config, config_path) = load_config(None, Some(options.clone()))?;
+    let (files, walked_files) = expand_directories(files, &config)?;
+
+    options.verify_file_lines(&files);
 
     if config.verbose() == Verbosity::Verbose {
         if let Some(path) = config_path.as_ref() {
@@ -300,38 +682,107 @@ fn format(
 
     let out = &mut stdout();
     let mut session = Session::new(config, Some(out));
+    // The formatted files are only needed until they are emitted.
+    session.set_stream_output(true);
+    // A module given along with its crate root is only formatted once.
+    session.set_skip_formatted_files(true);
+
+    let mut timings = Timings::default();
+    if options.crate_roots {
+        let mut roots = vec![];
+        for file in files {
+            if file.exists() {
+                roots.push(file);
+            } else {
+                report_error(
+                    options.error_format,
+                    "error",
+                    &format!("Error: file `{}` does not exist", file.to_str().unwrap()),
+                );
+                session.add_operational_error();
+            }
+        }
+        let report = format_crate_roots(
+            &mut session,
+            roots,
+            &target_editions,
+            config_path.is_none(),
+            options,
+        )?;
+        if let Some(report) = report {
+            timings.record(&report);
+        }
+    } else {
+        for file in files {
+            if !file.exists() {
+                report_error(
+                    options.error_format,
+                    "error",
+                    &format!("Error: file `{}` does not exist", file.to_str().unwrap()),
+                );
+                session.add_operational_error();
+            } else {
+                // The edition of a Cargo target applies unless `--edition` is given.
+                let target_edition = match options.edition {
+                    Some(_) => None,
+                    None => target_editions.get(&canonical_path(&file)).cloned(),
+                };
+                // Every file found in a directory is formatted on its own, so its out of line
+                // modules don't need to be formatted with it.
+                let walked = walked_files.contains(&file);
+                // Check the file directory if the config-path could not be read or not provided
+                let report = if config_path.is_none() {
+                    let (mut local_config, config_path) =
+                        load_config(Some(parent_dir(&file)?), Some(options.clone()))?;
+                    if local_config.verbose() == Verbosity::Verbose {
+                        if let Some(path) = config_path {
+                            println!(
+                                "Using rustfmt config file {} for {}",
+                                path.display(),
+                                file.display()
+                            );
+                        }
+                    }
 
-    for file in files {
-        if !file.exists() {
-            eprintln!("Error: file `{}` does not exist", file.to_str().unwrap());
-            session.add_operational_error();
-        } else if file.is_dir() {
-            eprintln!("Error: `{}` is a directory", file.to_str().unwrap());
-            session.add_operational_error();
-        } else {
-            // Check the file directory if the config-path could not be read or not provided
-            if config_path.is_none() {
-                let (local_config, config_path) =
-                    load_config(Some(file.parent().unwrap()), Some(options.clone()))?;
-                if local_config.verbose() == Verbosity::Verbose {
-                    if let Some(path) = config_path {
-                        println!(
-                            "Using rustfmt config file {} for {}",
-                            path.display(),
-                            file.display()
-                        );
+                    if let Some(edition) = target_edition {
+                        local_config.set().edition(edition);
+                    }
+                    if walked {
+                        local_config
+                            .set()
+                            .module_traversal(ModuleTraversal::FormatRoots);
                     }
-                }
 
-                session.override_config(local_config, |sess| {
-                    format_and_emit_report(sess, Input::File(file))
-                });
-            } else {
-                format_and_emit_report(&mut session, Input::File(file));
+                    session.override_config(local_config, |sess| {
+                        format_and_emit_report(sess, Input::File(file), options.error_format)
+                    })
+                } else if target_edition.is_some() || walked {
+                    let mut file_config = session.config.clone();
+                    if let Some(edition) = target_edition {
+                        file_config.set().edition(edition);
+                    }
+                    if walked {
+                        file_config
+                            .set()
+                            .module_traversal(ModuleTraversal::FormatRoots);
+                    }
+                    session.override_config(file_config, |sess| {
+                        format_and_emit_report(sess, Input::File(file), options.error_format)
+                    })
+                } else {
+                    format_and_emit_report(&mut session, Input::File(file), options.error_format)
+                };
+                if let Some(report) = report {
+                    timings.record(&report);
+                }
             }
         }
     }
 
+    if options.timings {
+        timings.print()?;
+    }
+
     // If we were given a path via dump-minimal-config, output any options
     // that were used during formatting as TOML.
     if let Some(path) = minimal_config_path {
@@ -340,32 +791,310 @@ fn format(
         file.write_all(toml.as_bytes())?;
     }
 
-    let exit_code = if session.has_operational_errors()
-        || session.has_parsing_errors()
-        || ((session.has_diff() || session.has_check_errors()) && options.check)
-    {
-        1
+    if session.config.verbose() == Verbosity::Verbose {
+        println!(
+            "{} files changed, {} files unchanged",
+            session.files_changed(),
+            session.files_unchanged()
+        );
+    }
+
+    Ok(exit_code(&session, options.check))
+}
+
+/// Formats `roots` as the crate roots of one package with `Session::format_roots`. Unless a
+/// config file is given, the config is looked up from the directory of the first root, and the
+/// edition of its Cargo target applies to all the roots.
+fn format_crate_roots<T: Write>(
+    session: &mut Session<'_, T>,
+    roots: Vec<PathBuf>,
+    target_editions: &HashMap<PathBuf, Edition>,
+    load_local_config: bool,
+    options: &GetOptsOptions,
+) -> Result<Option<FormatReport>> {
+    let first_root = match roots.first() {
+        Some(root) => root,
+        None => return Ok(None),
+    };
+    let mut roots_config = if load_local_config {
+        let (local_config, config_path) =
+            load_config(Some(parent_dir(first_root)?), Some(options.clone()))?;
+        if local_config.verbose() == Verbosity::Verbose {
+            if let Some(path) = config_path {
+                println!("Using rustfmt config file {}", path.display());
+            }
+        }
+        local_config
     } else {
-        0
+        session.config.clone()
+    };
+    if options.edition.is_none() {
+        if let Some(&edition) = target_editions.get(&canonical_path(first_root)) {
+            roots_config.set().edition(edition);
+        }
+    }
+
+    Ok(session.override_config(roots_config, |sess| {
+        let result = sess.format_roots(roots);
+        emit_report(sess, result, options.error_format)
+    }))
+}
+
+/// Formats `files` with and without the option given to `--explain`, and prints how many lines
+/// of each file would change if the option was set.
+fn explain(files: Vec<PathBuf>, options: &GetOptsOptions) -> Result<i32> {
+    let (key, val) = options.explain.clone().unwrap();
+    let (config, config_path) = load_config(None, Some(options.clone()))?;
+    let (files, _) = expand_directories(files, &config)?;
+    let mut session = Session::<io::Stdout>::new(config, None);
+
+    let mut files_changed = 0;
+    let mut lines_removed = 0;
+    let mut lines_added = 0;
+    for file in files {
+        if !file.exists() {
+            report_error(
+                options.error_format,
+                "error",
+                &format!("Error: file `{}` does not exist", file.to_str().unwrap()),
+            );
+            session.add_operational_error();
+            continue;
+        }
+
+        let file_config = if config_path.is_none() {
+            load_config(Some(parent_dir(&file)?), Some(options.clone()))?.0
+        } else {
+            session.config.clone()
+        };
+        let mut overridden = file_config.clone();
+        overridden.override_value(&key, &val);
+
+        let input = Input::File(file.clone());
+        match session.override_config(file_config, |sess| sess.explain(input, overridden)) {
+            Ok(stats) => {
+                for stat in stats {
+                    if stat.lines_removed + stat.lines_added == 0 {
+                        continue;
+                    }
+                    println!(
+                        "{}: {} lines removed, {} lines added",
+                        stat.file, stat.lines_removed, stat.lines_added
+                    );
+                    files_changed += 1;
+                    lines_removed += stat.lines_removed;
+                    lines_added += stat.lines_added;
+                }
+            }
+            Err(e) => {
+                report_error(
+                    options.error_format,
+                    "error",
+                    &format!("Error formatting {}: {}", file.display(), e),
+                );
+                session.add_operational_error();
+            }
+        }
+    }
+
+    println!(
+        "Setting `{}` to `{}` would change {} files: {} lines removed, {} lines added",
+        key, val, files_changed, lines_removed, lines_added
+    );
+    Ok(exit_code(&session, false))
+}
+
+/// Replaces the directories in `files` with the Rust files found in them, recursively.
+/// Hidden files and the files ignored by `.gitignore` or `.rustfmtignore` files are skipped.
+///
+/// Also returns the set of files found in directories.
+fn expand_directories(
+    files: Vec<PathBuf>,
+    config: &Config,
+) -> Result<(Vec<PathBuf>, HashSet<PathBuf>)> {
+    let mut expanded = vec![];
+    let mut walked_files = HashSet::new();
+    // The canonical paths of the files, so that a file given through several paths, e.g. through
+    // a symbolic link, is only formatted once.
+    let mut seen_files = HashSet::new();
+    let mut add_file = |path: PathBuf, walked: bool| {
+        let canonical = canonical_path(&path);
+        if !seen_files.insert(canonical.clone()) {
+            return;
+        }
+        let path = if config.canonicalize_paths() {
+            canonical
+        } else {
+            path
+        };
+        if walked {
+            walked_files.insert(path.clone());
+        }
+        expanded.push(path);
+    };
+    for file in files {
+        if !file.is_dir() {
+            add_file(file, false);
+            continue;
+        }
+
+        let mut dir_files = vec![];
+        for entry in WalkBuilder::new(&file)
+            .add_custom_ignore_filename(".rustfmtignore")
+            .follow_links(config.symlinks() == SymlinkPolicy::Follow)
+            .build()
+        {
+            let entry = entry?;
+            if entry.path_is_symlink() && config.symlinks() == SymlinkPolicy::Skip {
+                continue;
+            }
+            let path = entry.into_path();
+            if is_rust_file(&path) {
+                dir_files.push(path);
+            }
+        }
+        dir_files.sort();
+        for path in dir_files {
+            add_file(path, true);
+        }
+    }
+    Ok((expanded, walked_files))
+}
+
+fn is_rust_file(path: &Path) -> bool {
+    path.is_file() && path.extension().map_or(false, |ext| ext == "rs")
+}
+
+// Returns the directory to look for the config files of `file` from.
+fn parent_dir(file: &Path) -> Result<&Path, OperationError> {
+    file.parent()
+        .ok_or_else(|| OperationError::NoParentDirectory(file.to_path_buf()))
+}
+
+// Returns `path` with `..` and symbolic links resolved.
+fn canonical_path(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Returns the entry points of the Cargo targets of `packages`, along with their edition.
+///
+/// Without packages, the targets of the package of the manifest are returned, or those of
+/// every workspace member if the manifest is a virtual manifest. Without a manifest path,
+/// the nearest `Cargo.toml` in the current directory or its parents is used.
+fn package_targets(
+    manifest_path: Option<&Path>,
+    packages: &[String],
+) -> Result<Vec<(PathBuf, Option<Edition>)>> {
+    let manifest_path = match manifest_path {
+        Some(manifest_path) => manifest_path.canonicalize()?,
+        None => find_manifest(&env::current_dir()?.canonicalize()?)?,
     };
-    Ok(exit_code)
+
+    let mut cmd = cargo_metadata::MetadataCommand::new();
+    cmd.no_deps().manifest_path(&manifest_path);
+    let metadata = cmd.exec().map_err(|e| format_err!("{}", e))?;
+
+    let is_selected = |package: &cargo_metadata::Package| {
+        if packages.is_empty() {
+            package.manifest_path.canonicalize().ok().as_ref() == Some(&manifest_path)
+        } else {
+            packages.contains(&package.name)
+        }
+    };
+    if let Some(package) = packages
+        .iter()
+        .find(|name| !metadata.packages.iter().any(|p| &p.name == *name))
+    {
+        return Err(format_err!(
+            "package `{}` is not a member of the workspace",
+            package
+        ));
+    }
+    let mut selected: Vec<_> = metadata
+        .packages
+        .iter()
+        .filter(|p| is_selected(p))
+        .collect();
+    if selected.is_empty() {
+        // A virtual manifest doesn't belong to any package.
+        selected = metadata.packages.iter().collect();
+    }
+
+    let mut targets: Vec<_> = selected
+        .into_iter()
+        .flat_map(|package| &package.targets)
+        .map(|target| {
+            let path = PathBuf::from(&target.src_path);
+            let path = path.canonicalize().unwrap_or(path);
+            (path, edition_from_edition_str(&target.edition).ok())
+        })
+        .collect();
+    targets.sort_by(|a, b| a.0.cmp(&b.0));
+    targets.dedup_by(|a, b| a.0 == b.0);
+    Ok(targets)
 }
 
-fn format_and_emit_report<T: Write>(session: &mut Session<'_, T>, input: Input) {
-    match session.format(input) {
+fn find_manifest(dir: &Path) -> Result<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join("Cargo.toml"))
+        .find(|manifest_path| manifest_path.is_file())
+        .ok_or_else(|| {
+            format_err!(
+                "could not find `Cargo.toml` in `{}` or any parent directory",
+                dir.display()
+            )
+        })
+}
+
+fn exit_code<T: Write>(session: &Session<'_, T>, check: bool) -> i32 {
+    if session.has_internal_errors() {
+        INTERNAL_ERROR
+    } else if session.has_parsing_errors() {
+        PARSE_ERROR
+    } else if session.has_operational_errors() {
+        OPERATIONAL_ERROR
+    } else if check && (session.has_diff() || session.has_check_errors()) {
+        DIFF_FOUND
+    } else {
+        SUCCESS
+    }
+}
+
+fn format_and_emit_report<T: Write>(
+    session: &mut Session<'_, T>,
+    input: Input,
+    error_format: ErrorFormat,
+) -> Option<FormatReport> {
+    let result = session.format(input);
+    emit_report(session, result, error_format)
+}
+
+// Prints the warnings of a formatting report, or the error which prevented formatting.
+fn emit_report<T: Write>(
+    session: &mut Session<'_, T>,
+    result: Result<FormatReport, ErrorKind>,
+    error_format: ErrorFormat,
+) -> Option<FormatReport> {
+    match result {
         Ok(report) => {
             if report.has_warnings() {
-                eprintln!(
-                    "{}",
-                    FormatReportFormatterBuilder::new(&report)
-                        .enable_colors(should_print_with_colors(session))
-                        .build()
-                );
+                let enable_colors =
+                    error_format == ErrorFormat::Human && should_print_with_colors(session);
+                let formatter = FormatReportFormatterBuilder::new(&report)
+                    .enable_colors(enable_colors)
+                    .build();
+                report_error(error_format, "warning", &formatter.to_string());
             }
+            Some(report)
         }
         Err(msg) => {
-            eprintln!("Error writing files: {}", msg);
+            report_error(
+                error_format,
+                "error",
+                &format!("Error writing files: {}", msg),
+            );
             session.add_operational_error();
+            None
         }
     }
 }
@@ -390,7 +1119,7 @@ fn print_usage_to_stdout(opts: &Options, reason: &str) {
         format!("{}\n\n", reason)
     };
     let msg = format!(
-        "{}Format Rust code\n\nusage: {} [options] <file>...",
+        "{}Format Rust code\n\nusage: {} [options] <file|dir>...",
         sep,
         env::args_os().next().unwrap().to_string_lossy()
     );
@@ -416,7 +1145,17 @@ rustfmt --file-lines '[
 
 would format lines `7-13` and `21-29` of `src/lib.rs`, and lines `10-11`,
 and `15` of `src/foo.rs`. No other files would be formatted, even if they
-are included as out of line modules from `src/lib.rs`."
+are included as out of line modules from `src/lib.rs`.
+
+The `range` of a file can also be `\"all\"`, to format all of its lines, and
+lines can be excluded with a `!range` property instead. A file with only
+excluded ranges is formatted except for them. For example,
+
+```
+rustfmt --file-lines '[{{\"file\":\"src/lib.rs\",\"!range\":[100,250]}}]'
+```
+
+would format `src/lib.rs` except for lines `100-250`."
     );
 }
 
@@ -451,6 +1190,7 @@ fn determine_operation(matches: &Matches) -> Result<Operation, OperationError> {
         match kind.as_str() {
             "default" => return Ok(Operation::ConfigOutputDefault { path }),
             "current" => return Ok(Operation::ConfigOutputCurrent { path }),
+            "schema" => return Ok(Operation::ConfigOutputSchema { path }),
             "minimal" => {
                 minimal_config_path = path;
                 if minimal_config_path.is_none() {
@@ -467,18 +1207,30 @@ fn determine_operation(matches: &Matches) -> Result<Operation, OperationError> {
         return Ok(Operation::Version);
     }
 
-    let files: Vec<_> = free_matches
-        .map(|s| {
-            let p = PathBuf::from(s);
-            // we will do comparison later, so here tries to canonicalize first
-            // to get the expected behavior.
-            p.canonicalize().unwrap_or(p)
-        })
-        .collect();
+    if matches.opt_present("stdin-batch") {
+        return Ok(Operation::StdinBatch);
+    }
+
+    let mut file_names: Vec<_> = free_matches.cloned().collect();
+    let files_from = matches.opt_str("files-from");
+    if let Some(ref list) = files_from {
+        file_names.extend(read_file_list(list)?);
+    }
+
+    // The paths are canonicalized once the config is loaded, see `expand_directories`.
+    let files: Vec<_> = file_names.into_iter().map(PathBuf::from).collect();
 
     // if no file argument is supplied, read from stdin
-    if files.is_empty() {
-        if minimal_config_path.is_some() {
+    let formats_packages = matches.opt_present("manifest-path") || matches.opt_present("package");
+    // An empty `--files-from` list, e.g. of a change set without Rust files, formats nothing.
+    if files.is_empty() && !formats_packages && files_from.is_none() {
+        // `--print-config minimal FILE.rs` on its own reports the non-default options that
+        // apply to the Rust file. Any other PATH is where to write the minimal config of the
+        // input, which can't be done for standard input.
+        if let Some(path) = minimal_config_path {
+            if is_rust_file(Path::new(&path)) {
+                return Ok(Operation::ConfigOutputMinimal { path });
+            }
             return Err(OperationError::MinimalPathWithStdin);
         }
         let mut buffer = String::new();
@@ -493,6 +1245,35 @@ fn determine_operation(matches: &Matches) -> Result<Operation, OperationError> {
     })
 }
 
+/// Reads the paths listed in `list`, or in stdin if it is `-`. The paths are separated by NULs if
+/// the list has any, and by newlines otherwise.
+fn read_file_list(list: &str) -> Result<Vec<String>, IoError> {
+    let content = if list == "-" {
+        let mut buffer = String::new();
+        io::stdin().read_to_string(&mut buffer)?;
+        buffer
+    } else {
+        fs::read_to_string(list)?
+    };
+    Ok(split_file_list(&content))
+}
+
+fn split_file_list(content: &str) -> Vec<String> {
+    if content.contains('\0') {
+        content
+            .split('\0')
+            .filter(|path| !path.is_empty())
+            .map(str::to_owned)
+            .collect()
+    } else {
+        content
+            .lines()
+            .filter(|path| !path.trim().is_empty())
+            .map(str::to_owned)
+            .collect()
+    }
+}
+
 const STABLE_EMIT_MODES: [EmitMode; 3] = [EmitMode::Files, EmitMode::Stdout, EmitMode::Diff];
 
 /// Parsed command line options.
@@ -502,16 +1283,26 @@ struct GetOptsOptions {
     quiet: bool,
     verbose: bool,
     config_path: Option<PathBuf>,
-    inline_config: HashMap<String, String>,
+    inline_config: BTreeMap<String, String>,
     emit_mode: Option<EmitMode>,
     backup: bool,
+    out_dir: Option<PathBuf>,
     check: bool,
     edition: Option<Edition>,
     color: Option<Color>,
+    diff_style: Option<DiffStyle>,
+    diff_context: Option<usize>,
+    diff_summary: bool,
     file_lines: FileLines, // Default is all lines in all files.
     unstable_features: bool,
     error_on_unformatted: Option<bool>,
     print_misformatted_file_names: bool,
+    error_format: ErrorFormat,
+    manifest_path: Option<PathBuf>,
+    packages: Vec<String>,
+    explain: Option<(String, String)>,
+    timings: bool,
+    crate_roots: bool,
 }
 
 impl GetOptsOptions {
@@ -561,6 +1352,8 @@ impl GetOptsOptions {
         }
 
         options.config_path = matches.opt_str("config-path").map(PathBuf::from);
+        options.manifest_path = matches.opt_str("manifest-path").map(PathBuf::from);
+        options.packages = matches.opt_strs("package");
 
         options.inline_config = matches
             .opt_strs("config")
@@ -583,9 +1376,24 @@ impl GetOptsOptions {
                     )),
                 },
             )
-            .collect::<Result<HashMap<_, _>, _>>()?;
+            .collect::<Result<BTreeMap<_, _>, _>>()?;
+
+        if let Some(ref key_val) = matches.opt_str("explain") {
+            match key_val.find('=') {
+                Some(middle)
+                    if Config::is_valid_key_val(&key_val[..middle], &key_val[middle + 1..]) =>
+                {
+                    options.explain = Some((
+                        key_val[..middle].to_string(),
+                        key_val[middle + 1..].to_string(),
+                    ));
+                }
+                _ => return Err(format_err!("invalid key=val pair: `{}`", key_val)),
+            }
+        }
 
         options.check = matches.opt_present("check");
+        options.crate_roots = matches.opt_present("crate-roots");
         if let Some(ref emit_str) = matches.opt_str("emit") {
             if options.check {
                 return Err(format_err!("Invalid to use `--emit` and `--check`"));
@@ -602,7 +1410,21 @@ impl GetOptsOptions {
             options.backup = true;
         }
 
-        if matches.opt_present("files-with-diff") {
+        if let Some(out_dir) = matches.opt_str("out-dir") {
+            match options.emit_mode {
+                Some(EmitMode::Files) | None if !options.check => {
+                    options.out_dir = Some(PathBuf::from(out_dir))
+                }
+                _ => {
+                    return Err(format_err!(
+                        "`--out-dir` can only be used with `--emit files`"
+                    ))
+                }
+            }
+        }
+
+        // In check mode, being quiet means only listing the files which need formatting.
+        if matches.opt_present("files-with-diff") || (options.check && options.quiet) {
             options.print_misformatted_file_names = true;
         }
 
@@ -624,13 +1446,40 @@ impl GetOptsOptions {
             }
         }
 
+        if let Some(ref diff_style) = matches.opt_str("diff-style") {
+            options.diff_style = match diff_style.as_str() {
+                "unified" => Some(DiffStyle::Unified),
+                "side-by-side" => Some(DiffStyle::SideBySide),
+                _ => return Err(format_err!("Invalid diff style: {}", diff_style)),
+            };
+        }
+
+        if let Some(ref diff_context) = matches.opt_str("diff-context") {
+            match diff_context.parse() {
+                Ok(diff_context) => options.diff_context = Some(diff_context),
+                _ => return Err(format_err!("Invalid diff context: {}", diff_context)),
+            }
+        }
+        options.diff_summary = matches.opt_present("diff-summary");
+
+        if let Some(ref error_format) = matches.opt_str("error-format") {
+            options.error_format = ErrorFormat::from_str(error_format)?;
+        }
+
+        if let Some(ref timings) = matches.opt_str("timings") {
+            if timings != "json" {
+                return Err(format_err!("Invalid value for `--timings`: {}", timings));
+            }
+            options.timings = true;
+        }
+
         Ok(options)
     }
 
     fn verify_file_lines(&self, files: &[PathBuf]) {
         for f in self.file_lines.files() {
             match *f {
-                FileName::Real(ref f) if files.contains(f) => {}
+                FileName::Real(ref f) if files.iter().any(|file| canonical_path(file) == *f) => {}
                 FileName::Real(_) => {
                     eprintln!("Warning: Extra file listed in file_lines option '{}'", f)
                 }
@@ -668,9 +1517,21 @@ impl CliOptions for GetOptsOptions {
         if self.backup {
             config.set().make_backup(true);
         }
+        if let Some(out_dir) = self.out_dir {
+            config.set().out_dir(out_dir.to_string_lossy().into_owned());
+        }
         if let Some(color) = self.color {
             config.set().color(color);
         }
+        if let Some(diff_style) = self.diff_style {
+            config.set().diff_style(diff_style);
+        }
+        if let Some(diff_context) = self.diff_context {
+            config.set().diff_context(diff_context);
+        }
+        if self.diff_summary {
+            config.set().diff_summary(true);
+        }
         if self.print_misformatted_file_names {
             config.set().print_misformatted_file_names(true);
         }
@@ -700,6 +1561,9 @@ fn emit_mode_from_emit_str(emit_str: &str) -> Result<EmitMode> {
         "coverage" => Ok(EmitMode::Coverage),
         "checkstyle" => Ok(EmitMode::Checkstyle),
         "json" => Ok(EmitMode::Json),
+        "modified-lines" => Ok(EmitMode::ModifiedLines),
+        "stats" => Ok(EmitMode::Stats),
+        "stats-json" => Ok(EmitMode::StatsJson),
         _ => Err(format_err!("Invalid value for `--emit`")),
     }
 }
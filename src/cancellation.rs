@@ -0,0 +1,47 @@
+//! Cooperative cancellation of formatting, e.g. for editors which abort formatting a buffer
+//! when it changes.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// A handle to cancel the formatting done by a [`Session`](crate::Session), possibly from
+/// another thread.
+///
+/// Formatting checks for cancellation between items. The items which are visited after
+/// formatting is cancelled are left as they are.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// Cancels the formatting of the sessions this token was given to.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// The conditions under which formatting stops early.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Cancellation {
+    pub(crate) token: Option<CancellationToken>,
+    pub(crate) deadline: Option<Instant>,
+}
+
+impl Cancellation {
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.token
+            .as_ref()
+            .map_or(false, CancellationToken::is_cancelled)
+            || self
+                .deadline
+                .map_or(false, |deadline| Instant::now() >= deadline)
+    }
+}
@@ -69,6 +69,7 @@ use crate::macros::convert_try_mac;
 use crate::rewrite::{Rewrite, RewriteContext};
 use crate::shape::Shape;
 use crate::source_map::SpanUtils;
+use crate::trace;
 use crate::utils::{
     self, first_line_width, last_line_extendable, last_line_width, mk_sp, rewrite_ident,
     trimmed_last_line_width, wrap_str,
@@ -88,7 +89,14 @@ pub(crate) fn rewrite_chain(
         return chain.parent.rewrite(context, shape);
     }
 
-    chain.rewrite(context, shape)
+    let result = chain.rewrite(context, shape);
+    let tactic = match result {
+        Some(ref s) if s.contains('\n') => "Vertical",
+        Some(_) => "Horizontal",
+        None => "Failed",
+    };
+    trace::trace_decision(context.parse_sess, expr.span, "chain", tactic, shape);
+    result
 }
 
 #[derive(Debug)]
@@ -427,7 +435,7 @@ impl Rewrite for Chain {
     fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String> {
         debug!("rewrite chain {:?} {:?}", self, shape);
 
-        let mut formatter = match context.config.indent_style() {
+        let mut formatter = match context.indent_style() {
             IndentStyle::Block => {
                 Box::new(ChainFormatterBlock::new(self)) as Box<dyn ChainFormatter>
             }
@@ -447,7 +455,7 @@ impl Rewrite for Chain {
         formatter.format_children(context, child_shape)?;
         formatter.format_last_child(context, shape, child_shape)?;
 
-        let result = formatter.join_rewrites(context, child_shape)?;
+        let result = formatter.join_rewrites(context, shape, child_shape)?;
         wrap_str(result, context.config.max_width(), shape)
     }
 }
@@ -479,7 +487,12 @@ trait ChainFormatter {
         shape: Shape,
         child_shape: Shape,
     ) -> Option<()>;
-    fn join_rewrites(&self, context: &RewriteContext<'_>, child_shape: Shape) -> Option<String>;
+    fn join_rewrites(
+        &self,
+        context: &RewriteContext<'_>,
+        shape: Shape,
+        child_shape: Shape,
+    ) -> Option<String>;
     // Returns `Some` if the chain is only a root, None otherwise.
     fn pure_root(&mut self) -> Option<String>;
 }
@@ -497,6 +510,8 @@ struct ChainFormatterShared<'a> {
     // The number of children in the chain. This is not equal to `self.children.len()`
     // because `self.children` will change size as we process the chain.
     child_count: usize,
+    // The number of children which are method calls or `.await`s, i.e. not field accesses.
+    call_count: usize,
 }
 
 impl<'a> ChainFormatterShared<'a> {
@@ -506,6 +521,14 @@ impl<'a> ChainFormatterShared<'a> {
             rewrites: Vec::with_capacity(chain.children.len() + 1),
             fits_single_line: false,
             child_count: chain.children.len(),
+            call_count: chain
+                .children
+                .iter()
+                .filter(|item| match item.kind {
+                    ChainItemKind::MethodCall(..) | ChainItemKind::Await => true,
+                    _ => false,
+                })
+                .count(),
         }
     }
 
@@ -570,7 +593,12 @@ impl<'a> ChainFormatterShared<'a> {
                 .map(|rw| utils::unicode_str_width(&rw))
                 .sum()
         } + last.tries;
-        let one_line_budget = if self.child_count == 1 {
+        let is_short_chain = if context.config.chain_width_counts_fields() {
+            self.child_count == 1
+        } else {
+            self.call_count <= 1
+        };
+        let one_line_budget = if is_short_chain {
             shape.width
         } else {
             min(shape.width, context.config.width_heuristics().chain_width)
@@ -649,7 +677,12 @@ impl<'a> ChainFormatterShared<'a> {
         Some(())
     }
 
-    fn join_rewrites(&self, context: &RewriteContext<'_>, child_shape: Shape) -> Option<String> {
+    fn join_rewrites(
+        &self,
+        context: &RewriteContext<'_>,
+        shape: Shape,
+        child_shape: Shape,
+    ) -> Option<String> {
         let connector = if self.fits_single_line {
             // Yay, we can put everything on one line.
             Cow::from("")
@@ -667,12 +700,28 @@ impl<'a> ChainFormatterShared<'a> {
         let iter = rewrite_iter.zip(children_iter);
 
         for (rewrite, chain_item) in iter {
+            let mut rewrite = rewrite.as_str();
             match chain_item.kind {
                 ChainItemKind::Comment(_, CommentPosition::Back) => result.push(' '),
                 ChainItemKind::Comment(_, CommentPosition::Top) => result.push_str(&connector),
+                // Keep a tuple index next to what it indexes, e.g. `foo().0.1` rather than `.0`
+                // and `.1` on lines of their own, as long as it fits.
+                ChainItemKind::TupleField(..) if context.config.chain_tuple_indices_attached() => {
+                    rewrite = rewrite.trim_start();
+                    let used_width = if result.contains('\n') {
+                        0
+                    } else {
+                        shape.offset
+                    };
+                    if used_width + last_line_width(&result) + rewrite.len()
+                        > context.config.max_width()
+                    {
+                        result.push_str(&connector);
+                    }
+                }
                 _ => result.push_str(&connector),
             }
-            result.push_str(&rewrite);
+            result.push_str(rewrite);
         }
 
         Some(result)
@@ -758,8 +807,13 @@ impl<'a> ChainFormatter for ChainFormatterBlock<'a> {
             .format_last_child(true, context, shape, child_shape)
     }
 
-    fn join_rewrites(&self, context: &RewriteContext<'_>, child_shape: Shape) -> Option<String> {
-        self.shared.join_rewrites(context, child_shape)
+    fn join_rewrites(
+        &self,
+        context: &RewriteContext<'_>,
+        shape: Shape,
+        child_shape: Shape,
+    ) -> Option<String> {
+        self.shared.join_rewrites(context, shape, child_shape)
     }
 
     fn pure_root(&mut self) -> Option<String> {
@@ -852,8 +906,13 @@ impl<'a> ChainFormatter for ChainFormatterVisual<'a> {
             .format_last_child(false, context, shape, child_shape)
     }
 
-    fn join_rewrites(&self, context: &RewriteContext<'_>, child_shape: Shape) -> Option<String> {
-        self.shared.join_rewrites(context, child_shape)
+    fn join_rewrites(
+        &self,
+        context: &RewriteContext<'_>,
+        shape: Shape,
+        child_shape: Shape,
+    ) -> Option<String> {
+        self.shared.join_rewrites(context, shape, child_shape)
     }
 
     fn pure_root(&mut self) -> Option<String> {
@@ -176,20 +176,22 @@ fn rewrite_closure_expr(
     context: &RewriteContext<'_>,
     shape: Shape,
 ) -> Option<String> {
-    fn allow_multi_line(expr: &ast::Expr) -> bool {
+    fn allow_multi_line(expr: &ast::Expr, context: &RewriteContext<'_>) -> bool {
         match expr.kind {
-            ast::ExprKind::Match(..)
-            | ast::ExprKind::Async(..)
+            ast::ExprKind::Match(..) | ast::ExprKind::Loop(..) => {
+                context.config.combine_control_expr_in_closures()
+            }
+
+            ast::ExprKind::Async(..)
             | ast::ExprKind::Block(..)
             | ast::ExprKind::TryBlock(..)
-            | ast::ExprKind::Loop(..)
             | ast::ExprKind::Struct(..) => true,
 
             ast::ExprKind::AddrOf(_, _, ref expr)
             | ast::ExprKind::Box(ref expr)
             | ast::ExprKind::Try(ref expr)
             | ast::ExprKind::Unary(_, ref expr)
-            | ast::ExprKind::Cast(ref expr, _) => allow_multi_line(expr),
+            | ast::ExprKind::Cast(ref expr, _) => allow_multi_line(expr, context),
 
             _ => false,
         }
@@ -197,8 +199,9 @@ fn rewrite_closure_expr(
 
     // When rewriting closure's body without block, we require it to fit in a single line
     // unless it is a block-like expression or we are inside macro call.
-    let veto_multiline = (!allow_multi_line(expr) && !context.inside_macro())
-        || context.config.force_multiline_blocks();
+    let veto_multiline = (!allow_multi_line(expr, context) && !context.inside_macro())
+        || context.config.force_multiline_blocks()
+        || context.config.closure_multiline_blocks();
     expr.rewrite(context, shape)
         .and_then(|rw| {
             if veto_multiline && rw.contains('\n') {
@@ -306,6 +309,28 @@ pub(crate) fn rewrite_last_closure(
     context: &RewriteContext<'_>,
     expr: &ast::Expr,
     shape: Shape,
+) -> Option<String> {
+    if !context.config.closure_overflow_last_arg() {
+        return None;
+    }
+    let rewrite = rewrite_last_closure_inner(context, expr, shape)?;
+
+    // Long `move` closures are put on their own line rather than being overflowed.
+    let threshold = context.config.move_closure_own_line_threshold();
+    match expr.kind {
+        ast::ExprKind::Closure(ast::CaptureBy::Value, ..)
+            if threshold > 0 && rewrite.lines().count() > threshold =>
+        {
+            None
+        }
+        _ => Some(rewrite),
+    }
+}
+
+fn rewrite_last_closure_inner(
+    context: &RewriteContext<'_>,
+    expr: &ast::Expr,
+    shape: Shape,
 ) -> Option<String> {
     if let ast::ExprKind::Closure(capture, ref is_async, movability, ref fn_decl, ref body, _) =
         expr.kind
@@ -408,7 +433,7 @@ fn is_block_closure_forced_inner(expr: &ast::Expr, version: Version) -> bool {
 ///      |x| 5
 /// isn't parsed as (if true {...} else {...} | x) | 5
 // From https://github.com/rust-lang/rust/blob/master/src/libsyntax/parse/classify.rs.
-fn expr_requires_semi_to_be_stmt(e: &ast::Expr) -> bool {
+pub(crate) fn expr_requires_semi_to_be_stmt(e: &ast::Expr) -> bool {
     match e.kind {
         ast::ExprKind::If(..)
         | ast::ExprKind::Match(..)
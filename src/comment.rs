@@ -1,5 +1,6 @@
 // Formatting and tools for comments.
 
+use std::collections::HashMap;
 use std::{self, borrow::Cow, iter};
 
 use itertools::{multipeek, MultiPeek};
@@ -500,6 +501,8 @@ struct CommentRewrite<'a> {
     is_prev_line_multi_line: bool,
     code_block_attr: Option<CodeBlockAttribute>,
     item_block: Option<ItemizedBlock>,
+    // Whether the current line is part of a table which follows the keep-table directive.
+    in_kept_table: bool,
     comment_line_separator: String,
     indent_str: String,
     max_width: usize,
@@ -536,6 +539,7 @@ impl<'a> CommentRewrite<'a> {
             is_prev_line_multi_line: false,
             code_block_attr: None,
             item_block: None,
+            in_kept_table: false,
             comment_line_separator: format!("{}{}", indent_str, line_start),
             max_width,
             indent_str,
@@ -689,9 +693,23 @@ impl<'a> CommentRewrite<'a> {
 
         self.code_block_attr = None;
         self.item_block = None;
+        if line.trim() == KEEP_TABLE_DIRECTIVE {
+            self.in_kept_table = true;
+        } else if line.trim().is_empty() {
+            self.in_kept_table = false;
+        }
+        // Table rows are kept as they are, and the rows before them aren't joined with them.
+        let is_table_row = self.in_kept_table || is_table_row(line);
+        if is_table_row {
+            self.is_prev_line_multi_line = false;
+        }
+
         if line.starts_with("```") {
             self.code_block_attr = Some(CodeBlockAttribute::new(&line[3..]))
-        } else if self.fmt.config.wrap_comments() && ItemizedBlock::is_itemized_line(&line) {
+        } else if self.fmt.config.wrap_comments()
+            && !is_table_row
+            && ItemizedBlock::is_itemized_line(&line)
+        {
             let ib = ItemizedBlock::new(&line);
             self.item_block = Some(ib);
             return false;
@@ -723,6 +741,7 @@ impl<'a> CommentRewrite<'a> {
         if self.fmt.config.wrap_comments()
             && unicode_str_width(line) > self.fmt.shape.width
             && !has_url(line)
+            && !is_table_row
         {
             match rewrite_string(line, &self.fmt, self.max_width) {
                 Some(ref s) => {
@@ -846,6 +865,35 @@ fn trim_custom_comment_prefix(s: &str) -> String {
         .join("\n")
 }
 
+/// A comment line which marks the lines after it, up to the next blank line, as a table which
+/// must not be reflowed, for tables which `is_table_row` doesn't recognize.
+const KEEP_TABLE_DIRECTIVE: &str = "rustfmt: keep-table";
+
+/// Returns `true` if the given comment line looks like a row of a table: either a row of cells
+/// separated by `|`, as in Markdown tables, or at least three columns separated by runs of
+/// spaces. Tables are not reflowed, as that would mangle them.
+fn is_table_row(line: &str) -> bool {
+    let line = line.trim();
+    let is_markdown_row = line.len() > 1 && line.starts_with('|') && line.ends_with('|');
+    let is_separator_row =
+        line.contains('|') && line.contains('-') && line.chars().all(|c| "|-: ".contains(c));
+    if is_markdown_row || is_separator_row {
+        return true;
+    }
+
+    // Sentences are often separated by two spaces, which doesn't make columns.
+    let columns = line
+        .split("  ")
+        .filter(|cell| !cell.trim().is_empty())
+        .collect::<Vec<_>>();
+    columns.len() >= 3
+        && columns[..columns.len() - 1].iter().all(|cell| {
+            !cell
+                .trim_end()
+                .ends_with(|c| c == '.' || c == '!' || c == '?')
+        })
+}
+
 /// Returns `true` if the given string MAY include URLs or alike.
 fn has_url(s: &str) -> bool {
     // This function may return false positive, but should get its job done in most cases.
@@ -1186,6 +1234,22 @@ where
     true
 }
 
+/// Returns `true` if the `#`s following an `r` (the first of which has already been peeked)
+/// are followed by a `"`, i.e. they open a raw string literal.
+fn is_raw_string_prefix<T>(iter: &mut MultiPeek<T>) -> bool
+where
+    T: Iterator,
+    T::Item: RichChar,
+{
+    loop {
+        match iter.peek().map(RichChar::get_char) {
+            Some('#') => continue,
+            Some('"') => return true,
+            _ => return false,
+        }
+    }
+}
+
 impl<T> Iterator for CharClasses<T>
 where
     T: Iterator,
@@ -1255,7 +1319,12 @@ where
             CharClassesStatus::LitCharEscape => CharClassesStatus::LitChar,
             CharClassesStatus::Normal => match chr {
                 'r' => match self.base.peek().map(RichChar::get_char) {
-                    Some('#') | Some('"') => {
+                    Some('"') => {
+                        char_kind = FullCodeCharKind::InString;
+                        CharClassesStatus::RawStringPrefix(0)
+                    }
+                    // `r#"..."#` is a raw string, whereas `r#ident` is a raw identifier.
+                    Some('#') if is_raw_string_prefix(&mut self.base) => {
                         char_kind = FullCodeCharKind::InString;
                         CharClassesStatus::RawStringPrefix(0)
                     }
@@ -1589,6 +1658,94 @@ pub(crate) fn filter_normal_code(code: &str) -> String {
     buffer
 }
 
+/// A comment of a source file along with the 1-based line it starts on.
+#[derive(Debug, PartialEq)]
+pub(crate) struct LineComment {
+    pub(crate) line: usize,
+    pub(crate) text: String,
+}
+
+/// Returns the comments of `code`, in order.
+fn comments_with_lines(code: &str) -> Vec<LineComment> {
+    let mut comments = vec![];
+    let mut current: Option<LineComment> = None;
+    let mut line = 1;
+    for (kind, c) in CharClasses::new(code.chars()) {
+        if kind.is_comment() {
+            current
+                .get_or_insert_with(|| LineComment {
+                    line,
+                    text: String::new(),
+                })
+                .text
+                .push(c);
+        }
+        if kind == FullCodeCharKind::EndComment || !kind.is_comment() {
+            comments.extend(current.take());
+        }
+        if c == '\n' {
+            line += 1;
+        }
+    }
+    comments.extend(current);
+    comments
+}
+
+// The words of a comment, ignoring comment marks, punctuation and whitespace, so that rewrapping
+// or normalizing a comment, or formatting the code in it, doesn't change them.
+fn comment_words(comment: &str) -> impl Iterator<Item = &str> {
+    comment
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|word| !word.is_empty())
+}
+
+/// Returns the comments of `from` whose words can't all be found in the comments of `to`. Each
+/// word of `to` only accounts for one occurrence of that word in `from`, so a comment which
+/// appears twice in `from` but once in `to` is returned once.
+fn missing_comments(from: &str, to: &str) -> Vec<LineComment> {
+    let mut available: HashMap<String, usize> = HashMap::new();
+    for comment in comments_with_lines(to) {
+        for word in comment_words(&comment.text) {
+            *available.entry(word.to_owned()).or_insert(0) += 1;
+        }
+    }
+
+    let mut missing = vec![];
+    for comment in comments_with_lines(from) {
+        let mut needed: HashMap<&str, usize> = HashMap::new();
+        for word in comment_words(&comment.text) {
+            *needed.entry(word).or_insert(0) += 1;
+        }
+        let found = needed
+            .iter()
+            .all(|(word, n)| available.get(*word).map_or(false, |m| m >= n));
+        if found {
+            for (word, n) in needed {
+                if let Some(m) = available.get_mut(word) {
+                    *m -= n;
+                }
+            }
+        } else {
+            missing.push(comment);
+        }
+    }
+    missing
+}
+
+/// Compares the comments of the original and the formatted text of a file, and returns the
+/// comments which formatting dropped (with their lines in `original`) and the ones it duplicated
+/// or added (with their lines in `formatted`). Comments may be reordered, rewrapped, normalized or
+/// have the code in them formatted.
+pub(crate) fn changed_comments(
+    original: &str,
+    formatted: &str,
+) -> (Vec<LineComment>, Vec<LineComment>) {
+    (
+        missing_comments(original, formatted),
+        missing_comments(formatted, original),
+    )
+}
+
 /// Returns `true` if the two strings of code have the same payload of comments.
 /// The payload of comments is everything in the string except:
 /// - actual code (not comments),
@@ -1696,6 +1853,16 @@ mod test {
         assert_eq!(None, iter.next());
     }
 
+    #[test]
+    fn char_classes_raw_strings() {
+        let kinds = |s: &str| -> Vec<FullCodeCharKind> {
+            CharClasses::new(s.chars()).map(|(kind, _)| kind).collect()
+        };
+
+        assert!(kinds(r##"r#"a"#"##)[..5].iter().all(|k| k.is_string()));
+        assert!(kinds("r#type").iter().all(|k| !k.is_string()));
+    }
+
     #[test]
     fn comment_code_slices() {
         let input = "code(); /* test */ 1 + 1";
@@ -1899,4 +2066,62 @@ fn main() {
 "#;
         assert_eq!(s, filter_normal_code(s_with_comment));
     }
+
+    #[test]
+    fn test_changed_comments() {
+        let original = r#"
+// Reordered
+use b;
+// Kept, but rewrapped.
+use a;
+
+fn main() {
+    /* normalized */
+    let s = "// not a comment";
+    foo(); // dropped
+}
+"#;
+        let formatted = r#"
+// Kept, but
+// rewrapped.
+use a;
+// Reordered
+use b;
+
+fn main() {
+    // normalized
+    let s = "// not a comment";
+    foo();
+    // Reordered
+}
+"#;
+        let (dropped, added) = changed_comments(original, formatted);
+        assert_eq!(
+            dropped,
+            vec![LineComment {
+                line: 10,
+                text: "// dropped\n".to_owned(),
+            }]
+        );
+        assert_eq!(
+            added,
+            vec![LineComment {
+                line: 12,
+                text: "// Reordered\n".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_is_table_row() {
+        assert!(is_table_row("| Option | Default |"));
+        assert!(is_table_row("|--------|:-------:|"));
+        assert!(is_table_row("---|---"));
+        assert!(is_table_row("max_width    usize    100"));
+        assert!(!is_table_row(
+            "A sentence.  Another sentence.  And a third one."
+        ));
+        assert!(!is_table_row("`a || b` is true if either is true"));
+        assert!(!is_table_row("|"));
+    }
 }
@@ -1,5 +1,5 @@
 use crate::config::file_lines::FileLines;
-use crate::config::options::{IgnoreList, WidthHeuristics};
+use crate::config::options::{IgnoreList, IssuePattern, MacroNames, WidthHeuristics};
 
 /// Trait for types that can be used in `Config`.
 pub(crate) trait ConfigType: Sized {
@@ -50,6 +50,18 @@ impl ConfigType for IgnoreList {
     }
 }
 
+impl ConfigType for MacroNames {
+    fn doc_hint() -> String {
+        String::from("[<string>,..]")
+    }
+}
+
+impl ConfigType for IssuePattern {
+    fn doc_hint() -> String {
+        String::from("<regex>")
+    }
+}
+
 macro_rules! create_config {
     ($($i:ident: $ty:ty, $def:expr, $stb:expr, $( $dstring:expr ),+ );+ $(;)*) => (
         #[cfg(test)]
@@ -62,8 +74,8 @@ macro_rules! create_config {
         #[allow(unreachable_pub)]
         pub struct Config {
             // if a license_template_path has been specified, successfully read, parsed and compiled
-            // into a regex, it will be stored here
-            pub license_template: Option<Regex>,
+            // into regexes, it will be stored here
+            pub license_template: Option<license::LicenseTemplate>,
             // For each config item, we store a bool indicating whether it has
             // been accessed and the value, and a bool whether the option was
             // manually initialised, or taken from the default,
@@ -93,10 +105,20 @@ macro_rules! create_config {
             $(
             #[allow(unreachable_pub)]
             pub fn $i(&mut self, value: $ty) {
+                // Like `override_value`, so that `set_heuristics` keeps explicit widths.
+                (self.0).$i.1 = true;
                 (self.0).$i.2 = value;
                 match stringify!($i) {
-                    "max_width" | "use_small_heuristics" => self.0.set_heuristics(),
-                    "license_template_path" => self.0.set_license_template(),
+                    "max_width"
+                    | "use_small_heuristics"
+                    | "fn_call_width"
+                    | "attr_fn_like_width"
+                    | "struct_lit_width"
+                    | "struct_variant_width"
+                    | "array_width"
+                    | "chain_width"
+                    | "single_line_if_else_max_width" => self.0.set_heuristics(),
+                    "license_template_path" | "license_owner" => self.0.set_license_template(),
                     &_ => (),
                 }
             }
@@ -169,6 +191,11 @@ macro_rules! create_config {
                 hash_set
             }
 
+            /// Returns the name of every config option, in declaration order.
+            pub(crate) fn option_names() -> &'static [&'static str] {
+                &[$(stringify!($i)),+]
+            }
+
             pub(crate) fn is_valid_name(name: &str) -> bool {
                 match name {
                     $(
@@ -192,7 +219,27 @@ macro_rules! create_config {
             pub fn used_options(&self) -> PartialConfig {
                 PartialConfig {
                     $(
-                        $i: if self.$i.0.get() {
+                        $i: if self.$i.0.get()
+                            && !Config::is_derived_width(stringify!($i), self.$i.1) {
+                                Some(self.$i.2.clone())
+                            } else {
+                                None
+                            },
+                    )+
+                }
+            }
+
+            /// Returns the options whose values differ from their defaults.
+            #[allow(unreachable_pub)]
+            pub fn non_default_options(&self) -> PartialConfig {
+                let mut default = Config::default();
+                // The ignore list remembers where it was read from, which should not
+                // by itself make it differ from the default.
+                default.set_ignore(self.ignore.2.rustfmt_toml_path());
+                PartialConfig {
+                    $(
+                        $i: if self.$i.2 != default.$i.2
+                            && !Config::is_derived_width(stringify!($i), self.$i.1) {
                                 Some(self.$i.2.clone())
                             } else {
                                 None
@@ -203,13 +250,37 @@ macro_rules! create_config {
 
             #[allow(unreachable_pub)]
             pub fn all_options(&self) -> PartialConfig {
+                let default = Config::default();
                 PartialConfig {
                     $(
-                        $i: Some(self.$i.2.clone()),
+                        $i: if self.$i.2 != default.$i.2
+                            && Config::is_derived_width(stringify!($i), self.$i.1) {
+                                None
+                            } else {
+                                Some(self.$i.2.clone())
+                            },
                     )+
                 }
             }
 
+            // The widths which were not set by the user follow `max_width` and
+            // `use_small_heuristics`, so they are left out of the printed options.
+            fn is_derived_width(name: &str, was_set: bool) -> bool {
+                if was_set {
+                    return false;
+                }
+                match name {
+                    "fn_call_width"
+                    | "attr_fn_like_width"
+                    | "struct_lit_width"
+                    | "struct_variant_width"
+                    | "array_width"
+                    | "chain_width"
+                    | "single_line_if_else_max_width" => true,
+                    _ => false,
+                }
+            }
+
             #[allow(unreachable_pub)]
             pub fn override_value(&mut self, key: &str, val: &str)
             {
@@ -228,8 +299,16 @@ macro_rules! create_config {
                 }
 
                 match key {
-                    "max_width" | "use_small_heuristics" => self.set_heuristics(),
-                    "license_template_path" => self.set_license_template(),
+                    "max_width"
+                    | "use_small_heuristics"
+                    | "fn_call_width"
+                    | "attr_fn_like_width"
+                    | "struct_lit_width"
+                    | "struct_variant_width"
+                    | "array_width"
+                    | "chain_width"
+                    | "single_line_if_else_max_width" => self.set_heuristics(),
+                    "license_template_path" | "license_owner" => self.set_license_template(),
                     &_ => (),
                 }
             }
@@ -278,24 +357,84 @@ macro_rules! create_config {
                 )+
             }
 
+            /// Writes a JSON description of every user-facing option: its name, type,
+            /// default value, stability and documentation.
+            #[allow(unreachable_pub)]
+            pub fn print_schema(out: &mut dyn Write) {
+                let mut options = Vec::new();
+                $(
+                    if !Config::is_hidden_option(stringify!($i)) {
+                        options.push(serde_json::json!({
+                            "name": stringify!($i),
+                            "type": stringify!($ty),
+                            "doc_hint": <$ty>::doc_hint(),
+                            "default": serde_json::to_value($def)
+                                .unwrap_or(serde_json::Value::Null),
+                            "stable": $stb,
+                            "description": [$($dstring),+].join(" "),
+                        }));
+                    }
+                )+
+                writeln!(out, "{}", serde_json::to_string_pretty(&options).unwrap()).unwrap();
+            }
+
             fn set_heuristics(&mut self) {
-                if self.use_small_heuristics.2 == Heuristics::Default {
-                    let max_width = self.max_width.2;
-                    self.set().width_heuristics(WidthHeuristics::scaled(max_width));
-                } else if self.use_small_heuristics.2 == Heuristics::Max {
-                    let max_width = self.max_width.2;
-                    self.set().width_heuristics(WidthHeuristics::set(max_width));
-                } else {
-                    self.set().width_heuristics(WidthHeuristics::null());
+                let max_width = self.max_width.2;
+                let mut heuristics = match self.use_small_heuristics.2 {
+                    Heuristics::Default => WidthHeuristics::scaled(max_width),
+                    Heuristics::Max => WidthHeuristics::set(max_width),
+                    Heuristics::Off => WidthHeuristics::null(),
+                };
+
+                // Widths set explicitly by the user take precedence over the ones derived
+                // from `use_small_heuristics`, but can never exceed `max_width`. Widths
+                // which were not set are updated to reflect the derived values, without
+                // being marked as set, see `is_derived_width`.
+                fn resolve_width(
+                    option: &mut (Cell<bool>, bool, usize, bool),
+                    heuristic: &mut usize,
+                    max_width: usize,
+                ) {
+                    if option.1 {
+                        *heuristic = ::std::cmp::min(option.2, max_width);
+                    } else {
+                        option.2 = ::std::cmp::min(*heuristic, max_width);
+                    }
                 }
+                resolve_width(&mut self.fn_call_width, &mut heuristics.fn_call_width, max_width);
+                resolve_width(
+                    &mut self.attr_fn_like_width,
+                    &mut heuristics.attr_fn_like_width,
+                    max_width,
+                );
+                resolve_width(
+                    &mut self.struct_lit_width,
+                    &mut heuristics.struct_lit_width,
+                    max_width,
+                );
+                resolve_width(
+                    &mut self.struct_variant_width,
+                    &mut heuristics.struct_variant_width,
+                    max_width,
+                );
+                resolve_width(&mut self.array_width, &mut heuristics.array_width, max_width);
+                resolve_width(&mut self.chain_width, &mut heuristics.chain_width, max_width);
+                resolve_width(
+                    &mut self.single_line_if_else_max_width,
+                    &mut heuristics.single_line_if_else_max_width,
+                    max_width,
+                );
+
+                self.set().width_heuristics(heuristics);
             }
 
             fn set_license_template(&mut self) {
                 if self.was_set().license_template_path() {
                     let lt_path = self.license_template_path();
                     if lt_path.len() > 0 {
-                        match license::load_and_compile_template(&lt_path) {
-                            Ok(re) => self.license_template = Some(re),
+                        let owner = self.license_owner();
+                        match license::load_and_compile_templates(&lt_path, &owner) {
+                            Ok(lt) => self.license_template = Some(lt),
                             Err(msg) => eprintln!("Warning for license template file {:?}: {}",
                                                 lt_path, msg),
                         }
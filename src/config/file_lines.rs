@@ -1,7 +1,7 @@
 //! This module contains types and functions to support formatting specific line ranges.
 
 use itertools::Itertools;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use std::rc::Rc;
 use std::{cmp, fmt, iter, str};
@@ -104,6 +104,11 @@ impl Range {
         Range { lo, hi }
     }
 
+    /// Returns the range of all lines of a file.
+    pub fn all() -> Range {
+        Range::new(1, usize::max_value())
+    }
+
     fn is_empty(self) -> bool {
         self.lo > self.hi
     }
@@ -130,7 +135,7 @@ impl Range {
         if self.is_empty() || other.is_empty() {
             false
         } else {
-            self.hi + 1 == other.lo || other.hi + 1 == self.lo
+            self.hi.saturating_add(1) == other.lo || other.hi.saturating_add(1) == self.lo
         }
     }
 
@@ -154,7 +159,7 @@ impl Range {
 /// non-overlapping ranges sorted by their start point. An inner `None` is interpreted to mean all
 /// lines in all files.
 #[derive(Clone, Debug, Default, PartialEq)]
-pub struct FileLines(Option<HashMap<FileName, Vec<Range>>>);
+pub struct FileLines(Option<BTreeMap<FileName, Vec<Range>>>);
 
 impl fmt::Display for FileLines {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -173,7 +178,7 @@ impl fmt::Display for FileLines {
 
 /// Normalizes the ranges so that the invariants for `FileLines` hold: ranges are non-overlapping,
 /// and ordered by their start point.
-fn normalize_ranges(ranges: &mut HashMap<FileName, Vec<Range>>) {
+fn normalize_ranges(ranges: &mut BTreeMap<FileName, Vec<Range>>) {
     for ranges in ranges.values_mut() {
         ranges.sort();
         let mut result = vec![];
@@ -194,6 +199,31 @@ fn normalize_ranges(ranges: &mut HashMap<FileName, Vec<Range>>) {
     }
 }
 
+/// Removes the lines of `excluded` from `ranges`. Both are normalized as by `normalize_ranges`.
+fn exclude_ranges(ranges: &[Range], excluded: &[Range]) -> Vec<Range> {
+    let mut result = vec![];
+    for &range in ranges {
+        let mut rest = Some(range);
+        for &excluded in excluded {
+            let range = match rest {
+                Some(range) if range.intersects(excluded) => range,
+                Some(_) => continue,
+                None => break,
+            };
+            if excluded.lo > range.lo {
+                result.push(Range::new(range.lo, excluded.lo - 1));
+            }
+            rest = if excluded.hi < range.hi {
+                Some(Range::new(excluded.hi + 1, range.hi))
+            } else {
+                None
+            };
+        }
+        result.extend(rest);
+    }
+    result
+}
+
 impl FileLines {
     /// Creates a `FileLines` that contains all lines in all files.
     pub(crate) fn all() -> FileLines {
@@ -205,14 +235,31 @@ impl FileLines {
         self.0.is_none()
     }
 
-    pub fn from_ranges(mut ranges: HashMap<FileName, Vec<Range>>) -> FileLines {
+    pub fn from_ranges(mut ranges: BTreeMap<FileName, Vec<Range>>) -> FileLines {
         normalize_ranges(&mut ranges);
         FileLines(Some(ranges))
     }
 
+    /// Returns the ranges of `file_name` as the ranges of stdin, e.g. to format the content of
+    /// the file given on stdin.
+    pub fn for_stdin(&self, file_name: &FileName) -> FileLines {
+        match self.0 {
+            None => FileLines::all(),
+            Some(ref map) => {
+                let ranges = canonicalize_path_string(file_name)
+                    .and_then(|file| map.get(&file))
+                    .cloned()
+                    .unwrap_or_default();
+                let mut stdin_ranges = BTreeMap::new();
+                stdin_ranges.insert(FileName::Stdin, ranges);
+                FileLines::from_ranges(stdin_ranges)
+            }
+        }
+    }
+
     /// Returns an iterator over the files contained in `self`.
     pub fn files(&self) -> Files<'_> {
-        Files(self.0.as_ref().map(HashMap::keys))
+        Files(self.0.as_ref().map(BTreeMap::keys))
     }
 
     /// Returns JSON representation as accepted by the `--file-lines JSON` arg.
@@ -224,7 +271,12 @@ impl FileLines {
                 .flat_map(|(file, ranges)| ranges.iter().map(move |r| (file, r)))
                 .map(|(file, range)| JsonSpan {
                     file: file.to_owned(),
-                    range: (range.lo, range.hi),
+                    range: Some(if *range == Range::all() {
+                        JsonRange::Keyword(ALL_LINES.to_owned())
+                    } else {
+                        JsonRange::Lines(range.lo, range.hi)
+                    }),
+                    excluded_range: None,
                 })
                 .collect(),
         }
@@ -271,7 +323,7 @@ impl FileLines {
 }
 
 /// `FileLines` files iterator.
-pub struct Files<'a>(Option<::std::collections::hash_map::Keys<'a, FileName, Vec<Range>>>);
+pub struct Files<'a>(Option<::std::collections::btree_map::Keys<'a, FileName, Vec<Range>>>);
 
 impl<'a> iter::Iterator for Files<'a> {
     type Item = &'a FileName;
@@ -294,6 +346,10 @@ pub enum FileLinesError {
     Json(json::Error),
     #[error("Can't canonicalize {0}")]
     CannotCanonicalize(FileName),
+    #[error("Invalid range `{0}`, expected a pair of lines or \"all\"")]
+    InvalidRange(String),
+    #[error("A span of {0} has neither a `range` nor a `!range`")]
+    MissingRange(FileName),
 }
 
 // This impl is needed for `Config::override_value` to work for use in tests.
@@ -302,28 +358,74 @@ impl str::FromStr for FileLines {
 
     fn from_str(s: &str) -> Result<FileLines, Self::Err> {
         let v: Vec<JsonSpan> = json::from_str(s).map_err(FileLinesError::Json)?;
-        let mut m = HashMap::new();
+        let mut included = BTreeMap::new();
+        let mut excluded = BTreeMap::new();
         for js in v {
-            let (s, r) = JsonSpan::into_tuple(js)?;
-            m.entry(s).or_insert_with(|| vec![]).push(r);
+            let (file, range, excluded_range) = JsonSpan::into_tuple(js)?;
+            if let Some(range) = range {
+                included
+                    .entry(file.clone())
+                    .or_insert_with(Vec::new)
+                    .push(range);
+            }
+            if let Some(range) = excluded_range {
+                excluded.entry(file).or_insert_with(Vec::new).push(range);
+            }
+        }
+
+        // The files with only excluded ranges are formatted except for them.
+        for file in excluded.keys() {
+            included
+                .entry(file.clone())
+                .or_insert_with(|| vec![Range::all()]);
         }
-        Ok(FileLines::from_ranges(m))
+        normalize_ranges(&mut included);
+        normalize_ranges(&mut excluded);
+        for (file, ranges) in included.iter_mut() {
+            if let Some(excluded) = excluded.get(file) {
+                *ranges = exclude_ranges(ranges, excluded);
+            }
+        }
+        Ok(FileLines::from_ranges(included))
     }
 }
 
-// For JSON decoding.
+// The value of `range` which stands for all the lines of a file.
+const ALL_LINES: &str = "all";
+
+// For JSON decoding. A span has a `range` of lines to format, which is `"all"` for the whole
+// file, and/or a `!range` of lines not to format.
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Deserialize, Serialize)]
 pub struct JsonSpan {
     file: FileName,
-    range: (usize, usize),
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    range: Option<JsonRange>,
+    #[serde(rename = "!range", default, skip_serializing_if = "Option::is_none")]
+    excluded_range: Option<(usize, usize)>,
+}
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+enum JsonRange {
+    Lines(usize, usize),
+    Keyword(String),
 }
 
 impl JsonSpan {
-    fn into_tuple(self) -> Result<(FileName, Range), FileLinesError> {
-        let (lo, hi) = self.range;
+    fn into_tuple(self) -> Result<(FileName, Option<Range>, Option<Range>), FileLinesError> {
+        let range = match self.range {
+            Some(JsonRange::Lines(lo, hi)) => Some(Range::new(lo, hi)),
+            Some(JsonRange::Keyword(ref keyword)) if keyword == ALL_LINES => Some(Range::all()),
+            Some(JsonRange::Keyword(keyword)) => return Err(FileLinesError::InvalidRange(keyword)),
+            None => None,
+        };
+        let excluded_range = self.excluded_range.map(|(lo, hi)| Range::new(lo, hi));
+        if range.is_none() && excluded_range.is_none() {
+            return Err(FileLinesError::MissingRange(self.file));
+        }
         let canonical = canonicalize_path_string(&self.file)
             .ok_or_else(|| FileLinesError::CannotCanonicalize(self.file))?;
-        Ok((canonical, Range::new(lo, hi)))
+        Ok((canonical, range, excluded_range))
     }
 }
 
@@ -406,11 +508,11 @@ mod test {
 
     use super::json::{self, json};
     use super::{FileLines, FileName};
-    use std::{collections::HashMap, path::PathBuf};
+    use std::{collections::BTreeMap, path::PathBuf};
 
     #[test]
     fn file_lines_to_json() {
-        let ranges: HashMap<FileName, Vec<Range>> = [
+        let ranges: BTreeMap<FileName, Vec<Range>> = [
             (
                 FileName::Real(PathBuf::from("src/main.rs")),
                 vec![Range::new(1, 3), Range::new(5, 7)],
@@ -437,4 +539,57 @@ mod test {
             ]}
         );
     }
+
+    #[test]
+    fn file_lines_are_ordered_by_file() {
+        let mut ranges = BTreeMap::new();
+        for file in &["src/main.rs", "src/a.rs", "src/lib.rs"] {
+            ranges.insert(FileName::Real(PathBuf::from(file)), vec![Range::new(1, 2)]);
+        }
+        let file_lines = FileLines::from_ranges(ranges);
+        assert_eq!(
+            file_lines.to_string(),
+            "src/a.rs: 1..2\nsrc/lib.rs: 1..2\nsrc/main.rs: 1..2\n"
+        );
+    }
+
+    #[test]
+    fn file_lines_exclude_ranges() {
+        let file_lines: FileLines = r#"[
+            {"file": "stdin", "range": [1, 30]},
+            {"file": "stdin", "!range": [10, 20]},
+            {"file": "stdin", "!range": [25, 40]}
+        ]"#
+        .parse()
+        .unwrap();
+        assert_eq!(file_lines.to_string(), "stdin: 1..9, 21..24\n");
+
+        let file_lines: FileLines = r#"[{"file": "stdin", "!range": [10, 20]}]"#.parse().unwrap();
+        assert!(file_lines.contains_line(&FileName::Stdin, 9));
+        assert!(!file_lines.contains_line(&FileName::Stdin, 15));
+        assert!(file_lines.contains_line(&FileName::Stdin, 100_000));
+
+        let file_lines: FileLines = r#"[{"file": "stdin", "range": "all"}]"#.parse().unwrap();
+        assert!(file_lines.contains_line(&FileName::Stdin, 100_000));
+        assert_eq!(
+            json::to_value(&file_lines.to_json_spans()).unwrap(),
+            json! {[{"file": "stdin", "range": "all"}]}
+        );
+
+        assert!(r#"[{"file": "stdin", "range": "some"}]"#.parse::<FileLines>().is_err());
+        assert!(r#"[{"file": "stdin"}]"#.parse::<FileLines>().is_err());
+    }
+
+    #[test]
+    fn file_lines_for_stdin() {
+        let file_lines: FileLines = r#"[{"file": "src/lib.rs", "range": [3, 5]}]"#.parse().unwrap();
+        let lib = file_lines.for_stdin(&FileName::Real(PathBuf::from("src/lib.rs")));
+        assert!(lib.contains_line(&FileName::Stdin, 4));
+        assert!(!lib.contains_line(&FileName::Stdin, 6));
+        let shape = file_lines.for_stdin(&FileName::Real(PathBuf::from("src/shape.rs")));
+        assert!(!shape.contains_line(&FileName::Stdin, 4));
+        assert!(FileLines::all()
+            .for_stdin(&FileName::Real(PathBuf::from("src/lib.rs")))
+            .is_all());
+    }
 }
@@ -2,6 +2,7 @@ use std::fmt;
 use std::fs::File;
 use std::io;
 use std::io::Read;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use regex;
 use regex::Regex;
@@ -47,16 +48,20 @@ enum ParsingState {
 
 use self::ParsingState::*;
 
+/// The regex matched by the `{year}` placeholder: a year or a range of years.
+const YEAR_REGEX: &str = r"\d{4}(?:-\d{4})?";
+
 pub(crate) struct TemplateParser {
     parsed: String,
     buffer: String,
     state: ParsingState,
     linum: u32,
     open_brace_line: u32,
+    owner: String,
 }
 
 impl TemplateParser {
-    fn new() -> Self {
+    fn new(owner: &str) -> Self {
         Self {
             parsed: "^".to_owned(),
             buffer: String::new(),
@@ -64,6 +69,7 @@ impl TemplateParser {
             linum: 1,
             // keeps track of last line on which a regex placeholder was started
             open_brace_line: 0,
+            owner: owner.to_owned(),
         }
     }
 
@@ -75,6 +81,8 @@ impl TemplateParser {
     ///
     /// - to insert literal `{`, `}` or `\`, escape it with `\`
     /// - an empty regex placeholder (`{}`) is shorthand for `{.*?}`
+    /// - `{year}` matches a year or a range of years, like `2019` or `2018-2020`
+    /// - `{owner}` matches the `license_owner` option, or any text if it is not set
     ///
     /// This function parses this input format and builds a properly escaped *string* representation
     /// of the equivalent regular expression. It **does not** however guarantee that the returned
@@ -111,7 +119,12 @@ impl TemplateParser {
     /// );
     /// ```
     pub(crate) fn parse(template: &str) -> Result<String, LicenseError> {
-        let mut parser = Self::new();
+        Self::parse_with_owner(template, "")
+    }
+
+    /// Like `parse`, with `{owner}` placeholders matching `owner` if it is not empty.
+    pub(crate) fn parse_with_owner(template: &str, owner: &str) -> Result<String, LicenseError> {
+        let mut parser = Self::new(owner);
         for chr in template.chars() {
             if chr == '\n' {
                 parser.linum += 1;
@@ -180,11 +193,13 @@ impl TemplateParser {
             '}' => {
                 match brace_nesting {
                     1 => {
-                        // default regex for empty placeholder {}
-                        if self.buffer.is_empty() {
-                            self.parsed.push_str(".*?");
-                        } else {
-                            self.parsed.push_str(&self.buffer);
+                        match self.buffer.as_str() {
+                            // default regex for empty placeholder {}
+                            "" => self.parsed.push_str(".*?"),
+                            "year" => self.parsed.push_str(YEAR_REGEX),
+                            "owner" if self.owner.is_empty() => self.parsed.push_str(".+?"),
+                            "owner" => self.parsed.push_str(&regex::escape(&self.owner)),
+                            _ => self.parsed.push_str(&self.buffer),
                         }
                         self.buffer.clear();
                         Lit
@@ -212,17 +227,109 @@ impl TemplateParser {
     }
 }
 
-pub(crate) fn load_and_compile_template(path: &str) -> Result<Regex, LicenseError> {
-    let mut lt_file = File::open(&path)?;
-    let mut lt_str = String::new();
-    lt_file.read_to_string(&mut lt_str)?;
-    let lt_parsed = TemplateParser::parse(&lt_str)?;
-    Ok(Regex::new(&lt_parsed)?)
+/// Renders a license template into a header which can be inserted into a file, substituting the
+/// current year for `{year}` and `owner` for `{owner}`.
+///
+/// Returns `None` if the template has other placeholders, or `{owner}` and `owner` is empty, since
+/// there is nothing to substitute for them.
+fn render_template(template: &str, owner: &str) -> Option<String> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut chars = template.chars();
+    while let Some(chr) = chars.next() {
+        match chr {
+            '\\' => rendered.push(chars.next()?),
+            '{' => {
+                let placeholder: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                match placeholder.as_str() {
+                    "year" => rendered.push_str(&current_year().to_string()),
+                    "owner" if !owner.is_empty() => rendered.push_str(owner),
+                    _ => return None,
+                }
+            }
+            _ => rendered.push(chr),
+        }
+    }
+    if !rendered.ends_with('\n') {
+        rendered.push('\n');
+    }
+    Some(rendered)
+}
+
+fn current_year() -> u64 {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() / 86_400);
+    year_of_day(days)
+}
+
+// Returns the year of the day `days` after the Unix epoch.
+fn year_of_day(days: u64) -> u64 {
+    // Converts days since the epoch to a year of the proleptic Gregorian calendar, in eras of
+    // 400 years starting on March 1st (see Howard Hinnant's `civil_from_days`).
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    // Months are counted from March, so January and February belong to the next year.
+    let year = era * 400 + year_of_era;
+    if month_index >= 10 {
+        year + 1
+    } else {
+        year
+    }
+}
+
+/// A set of compiled license templates, any of which a file may match.
+#[derive(Clone, Debug)]
+pub struct LicenseTemplate {
+    templates: Vec<(String, Regex)>,
+    owner: String,
+}
+
+impl LicenseTemplate {
+    /// Returns `true` if the beginning of `text` matches any of the templates.
+    pub fn is_match(&self, text: &str) -> bool {
+        self.templates.iter().any(|(_, re)| re.is_match(text))
+    }
+
+    /// Renders the first template which can be rendered, see `render_template`.
+    pub fn render(&self) -> Option<String> {
+        self.templates
+            .iter()
+            .find_map(|(template, _)| render_template(template, &self.owner))
+    }
+}
+
+/// Loads and compiles the license templates at `paths`, which are separated by commas.
+pub(crate) fn load_and_compile_templates(
+    paths: &str,
+    owner: &str,
+) -> Result<LicenseTemplate, LicenseError> {
+    let mut templates = vec![];
+    for path in paths
+        .split(',')
+        .map(str::trim)
+        .filter(|path| !path.is_empty())
+    {
+        let mut lt_file = File::open(&path)?;
+        let mut lt_str = String::new();
+        lt_file.read_to_string(&mut lt_str)?;
+        let lt_parsed = TemplateParser::parse_with_owner(&lt_str, owner)?;
+        let regex = Regex::new(&lt_parsed)?;
+        templates.push((lt_str, regex));
+    }
+    Ok(LicenseTemplate {
+        templates,
+        owner: owner.to_owned(),
+    })
 }
 
 #[cfg(test)]
 mod test {
-    use super::TemplateParser;
+    use super::{current_year, render_template, year_of_day, TemplateParser};
 
     #[test]
     fn test_parse_license_template() {
@@ -263,4 +370,47 @@ mod test {
             "parsing failed, incomplete escape sequence on l. 1"
         );
     }
+
+    #[test]
+    fn test_parse_license_template_placeholders() {
+        assert_eq!(
+            TemplateParser::parse("Copyright {year} {owner}").unwrap(),
+            r"^Copyright \d{4}(?:-\d{4})? .+?"
+        );
+        assert_eq!(
+            TemplateParser::parse_with_owner("Copyright {year} {owner}", "A. B. Cd").unwrap(),
+            r"^Copyright \d{4}(?:-\d{4})? A\. B\. Cd"
+        );
+    }
+
+    #[test]
+    fn test_render_license_template() {
+        assert_eq!(
+            render_template(r"// Copyright {year} {owner} \{x\}", "The Devs").unwrap(),
+            format!("// Copyright {} The Devs {{x}}\n", current_year())
+        );
+        assert!(render_template("// Copyright {year} {owner}", "").is_none());
+        assert!(render_template(r"// Copyright {\d+}", "The Devs").is_none());
+        assert!(current_year() >= 2020);
+    }
+
+    #[test]
+    fn test_year_of_day() {
+        assert_eq!(year_of_day(0), 1970);
+        // The last and first days of years.
+        assert_eq!(year_of_day(364), 1970);
+        assert_eq!(year_of_day(365), 1971);
+        assert_eq!(year_of_day(11_322), 2000);
+        assert_eq!(year_of_day(11_323), 2001);
+        assert_eq!(year_of_day(18_627), 2020);
+        assert_eq!(year_of_day(18_628), 2021);
+        // Leap days, also of a century which is a leap year, and the end of February of a
+        // century which is not.
+        assert_eq!(year_of_day(11_016), 2000);
+        assert_eq!(year_of_day(18_321), 2020);
+        assert_eq!(year_of_day(18_322), 2020);
+        assert_eq!(year_of_day(47_540), 2100);
+        assert_eq!(year_of_day(47_541), 2100);
+        assert_eq!(year_of_day(47_846), 2100);
+    }
 }
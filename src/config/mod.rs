@@ -1,16 +1,18 @@
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::default::Default;
 use std::fs::File;
 use std::io::{Error, ErrorKind, Read};
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use std::{env, fs};
 
-use regex::Regex;
 use thiserror::Error;
 
 use crate::config::config_type::ConfigType;
 #[allow(unreachable_pub)]
 pub use crate::config::file_lines::{FileLines, FileName, Range};
+pub use crate::config::license::LicenseTemplate;
 #[allow(unreachable_pub)]
 pub use crate::config::lists::*;
 #[allow(unreachable_pub)]
@@ -34,10 +36,39 @@ create_config! {
     max_width: usize, 100, true, "Maximum width of each line";
     hard_tabs: bool, false, true, "Use tab characters for indentation, spaces for alignment";
     tab_spaces: usize, 4, true, "Number of spaces per tab";
+    smart_tabs: bool, false, false,
+        "Only use tab characters for block indentation, never for alignment. Requires hard_tabs";
     newline_style: NewlineStyle, NewlineStyle::Auto, true, "Unix or Windows line endings";
+    require_final_newline: FinalNewline, FinalNewline::Always, false,
+        "Whether files end with a newline";
+    strip_bom: bool, false, false, "Remove the UTF-8 byte order mark from the start of files";
     use_small_heuristics: Heuristics, Heuristics::Default, true, "Whether to use different \
         formatting for items and expressions if they satisfy a heuristic notion of 'small'";
+    fn_call_width: usize, 60, false,
+        "Maximum width of the args of a function call before falling back to vertical formatting";
+    attr_fn_like_width: usize, 70, false, "Maximum width of the args of a function-like \
+        attributes before falling back to vertical formatting";
+    struct_lit_width: usize, 18, false,
+        "Maximum width in the body of a struct lit before falling back to vertical formatting";
+    struct_variant_width: usize, 35, false,
+        "Maximum width in the body of a struct variant before falling back to vertical formatting";
+    array_width: usize, 60, false,
+        "Maximum width of an array literal before falling back to vertical formatting";
+    chain_width: usize, 60, false, "Maximum length of a chain to fit on a single line";
+    chain_width_counts_fields: bool, true, false,
+        "Count field accesses along with method calls when deciding whether a chain is limited \
+         to chain_width";
+    chain_tuple_indices_attached: bool, false, false,
+        "Keep the tuple indices of a vertical chain on the line of what they index";
+    single_line_if_else_max_width: usize, 50, false, "Maximum line length for single line \
+        if-else expressions. A value of zero means always break if-else expressions";
     indent_style: IndentStyle, IndentStyle::Block, false, "How do we indent expressions or items";
+    fn_args_indent_style: ConstructIndentStyle, ConstructIndentStyle::Default, false,
+        "How do we indent the parameters and return type of functions, overriding indent_style";
+    where_indent_style: ConstructIndentStyle, ConstructIndentStyle::Default, false,
+        "How do we indent where-clauses, overriding indent_style";
+    control_flow_indent_style: ConstructIndentStyle, ConstructIndentStyle::Default, false,
+        "How do we indent the conditions of control flow expressions, overriding indent_style";
 
     // Comments. macros, and strings
     wrap_comments: bool, false, false, "Break comments to fit on the line";
@@ -46,20 +77,48 @@ create_config! {
         "Maximum length of comments. No effect unless wrap_comments = true";
     normalize_comments: bool, false, false, "Convert /* */ comments to // comments where possible";
     normalize_doc_attributes: bool, false, false, "Normalize doc attributes as doc comments";
+    format_doc_attributes: bool, false, false,
+        "Wrap the string literals of doc attributes which exceed the max width";
     license_template_path: String, String::default(), false,
-        "Beginning of file must match license template";
+        "Beginning of file must match one of the comma separated license templates";
+    license_owner: String, String::default(), false,
+        "Owner substituted for `{owner}` in license templates";
+    insert_license: bool, false, false,
+        "Insert the license template into files which do not match it";
     format_strings: bool, false, false, "Format string literals where necessary";
+    format_strings_style: FormatStringsStyle, FormatStringsStyle::Backslash, false,
+        "How to continue a string literal split by format_strings";
     format_macro_matchers: bool, false, false,
         "Format the metavariable matching patterns in macros";
     format_macro_bodies: bool, true, false, "Format the bodies of macros";
+    skip_macro_invocations: MacroNames, MacroNames::default(), false,
+        "Macros whose invocations are left exactly as written";
 
     // Single line expressions and items
     empty_item_single_line: bool, true, false,
-        "Put empty-body functions and impls on a single line";
+        "Put empty-body functions, impls, traits, modules and extern blocks on a single line";
     struct_lit_single_line: bool, true, false,
         "Put small struct literals on a single line";
+    struct_lit_force_multiline_fields: usize, 0, false,
+        "Always put struct literals with more fields than this on multiple lines";
+    struct_pat_force_multiline_fields: usize, 0, false,
+        "Always put struct patterns with more fields than this on multiple lines";
+    struct_pat_rest_own_line: bool, true, false,
+        "Put the `..` of a multi-line struct pattern on its own line";
+    struct_pat_field_align_threshold: usize, 0, false,
+        "Align the bindings of struct pattern fields if the diffs of their names fit within threshold";
+    array_element_per_line_threshold: usize, 0, false,
+        "Maximum number of elements on each line of a multi-line array of short, simple elements";
     fn_single_line: bool, false, false, "Put single-expression functions on a single line";
     where_single_line: bool, false, false, "Force where-clauses to be on a single line";
+    group_lifetime_params: bool, false, false,
+        "Put the lifetime parameters of generics broken over several lines together on a line";
+    where_always_on_new_line: bool, false, false,
+        "Always put the `where` keyword on a new line, even after a multi-line signature";
+    where_compressed: bool, false, false,
+        "Put the predicates of a where-clause on a single line after `where` if they fit";
+    where_trailing_comma: bool, true, false,
+        "Put a trailing comma after the last predicate of a vertical where-clause";
 
     // Imports
     imports_indent: IndentStyle, IndentStyle::Block, false, "Indent of imports";
@@ -68,8 +127,15 @@ create_config! {
 
     // Ordering
     reorder_imports: bool, true, true, "Reorder import and extern crate statements alphabetically";
+    reorder_imports_in_group: bool, true, false,
+        "Only reorder imports within the groups separated by blank lines";
     reorder_modules: bool, true, true, "Reorder module statements alphabetically in group";
     reorder_impl_items: bool, false, false, "Reorder impl items";
+    reorder_impls: bool, false, false,
+        "Reorder consecutive impl blocks by trait and then type name";
+    reorder_foreign_items: bool, false, false, "Reorder the items of extern blocks alphabetically";
+    reorder_inner_attributes: bool, false, false,
+        "Put the feature gates of a module before its other inner attributes";
 
     // Spaces around punctuation
     type_punctuation_density: TypeDensity, TypeDensity::Wide, false,
@@ -77,31 +143,61 @@ create_config! {
     space_before_colon: bool, false, false, "Leave a space before the colon";
     space_after_colon: bool, true, false, "Leave a space after the colon";
     spaces_around_ranges: bool, false, false, "Put spaces around the  .. and ..= range operators";
+    spaces_within_parens: bool, false, false, "Put spaces just inside parentheses";
+    spaces_within_brackets: bool, false, false, "Put spaces just inside square brackets";
+    spaces_within_angle_brackets: bool, false, false,
+        "Put spaces just inside the angle brackets of generics";
+    spaces_within_struct_lit_braces: bool, true, false,
+        "Put spaces just inside the braces of single-line struct literals and patterns";
     binop_separator: SeparatorPlace, SeparatorPlace::Front, false,
         "Where to put a binary operator when a binary expression goes multiline";
 
     // Misc.
     remove_nested_parens: bool, true, true, "Remove nested parens";
     combine_control_expr: bool, true, false, "Combine control expressions with function calls";
+    combine_control_expr_in_closures: bool, true, false,
+        "Combine match and loop expressions with closure parameters";
+    combine_control_expr_in_return: bool, true, false,
+        "Combine control expressions with the return keyword";
     overflow_delimited_expr: bool, false, false,
         "Allow trailing bracket/brace delimited expressions to overflow";
     struct_field_align_threshold: usize, 0, false,
         "Align struct fields if their diffs fits within threshold";
     enum_discrim_align_threshold: usize, 0, false,
         "Align enum variants discrims, if their diffs fit within threshold";
+    foreign_item_align_threshold: usize, 0, false,
+        "Align the return types of foreign functions and the types of foreign statics, if the \
+         diffs of their signatures fit within threshold";
+    trailing_comment_align_threshold: usize, 0, false,
+        "Align the trailing comments of consecutive lines, if their diffs fit within threshold";
     match_arm_blocks: bool, true, false, "Wrap the body of arms in blocks when it does not fit on \
         the same line with the pattern of arms";
     match_arm_leading_pipes: MatchArmLeadingPipe, MatchArmLeadingPipe::Never, true,
         "Determines whether leading pipes are emitted on match arms";
+    match_arm_wrapping: MatchArmWrapping, MatchArmWrapping::Default, false,
+        "Determines when the body of a match arm is wrapped in a block";
     force_multiline_blocks: bool, false, false,
         "Force multiline closure bodies and match arms to be wrapped in a block";
+    closure_multiline_blocks: bool, false, false,
+        "Force multiline closure bodies to be wrapped in a block";
+    closure_overflow_last_arg: bool, true, false,
+        "Allow a closure which is the last argument of a call to overflow";
+    move_closure_own_line_threshold: usize, 0, false,
+        "Put a move closure on its own line if it is longer than this number of lines";
     fn_args_layout: Density, Density::Tall, true,
         "Control the layout of arguments in a function";
+    fn_params_force_multiline_threshold: usize, 0, false,
+        "Put each parameter on its own line if a function has more parameters than this";
     brace_style: BraceStyle, BraceStyle::SameLineWhere, false, "Brace style for items";
     control_brace_style: ControlBraceStyle, ControlBraceStyle::AlwaysSameLine, false,
         "Brace style for control flow constructs";
     trailing_semicolon: bool, true, false,
         "Add trailing semicolon after break, continue and return";
+    semicolon_if_nothing_returned: bool, false, false,
+        "Remove redundant semicolons after items and unit-valued blocks, and add semicolons \
+         after assignments ending a block";
+    return_style: ReturnStyle, ReturnStyle::Preserve, false,
+        "Whether the value of a function is returned with a return statement or a tail expression";
     trailing_comma: SeparatorTactic, SeparatorTactic::Vertical, false,
         "How to handle trailing commas for lists";
     match_block_trailing_comma: bool, false, false,
@@ -110,6 +206,8 @@ create_config! {
         "Maximum number of blank lines which can be put between items";
     blank_lines_lower_bound: usize, 0, false,
         "Minimum number of blank lines which must be put between items";
+    remove_blank_lines_after_comments: bool, false, false,
+        "Remove the blank lines between a comment and the code following it";
     edition: Edition, Edition::Edition2015, true, "The edition of the parser (RFC 2052)";
     version: Version, Version::One, false, "Version of formatting rules";
     inline_attribute_width: usize, 0, false,
@@ -118,32 +216,92 @@ create_config! {
 
     // Options that can change the source code beyond whitespace/blocks (somewhat linty things)
     merge_derives: bool, true, true, "Merge multiple `#[derive(...)]` into a single one";
+    sort_derives: bool, false, false, "Sort the traits of `#[derive(...)]` alphabetically";
+    normalize_cfg: bool, false, false,
+        "Sort and deduplicate the predicates of `any(..)` and `all(..)` in `#[cfg(..)]`";
     use_try_shorthand: bool, false, true, "Replace uses of the try! macro by the ? shorthand";
     use_field_init_shorthand: bool, false, true, "Use field initialization shorthand if possible";
     force_explicit_abi: bool, true, true, "Always print the abi for extern items";
+    hex_literal_case: LiteralCase, LiteralCase::Preserve, false,
+        "Case of the digits of hexadecimal integer literals";
+    float_exponent_case: LiteralCase, LiteralCase::Preserve, false,
+        "Case of the exponent marker of float literals";
+    group_literal_digits: bool, false, false,
+        "Group the digits of decimal, hexadecimal and binary literals with underscores";
+    hex_escape_style: HexEscapeStyle, HexEscapeStyle::Preserve, false,
+        "Form of numeric escape sequences in char and string literals";
+    escape_hex_case: LiteralCase, LiteralCase::Preserve, false,
+        "Case of the hex digits of escape sequences in char and string literals";
     condense_wildcard_suffixes: bool, false, false, "Replace strings of _ wildcards by a single .. \
                                                      in tuple patterns";
+    collapse_else_if: bool, false, false,
+        "Collapse an else block containing only an if expression into an else-if";
 
     // Control options (changes the operation of rustfmt, rather than the formatting)
     color: Color, Color::Auto, false,
         "What Color option to use when none is supplied: Always, Never, Auto";
+    diff_style: DiffStyle, DiffStyle::Unified, false,
+        "How the diffs printed by --check are laid out: Unified, SideBySide";
+    diff_summary: bool, false, false,
+        "Print how many lines differ in each misformatted file instead of its diff";
+    diff_context: usize, 3, false,
+        "Number of unchanged lines printed around the changed lines of diffs";
     required_version: String, env!("CARGO_PKG_VERSION").to_owned(), false,
         "Require a specific version of rustfmt";
     unstable_features: bool, false, false,
             "Enables unstable features. Only available on nightly channel";
     disable_all_formatting: bool, false, false, "Don't reformat anything";
+    minimal_diff: bool, false, false,
+        "Keep the original lines which formatting only changes the whitespace of";
+    formatting_profile: FormattingProfile, FormattingProfile::Full, false,
+        "How much formatting changes the layout of the code: Full, Conservative";
     skip_children: bool, false, false, "Don't reformat out of line modules";
+    module_traversal: ModuleTraversal, ModuleTraversal::FormatReachable, false,
+        "Which out of line modules are formatted along with an input file";
+    format_included_files: bool, false, false,
+        "Reformat files included by item-level `include!` calls with a literal path";
+    symlinks: SymlinkPolicy, SymlinkPolicy::FollowFiles, false,
+        "How files and directories reached through symbolic links are formatted";
+    canonicalize_paths: bool, true, false,
+        "Refer to the formatted files by their canonical paths rather than as they are given";
     hide_parse_errors: bool, false, false, "Hide errors from the parser";
+    recover_parse_errors: bool, false, false,
+        "Format the code of files with parse errors the parser recovered from, leaving the items \
+         and statements with errors as they are";
+    file_rewrite_time_limit: usize, 60, false,
+        "Number of seconds after which formatting a file gives up, leaving the code left as it is";
     error_on_line_overflow: bool, false, false, "Error if unable to get all lines within max_width";
     error_on_unformatted: bool, false, false,
         "Error if unable to get comments or string literals within max_width, \
          or they are left with trailing whitespaces";
+    error_on_raw_string_overflow: bool, true, false,
+        "Error if unable to get lines containing raw or byte string literals within max_width";
+    ignore_long_urls: bool, false, false,
+        "Don't report lines which exceed max_width because of a URL or path in a comment or string";
+    verify_comments: bool, false, false,
+        "Check that formatting neither drops nor duplicates comments, and leave files \
+         unformatted if it does";
+    verify_semantics: bool, false, false,
+        "Check that the formatted code parses to the same syntax tree as the original code, and \
+         leave files unformatted if it doesn't";
     report_todo: ReportTactic, ReportTactic::Never, false,
         "Report all, none or unnumbered occurrences of TODO in source file comments";
     report_fixme: ReportTactic, ReportTactic::Never, false,
         "Report all, none or unnumbered occurrences of FIXME in source file comments";
+    issue_pattern: IssuePattern, IssuePattern::default(), false,
+        "Regex which the issue reference of a TODO or FIXME must match when reporting \
+         unnumbered ones";
     ignore: IgnoreList, IgnoreList::default(), false,
         "Skip formatting the specified files and directories";
+    format_cache: String, String::default(), false,
+        "Path of a cache of the files known to be formatted, which are skipped while unchanged";
+    failure_report_dir: String, String::default(), false,
+        "Directory to write reports of the code which could not be formatted to";
+    out_dir: String, String::default(), false,
+        "Directory to write the formatted files to, mirroring the input tree, instead of \
+         overwriting them";
+    strict_config: bool, false, false,
+        "Error if the config file contains unknown or deprecated options";
 
     // Not user-facing
     verbose: Verbosity, Verbosity::Normal, false, "How much to information to emit to the user";
@@ -221,8 +379,12 @@ impl Config {
     pub(super) fn from_resolved_toml_path(dir: &Path) -> Result<(Config, Option<PathBuf>), Error> {
         /// Try to find a project file in the given directory and its parents.
         /// Returns the path of a the nearest project file if one exists,
-        /// or `None` if no project file was found.
-        fn resolve_project_file(dir: &Path) -> Result<Option<PathBuf>, Error> {
+        /// or `None` if no project file was found. The directories searched are added to
+        /// `searched`.
+        fn resolve_project_file(
+            dir: &Path,
+            searched: &mut Vec<PathBuf>,
+        ) -> Result<Option<PathBuf>, Error> {
             let mut current = if dir.is_relative() {
                 env::current_dir()?.join(dir)
             } else {
@@ -232,6 +394,7 @@ impl Config {
             current = fs::canonicalize(current)?;
 
             loop {
+                searched.push(current.clone());
                 match get_toml_path(&current) {
                     Ok(Some(path)) => return Ok(Some(path)),
                     Err(e) => return Err(e),
@@ -246,6 +409,7 @@ impl Config {
 
             // If nothing was found, check in the home directory.
             if let Some(home_dir) = dirs::home_dir() {
+                searched.push(home_dir.clone());
                 if let Some(path) = get_toml_path(&home_dir)? {
                     return Ok(Some(path));
                 }
@@ -254,6 +418,7 @@ impl Config {
             // If none was found ther either, check in the user's configuration directory.
             if let Some(mut config_dir) = dirs::config_dir() {
                 config_dir.push("rustfmt");
+                searched.push(config_dir.clone());
                 if let Some(path) = get_toml_path(&config_dir)? {
                     return Ok(Some(path));
                 }
@@ -262,10 +427,16 @@ impl Config {
             Ok(None)
         }
 
-        match resolve_project_file(dir)? {
-            None => Ok((Config::default(), None)),
-            Some(path) => Config::from_toml_path(&path).map(|config| (config, Some(path))),
+        if let Some(cached) = cached_config(dir) {
+            return Ok(cached);
         }
+        let mut searched = vec![];
+        let (config, path) = match resolve_project_file(dir, &mut searched)? {
+            None => (Config::default(), None),
+            Some(path) => (Config::from_toml_path(&path)?, Some(path)),
+        };
+        cache_config(dir, &config, path.as_ref().map(|p| &**p), searched);
+        Ok((config, path))
     }
 
     pub(crate) fn from_toml(toml: &str, dir: &Path) -> Result<Config, String> {
@@ -278,16 +449,21 @@ impl Config {
             .ok_or_else(|| String::from("Parsed config was not table"))?;
         for key in table.keys() {
             if !Config::is_valid_name(key) {
-                let msg = &format!("Warning: Unknown configuration option `{}`\n", key);
-                err.push_str(msg)
+                err.push_str(&unknown_option_message(key));
             }
         }
         match parsed.try_into() {
             Ok(parsed_config) => {
+                let config = Config::default().fill_from_parsed_config(parsed_config, dir);
                 if !err.is_empty() {
+                    if config.strict_config() {
+                        err.push_str("Error: Unknown or deprecated configuration options found.\n");
+                        err.push_str("Please check your config file or disable `strict_config`.");
+                        return Err(err);
+                    }
                     eprint!("{}", err);
                 }
-                Ok(Config::default().fill_from_parsed_config(parsed_config, dir))
+                Ok(config)
             }
             Err(e) => {
                 err.push_str("Error: Decoding config file failed:\n");
@@ -299,6 +475,103 @@ impl Config {
     }
 }
 
+/// Options which used to be recognised by rustfmt, paired with the option that
+/// replaces them (if any).
+const DEPRECATED_OPTIONS: &[(&str, Option<&str>)] = &[
+    ("array_layout", Some("indent_style")),
+    ("chain_indent", Some("indent_style")),
+    (
+        "error_on_line_overflow_comments",
+        Some("error_on_unformatted"),
+    ),
+    ("fn_args_density", Some("fn_args_layout")),
+    ("fn_args_paren_newline", None),
+    ("fn_call_style", Some("indent_style")),
+    ("fn_empty_single_line", Some("empty_item_single_line")),
+    ("force_format_strings", Some("format_strings")),
+    ("generics_indent", Some("indent_style")),
+    ("impl_empty_single_line", Some("empty_item_single_line")),
+    (
+        "match_pattern_separator_break_point",
+        Some("binop_separator"),
+    ),
+    (
+        "multiline_closure_forces_block",
+        Some("force_multiline_blocks"),
+    ),
+    (
+        "multiline_match_arm_forces_block",
+        Some("force_multiline_blocks"),
+    ),
+    ("reorder_extern_crates", Some("reorder_imports")),
+    ("reorder_imported_names", Some("reorder_imports")),
+    ("space_before_type_annotation", Some("space_before_colon")),
+    (
+        "spaces_within_parens_and_brackets",
+        Some("spaces_within_parens"),
+    ),
+    ("struct_lit_style", Some("indent_style")),
+    ("where_style", Some("indent_style")),
+    ("wrap_match_arms", Some("match_arm_blocks")),
+    ("write_mode", None),
+];
+
+/// Builds the warning for a key in a config file that does not name a known option,
+/// pointing at its replacement or at a similarly spelled option where possible.
+fn unknown_option_message(key: &str) -> String {
+    if let Some(&(_, replacement)) = DEPRECATED_OPTIONS.iter().find(|(name, _)| *name == key) {
+        return match replacement {
+            Some(replacement) => format!(
+                "Warning: `{}` is deprecated; use `{}` instead\n",
+                key, replacement
+            ),
+            None => format!(
+                "Warning: `{}` is deprecated and no longer has any effect\n",
+                key
+            ),
+        };
+    }
+
+    match suggest_option_name(key) {
+        Some(name) => format!(
+            "Warning: Unknown configuration option `{}`, did you mean `{}`?\n",
+            key, name
+        ),
+        None => format!("Warning: Unknown configuration option `{}`\n", key),
+    }
+}
+
+/// Returns the known option whose name is closest to `key`, provided the two are
+/// close enough for `key` to plausibly be a typo.
+fn suggest_option_name(key: &str) -> Option<&'static str> {
+    let max_distance = key.len().max(3) / 3;
+    Config::option_names()
+        .iter()
+        .filter(|name| !Config::is_hidden_option(name))
+        .map(|name| (edit_distance(key, name), *name))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, name)| name)
+}
+
+/// Computes the Levenshtein distance between `a` and `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b_chars: Vec<char> = b.chars().collect();
+    let mut prev_row: Vec<usize> = (0..=b_chars.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut row = Vec::with_capacity(b_chars.len() + 1);
+        row.push(i + 1);
+        for (j, b_char) in b_chars.iter().enumerate() {
+            let substitution = prev_row[j] + if a_char == *b_char { 0 } else { 1 };
+            let insertion = row[j] + 1;
+            let deletion = prev_row[j + 1] + 1;
+            row.push(substitution.min(insertion).min(deletion));
+        }
+        prev_row = row;
+    }
+    prev_row[b_chars.len()]
+}
+
 /// Loads a config by checking the client-supplied options and if appropriate, the
 /// file system (including searching the file system for overrides).
 pub fn load_config<O: CliOptions>(
@@ -326,6 +599,64 @@ pub fn load_config<O: CliOptions>(
     })
 }
 
+/// A config resolved for a directory, along with the stamps of the config file and of the
+/// directories searched for it when it was loaded. Creating or removing a config file in one of
+/// these directories changes the stamp of the directory.
+struct CachedConfig {
+    config: Config,
+    path: Option<PathBuf>,
+    stamps: Vec<(PathBuf, Option<FileStamp>)>,
+}
+
+/// The modification time and the size of a file or directory.
+type FileStamp = (SystemTime, u64);
+
+thread_local! {
+    /// Configs resolved for directories, so that formatting many files doesn't search for, read
+    /// and parse the same config file for each of them.
+    static CONFIG_CACHE: RefCell<HashMap<PathBuf, CachedConfig>> = RefCell::new(HashMap::new());
+}
+
+fn file_stamp(path: &Path) -> Option<FileStamp> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Returns the config cached for `dir`, unless its config file or one of the directories
+/// searched for it has been modified since.
+fn cached_config(dir: &Path) -> Option<(Config, Option<PathBuf>)> {
+    CONFIG_CACHE.with(|cache| {
+        let cache = cache.borrow();
+        let cached = cache.get(dir)?;
+        let is_unchanged = cached
+            .stamps
+            .iter()
+            .all(|(path, stamp)| file_stamp(path) == *stamp);
+        if is_unchanged {
+            Some((cached.config.clone(), cached.path.clone()))
+        } else {
+            None
+        }
+    })
+}
+
+fn cache_config(dir: &Path, config: &Config, path: Option<&Path>, searched: Vec<PathBuf>) {
+    let stamps = searched
+        .into_iter()
+        .chain(path.map(Path::to_path_buf))
+        .map(|path| {
+            let stamp = file_stamp(&path);
+            (path, stamp)
+        })
+        .collect();
+    let cached = CachedConfig {
+        config: config.clone(),
+        path: path.map(Path::to_path_buf),
+        stamps,
+    };
+    CONFIG_CACHE.with(|cache| cache.borrow_mut().insert(dir.to_path_buf(), cached));
+}
+
 // Check for the presence of known config file names (`rustfmt.toml, `.rustfmt.toml`) in `dir`
 //
 // Return the path if a config file exists, empty if no file exists, and Error for IO errors
@@ -394,6 +725,8 @@ mod test {
                  expressions if they satisfy a heuristic notion of 'small'.";
             license_template_path: String, String::default(), false,
                 "Beginning of file must match license template";
+            license_owner: String, String::default(), false,
+                "Owner substituted for `{owner}` in license templates";
             required_version: String, env!("CARGO_PKG_VERSION").to_owned(), false,
                 "Require a specific version of rustfmt.";
             ignore: IgnoreList, IgnoreList::default(), false,
@@ -405,6 +738,16 @@ mod test {
                     via the --file-lines option";
             width_heuristics: WidthHeuristics, WidthHeuristics::scaled(100), false,
                 "'small' heuristic values";
+            fn_call_width: usize, 60, false, "Maximum width of the args of a function call";
+            attr_fn_like_width: usize, 70, false,
+                "Maximum width of the args of a function-like attributes";
+            struct_lit_width: usize, 18, false, "Maximum width in the body of a struct lit";
+            struct_variant_width: usize, 35, false,
+                "Maximum width in the body of a struct variant";
+            array_width: usize, 60, false, "Maximum width of an array literal";
+            chain_width: usize, 60, false, "Maximum length of a chain to fit on a single line";
+            single_line_if_else_max_width: usize, 50, false,
+                "Maximum line length for single line if-else expressions";
 
             // Options that are used by the tests
             stable_option: bool, false, true, "A stable option";
@@ -439,6 +782,83 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_non_default_options_to_toml() {
+        let config = Config::from_toml("max_width = 80\ntab_spaces = 4", Path::new("")).unwrap();
+        let toml = config.non_default_options().to_toml().unwrap();
+        assert_eq!(toml, "max_width = 80\n");
+
+        let toml = Config::default().non_default_options().to_toml().unwrap();
+        assert_eq!(toml, "");
+    }
+
+    #[test]
+    fn test_derived_widths_are_not_printed() {
+        let config = Config::from_toml("max_width = 200", Path::new("")).unwrap();
+        assert_eq!(
+            config.non_default_options().to_toml().unwrap(),
+            "max_width = 200\n"
+        );
+        let toml = config.all_options().to_toml().unwrap();
+        assert!(toml.contains("max_width = 200"));
+        assert!(!toml.contains("fn_call_width"));
+
+        if crate::is_nightly_channel!() {
+            let toml = "max_width = 200\nfn_call_width = 60";
+            let config = Config::from_toml(toml, Path::new("")).unwrap();
+            let toml = config.all_options().to_toml().unwrap();
+            assert!(toml.contains("fn_call_width = 60"));
+        }
+    }
+
+    #[test]
+    fn test_width_heuristics_derived_from_max_width() {
+        let config = Config::from_toml("max_width = 200", Path::new("")).unwrap();
+        assert_eq!(config.width_heuristics(), WidthHeuristics::scaled(200));
+        assert_eq!(config.fn_call_width(), 120);
+
+        let config = Config::from_toml(
+            "max_width = 80\nuse_small_heuristics = \"Max\"",
+            Path::new(""),
+        )
+        .unwrap();
+        assert_eq!(config.width_heuristics(), WidthHeuristics::set(80));
+        assert_eq!(config.chain_width(), 80);
+    }
+
+    #[test]
+    fn test_override_width_heuristics() {
+        if !crate::is_nightly_channel!() {
+            return;
+        }
+        let toml = "max_width = 80\nfn_call_width = 30\narray_width = 100";
+        let config = Config::from_toml(toml, Path::new("")).unwrap();
+        let heuristics = config.width_heuristics();
+        assert_eq!(heuristics.fn_call_width, 30);
+        // Explicit widths cannot exceed `max_width`.
+        assert_eq!(heuristics.array_width, 80);
+        assert_eq!(heuristics.chain_width, 60);
+
+        let mut config = Config::default();
+        config.override_value("use_small_heuristics", "Max");
+        config.override_value("struct_lit_width", "25");
+        assert_eq!(config.width_heuristics().struct_lit_width, 25);
+        assert_eq!(config.width_heuristics().fn_call_width, 100);
+    }
+
+    #[test]
+    fn test_set_width_heuristics() {
+        let mut config = Config::default();
+        config.set().chain_width(30);
+        // Resolving the heuristics again keeps the width which was set.
+        config.set().max_width(80);
+        config.set().use_small_heuristics(Heuristics::Max);
+        assert!(config.was_set().chain_width());
+        assert_eq!(config.chain_width(), 30);
+        assert_eq!(config.width_heuristics().chain_width, 30);
+        assert_eq!(config.width_heuristics().fn_call_width, 80);
+    }
+
     #[test]
     fn test_was_set() {
         let config = Config::from_toml("hard_tabs = true", Path::new("")).unwrap();
@@ -447,6 +867,53 @@ mod test {
         assert_eq!(config.was_set().verbose(), false);
     }
 
+    #[test]
+    fn test_unknown_option_suggestion() {
+        assert_eq!(
+            unknown_option_message("max_widht"),
+            "Warning: Unknown configuration option `max_widht`, did you mean `max_width`?\n"
+        );
+        assert_eq!(
+            unknown_option_message("foo"),
+            "Warning: Unknown configuration option `foo`\n"
+        );
+    }
+
+    #[test]
+    fn test_deprecated_option_message() {
+        assert_eq!(
+            unknown_option_message("fn_args_density"),
+            "Warning: `fn_args_density` is deprecated; use `fn_args_layout` instead\n"
+        );
+        assert_eq!(
+            unknown_option_message("write_mode"),
+            "Warning: `write_mode` is deprecated and no longer has any effect\n"
+        );
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("max_width", "max_width"), 0);
+        assert_eq!(edit_distance("max_widht", "max_width"), 2);
+        assert_eq!(edit_distance("tab_space", "tab_spaces"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_strict_config() {
+        let toml = "max_widht = 100";
+        assert!(Config::from_toml(toml, Path::new("")).is_ok());
+
+        if !crate::is_nightly_channel!() {
+            return;
+        }
+        let toml = "strict_config = true\nmax_widht = 100";
+        assert!(Config::from_toml(toml, Path::new("")).is_err());
+        let toml = "strict_config = true\nmax_width = 100";
+        assert!(Config::from_toml(toml, Path::new("")).is_ok());
+    }
+
     #[test]
     fn test_print_docs_exclude_unstable() {
         use self::mock::Config;
@@ -474,6 +941,34 @@ mod test {
         assert_eq!(s.contains("(unstable)"), true);
     }
 
+    #[test]
+    fn test_print_schema() {
+        use self::mock::Config;
+
+        let mut output = Vec::new();
+        Config::print_schema(&mut output);
+
+        let schema: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        let options = schema.as_array().unwrap();
+        let option = |name: &str| {
+            options
+                .iter()
+                .find(|option| option["name"] == name)
+                .cloned()
+                .unwrap()
+        };
+
+        assert_eq!(option("max_width")["type"], "usize");
+        assert_eq!(option("max_width")["default"], 100);
+        assert_eq!(option("stable_option")["stable"], true);
+        assert_eq!(option("unstable_option")["stable"], false);
+        assert_eq!(
+            option("unstable_option")["description"],
+            "An unstable option"
+        );
+        assert!(options.iter().all(|option| option["name"] != "file_lines"));
+    }
+
     #[test]
     fn test_empty_string_license_template_path() {
         let toml = r#"license_template_path = """#;
@@ -503,74 +998,206 @@ mod test {
         assert!(config.license_template.is_none());
     }
 
+    #[test]
+    fn test_multiple_license_template_paths() {
+        if !crate::is_nightly_channel!() {
+            return;
+        }
+        let toml = r#"
+            license_template_path = "tests/license-template/lt.txt, tests/license-template/owner.txt"
+            license_owner = "The rustfmt developers"
+        "#;
+        let config = Config::from_toml(toml, Path::new("")).unwrap();
+        let license_template = config.license_template.unwrap();
+        assert!(license_template.is_match("// Copyright 2019 The rustfmt developers.\n"));
+        assert!(license_template.is_match("// Copyright The rustfmt developers.\n"));
+        assert!(!license_template.is_match("// Copyright Someone else.\n"));
+        assert_eq!(
+            license_template.render().as_deref(),
+            Some("// Copyright The rustfmt developers.\n")
+        );
+    }
+
+    #[test]
+    fn test_resolved_config_is_cached_until_modified() {
+        let dir = env::temp_dir().join(format!("rustfmt-config-cache-{}", std::process::id()));
+        let nested = dir.join("nested");
+        fs::create_dir_all(&nested).unwrap();
+        let dir = dir.canonicalize().unwrap();
+        let config_file = dir.join("rustfmt.toml");
+        let nested_config_file = nested.canonicalize().unwrap().join("rustfmt.toml");
+        fs::write(&config_file, "max_width = 80\n").unwrap();
+
+        let (config, path) = Config::from_resolved_toml_path(&nested).unwrap();
+        let cached = cached_config(&nested).map(|(config, _)| config.max_width());
+        // A modification is noticed even if it keeps the modification time of the file, as long
+        // as it changes its size.
+        fs::write(&config_file, "max_width = 100\n").unwrap();
+        let (modified, _) = Config::from_resolved_toml_path(&nested).unwrap();
+        // So is a config file created closer to the directory than the cached one, once the
+        // modification time of its directory, which may be coarse, has changed.
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        fs::write(&nested_config_file, "max_width = 70\n").unwrap();
+        let (closer, closer_path) = Config::from_resolved_toml_path(&nested).unwrap();
+        fs::remove_file(&nested_config_file).unwrap();
+        fs::remove_file(&config_file).unwrap();
+        let (_, path_after_removal) = Config::from_resolved_toml_path(&nested).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(config.max_width(), 80);
+        assert_eq!(path.as_ref(), Some(&config_file));
+        assert_eq!(cached, Some(80));
+        assert_eq!(modified.max_width(), 100);
+        assert_eq!(closer.max_width(), 70);
+        assert_eq!(closer_path, Some(nested_config_file));
+        assert_ne!(path_after_removal, Some(config_file));
+    }
+
     #[test]
     fn test_dump_default_config() {
         let default_config = format!(
             r#"max_width = 100
 hard_tabs = false
 tab_spaces = 4
+smart_tabs = false
 newline_style = "Auto"
+require_final_newline = "Always"
+strip_bom = false
 use_small_heuristics = "Default"
+fn_call_width = 60
+attr_fn_like_width = 70
+struct_lit_width = 18
+struct_variant_width = 35
+array_width = 60
+chain_width = 60
+chain_width_counts_fields = true
+chain_tuple_indices_attached = false
+single_line_if_else_max_width = 50
 indent_style = "Block"
+fn_args_indent_style = "Default"
+where_indent_style = "Default"
+control_flow_indent_style = "Default"
 wrap_comments = false
 format_code_in_doc_comments = false
 comment_width = 80
 normalize_comments = false
 normalize_doc_attributes = false
+format_doc_attributes = false
 license_template_path = ""
+license_owner = ""
+insert_license = false
 format_strings = false
+format_strings_style = "Backslash"
 format_macro_matchers = false
 format_macro_bodies = true
+skip_macro_invocations = []
 empty_item_single_line = true
 struct_lit_single_line = true
+struct_lit_force_multiline_fields = 0
+struct_pat_force_multiline_fields = 0
+struct_pat_rest_own_line = true
+struct_pat_field_align_threshold = 0
+array_element_per_line_threshold = 0
 fn_single_line = false
 where_single_line = false
+group_lifetime_params = false
+where_always_on_new_line = false
+where_compressed = false
+where_trailing_comma = true
 imports_indent = "Block"
 imports_layout = "Mixed"
 merge_imports = false
 reorder_imports = true
+reorder_imports_in_group = true
 reorder_modules = true
 reorder_impl_items = false
+reorder_impls = false
+reorder_foreign_items = false
+reorder_inner_attributes = false
 type_punctuation_density = "Wide"
 space_before_colon = false
 space_after_colon = true
 spaces_around_ranges = false
+spaces_within_parens = false
+spaces_within_brackets = false
+spaces_within_angle_brackets = false
+spaces_within_struct_lit_braces = true
 binop_separator = "Front"
 remove_nested_parens = true
 combine_control_expr = true
+combine_control_expr_in_closures = true
+combine_control_expr_in_return = true
 overflow_delimited_expr = false
 struct_field_align_threshold = 0
 enum_discrim_align_threshold = 0
+foreign_item_align_threshold = 0
+trailing_comment_align_threshold = 0
 match_arm_blocks = true
 match_arm_leading_pipes = "Never"
+match_arm_wrapping = "Default"
 force_multiline_blocks = false
+closure_multiline_blocks = false
+closure_overflow_last_arg = true
+move_closure_own_line_threshold = 0
 fn_args_layout = "Tall"
+fn_params_force_multiline_threshold = 0
 brace_style = "SameLineWhere"
 control_brace_style = "AlwaysSameLine"
 trailing_semicolon = true
+semicolon_if_nothing_returned = false
+return_style = "Preserve"
 trailing_comma = "Vertical"
 match_block_trailing_comma = false
 blank_lines_upper_bound = 1
 blank_lines_lower_bound = 0
+remove_blank_lines_after_comments = false
 edition = "2015"
 version = "One"
 inline_attribute_width = 0
 merge_derives = true
+sort_derives = false
+normalize_cfg = false
 use_try_shorthand = false
 use_field_init_shorthand = false
 force_explicit_abi = true
+hex_literal_case = "Preserve"
+float_exponent_case = "Preserve"
+group_literal_digits = false
+hex_escape_style = "Preserve"
+escape_hex_case = "Preserve"
 condense_wildcard_suffixes = false
+collapse_else_if = false
 color = "Auto"
+diff_style = "Unified"
+diff_summary = false
+diff_context = 3
 required_version = "{}"
 unstable_features = false
 disable_all_formatting = false
+minimal_diff = false
+formatting_profile = "Full"
 skip_children = false
+module_traversal = "FormatReachable"
+format_included_files = false
+symlinks = "FollowFiles"
+canonicalize_paths = true
 hide_parse_errors = false
+recover_parse_errors = false
+file_rewrite_time_limit = 60
 error_on_line_overflow = false
 error_on_unformatted = false
+error_on_raw_string_overflow = true
+ignore_long_urls = false
+verify_comments = false
+verify_semantics = false
 report_todo = "Never"
 report_fixme = "Never"
+issue_pattern = ""
 ignore = []
+format_cache = ""
+failure_report_dir = ""
+out_dir = ""
+strict_config = false
 emit_mode = "Files"
 make_backup = false
 "#,
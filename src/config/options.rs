@@ -3,13 +3,15 @@ use std::fmt;
 use std::path::{Path, PathBuf};
 
 use itertools::Itertools;
+use regex::Regex;
 use rustfmt_config_proc_macro::config_type;
 use serde::de::{SeqAccess, Visitor};
 use serde::ser::SerializeSeq;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::config::lists::*;
-use crate::config::Config;
+use crate::config::{Config, FileName};
+use crate::ignore_path::IgnorePathSet;
 
 #[config_type]
 pub enum NewlineStyle {
@@ -23,6 +25,61 @@ pub enum NewlineStyle {
     Native,
 }
 
+/// Which out of line modules are formatted along with an input file.
+#[config_type]
+pub enum ModuleTraversal {
+    /// Format the input file and every out of line module reachable from it.
+    FormatReachable,
+    /// Format the input file only, without looking for its out of line modules.
+    FormatRoots,
+    /// Format the input file only, but still resolve its out of line modules so that missing
+    /// or unparsable modules are reported.
+    FormatExplicitOnly,
+}
+
+/// How the files and directories reached through symbolic links are formatted.
+#[config_type]
+pub enum SymlinkPolicy {
+    /// Format the symlinked files, but don't look for files in symlinked directories.
+    FollowFiles,
+    /// Format the symlinked files and the files in symlinked directories.
+    Follow,
+    /// Leave the files reached through a symbolic link as they are, except the input files.
+    Skip,
+}
+
+/// How much formatting changes the layout of the code.
+#[config_type]
+pub enum FormattingProfile {
+    /// Format the code fully.
+    Full,
+    /// Only make the changes within lines, e.g. to indentation and spacing, and to blank lines.
+    /// Long expressions and chains are never wrapped or joined.
+    Conservative,
+}
+
+/// Whether the value of a function is returned with a `return` statement or a tail expression.
+#[config_type]
+pub enum ReturnStyle {
+    /// Keep `return` statements and tail expressions as they are.
+    Preserve,
+    /// Convert a `return expr;` ending the body of a function into the tail expression `expr`.
+    Implicit,
+    /// Convert the tail expression ending the body of a function into `return expr;`.
+    Explicit,
+}
+
+/// Whether a file ends with a newline.
+#[config_type]
+pub enum FinalNewline {
+    /// Always end files with a newline.
+    Always,
+    /// Never end files with a newline.
+    Never,
+    /// Keep the final newline if the input has one.
+    Preserve,
+}
+
 #[config_type]
 /// Where to put the opening brace of items (`fn`, `impl`, etc.).
 pub enum BraceStyle {
@@ -56,6 +113,28 @@ pub enum IndentStyle {
     Block,
 }
 
+#[config_type]
+/// How to indent a construct, overriding `indent_style` for it.
+pub enum ConstructIndentStyle {
+    /// Indent as configured by `indent_style`
+    Default,
+    /// Visual indent, as `IndentStyle::Visual`
+    Visual,
+    /// Block indent, as `IndentStyle::Block`
+    Block,
+}
+
+impl ConstructIndentStyle {
+    /// Returns the indent style of the construct, given the `indent_style` of the config.
+    pub fn resolve(self, indent_style: IndentStyle) -> IndentStyle {
+        match self {
+            ConstructIndentStyle::Default => indent_style,
+            ConstructIndentStyle::Visual => IndentStyle::Visual,
+            ConstructIndentStyle::Block => IndentStyle::Block,
+        }
+    }
+}
+
 #[config_type]
 /// How to place a list-like items.
 /// FIXME: Issue-3581: this should be renamed to ItemsLayout when publishing 2.0
@@ -121,8 +200,12 @@ pub enum EmitMode {
     /// Writes the resulting diffs in a JSON format. Returns an empty array
     /// `[]` if there were no diffs.
     Json,
-    /// Output the changed lines (for internal value only)
+    /// Output the changed lines, as chunks of lines replacing lines of the original file.
     ModifiedLines,
+    /// Displays statistics about the run as a table, without changing any file.
+    Stats,
+    /// Writes statistics about the run in a JSON format, without changing any file.
+    StatsJson,
     /// Checks if a diff can be generated. If so, rustfmt outputs a diff and
     /// quits with exit code 1.
     /// This option is designed to be run in CI where a non-zero exit signifies
@@ -141,6 +224,15 @@ pub enum Color {
     Auto,
 }
 
+/// How the diffs printed by `--check` are laid out.
+#[config_type]
+pub enum DiffStyle {
+    /// The removed and the added lines below each other.
+    Unified,
+    /// The original lines on the left, and the formatted lines on the right.
+    SideBySide,
+}
+
 #[config_type]
 /// rustfmt format style version.
 pub enum Version {
@@ -348,6 +440,13 @@ impl IgnoreList {
     pub fn rustfmt_toml_path(&self) -> &Path {
         &self.rustfmt_toml_path
     }
+
+    /// Returns `true` if the file at `path` is ignored, e.g. to skip a file whose content is
+    /// given on stdin.
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        IgnorePathSet::from_ignore_list(self)
+            .map_or(false, |set| set.is_match(&FileName::Real(path.to_owned())))
+    }
 }
 
 impl ::std::str::FromStr for IgnoreList {
@@ -358,6 +457,94 @@ impl ::std::str::FromStr for IgnoreList {
     }
 }
 
+/// A list of macro names, written without the trailing `!`.
+#[derive(Default, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MacroNames(Vec<String>);
+
+impl fmt::Display for MacroNames {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}]", self.0.iter().format(", "))
+    }
+}
+
+impl MacroNames {
+    /// Returns `true` if the list contains the macro `name`, with or without
+    /// its trailing `!`.
+    pub fn contains(&self, name: &str) -> bool {
+        let name = name.trim_end_matches('!');
+        self.0.iter().any(|n| n.trim_end_matches('!') == name)
+    }
+}
+
+impl ::std::str::FromStr for MacroNames {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().trim_start_matches('[').trim_end_matches(']');
+        Ok(MacroNames(
+            s.split(|c: char| c == ',' || c.is_whitespace())
+                .map(|name| name.trim_matches('"'))
+                .filter(|name| !name.is_empty())
+                .map(str::to_owned)
+                .collect(),
+        ))
+    }
+}
+
+/// A regular expression which issue references of TODO and FIXME comments must match, or none
+/// to accept any `(#123)`-style reference.
+#[derive(Default, Clone, Debug)]
+pub struct IssuePattern(Option<Regex>);
+
+impl IssuePattern {
+    pub(crate) fn regex(&self) -> Option<&Regex> {
+        self.0.as_ref()
+    }
+}
+
+impl PartialEq for IssuePattern {
+    fn eq(&self, other: &IssuePattern) -> bool {
+        self.0.as_ref().map(Regex::as_str) == other.0.as_ref().map(Regex::as_str)
+    }
+}
+
+impl fmt::Display for IssuePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0.as_ref().map_or("", Regex::as_str))
+    }
+}
+
+impl ::std::str::FromStr for IssuePattern {
+    type Err = regex::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            Ok(IssuePattern(None))
+        } else {
+            Regex::new(s).map(|re| IssuePattern(Some(re)))
+        }
+    }
+}
+
+impl Serialize for IssuePattern {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for IssuePattern {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 /// Maps client-supplied options to Rustfmt's internals, mostly overriding
 /// values in a config with values from the command line.
 pub trait CliOptions {
@@ -403,3 +590,57 @@ pub enum MatchArmLeadingPipe {
     /// Preserve any existing leading pipes
     Preserve,
 }
+
+/// How `format_strings` continues a string literal which is split across lines.
+#[config_type]
+pub enum FormatStringsStyle {
+    /// End each line with a `\` line continuation
+    Backslash,
+    /// Split the literal into several literals joined with `concat!`
+    Concat,
+}
+
+/// Controls the case of letters in numeric literals and escape sequences.
+#[config_type]
+pub enum LiteralCase {
+    /// Leave the literal as is
+    Preserve,
+    /// Ensure all letters are uppercase
+    Upper,
+    /// Ensure all letters are lowercase
+    Lower,
+}
+
+impl LiteralCase {
+    pub fn apply(self, s: &str) -> String {
+        match self {
+            LiteralCase::Preserve => s.to_owned(),
+            LiteralCase::Upper => s.to_ascii_uppercase(),
+            LiteralCase::Lower => s.to_ascii_lowercase(),
+        }
+    }
+}
+
+/// Controls the form of numeric escape sequences in char and string literals.
+#[config_type]
+pub enum HexEscapeStyle {
+    /// Leave escape sequences as is
+    Preserve,
+    /// Prefer `\x7f` escapes where the value allows it
+    Hex,
+    /// Prefer `\u{7f}` escapes, except in byte literals
+    Unicode,
+}
+
+/// Controls when rustfmt wraps the body of a match arm in a block.
+#[config_type]
+pub enum MatchArmWrapping {
+    /// Wrap bodies in blocks as configured by `match_arm_blocks`
+    Default,
+    /// Wrap every body which spans multiple lines in a block
+    Always,
+    /// Never add a block around a body
+    Never,
+    /// Keep blocks written in the source and never add new ones
+    Preserve,
+}
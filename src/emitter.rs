@@ -4,8 +4,10 @@ pub(crate) use self::files::*;
 pub(crate) use self::files_with_backup::*;
 pub(crate) use self::json::*;
 pub(crate) use self::modified_lines::*;
+pub(crate) use self::stats::*;
 pub(crate) use self::stdout::*;
 use crate::FileName;
+use serde::Serialize;
 use std::io::{self, Write};
 use std::path::Path;
 
@@ -15,33 +17,79 @@ mod files;
 mod files_with_backup;
 mod json;
 mod modified_lines;
+mod stats;
 mod stdout;
 
-pub(crate) struct FormattedFile<'a> {
-    pub(crate) filename: &'a FileName,
-    pub(crate) original_text: &'a str,
-    pub(crate) formatted_text: &'a str,
+/// A file as formatted by rustfmt, which an [`Emitter`] is asked to emit.
+pub struct FormattedFile<'a> {
+    pub filename: &'a FileName,
+    pub original_text: &'a str,
+    pub formatted_text: &'a str,
 }
 
+/// What an [`Emitter`] found out while emitting a file.
 #[derive(Debug, Default, Clone)]
-pub(crate) struct EmitterResult {
-    pub(crate) has_diff: bool,
+pub struct EmitterResult {
+    /// The emitter reported a difference between the original and the formatted text, which
+    /// makes `--check` fail.
+    pub has_diff: bool,
+    /// The formatted text differs from the original text, whatever the emit mode.
+    pub changed: bool,
 }
 
-pub(crate) trait Emitter {
+/// Statistics about a formatting run which cannot be told from the formatted files.
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct RunStats {
+    /// Time spent parsing, in seconds.
+    pub parse_time: f32,
+    /// Time spent formatting, in seconds.
+    pub format_time: f32,
+    /// The number of items left unformatted, e.g. because of `#[rustfmt::skip]`.
+    pub skipped_items: usize,
+    /// The number of items and statements which were rewritten.
+    pub items_formatted: usize,
+    /// The number of items and statements which could not be rewritten and were left as is.
+    pub rewrite_fallbacks: usize,
+}
+
+/// Statistics about formatting a single file of a run, as returned by
+/// [`FormatReport::file_stats`](crate::FormatReport::file_stats).
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct FileStats {
+    /// Time spent formatting the file, in seconds.
+    pub format_time: f32,
+    /// The number of items and statements which were rewritten.
+    pub items_formatted: usize,
+    /// The number of items and statements which could not be rewritten and were left as is.
+    pub rewrite_fallbacks: usize,
+}
+
+/// Handles the output of a [`Session`](crate::Session), e.g. by writing the formatted files or
+/// printing a diff or a report of them.
+///
+/// The emitter for the configured `emit_mode` is created by
+/// [`create_emitter`](crate::create_emitter). Library users can implement their own, and pass it
+/// to [`Session::with_emitter`](crate::Session::with_emitter).
+pub trait Emitter {
+    /// Emits a formatted file, e.g. by writing it or a diff of it to `output`.
     fn emit_formatted_file(
         &mut self,
         output: &mut dyn Write,
         formatted_file: FormattedFile<'_>,
     ) -> Result<EmitterResult, io::Error>;
 
+    /// Emits what goes before the first file, when the session is created.
     fn emit_header(&self, _output: &mut dyn Write) -> Result<(), io::Error> {
         Ok(())
     }
 
+    /// Emits what goes after the last file, when the session is dropped.
     fn emit_footer(&self, _output: &mut dyn Write) -> Result<(), io::Error> {
         Ok(())
     }
+
+    /// Records the statistics of formatting an input.
+    fn record_stats(&mut self, _stats: RunStats) {}
 }
 
 fn ensure_real_path(filename: &FileName) -> &Path {
@@ -1,13 +1,21 @@
 use self::xml::XmlEscaped;
 use super::*;
-use crate::rustfmt_diff::{make_diff, DiffLine, Mismatch};
+use crate::rustfmt_diff::{count_changed_lines, make_diff, DiffLine, Mismatch};
 use std::io::{self, Write};
 use std::path::Path;
 
 mod xml;
 
 #[derive(Debug, Default)]
-pub(crate) struct CheckstyleEmitter;
+pub(crate) struct CheckstyleEmitter {
+    diff_summary: bool,
+}
+
+impl CheckstyleEmitter {
+    pub(crate) fn new(diff_summary: bool) -> Self {
+        Self { diff_summary }
+    }
+}
 
 impl Emitter for CheckstyleEmitter {
     fn emit_header(&self, output: &mut dyn Write) -> Result<(), io::Error> {
@@ -32,7 +40,7 @@ impl Emitter for CheckstyleEmitter {
         const CONTEXT_SIZE: usize = 0;
         let filename = ensure_real_path(filename);
         let diff = make_diff(original_text, formatted_text, CONTEXT_SIZE);
-        output_checkstyle_file(output, filename, diff)?;
+        output_checkstyle_file(output, filename, diff, self.diff_summary)?;
         Ok(EmitterResult::default())
     }
 }
@@ -41,11 +49,20 @@ pub(crate) fn output_checkstyle_file<T>(
     mut writer: T,
     filename: &Path,
     diff: Vec<Mismatch>,
+    diff_summary: bool,
 ) -> Result<(), io::Error>
 where
     T: Write,
 {
     write!(writer, r#"<file name="{}">"#, filename.display())?;
+    if diff_summary && !diff.is_empty() {
+        write!(
+            writer,
+            r#"<error line="{}" severity="info" message="{} lines differ" />"#,
+            diff[0].line_number,
+            count_changed_lines(&diff)
+        )?;
+    }
     for mismatch in diff {
         let begin_line = mismatch.line_number;
         let mut current_line;
@@ -77,7 +94,7 @@ mod tests {
     fn emits_empty_record_on_file_with_no_mismatches() {
         let file_name = "src/well_formatted.rs";
         let mut writer = Vec::new();
-        let _ = output_checkstyle_file(&mut writer, &PathBuf::from(file_name), vec![]);
+        let _ = output_checkstyle_file(&mut writer, &PathBuf::from(file_name), vec![], false);
         assert_eq!(
             &writer[..],
             format!(r#"<file name="{}"></file>"#, file_name).as_bytes()
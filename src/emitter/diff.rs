@@ -1,6 +1,6 @@
 use super::*;
 use crate::config::Config;
-use crate::rustfmt_diff::{make_diff, print_diff};
+use crate::rustfmt_diff::{count_changed_lines, make_diff, print_diff};
 
 pub(crate) struct DiffEmitter {
     config: Config,
@@ -22,13 +22,20 @@ impl Emitter for DiffEmitter {
             formatted_text,
         }: FormattedFile<'_>,
     ) -> Result<EmitterResult, io::Error> {
-        const CONTEXT_SIZE: usize = 3;
-        let mismatch = make_diff(&original_text, formatted_text, CONTEXT_SIZE);
+        let mismatch = make_diff(&original_text, formatted_text, self.config.diff_context());
         let has_diff = !mismatch.is_empty();
 
         if has_diff {
             if self.config.print_misformatted_file_names() {
                 writeln!(output, "{}", ensure_real_path(filename).display())?;
+            } else if self.config.diff_summary() {
+                let lines = count_changed_lines(&make_diff(original_text, formatted_text, 0));
+                writeln!(
+                    output,
+                    "{}: {} lines differ",
+                    ensure_real_path(filename).display(),
+                    lines
+                )?;
             } else {
                 print_diff(
                     mismatch,
@@ -42,10 +49,16 @@ impl Emitter for DiffEmitter {
             // original and formatted values line by line, independent of line endings.
             let file_path = ensure_real_path(filename);
             writeln!(output, "Incorrect newline style in {}", file_path.display())?;
-            return Ok(EmitterResult { has_diff: true });
+            return Ok(EmitterResult {
+                has_diff: true,
+                ..Default::default()
+            });
         }
 
-        return Ok(EmitterResult { has_diff });
+        return Ok(EmitterResult {
+            has_diff,
+            ..Default::default()
+        });
     }
 }
 
@@ -115,6 +128,29 @@ mod tests {
         )
     }
 
+    #[test]
+    fn prints_summary_when_config_is_enabled() {
+        let mut writer = Vec::new();
+        let mut config = Config::default();
+        config.set().diff_summary(true);
+        let mut emitter = DiffEmitter::new(config);
+        let result = emitter
+            .emit_formatted_file(
+                &mut writer,
+                FormattedFile {
+                    filename: &FileName::Real(PathBuf::from("src/lib.rs")),
+                    original_text: "fn main () {\n  foo (  );\n}\n\n\n",
+                    formatted_text: "fn main() {\n    foo();\n}\n",
+                },
+            )
+            .unwrap();
+        assert_eq!(result.has_diff, true);
+        assert_eq!(
+            String::from_utf8(writer).unwrap(),
+            "src/lib.rs: 4 lines differ\n"
+        );
+    }
+
     #[test]
     fn prints_newline_message_with_only_newline_style_diff() {
         let mut writer = Vec::new();
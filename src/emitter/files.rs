@@ -1,17 +1,28 @@
 use super::*;
+use std::env;
 use std::fs;
+use std::path::{Component, PathBuf};
+use std::process;
 
 #[derive(Debug, Default)]
 pub(crate) struct FilesEmitter {
     print_misformatted_file_names: bool,
+    // The directory mirroring the input tree to write the files to, instead of overwriting them.
+    out_dir: Option<PathBuf>,
 }
 
 impl FilesEmitter {
     pub(crate) fn new(print_misformatted_file_names: bool) -> Self {
         Self {
             print_misformatted_file_names,
+            out_dir: None,
         }
     }
+
+    pub(crate) fn with_out_dir(mut self, out_dir: PathBuf) -> Self {
+        self.out_dir = Some(out_dir);
+        self
+    }
 }
 
 impl Emitter for FilesEmitter {
@@ -24,10 +35,23 @@ impl Emitter for FilesEmitter {
             formatted_text,
         }: FormattedFile<'_>,
     ) -> Result<EmitterResult, io::Error> {
-        // Write text directly over original file if there is a diff.
         let filename = ensure_real_path(filename);
+        if let Some(ref out_dir) = self.out_dir {
+            // Every file is written, so that the output directory holds the whole tree.
+            let out_path = out_dir.join(mirrored_path(filename)?);
+            if let Some(parent) = out_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&out_path, formatted_text)?;
+            if original_text != formatted_text && self.print_misformatted_file_names {
+                writeln!(output, "{}", filename.display())?;
+            }
+            return Ok(EmitterResult::default());
+        }
+
+        // Write text over original file if there is a diff.
         if original_text != formatted_text {
-            fs::write(filename, formatted_text)?;
+            write_atomically(filename, formatted_text)?;
             if self.print_misformatted_file_names {
                 writeln!(output, "{}", filename.display())?;
             }
@@ -35,3 +59,149 @@ impl Emitter for FilesEmitter {
         Ok(EmitterResult::default())
     }
 }
+
+/// Returns the path of the file at `path` relative to a directory mirroring the input tree.
+/// Absolute paths are made relative to the current directory if they are within it, and to the
+/// root otherwise. A relative path whose `..` components lead out of the current directory is an
+/// error, as its file would be written outside of the directory or over another file.
+fn mirrored_path(path: &Path) -> Result<PathBuf, io::Error> {
+    let current_dir = env::current_dir().unwrap_or_default();
+    let relative_path = path.strip_prefix(&current_dir).unwrap_or(path);
+    let mut mirrored = PathBuf::new();
+    for component in relative_path.components() {
+        match component {
+            Component::Normal(part) => mirrored.push(part),
+            Component::ParentDir if path.is_relative() && mirrored.pop() => {}
+            Component::ParentDir => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "`{}` is outside of the current directory, so it can't be written to \
+                         out_dir",
+                        path.display()
+                    ),
+                ));
+            }
+            Component::Prefix(_) | Component::RootDir | Component::CurDir => {}
+        }
+    }
+    Ok(mirrored)
+}
+
+/// Replaces the contents of the file at `path` without ever leaving it truncated, even if
+/// rustfmt is interrupted: the contents are written to a temporary file in the same
+/// directory, which is then renamed over the original file.
+///
+/// The permissions of the original file are kept. If renaming would change the owner of the
+/// file or break its hard links, the file is written in place instead.
+pub(crate) fn write_atomically(path: &Path, contents: &str) -> Result<(), io::Error> {
+    // Replace the file a symlink points to, not the symlink itself.
+    let path = fs::canonicalize(path)?;
+    let metadata = fs::metadata(&path)?;
+    let tmp_path = tmp_path(&path);
+
+    let result = write_tmp_file(&tmp_path, contents, &metadata).and_then(|tmp_metadata| {
+        if can_replace(&metadata, &tmp_metadata) {
+            fs::rename(&tmp_path, &path)
+        } else {
+            fs::remove_file(&tmp_path)?;
+            fs::write(&path, contents)
+        }
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+fn tmp_path(path: &Path) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.rustfmt-{}.tmp", file_name, process::id()))
+}
+
+fn write_tmp_file(
+    tmp_path: &Path,
+    contents: &str,
+    metadata: &fs::Metadata,
+) -> Result<fs::Metadata, io::Error> {
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(tmp_path)?;
+    file.write_all(contents.as_bytes())?;
+    file.set_permissions(metadata.permissions())?;
+    file.sync_all()?;
+    file.metadata()
+}
+
+#[cfg(unix)]
+fn can_replace(original: &fs::Metadata, tmp: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    original.uid() == tmp.uid() && original.gid() == tmp.gid() && original.nlink() == 1
+}
+
+#[cfg(not(unix))]
+fn can_replace(_original: &fs::Metadata, _tmp: &fs::Metadata) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn mirrored_path_stays_in_out_dir() {
+        let current_dir = env::current_dir().unwrap();
+        assert_eq!(
+            mirrored_path(&current_dir.join("src/lib.rs")).unwrap(),
+            PathBuf::from("src/lib.rs")
+        );
+        assert_eq!(
+            mirrored_path(Path::new("src/../foo/./lib.rs")).unwrap(),
+            PathBuf::from("foo/lib.rs")
+        );
+        assert!(mirrored_path(Path::new("../foo/lib.rs")).is_err());
+        assert!(mirrored_path(Path::new("src/../../lib.rs")).is_err());
+    }
+
+    #[test]
+    fn write_atomically_replaces_contents() {
+        let dir = env::temp_dir().join(format!("rustfmt-write-atomically-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("lib.rs");
+        fs::write(&path, "fn main () {}\n").unwrap();
+
+        let result = write_atomically(&path, "fn main() {}\n");
+        let contents = fs::read_to_string(&path);
+        let entries = fs::read_dir(&dir).unwrap().count();
+        fs::remove_dir_all(&dir).unwrap();
+
+        result.unwrap();
+        assert_eq!(contents.unwrap(), "fn main() {}\n");
+        assert_eq!(entries, 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_atomically_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = env::temp_dir().join(format!("rustfmt-keep-permissions-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("build.rs");
+        fs::write(&path, "fn main () {}\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o750)).unwrap();
+
+        let result = write_atomically(&path, "fn main() {}\n");
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        fs::remove_dir_all(&dir).unwrap();
+
+        result.unwrap();
+        assert_eq!(mode & 0o777, 0o750);
+    }
+}
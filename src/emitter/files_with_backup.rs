@@ -16,15 +16,11 @@ impl Emitter for FilesWithBackupEmitter {
     ) -> Result<EmitterResult, io::Error> {
         let filename = ensure_real_path(filename);
         if original_text != formatted_text {
-            // Do a little dance to make writing safer - write to a temp file
-            // rename the original to a .bk, then rename the temp file to the
-            // original.
-            let tmp_name = filename.with_extension("tmp");
+            // Copy the original to a .bk, then atomically replace the original.
             let bk_name = filename.with_extension("bk");
 
-            fs::write(&tmp_name, formatted_text)?;
-            fs::rename(filename, bk_name)?;
-            fs::rename(tmp_name, filename)?;
+            fs::copy(filename, bk_name)?;
+            write_atomically(filename, formatted_text)?;
         }
         Ok(EmitterResult::default())
     }
@@ -1,5 +1,5 @@
 use super::*;
-use crate::rustfmt_diff::{make_diff, DiffLine, Mismatch};
+use crate::rustfmt_diff::{count_changed_lines, make_diff, DiffLine, Mismatch};
 use serde::Serialize;
 use serde_json::to_string as to_json_string;
 use std::io::{self, Write};
@@ -8,6 +8,7 @@ use std::path::Path;
 #[derive(Debug, Default)]
 pub(crate) struct JsonEmitter {
     num_files: u32,
+    diff_summary: bool,
 }
 
 #[derive(Debug, Default, Serialize)]
@@ -23,9 +24,21 @@ struct MismatchedBlock {
 #[derive(Debug, Default, Serialize)]
 struct MismatchedFile {
     name: String,
+    /// The number of lines which differ, with `diff_summary`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    differing_lines: Option<usize>,
     mismatches: Vec<MismatchedBlock>,
 }
 
+impl JsonEmitter {
+    pub(crate) fn new(diff_summary: bool) -> Self {
+        Self {
+            diff_summary,
+            ..Default::default()
+        }
+    }
+}
+
 impl Emitter for JsonEmitter {
     fn emit_header(&self, output: &mut dyn Write) -> Result<(), io::Error> {
         write!(output, "[")?;
@@ -52,11 +65,14 @@ impl Emitter for JsonEmitter {
         let has_diff = !diff.is_empty();
 
         if has_diff {
-            output_json_file(output, filename, diff, self.num_files)?;
+            output_json_file(output, filename, diff, self.num_files, self.diff_summary)?;
             self.num_files += 1;
         }
 
-        Ok(EmitterResult { has_diff })
+        Ok(EmitterResult {
+            has_diff,
+            ..Default::default()
+        })
     }
 }
 
@@ -65,10 +81,16 @@ fn output_json_file<T>(
     filename: &Path,
     diff: Vec<Mismatch>,
     num_emitted_files: u32,
+    diff_summary: bool,
 ) -> Result<(), io::Error>
 where
     T: Write,
 {
+    let differing_lines = if diff_summary {
+        Some(count_changed_lines(&diff))
+    } else {
+        None
+    };
     let mut mismatches = vec![];
     for mismatch in diff {
         let original_begin_line = mismatch.line_number_orig;
@@ -107,6 +129,7 @@ where
     }
     let json = to_json_string(&MismatchedFile {
         name: String::from(filename.to_str().unwrap()),
+        differing_lines,
         mismatches,
     })?;
     let prefix = if num_emitted_files > 0 { "," } else { "" };
@@ -125,6 +148,7 @@ mod tests {
         let file = "foo/bar.rs";
         let mismatched_file = MismatchedFile {
             name: String::from(file),
+            differing_lines: None,
             mismatches: vec![MismatchedBlock {
                 original_begin_line: 79,
                 original_end_line: 79,
@@ -148,7 +172,7 @@ mod tests {
 
         let mut writer = Vec::new();
         let exp_json = to_json_string(&mismatched_file).unwrap();
-        let _ = output_json_file(&mut writer, &PathBuf::from(file), vec![mismatch], 0);
+        let _ = output_json_file(&mut writer, &PathBuf::from(file), vec![mismatch], 0, false);
         assert_eq!(&writer[..], format!("{}", exp_json).as_bytes());
     }
 
@@ -157,6 +181,7 @@ mod tests {
         let file = "src/lib.rs";
         let mismatched_file = MismatchedFile {
             name: String::from(file),
+            differing_lines: None,
             mismatches: vec![MismatchedBlock {
                 original_begin_line: 5,
                 original_end_line: 5,
@@ -188,10 +213,28 @@ mod tests {
 
         let mut writer = Vec::new();
         let exp_json = to_json_string(&mismatched_file).unwrap();
-        let _ = output_json_file(&mut writer, &PathBuf::from(file), vec![mismatch], 0);
+        let _ = output_json_file(&mut writer, &PathBuf::from(file), vec![mismatch], 0, false);
         assert_eq!(&writer[..], format!("{}", exp_json).as_bytes());
     }
 
+    #[test]
+    fn differing_lines_included_with_diff_summary() {
+        let mut writer = Vec::new();
+        let mut emitter = JsonEmitter::new(true);
+        let _ = emitter
+            .emit_formatted_file(
+                &mut writer,
+                FormattedFile {
+                    filename: &FileName::Real(PathBuf::from("src/lib.rs")),
+                    original_text: "fn main () {\nfoo();\n}\n",
+                    formatted_text: "fn main() {\n    foo();\n}\n",
+                },
+            )
+            .unwrap();
+        let json = String::from_utf8(writer).unwrap();
+        assert!(json.starts_with(r#"{"name":"src/lib.rs","differing_lines":2,"mismatches":"#));
+    }
+
     #[test]
     fn emits_empty_array_on_no_diffs() {
         let mut writer = Vec::new();
@@ -257,6 +300,7 @@ mod tests {
         let _ = emitter.emit_footer(&mut writer);
         let exp_json = to_json_string(&MismatchedFile {
             name: String::from(file_name),
+            differing_lines: None,
             mismatches: vec![
                 MismatchedBlock {
                     original_begin_line: 2,
@@ -319,6 +363,7 @@ mod tests {
         let _ = emitter.emit_footer(&mut writer);
         let exp_bin_json = to_json_string(&MismatchedFile {
             name: String::from(bin_file),
+            differing_lines: None,
             mismatches: vec![MismatchedBlock {
                 original_begin_line: 2,
                 original_end_line: 2,
@@ -331,6 +376,7 @@ mod tests {
         .unwrap();
         let exp_lib_json = to_json_string(&MismatchedFile {
             name: String::from(lib_file),
+            differing_lines: None,
             mismatches: vec![MismatchedBlock {
                 original_begin_line: 2,
                 original_end_line: 2,
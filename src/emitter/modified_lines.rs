@@ -1,5 +1,5 @@
 use super::*;
-use crate::rustfmt_diff::{make_diff, ModifiedLines};
+use crate::rustfmt_diff::get_modified_lines;
 use std::io::Write;
 
 #[derive(Debug, Default)]
@@ -15,10 +15,12 @@ impl Emitter for ModifiedLinesEmitter {
             ..
         }: FormattedFile<'_>,
     ) -> Result<EmitterResult, io::Error> {
-        const CONTEXT_SIZE: usize = 0;
-        let mismatch = make_diff(original_text, formatted_text, CONTEXT_SIZE);
-        let has_diff = !mismatch.is_empty();
-        write!(output, "{}", ModifiedLines::from(mismatch))?;
-        Ok(EmitterResult { has_diff })
+        let modified_lines = get_modified_lines(original_text, formatted_text);
+        let has_diff = !modified_lines.chunks.is_empty();
+        write!(output, "{}", modified_lines)?;
+        Ok(EmitterResult {
+            has_diff,
+            ..Default::default()
+        })
     }
 }
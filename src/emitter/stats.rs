@@ -0,0 +1,147 @@
+use super::*;
+use crate::rustfmt_diff::{count_changed_lines, make_diff};
+use serde::Serialize;
+use serde_json::to_string as to_json_string;
+use std::io::{self, Write};
+
+/// The number of most changed files listed in the statistics.
+const TOP_CHANGED_FILES: usize = 10;
+
+#[derive(Debug, Default, Serialize)]
+struct ChangedFile {
+    name: String,
+    lines: usize,
+}
+
+/// Statistics about a formatting run, emitted as a table or as JSON once all files are
+/// formatted. Files are left untouched.
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct StatsEmitter {
+    #[serde(skip)]
+    json: bool,
+    files_scanned: usize,
+    files_changed: usize,
+    lines_reformatted: usize,
+    skipped_items: usize,
+    parse_time: f32,
+    format_time: f32,
+    most_changed_files: Vec<ChangedFile>,
+}
+
+impl StatsEmitter {
+    pub(crate) fn new(json: bool) -> Self {
+        Self {
+            json,
+            ..Default::default()
+        }
+    }
+
+    fn write_table(&self, output: &mut dyn Write) -> Result<(), io::Error> {
+        writeln!(output, "Files scanned:     {:>8}", self.files_scanned)?;
+        writeln!(output, "Files changed:     {:>8}", self.files_changed)?;
+        writeln!(output, "Lines reformatted: {:>8}", self.lines_reformatted)?;
+        writeln!(output, "Skipped items:     {:>8}", self.skipped_items)?;
+        writeln!(output, "Parse time:        {:>7.3}s", self.parse_time)?;
+        writeln!(output, "Format time:       {:>7.3}s", self.format_time)?;
+        if !self.most_changed_files.is_empty() {
+            writeln!(output, "\nMost changed files:")?;
+            for file in &self.most_changed_files {
+                writeln!(output, "{:>8}  {}", file.lines, file.name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Emitter for StatsEmitter {
+    fn emit_footer(&self, output: &mut dyn Write) -> Result<(), io::Error> {
+        if self.json {
+            writeln!(output, "{}", to_json_string(self)?)
+        } else {
+            self.write_table(output)
+        }
+    }
+
+    fn emit_formatted_file(
+        &mut self,
+        _output: &mut dyn Write,
+        FormattedFile {
+            filename,
+            original_text,
+            formatted_text,
+        }: FormattedFile<'_>,
+    ) -> Result<EmitterResult, io::Error> {
+        const CONTEXT_SIZE: usize = 0;
+        let diff = make_diff(original_text, formatted_text, CONTEXT_SIZE);
+        let has_diff = !diff.is_empty();
+
+        self.files_scanned += 1;
+        if has_diff {
+            let lines = count_changed_lines(&diff);
+            self.files_changed += 1;
+            self.lines_reformatted += lines;
+            self.most_changed_files.push(ChangedFile {
+                name: filename.to_string(),
+                lines,
+            });
+            self.most_changed_files
+                .sort_by(|a, b| b.lines.cmp(&a.lines).then_with(|| a.name.cmp(&b.name)));
+            self.most_changed_files.truncate(TOP_CHANGED_FILES);
+        }
+
+        Ok(EmitterResult {
+            has_diff,
+            ..Default::default()
+        })
+    }
+
+    fn record_stats(&mut self, stats: RunStats) {
+        self.skipped_items += stats.skipped_items;
+        self.parse_time += stats.parse_time;
+        self.format_time += stats.format_time;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn counts_changed_files_and_lines() {
+        let mut emitter = StatsEmitter::new(true);
+        let mut writer = Vec::new();
+        let unchanged = FileName::Real(PathBuf::from("src/unchanged.rs"));
+        let changed = FileName::Real(PathBuf::from("src/changed.rs"));
+        let _ = emitter.emit_formatted_file(
+            &mut writer,
+            FormattedFile {
+                filename: &unchanged,
+                original_text: "fn foo() {}\n",
+                formatted_text: "fn foo() {}\n",
+            },
+        );
+        let result = emitter.emit_formatted_file(
+            &mut writer,
+            FormattedFile {
+                filename: &changed,
+                original_text: "fn foo (){}\nfn bar () {}\n\nfn baz() {}\n",
+                formatted_text: "fn foo() {}\nfn bar() {}\n\nfn baz() {}\n",
+            },
+        );
+        emitter.record_stats(RunStats {
+            parse_time: 0.5,
+            format_time: 0.25,
+            skipped_items: 1,
+            ..RunStats::default()
+        });
+        let _ = emitter.emit_footer(&mut writer);
+
+        assert!(result.unwrap().has_diff);
+        let expected = r#"{"files_scanned":2,"files_changed":1,"lines_reformatted":2,"skipped_items":1,"parse_time":0.5,"format_time":0.25,"most_changed_files":[{"name":"src/changed.rs","lines":2}]}"#;
+        assert_eq!(
+            String::from_utf8(writer).unwrap(),
+            format!("{}\n", expected)
+        );
+    }
+}
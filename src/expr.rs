@@ -13,7 +13,7 @@ use crate::comment::{
     rewrite_comment, rewrite_missing_comment, CharClasses, FindUncommented,
 };
 use crate::config::lists::*;
-use crate::config::{Config, ControlBraceStyle, IndentStyle, Version};
+use crate::config::{Config, ControlBraceStyle, FormatStringsStyle, IndentStyle, Version};
 use crate::lists::{
     definitive_tactic, itemize_list, shape_for_tactic, struct_lit_formatting, struct_lit_shape,
     struct_lit_tactic, write_list, ListFormatting, Separator,
@@ -22,11 +22,13 @@ use crate::macros::{rewrite_macro, MacroPosition};
 use crate::matches::rewrite_match;
 use crate::overflow::{self, IntoOverflowableItem, OverflowableItem};
 use crate::pairs::{rewrite_all_pairs, rewrite_pair, PairParts};
+use crate::patterns::rewrite_top_level_pattern;
 use crate::rewrite::{Rewrite, RewriteContext};
 use crate::shape::{Indent, Shape};
 use crate::source_map::{LineRangeUtils, SpanUtils};
+use crate::spacing::Spacing;
 use crate::spanned::Spanned;
-use crate::string::{rewrite_string, StringFormat};
+use crate::string::{normalize_escapes, rewrite_string, StringFormat};
 use crate::types::{rewrite_path, PathContext};
 use crate::utils::{
     colon_spaces, contains_skip, count_newlines, first_line_ends_with, inner_attributes,
@@ -65,6 +67,18 @@ pub(crate) fn format_expr(
         shape
     };
 
+    context.budgeted_expr_rewrite(expr.span, shape, expr_type == ExprType::Statement, || {
+        rewrite_expr(expr, expr_type, context, shape)
+    })
+}
+
+// Rewrites `expr` along with its outer attributes.
+fn rewrite_expr(
+    expr: &ast::Expr,
+    expr_type: ExprType,
+    context: &RewriteContext<'_>,
+    shape: Shape,
+) -> Option<String> {
     let expr_rw = match expr.kind {
         ast::ExprKind::Array(ref expr_vec) => rewrite_array(
             "",
@@ -98,7 +112,7 @@ pub(crate) fn format_expr(
                 rewrite_pair(
                     &**lhs,
                     &**rhs,
-                    PairParts::infix(&format!(" {} ", context.snippet(op.span))),
+                    PairParts::infix(&Spacing::new(context.config).binop(context.snippet(op.span))),
                     context,
                     shape,
                     context.config.binop_separator(),
@@ -118,7 +132,7 @@ pub(crate) fn format_expr(
         ast::ExprKind::Tup(ref items) => {
             rewrite_tuple(context, items.iter(), expr.span, shape, items.len() == 1)
         }
-        ast::ExprKind::Let(..) => None,
+        ast::ExprKind::Let(ref pat, ref init) => rewrite_let_condition(context, pat, init, shape),
         ast::ExprKind::If(..)
         | ast::ExprKind::ForLoop(..)
         | ast::ExprKind::Loop(..)
@@ -165,24 +179,11 @@ pub(crate) fn format_expr(
         ast::ExprKind::AssignOp(ref op, ref lhs, ref rhs) => {
             rewrite_assignment(context, lhs, rhs, Some(op), shape)
         }
-        ast::ExprKind::Continue(ref opt_label) => {
-            let id_str = match *opt_label {
-                Some(label) => format!(" {}", label.ident),
-                None => String::new(),
-            };
-            Some(format!("continue{}", id_str))
+        ast::ExprKind::Continue(opt_label) => {
+            Some(format!("continue{}", rewrite_label_ref(opt_label)))
         }
-        ast::ExprKind::Break(ref opt_label, ref opt_expr) => {
-            let id_str = match *opt_label {
-                Some(label) => format!(" {}", label.ident),
-                None => String::new(),
-            };
-
-            if let Some(ref expr) = *opt_expr {
-                rewrite_unary_prefix(context, &format!("break{} ", id_str), &**expr, shape)
-            } else {
-                Some(format!("break{}", id_str))
-            }
+        ast::ExprKind::Break(opt_label, ref opt_expr) => {
+            rewrite_break(context, opt_label, opt_expr.as_deref(), shape)
         }
         ast::ExprKind::Yield(ref opt_expr) => {
             if let Some(ref expr) = *opt_expr {
@@ -209,9 +210,7 @@ pub(crate) fn format_expr(
             })
         }
         ast::ExprKind::Ret(None) => Some("return".to_owned()),
-        ast::ExprKind::Ret(Some(ref expr)) => {
-            rewrite_unary_prefix(context, "return ", &**expr, shape)
-        }
+        ast::ExprKind::Ret(Some(ref expr)) => rewrite_return(context, expr, shape),
         ast::ExprKind::Box(ref expr) => rewrite_unary_prefix(context, "box ", &**expr, shape),
         ast::ExprKind::AddrOf(borrow_kind, mutability, ref expr) => {
             rewrite_expr_addrof(context, borrow_kind, mutability, expr, shape)
@@ -227,7 +226,7 @@ pub(crate) fn format_expr(
         ast::ExprKind::Type(ref expr, ref ty) => rewrite_pair(
             &**expr,
             &**ty,
-            PairParts::infix(": "),
+            PairParts::infix(colon_spaces(context.config)),
             context,
             shape,
             SeparatorPlace::Back,
@@ -235,14 +234,21 @@ pub(crate) fn format_expr(
         ast::ExprKind::Index(ref expr, ref index) => {
             rewrite_index(&**expr, &**index, context, shape)
         }
-        ast::ExprKind::Repeat(ref expr, ref repeats) => rewrite_pair(
-            &**expr,
-            &*repeats.value,
-            PairParts::new("[", "; ", "]"),
-            context,
-            shape,
-            SeparatorPlace::Back,
-        ),
+        ast::ExprKind::Repeat(ref expr, ref repeats) => {
+            let pair_parts = if context.config.spaces_within_brackets() {
+                PairParts::new("[ ", "; ", " ]")
+            } else {
+                PairParts::new("[", "; ", "]")
+            };
+            rewrite_pair(
+                &**expr,
+                &*repeats.value,
+                pair_parts,
+                context,
+                shape,
+                SeparatorPlace::Back,
+            )
+        }
         ast::ExprKind::Range(ref lhs, ref rhs, limits) => {
             let delim = match limits {
                 ast::RangeLimits::HalfOpen => "..",
@@ -285,13 +291,12 @@ pub(crate) fn format_expr(
                 )
             };
 
+            let spacing = Spacing::new(context.config);
             match (lhs.as_ref().map(|x| &**x), rhs.as_ref().map(|x| &**x)) {
                 (Some(lhs), Some(rhs)) => {
-                    let sp_delim = if context.config.spaces_around_ranges() {
-                        format!(" {} ", delim)
-                    } else {
-                        default_sp_delim(Some(lhs), Some(rhs))
-                    };
+                    let sp_delim = spacing
+                        .range(delim, true, true)
+                        .unwrap_or_else(|| default_sp_delim(Some(lhs), Some(rhs)));
                     rewrite_pair(
                         &*lhs,
                         &*rhs,
@@ -302,19 +307,15 @@ pub(crate) fn format_expr(
                     )
                 }
                 (None, Some(rhs)) => {
-                    let sp_delim = if context.config.spaces_around_ranges() {
-                        format!("{} ", delim)
-                    } else {
-                        default_sp_delim(None, Some(rhs))
-                    };
+                    let sp_delim = spacing
+                        .range(delim, false, true)
+                        .unwrap_or_else(|| default_sp_delim(None, Some(rhs)));
                     rewrite_unary_prefix(context, &sp_delim, &*rhs, shape)
                 }
                 (Some(lhs), None) => {
-                    let sp_delim = if context.config.spaces_around_ranges() {
-                        format!(" {}", delim)
-                    } else {
-                        default_sp_delim(Some(lhs), None)
-                    };
+                    let sp_delim = spacing
+                        .range(delim, true, false)
+                        .unwrap_or_else(|| default_sp_delim(Some(lhs), None));
                     rewrite_unary_suffix(context, &sp_delim, &*lhs, shape)
                 }
                 (None, None) => Some(delim.to_owned()),
@@ -592,8 +593,10 @@ pub(crate) fn rewrite_cond(
 ) -> Option<String> {
     match expr.kind {
         ast::ExprKind::Match(ref cond, _) => {
+            let _indent_style =
+                context.override_indent_style(context.config.control_flow_indent_style());
             // `match `cond` {`
-            let cond_shape = match context.config.indent_style() {
+            let cond_shape = match context.indent_style() {
                 IndentStyle::Visual => shape.shrink_left(6).and_then(|s| s.sub_width(2))?,
                 IndentStyle::Block => shape.offset_left(8)?,
             };
@@ -762,6 +765,12 @@ impl<'a> ControlFlow<'a> {
         assert!(self.allow_single_line);
         let else_block = self.else_block?;
         let fixed_cost = self.keyword.len() + "  {  } else {  }".len();
+        let else_kwd_hi = context
+            .snippet_provider
+            .span_after(mk_sp(self.block.span.hi(), else_block.span.lo()), "else");
+        if collapsible_else_if(context, else_kwd_hi, else_block).is_some() {
+            return None;
+        }
 
         if let ast::ExprKind::Block(ref else_node, _) = else_block.kind {
             if !is_simple_block(context, self.block, None)
@@ -820,6 +829,8 @@ impl<'a> ControlFlow<'a> {
         offset: usize,
     ) -> Option<String> {
         debug!("rewrite_pat_expr {:?} {:?} {:?}", shape, self.pat, expr);
+        let _indent_style =
+            context.override_indent_style(context.config.control_flow_indent_style());
 
         let cond_shape = shape.offset_left(offset)?;
         if let Some(pat) = self.pat {
@@ -831,7 +842,7 @@ impl<'a> ControlFlow<'a> {
             let pat_shape = cond_shape
                 .offset_left(matcher.len())?
                 .sub_width(self.connector.len())?;
-            let pat_string = pat.rewrite(context, pat_shape)?;
+            let pat_string = rewrite_top_level_pattern(context, pat, pat_shape)?;
             let comments_lo = context
                 .snippet_provider
                 .span_after(self.span, self.connector.trim());
@@ -1057,6 +1068,19 @@ impl<'a> Rewrite for ControlFlow<'a> {
 
         if let Some(else_block) = self.else_block {
             let shape = Shape::indented(shape.indent, context.config);
+            let else_kwd_hi = context
+                .snippet_provider
+                .span_after(mk_sp(self.block.span.hi(), else_block.span.lo()), "else");
+            // `else { if .. }` is rewritten as `else if ..` when `collapse_else_if` is set.
+            // Comments between the opening brace and the inner `if` are kept after `else`.
+            let (else_block, after_else) =
+                match collapsible_else_if(context, else_kwd_hi, else_block) {
+                    Some(inner_if) => (
+                        inner_if,
+                        mk_sp(else_block.span.lo() + BytePos(1), inner_if.span.lo()),
+                    ),
+                    None => (else_block, mk_sp(else_kwd_hi, else_block.span.lo())),
+                };
             let mut last_in_chain = false;
             let rewrite = match else_block.kind {
                 // If the else expression is another if-else expression, prevent it
@@ -1097,12 +1121,6 @@ impl<'a> Rewrite for ControlFlow<'a> {
             let between_kwd_else_block_comment =
                 extract_comment(between_kwd_else_block, context, shape);
 
-            let after_else = mk_sp(
-                context
-                    .snippet_provider
-                    .span_after(mk_sp(self.block.span.hi(), else_block.span.lo()), "else"),
-                else_block.span.lo(),
-            );
             let after_else_comment = extract_comment(after_else, context, shape);
 
             let between_sep = match context.config.control_brace_style() {
@@ -1130,6 +1148,102 @@ impl<'a> Rewrite for ControlFlow<'a> {
     }
 }
 
+/// Returns the `if` expression in `else { if .. }` if the else block can be collapsed
+/// into an `else if` chain.
+fn collapsible_else_if<'a>(
+    context: &RewriteContext<'_>,
+    else_kwd_hi: BytePos,
+    else_block: &'a ast::Expr,
+) -> Option<&'a ast::Expr> {
+    if !context.config.collapse_else_if() || !else_block.attrs.is_empty() {
+        return None;
+    }
+    let block = match else_block.kind {
+        ast::ExprKind::Block(ref block, None)
+            if block.rules == ast::BlockCheckMode::Default && block.stmts.len() == 1 =>
+        {
+            block
+        }
+        _ => return None,
+    };
+    let inner_if = match block.stmts[0].kind {
+        ast::StmtKind::Expr(ref expr) if expr.attrs.is_empty() => expr,
+        _ => return None,
+    };
+    // Comments before the opening brace or after the inner `if` would have no place to go.
+    let before_block = mk_sp(else_kwd_hi, else_block.span.lo());
+    let after_inner_if = mk_sp(inner_if.span.hi(), else_block.span.hi());
+    match inner_if.kind {
+        ast::ExprKind::If(..)
+            if !contains_comment(context.snippet(before_block))
+                && !contains_comment(context.snippet(after_inner_if)) =>
+        {
+            Some(&**inner_if)
+        }
+        _ => None,
+    }
+}
+
+// Rewrite `return expr`. Unless `combine_control_expr_in_return` is set, a control
+// expression which does not fit on a single line is put on the next line.
+fn rewrite_return(context: &RewriteContext<'_>, expr: &ast::Expr, shape: Shape) -> Option<String> {
+    let rewrite = rewrite_unary_prefix(context, "return ", expr, shape);
+    let is_control_expr = match expr.kind {
+        ast::ExprKind::If(..)
+        | ast::ExprKind::Match(..)
+        | ast::ExprKind::Loop(..)
+        | ast::ExprKind::ForLoop(..)
+        | ast::ExprKind::While(..) => true,
+        _ => false,
+    };
+    if context.config.combine_control_expr_in_return()
+        || !is_control_expr
+        || rewrite.as_ref().map_or(false, |rw| !rw.contains('\n'))
+    {
+        return rewrite;
+    }
+
+    let nested_shape = shape
+        .block_indent(context.config.tab_spaces())
+        .with_max_width(context.config);
+    let expr_str = expr.rewrite(context, nested_shape)?;
+    Some(format!(
+        "return{}{}",
+        nested_shape.indent.to_string_with_newline(context.config),
+        expr_str
+    ))
+}
+
+// Rewrites `break` with its optional label and value. If the value doesn't fit after the
+// keyword and the label, it goes on the next line, block indented.
+fn rewrite_break(
+    context: &RewriteContext<'_>,
+    opt_label: Option<ast::Label>,
+    opt_expr: Option<&ast::Expr>,
+    shape: Shape,
+) -> Option<String> {
+    let keyword = format!("break{}", rewrite_label_ref(opt_label));
+    let expr = match opt_expr {
+        Some(expr) => expr,
+        None => return Some(keyword),
+    };
+    let rewrite = rewrite_unary_prefix(context, &format!("{} ", keyword), expr, shape);
+    if rewrite.is_some() {
+        return rewrite;
+    }
+
+    let nested_shape = shape
+        .block_indent(context.config.tab_spaces())
+        .with_max_width(context.config);
+    let expr_str = expr.rewrite(context, nested_shape)?;
+    Some(format!(
+        "{}{}{}",
+        keyword,
+        nested_shape.indent.to_string_with_newline(context.config),
+        expr_str
+    ))
+}
+
 fn rewrite_label(opt_label: Option<ast::Label>) -> Cow<'static, str> {
     match opt_label {
         Some(label) => Cow::from(format!("{}: ", label.ident)),
@@ -1137,6 +1251,14 @@ fn rewrite_label(opt_label: Option<ast::Label>) -> Cow<'static, str> {
     }
 }
 
+// Rewrites the label which `break` and `continue` refer to, along with the space before it.
+fn rewrite_label_ref(opt_label: Option<ast::Label>) -> Cow<'static, str> {
+    match opt_label {
+        Some(label) => Cow::from(format!(" {}", label.ident)),
+        None => Cow::from(""),
+    }
+}
+
 fn extract_comment(span: Span, context: &RewriteContext<'_>, shape: Shape) -> Option<String> {
     match rewrite_missing_comment(span, shape, context) {
         Some(ref comment) if !comment.is_empty() => Some(format!(
@@ -1212,7 +1334,25 @@ pub(crate) fn rewrite_literal(
     shape: Shape,
 ) -> Option<String> {
     match l.kind {
-        ast::LitKind::Str(_, ast::StrStyle::Cooked) => rewrite_string_lit(context, l.span, shape),
+        ast::LitKind::Str(_, ast::StrStyle::Cooked) => {
+            let string_lit = normalize_escapes(context.snippet(l.span), false, context.config);
+            rewrite_string_lit(context, &string_lit, shape)
+        }
+        ast::LitKind::Int(..) | ast::LitKind::Float(..) => wrap_str(
+            normalize_number_lit(context.snippet(l.span), context.config),
+            context.config.max_width(),
+            shape,
+        ),
+        ast::LitKind::Char(_) | ast::LitKind::Byte(_) | ast::LitKind::ByteStr(_) => {
+            let snippet = context.snippet(l.span);
+            // Raw byte strings have no escape sequences.
+            let lit = if snippet.starts_with("br") {
+                snippet.to_owned()
+            } else {
+                normalize_escapes(snippet, snippet.starts_with('b'), context.config)
+            };
+            wrap_str(lit, context.config.max_width(), shape)
+        }
         _ => wrap_str(
             context.snippet(l.span).to_owned(),
             context.config.max_width(),
@@ -1221,9 +1361,81 @@ pub(crate) fn rewrite_literal(
     }
 }
 
-fn rewrite_string_lit(context: &RewriteContext<'_>, span: Span, shape: Shape) -> Option<String> {
-    let string_lit = context.snippet(span);
+// Normalizes the letter case and digit grouping of a numeric literal as configured.
+fn normalize_number_lit(lit: &str, config: &Config) -> String {
+    let (prefix, radix) = match lit.get(..2) {
+        Some("0x") => ("0x", 16),
+        Some("0o") => ("0o", 8),
+        Some("0b") => ("0b", 2),
+        _ => ("", 10),
+    };
+    let rest = &lit[prefix.len()..];
+    let (number, suffix) = rest.split_at(number_lit_len(rest, radix));
+
+    let number = match radix {
+        16 => {
+            let digits = config.hex_literal_case().apply(number);
+            group_lit_digits(&digits, 4, config)
+        }
+        2 => group_lit_digits(number, 4, config),
+        8 => number.to_owned(),
+        _ => {
+            let (mantissa, exponent) =
+                number.split_at(number.find(&['e', 'E'][..]).unwrap_or(number.len()));
+            let (int_part, fraction) =
+                mantissa.split_at(mantissa.find('.').unwrap_or(mantissa.len()));
+            format!(
+                "{}{}{}",
+                group_lit_digits(int_part, 3, config),
+                fraction,
+                config.float_exponent_case().apply(exponent),
+            )
+        }
+    };
+    format!("{}{}{}", prefix, number, suffix)
+}
+
+// Returns the length of the digits of a numeric literal (without its radix prefix), i.e. the
+// position where its type suffix begins.
+fn number_lit_len(lit: &str, radix: u32) -> usize {
+    let mut chars = lit.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '_' => (),
+            c if c.is_digit(radix) => (),
+            '.' if radix == 10 => (),
+            'e' | 'E' if radix == 10 => {
+                if let Some(&(_, '+')) | Some(&(_, '-')) = chars.peek() {
+                    chars.next();
+                }
+            }
+            _ => return i,
+        }
+    }
+    lit.len()
+}
 
+// Regroups `digits` with an underscore every `group_size` digits, counting from the right.
+fn group_lit_digits(digits: &str, group_size: usize, config: &Config) -> String {
+    if !config.group_literal_digits() {
+        return digits.to_owned();
+    }
+    let digits: Vec<char> = digits.chars().filter(|&c| c != '_').collect();
+    let mut result = String::with_capacity(digits.len() + digits.len() / group_size);
+    for (i, c) in digits.iter().enumerate() {
+        if i > 0 && (digits.len() - i) % group_size == 0 {
+            result.push('_');
+        }
+        result.push(*c);
+    }
+    result
+}
+
+fn rewrite_string_lit(
+    context: &RewriteContext<'_>,
+    string_lit: &str,
+    shape: Shape,
+) -> Option<String> {
     if !context.config.format_strings() {
         if string_lit
             .lines()
@@ -1240,6 +1452,15 @@ fn rewrite_string_lit(context: &RewriteContext<'_>, span: Span, shape: Shape) ->
     // Remove the quote characters.
     let str_lit = &string_lit[1..string_lit.len() - 1];
 
+    if context.config.format_strings_style() == FormatStringsStyle::Concat
+        && !str_lit.contains('\n')
+        && unicode_str_width(string_lit) > shape.width
+    {
+        if let Some(rewrite) = rewrite_string_lit_with_concat(context, str_lit, shape) {
+            return Some(rewrite);
+        }
+    }
+
     rewrite_string(
         str_lit,
         &StringFormat::new(shape.visual_indent(0), context.config),
@@ -1247,6 +1468,37 @@ fn rewrite_string_lit(context: &RewriteContext<'_>, span: Span, shape: Shape) ->
     )
 }
 
+// Split a string literal into several literals joined with `concat!`, one per line.
+fn rewrite_string_lit_with_concat(
+    context: &RewriteContext<'_>,
+    str_lit: &str,
+    shape: Shape,
+) -> Option<String> {
+    let nested_shape = shape
+        .block_indent(context.config.tab_spaces())
+        .with_max_width(context.config);
+    let fmt = StringFormat {
+        opener: "\"",
+        closer: "\",",
+        line_start: "\"",
+        line_end: "\",",
+        shape: nested_shape,
+        trim_end: false,
+        config: context.config,
+    };
+    // 3 = `"",`
+    let pieces = rewrite_string(str_lit, &fmt, nested_shape.width.checked_sub(3)?)?;
+    if !pieces.contains('\n') {
+        return None;
+    }
+    Some(format!(
+        "concat!({}{}{})",
+        nested_shape.indent.to_string_with_newline(context.config),
+        pieces,
+        shape.indent.to_string_with_newline(context.config)
+    ))
+}
+
 fn choose_separator_tactic(context: &RewriteContext<'_>, span: Span) -> Option<SeparatorTactic> {
     if context.inside_macro() {
         if span_ends_with_comma(context, span) {
@@ -1308,7 +1560,7 @@ pub(crate) fn can_be_overflowed_expr(
         _ if !expr.attrs.is_empty() => false,
         ast::ExprKind::Match(..) => {
             (context.use_block_indent() && args_len == 1)
-                || (context.config.indent_style() == IndentStyle::Visual && args_len > 1)
+                || (context.indent_style() == IndentStyle::Visual && args_len > 1)
                 || context.config.overflow_delimited_expr()
         }
         ast::ExprKind::If(..)
@@ -1420,12 +1672,19 @@ fn rewrite_paren(
         break;
     }
 
-    // 1 = `(` and `)`
-    let sub_shape = shape.offset_left(1)?.sub_width(1)?;
+    let (lhs, rhs) = if context.config.spaces_within_parens() {
+        ("( ", " )")
+    } else {
+        ("(", ")")
+    };
+    let sub_shape = shape.offset_left(lhs.len())?.sub_width(rhs.len())?;
     let subexpr_str = subexpr.rewrite(context, sub_shape)?;
     let fits_single_line = !pre_comment.contains("//") && !post_comment.contains("//");
     if fits_single_line {
-        Some(format!("({}{}{})", pre_comment, subexpr_str, post_comment))
+        Some(format!(
+            "{}{}{}{}{}",
+            lhs, pre_comment, subexpr_str, post_comment, rhs
+        ))
     } else {
         rewrite_paren_in_multi_line(context, subexpr, shape, pre_span, post_span)
     }
@@ -1470,18 +1729,23 @@ fn rewrite_index(
 ) -> Option<String> {
     let expr_str = expr.rewrite(context, shape)?;
 
-    let offset = last_line_width(&expr_str) + 1;
+    let (lbr, rbr) = if context.config.spaces_within_brackets() {
+        ("[ ", " ]")
+    } else {
+        ("[", "]")
+    };
+    let offset = last_line_width(&expr_str) + lbr.len();
     let rhs_overhead = shape.rhs_overhead(context.config);
     let index_shape = if expr_str.contains('\n') {
         Shape::legacy(context.config.max_width(), shape.indent)
             .offset_left(offset)
-            .and_then(|shape| shape.sub_width(1 + rhs_overhead))
+            .and_then(|shape| shape.sub_width(rbr.len() + rhs_overhead))
     } else {
-        match context.config.indent_style() {
+        match context.indent_style() {
             IndentStyle::Block => shape
                 .offset_left(offset)
-                .and_then(|shape| shape.sub_width(1)),
-            IndentStyle::Visual => shape.visual_indent(offset).sub_width(offset + 1),
+                .and_then(|shape| shape.sub_width(rbr.len())),
+            IndentStyle::Visual => shape.visual_indent(offset).sub_width(offset + rbr.len()),
         }
     };
     let orig_index_rw = index_shape.and_then(|s| index.rewrite(context, s));
@@ -1489,30 +1753,34 @@ fn rewrite_index(
     // Return if index fits in a single line.
     match orig_index_rw {
         Some(ref index_str) if !index_str.contains('\n') => {
-            return Some(format!("{}[{}]", expr_str, index_str));
+            return Some(format!("{}{}{}{}", expr_str, lbr, index_str, rbr));
         }
         _ => (),
     }
 
     // Try putting index on the next line and see if it fits in a single line.
     let indent = shape.indent.block_indent(context.config);
-    let index_shape = Shape::indented(indent, context.config).offset_left(1)?;
-    let index_shape = index_shape.sub_width(1 + rhs_overhead)?;
+    let index_shape = Shape::indented(indent, context.config).offset_left(lbr.len())?;
+    let index_shape = index_shape.sub_width(rbr.len() + rhs_overhead)?;
     let new_index_rw = index.rewrite(context, index_shape);
     match (orig_index_rw, new_index_rw) {
         (_, Some(ref new_index_str)) if !new_index_str.contains('\n') => Some(format!(
-            "{}{}[{}]",
+            "{}{}{}{}{}",
             expr_str,
             indent.to_string_with_newline(context.config),
+            lbr,
             new_index_str,
+            rbr,
         )),
         (None, Some(ref new_index_str)) => Some(format!(
-            "{}{}[{}]",
+            "{}{}{}{}{}",
             expr_str,
             indent.to_string_with_newline(context.config),
+            lbr,
             new_index_str,
+            rbr,
         )),
-        (Some(ref index_str), _) => Some(format!("{}[{}]", expr_str, index_str)),
+        (Some(ref index_str), _) => Some(format!("{}{}{}{}", expr_str, lbr, index_str, rbr)),
         _ => None,
     }
 }
@@ -1551,6 +1819,10 @@ fn rewrite_struct_lit<'a>(
 
     // Foo { a: Foo } - indent is +3, width is -5.
     let (h_shape, v_shape) = struct_lit_shape(shape, context, path_str.len() + 3, 2)?;
+    // Struct literals with too many fields are always formatted vertically.
+    let force_multiline_fields = context.config.struct_lit_force_multiline_fields();
+    let h_shape =
+        h_shape.filter(|_| force_multiline_fields == 0 || fields.len() <= force_multiline_fields);
 
     let one_line_width = h_shape.map_or(0, |shape| shape.width);
     let body_lo = context.snippet_provider.span_after(span, "{");
@@ -1641,7 +1913,7 @@ pub(crate) fn wrap_struct_field(
     nested_shape: Shape,
     one_line_width: usize,
 ) -> Option<String> {
-    let should_vertical = context.config.indent_style() == IndentStyle::Block
+    let should_vertical = context.indent_style() == IndentStyle::Block
         && (fields_str.contains('\n')
             || !context.config.struct_lit_single_line()
             || fields_str.len() > one_line_width);
@@ -1655,6 +1927,8 @@ pub(crate) fn wrap_struct_field(
                 fields_str,
                 shape.indent.to_string_with_newline(context.config)
             ))
+        } else if !context.config.spaces_within_struct_lit_braces() && !fields_str.contains('\n') {
+            Some(fields_str.to_owned())
         } else {
             // One liner or visual indent.
             Some(format!(" {} ", fields_str))
@@ -1712,9 +1986,10 @@ pub(crate) fn rewrite_field(
                     .rewrite(context, Shape::indented(expr_offset, context.config));
                 expr.map(|s| {
                     format!(
-                        "{}{}:\n{}{}",
+                        "{}{}{}\n{}{}",
                         attrs_str,
                         name,
+                        Spacing::new(context.config).colon_before_padding(),
                         expr_offset.to_string(context.config),
                         s
                     )
@@ -1863,6 +2138,22 @@ fn rewrite_assignment(
     rewrite_assign_rhs(context, lhs_str, rhs, shape)
 }
 
+// Rewrites a `let` condition which is part of a chain of conditions, e.g. `let Some(b) = a` in
+// `if let Some(a) = foo() && let Some(b) = a`.
+fn rewrite_let_condition(
+    context: &RewriteContext<'_>,
+    pat: &ast::Pat,
+    init: &ast::Expr,
+    shape: Shape,
+) -> Option<String> {
+    // 4 = `let `, 2 = ` =`
+    let pat_shape = shape.offset_left(4)?.sub_width(2)?;
+    let pat_str = rewrite_top_level_pattern(context, pat, pat_shape)?;
+    let lhs_str = format!("let {} =", pat_str);
+
+    rewrite_assign_rhs(context, lhs_str, init, shape)
+}
+
 /// Controls where to put the rhs.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub(crate) enum RhsTactics {
@@ -0,0 +1,119 @@
+//! Reports of the code which rustfmt failed to format, written to the directory given by the
+//! `failure_report_dir` option so that they can be attached to bug reports.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::config::{Config, FileName};
+use crate::format_cache::hash_str;
+
+/// Code which rustfmt failed to format.
+pub(crate) struct Failure<'a> {
+    pub(crate) file: &'a FileName,
+    /// The 1-based line where the failed code starts.
+    pub(crate) line: usize,
+    /// What went wrong, e.g. the kind of item which could not be rewritten.
+    pub(crate) reason: &'a str,
+    /// The original text of the failed code, starting at the beginning of its first line.
+    pub(crate) snippet: &'a str,
+}
+
+/// Writes a report of `failure` if `failure_report_dir` is set. Failing to write the report
+/// only prints a warning, as it shouldn't affect formatting.
+pub(crate) fn report_failure(config: &Config, failure: &Failure<'_>) {
+    if config.failure_report_dir().is_empty() {
+        return;
+    }
+    let dir = Path::new(config.failure_report_dir());
+    if let Err(e) = write_report(dir, config, failure) {
+        eprintln!(
+            "Warning: failed to write a failure report to `{}`: {}",
+            dir.display(),
+            e
+        );
+    }
+}
+
+fn write_report(dir: &Path, config: &Config, failure: &Failure<'_>) -> Result<PathBuf, io::Error> {
+    let report = render_report(config, failure);
+    // Reports are named after their contents, so the same failure is only reported once, even
+    // across runs and builds of rustfmt.
+    let path = dir.join(format!("rustfmt-failure-{:016x}.md", hash_str(&report)));
+
+    fs::create_dir_all(dir)?;
+    fs::write(&path, report)?;
+    Ok(path)
+}
+
+fn render_report(config: &Config, failure: &Failure<'_>) -> String {
+    // Only the options which differ from their defaults are needed to reproduce the failure.
+    let toml = config.non_default_options().to_toml().unwrap_or_default();
+
+    let mut report = String::new();
+    let _ = writeln!(report, "# rustfmt failure report\n");
+    let _ = writeln!(report, "- **Version**: {}", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(report, "- **File**: {}:{}", failure.file, failure.line);
+    let _ = writeln!(report, "- **Reason**: {}\n", failure.reason);
+    let _ = writeln!(report, "## Config\n\n```toml\n{}```\n", toml);
+    let _ = writeln!(
+        report,
+        "## Snippet\n\n```rust\n{}\n```",
+        minimize_snippet(failure.snippet)
+    );
+    report
+}
+
+/// Strips the indentation which all lines of `snippet` have in common, along with leading and
+/// trailing blank lines, so that the snippet can be formatted on its own.
+fn minimize_snippet(snippet: &str) -> String {
+    let lines: Vec<&str> = snippet
+        .lines()
+        .map(str::trim_end)
+        .skip_while(|line| line.is_empty())
+        .collect();
+    let len = lines.len() - lines.iter().rev().take_while(|l| l.is_empty()).count();
+    let lines = &lines[..len];
+
+    let indent = lines
+        .iter()
+        .filter(|line| !line.is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    lines
+        .iter()
+        .map(|line| line.get(indent..).unwrap_or(""))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn minimize_snippet_strips_common_indentation() {
+        let snippet = "\n        fn foo() {\n            bar();\n\n        }\n   \n";
+        assert_eq!(minimize_snippet(snippet), "fn foo() {\n    bar();\n\n}");
+    }
+
+    #[test]
+    fn report_includes_location_and_non_default_options() {
+        let mut config = Config::default();
+        config.set().max_width(80);
+        let file = FileName::Stdin;
+        let failure = Failure {
+            file: &file,
+            line: 3,
+            reason: "failed to rewrite a statement",
+            snippet: "    let x =\n        1;",
+        };
+        let report = render_report(&config, &failure);
+        assert!(report.contains("- **File**: stdin:3\n"));
+        assert!(report.contains("- **Reason**: failed to rewrite a statement\n"));
+        assert!(report.contains("max_width = 80\n"));
+        assert!(report.ends_with("```rust\nlet x =\n    1;\n```\n"));
+    }
+}
@@ -12,7 +12,7 @@ use serde::{Deserialize, Serialize};
 use serde_json as json;
 use thiserror::Error;
 
-use std::collections::HashSet;
+use std::collections::BTreeSet;
 use std::env;
 use std::ffi::OsStr;
 use std::io::{self, BufRead};
@@ -85,7 +85,7 @@ fn run(opts: Opts) -> Result<(), FormatDiffError> {
     run_rustfmt(&files, &ranges)
 }
 
-fn run_rustfmt(files: &HashSet<String>, ranges: &[Range]) -> Result<(), FormatDiffError> {
+fn run_rustfmt(files: &BTreeSet<String>, ranges: &[Range]) -> Result<(), FormatDiffError> {
     if files.is_empty() || ranges.is_empty() {
         debug!("No files to format found");
         return Ok(());
@@ -122,7 +122,7 @@ fn scan_diff<R>(
     from: R,
     skip_prefix: u32,
     file_filter: &str,
-) -> Result<(HashSet<String>, Vec<Range>), FormatDiffError>
+) -> Result<(BTreeSet<String>, Vec<Range>), FormatDiffError>
 where
     R: io::Read,
 {
@@ -135,7 +135,7 @@ where
 
     let mut current_file = None;
 
-    let mut files = HashSet::new();
+    let mut files = BTreeSet::new();
     let mut ranges = vec![];
     for line in io::BufReader::new(from).lines() {
         let line = line.unwrap();
@@ -0,0 +1,172 @@
+//! An on-disk cache of the inputs which are known to be formatted, so that they can be
+//! skipped without being parsed when neither they nor the config have changed since.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+
+/// The files formatted for an input file, with the hashes of their formatted contents.
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedInput {
+    files: Vec<(PathBuf, u64)>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct FormatCache {
+    #[serde(skip)]
+    path: PathBuf,
+    #[serde(skip)]
+    dirty: bool,
+    /// The version of rustfmt which wrote the cache, as other versions may format differently.
+    version: String,
+    /// The cached inputs, by path and by digest of the config they were formatted with.
+    inputs: BTreeMap<PathBuf, BTreeMap<String, CachedInput>>,
+}
+
+impl FormatCache {
+    /// Loads the cache stored at `path`. A missing or invalid cache, or a cache written by
+    /// another version of rustfmt, is treated as an empty cache.
+    pub(crate) fn load(path: &Path) -> FormatCache {
+        let cache = fs::read_to_string(path)
+            .ok()
+            .and_then(|json| serde_json::from_str::<FormatCache>(&json).ok())
+            .filter(|cache| cache.version == env!("CARGO_PKG_VERSION"));
+        FormatCache {
+            path: path.to_path_buf(),
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            ..cache.unwrap_or_default()
+        }
+    }
+
+    /// Returns `true` if `input` was formatted with the same config and none of the files
+    /// formatted with it have changed since.
+    pub(crate) fn is_formatted(&self, input: &Path, config: &Config) -> bool {
+        match self
+            .inputs
+            .get(input)
+            .and_then(|configs| configs.get(&config_digest(config)))
+        {
+            Some(cached) => cached.files.iter().all(|(file, hash)| {
+                fs::read_to_string(file).map_or(false, |text| hash_str(&text) == *hash)
+            }),
+            None => false,
+        }
+    }
+
+    /// Returns the files formatted with `input` and the same config.
+    pub(crate) fn files(&self, input: &Path, config: &Config) -> Vec<PathBuf> {
+        self.inputs
+            .get(input)
+            .and_then(|configs| configs.get(&config_digest(config)))
+            .map_or_else(Vec::new, |cached| {
+                cached.files.iter().map(|(file, _)| file.clone()).collect()
+            })
+    }
+
+    /// Records that `input` is formatted, along with the hashes of the formatted contents of the
+    /// files formatted with it, as returned by `hash_str`. These are only hits later on if the
+    /// files on disk match them.
+    pub(crate) fn insert<'a>(
+        &mut self,
+        input: &Path,
+        config: &Config,
+        files: impl Iterator<Item = (&'a Path, u64)>,
+    ) {
+        let cached = CachedInput {
+            files: files
+                .map(|(file, hash)| (file.to_path_buf(), hash))
+                .collect(),
+        };
+        self.inputs
+            .entry(input.to_path_buf())
+            .or_insert_with(BTreeMap::new)
+            .insert(config_digest(config), cached);
+        self.dirty = true;
+    }
+
+    /// Forgets about `input`, e.g. because formatting it failed.
+    pub(crate) fn remove(&mut self, input: &Path) {
+        if self.inputs.remove(input).is_some() {
+            self.dirty = true;
+        }
+    }
+
+    /// Writes the cache to disk if it changed since it was loaded.
+    pub(crate) fn save(&mut self) -> Result<(), io::Error> {
+        if !self.dirty {
+            return Ok(());
+        }
+        let json = serde_json::to_string(self)?;
+        fs::write(&self.path, json)?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+/// Returns the 64-bit FNV-1a hash of `text`. Unlike `DefaultHasher`, it is the same across
+/// runs and builds of rustfmt, which the cache and the failure reports named after it outlive.
+pub(crate) fn hash_str(text: &str) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    text.bytes().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    })
+}
+
+fn config_digest(config: &Config) -> String {
+    // Every option shows up in the TOML dump, so any change to the config changes the digest.
+    let toml = config.all_options().to_toml().unwrap_or_default();
+    format!("{:016x}", hash_str(&toml))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn cache_hits_only_unchanged_files() {
+        let dir = env::temp_dir().join(format!("rustfmt-format-cache-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let cache_path = dir.join(".rustfmt-cache");
+        let input = dir.join("lib.rs");
+        let formatted = "fn main() {}\n";
+        fs::write(&input, formatted).unwrap();
+
+        let config = Config::default();
+        let mut cache = FormatCache::load(&cache_path);
+        let miss = cache.is_formatted(&input, &config);
+        cache.insert(
+            &input,
+            &config,
+            vec![(&*input, hash_str(formatted))].into_iter(),
+        );
+        cache.save().unwrap();
+
+        let cache = FormatCache::load(&cache_path);
+        let hit = cache.is_formatted(&input, &config);
+        let mut other_config = Config::default();
+        other_config.set().max_width(80);
+        let other_config_hit = cache.is_formatted(&input, &other_config);
+        fs::write(&input, "fn main () {}\n").unwrap();
+        let changed_hit = cache.is_formatted(&input, &config);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(!miss);
+        assert!(hit);
+        assert!(!other_config_hit);
+        assert!(!changed_hit);
+    }
+
+    #[test]
+    fn hashes_are_stable() {
+        assert_eq!(hash_str(""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash_str("a"), 0xaf63_dc4c_8601_ec8c);
+    }
+}
@@ -165,10 +165,18 @@ fn error_kind_to_snippet_annotation_type(error_kind: &ErrorKind) -> AnnotationTy
         | ErrorKind::ModuleResolutionError(_)
         | ErrorKind::ParseError
         | ErrorKind::LostComment
+        | ErrorKind::CommentLoss
+        | ErrorKind::SemanticChange
         | ErrorKind::LicenseCheck
+        | ErrorKind::MissingNewlineAtEof
+        | ErrorKind::FormatPanic
         | ErrorKind::BadAttr
         | ErrorKind::InvalidGlobPattern(_)
         | ErrorKind::VersionMismatch => AnnotationType::Error,
-        ErrorKind::BadIssue(_) | ErrorKind::DeprecatedAttr => AnnotationType::Warning,
+        ErrorKind::BadIssue(_)
+        | ErrorKind::DeprecatedAttr
+        | ErrorKind::RewriteLimitExceeded
+        | ErrorKind::FileTimeLimitExceeded
+        | ErrorKind::MixedIndentation => AnnotationType::Warning,
     }
 }
@@ -1,23 +1,40 @@
 // High level formatting functions.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
 use std::io::{self, Write};
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::time::{Duration, Instant};
 
 use rustc_ast::ast;
 use rustc_span::Span;
 
+use self::conservative::apply_conservative_profile;
+use self::minimal_diff::apply_minimal_diff;
 use self::newline_style::apply_newline_style;
-use crate::comment::{CharClasses, FullCodeCharKind};
-use crate::config::{Config, FileName, Verbosity};
-use crate::issues::BadIssueSeeker;
+use crate::cancellation::Cancellation;
+use crate::comment::{changed_comments, CharClasses, FullCodeCharKind};
+use crate::config::{
+    Config, EmitMode, FileName, FinalNewline, FormattingProfile, ModuleTraversal, Verbosity,
+};
+use crate::emitter::RunStats;
+use crate::failure_report::{report_failure, Failure};
+use crate::issues::{BadIssueSeeker, Issue};
+use crate::line_mapping::LineMapping;
 use crate::modules::Module;
+use crate::rewrite::RewriteHook;
+use crate::semantic_check::changed_item_line;
 use crate::syntux::parser::{DirectoryOwnership, Parser, ParserError};
 use crate::syntux::session::ParseSess;
 use crate::utils::count_newlines;
 use crate::visitor::FmtVisitor;
-use crate::{modules, source_file, ErrorKind, FormatReport, Input, Session};
+use crate::{format_cache, modules, source_file, ErrorKind, FormatReport, Input, Session};
 
+mod conservative;
+mod minimal_diff;
 mod newline_style;
 
 // A map of the files of a crate, with their new content
@@ -41,8 +58,42 @@ impl<'b, T: Write + 'b> Session<'b, T> {
                 return Ok(FormatReport::new());
             }
 
+            let cached_input = self.cached_input(&input);
+            if let Some(ref path) = cached_input {
+                if self
+                    .format_cache
+                    .as_ref()
+                    .unwrap()
+                    .is_formatted(path, &self.config)
+                {
+                    self.emit_cached_files(path)?;
+                    return Ok(FormatReport::new());
+                }
+            }
+            let formatted_files = self.file_digests.len();
+
             let config = &self.config.clone();
-            let format_result = format_project(input, config, self);
+            let format_result = format_project(vec![input], config, self);
+
+            if let Some(ref path) = cached_input {
+                let is_clean = match format_result {
+                    Ok(ref report) => report.internal.borrow().1.is_clean(),
+                    Err(_) => false,
+                };
+                let format_cache = self.format_cache.as_mut().unwrap();
+                if is_clean {
+                    let files =
+                        self.file_digests[formatted_files..]
+                            .iter()
+                            .filter_map(|(file, digest)| match file {
+                                FileName::Real(file) => Some((file.as_path(), *digest)),
+                                FileName::Stdin => None,
+                            });
+                    format_cache.insert(path, config, files);
+                } else {
+                    format_cache.remove(path);
+                }
+            }
 
             format_result.map(|report| {
                 self.errors.add(&report.internal.borrow().1);
@@ -50,63 +101,183 @@ impl<'b, T: Write + 'b> Session<'b, T> {
             })
         })
     }
+
+    pub(crate) fn format_roots_inner(
+        &mut self,
+        roots: Vec<PathBuf>,
+    ) -> Result<FormatReport, ErrorKind> {
+        if !self.config.version_meets_requirement() {
+            return Err(ErrorKind::VersionMismatch);
+        }
+
+        rustc_span::with_session_globals(self.config.edition().to_libsyntax_pos_edition(), || {
+            if self.config.disable_all_formatting() {
+                return Ok(FormatReport::new());
+            }
+
+            let config = &self.config.clone();
+            let inputs = roots.into_iter().map(Input::File).collect();
+            format_project(inputs, config, self).map(|report| {
+                self.errors.add(&report.internal.borrow().1);
+                report
+            })
+        })
+    }
+
+    /// Writes the files of an input skipped thanks to the format cache to `out_dir` as they are,
+    /// so that the output directory still holds the whole tree.
+    fn emit_cached_files(&mut self, input: &Path) -> Result<(), ErrorKind> {
+        if self.config.emit_mode() != EmitMode::Files || self.config.out_dir().is_empty() {
+            return Ok(());
+        }
+        let files = self
+            .format_cache
+            .as_ref()
+            .unwrap()
+            .files(input, &self.config);
+        if let Some(ref mut out) = self.out {
+            for file in files {
+                let text = fs::read_to_string(&file)?;
+                source_file::write_file(
+                    None,
+                    &FileName::Real(file),
+                    &text,
+                    out,
+                    &mut *self.emitter,
+                    &self.config,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the path of `input` if it can be looked up in the format cache: only whole files
+    /// which are checked or overwritten can be skipped, as the other emit modes print every
+    /// formatted file.
+    fn cached_input(&self, input: &Input) -> Option<PathBuf> {
+        match (input, self.config.emit_mode()) {
+            (Input::File(ref path), EmitMode::Files) | (Input::File(ref path), EmitMode::Diff)
+                if self.format_cache.is_some() && self.config.file_lines().is_all() =>
+            {
+                Some(path.clone())
+            }
+            _ => None,
+        }
+    }
 }
 
-// Format an entire crate (or subset of the module tree).
+// Format entire crates (or subsets of their module trees). The crates share one parse session,
+// and a file reachable from several crates is only formatted with the first of them.
 fn format_project<T: FormatHandler>(
-    input: Input,
+    inputs: Vec<Input>,
     config: &Config,
     handler: &mut T,
 ) -> Result<FormatReport, ErrorKind> {
     let mut timer = Timer::start();
 
-    let main_file = input.file_name();
-    let input_is_stdin = main_file == FileName::Stdin;
+    let input_is_stdin = inputs
+        .iter()
+        .any(|input| input.file_name() == FileName::Stdin);
 
     let parse_session = ParseSess::new(config)?;
-    if config.skip_children() && parse_session.ignore_file(&main_file) {
-        return Ok(FormatReport::new());
-    }
 
-    // Parse the crate.
+    // Parse the crates.
     let mut report = FormatReport::new();
-    let directory_ownership = input.to_directory_ownership();
-    let krate = match Parser::parse_crate(config, input, directory_ownership, &parse_session) {
-        Ok(krate) => krate,
-        // Surface parse error via Session (errors are merged there from report)
-        Err(e) => {
-            let forbid_verbose = input_is_stdin || e != ParserError::ParsePanicError;
-            should_emit_verbose(forbid_verbose, config, || {
-                eprintln!("The Rust parser panicked");
-            });
-            report.add_parsing_error();
-            return Ok(report);
+    let mut crates = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let main_file = input.file_name();
+        let traversal = ModuleTraversal::for_input(config, &main_file);
+        if !traversal.resolves_modules() && parse_session.ignore_file(&main_file) {
+            continue;
         }
-    };
 
-    let mut context = FormatContext::new(&krate, report, parse_session, config, handler);
-    let files = modules::ModResolver::new(
-        &context.parse_session,
-        directory_ownership.unwrap_or(DirectoryOwnership::UnownedViaMod),
-        !input_is_stdin && !config.skip_children(),
-    )
-    .visit_crate(&krate)?;
+        let directory_ownership = input.to_directory_ownership();
+        match Parser::parse_crate(config, input, directory_ownership, &parse_session) {
+            Ok(krate) => crates.push((krate, main_file, traversal, directory_ownership)),
+            // Surface parse error via Session (errors are merged there from report)
+            Err(e) => {
+                let forbid_verbose = input_is_stdin || e != ParserError::ParsePanicError;
+                should_emit_verbose(forbid_verbose, config, || {
+                    eprintln!("The Rust parser panicked");
+                });
+                report.add_parsing_error();
+            }
+        }
+    }
+    // The code with errors is left as it is, but the errors are still reported.
+    if parse_session.has_recovered_errors() {
+        report.add_parsing_error();
+    }
+    if crates.is_empty() {
+        return Ok(report);
+    }
+
+    let mut context = FormatContext::new(report, parse_session, config, handler);
+    let mut files = vec![];
+    // The canonical paths of the files to format.
+    let mut seen_files = HashSet::new();
+    for (krate, main_file, traversal, directory_ownership) in &crates {
+        let modules = modules::ModResolver::new(
+            &context.parse_session,
+            directory_ownership.unwrap_or(DirectoryOwnership::UnownedViaMod),
+            *traversal,
+            config.format_included_files(),
+            config.symlinks(),
+        )
+        .visit_crate(krate)?;
+        for (path, module) in modules {
+            let should_ignore = !input_is_stdin && context.ignore_file(&path);
+            if !traversal.formats(&path, main_file) || should_ignore {
+                continue;
+            }
+            let is_new = match path {
+                FileName::Real(ref file) => {
+                    let file = modules::canonical_path(file);
+                    seen_files.insert(file.clone()) && context.handler.claim_file(&file)
+                }
+                FileName::Stdin => true,
+            };
+            if is_new {
+                files.push((path, module, krate));
+            }
+        }
+    }
 
     timer = timer.done_parsing();
 
     // Suppress error output if we have to do any further parsing.
     context.parse_session.set_silent_emitter();
 
-    for (path, module) in files {
-        let should_ignore = !input_is_stdin && context.ignore_file(&path);
-        if (config.skip_children() && path != main_file) || should_ignore {
-            continue;
-        }
+    for (path, module, krate) in files {
         should_emit_verbose(input_is_stdin, config, || println!("Formatting {}", path));
-        context.format_file(path, &module)?;
+        // A panic while formatting a file shouldn't abort formatting the other files.
+        let file_timer = Timer::start();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            context.format_file(path.clone(), &module, krate)
+        }));
+        context
+            .report
+            .finish_file_stats(path.clone(), file_timer.get_elapsed_time());
+        match result {
+            Ok(result) => result?,
+            Err(_) => context.handle_panicked_file(path, &module)?,
+        }
     }
     timer = timer.done_formatting();
 
+    if let Timer::DoneFormatting(..) = timer {
+        let file_stats = context.report.file_stats();
+        let stats = RunStats {
+            parse_time: timer.get_parse_time(),
+            format_time: timer.get_format_time(),
+            skipped_items: context.report.non_formatted_ranges.len(),
+            items_formatted: file_stats.values().map(|s| s.items_formatted).sum(),
+            rewrite_fallbacks: file_stats.values().map(|s| s.rewrite_fallbacks).sum(),
+        };
+        context.report.set_run_stats(stats);
+        context.handler.record_stats(stats);
+    }
+
     should_emit_verbose(input_is_stdin, config, || {
         println!(
             "Spent {0:.3} secs in the parsing phase, and {1:.3} secs in the formatting phase",
@@ -118,10 +289,15 @@ fn format_project<T: FormatHandler>(
     Ok(context.report)
 }
 
+#[cfg(test)]
+thread_local! {
+    /// Makes formatting files panic, to test how such panics are reported.
+    pub(crate) static PANIC_WHILE_FORMATTING: std::cell::Cell<bool> = std::cell::Cell::new(false);
+}
+
 // Used for formatting files.
 #[derive(new)]
 struct FormatContext<'a, T: FormatHandler> {
-    krate: &'a ast::Crate,
     report: FormatReport,
     parse_session: ParseSess,
     config: &'a Config,
@@ -133,8 +309,86 @@ impl<'a, T: FormatHandler + 'a> FormatContext<'a, T> {
         self.parse_session.ignore_file(path)
     }
 
-    // Formats a single file/module.
-    fn format_file(&mut self, path: FileName, module: &Module<'_>) -> Result<(), ErrorKind> {
+    // Reports a file whose formatting panicked. Unless files are overwritten, in which case
+    // the file is left as is, the original text of the file is emitted.
+    fn handle_panicked_file(
+        &mut self,
+        path: FileName,
+        module: &Module<'_>,
+    ) -> Result<(), ErrorKind> {
+        let snippet_provider = self.parse_session.snippet_provider(module.as_ref().inner);
+        let original = snippet_provider.entire_snippet();
+        report_failure(
+            self.config,
+            &Failure {
+                file: &path,
+                line: 1,
+                reason: "rustfmt panicked while formatting this file",
+                snippet: original,
+            },
+        );
+        let error = FormattingError {
+            line: 1,
+            kind: ErrorKind::FormatPanic,
+            is_comment: false,
+            is_string: false,
+            line_buffer: original.lines().next().unwrap_or("").to_owned(),
+        };
+        self.report.append(path.clone(), vec![error]);
+        self.leave_unformatted(path, original)
+    }
+
+    // Emits the original text of a file instead of its formatted text. Files which would be
+    // overwritten are left untouched.
+    fn leave_unformatted(&mut self, path: FileName, original: &str) -> Result<(), ErrorKind> {
+        if self.config.emit_mode() == EmitMode::Files {
+            return Ok(());
+        }
+        self.handler.handle_formatted_file(
+            &self.parse_session,
+            path,
+            original.to_owned(),
+            &mut self.report,
+        )
+    }
+
+    // Reports the comments which formatting dropped or duplicated. Returns `true` if there are
+    // any, in which case the file must be left unformatted.
+    fn report_changed_comments(&self, path: &FileName, original: &str, formatted: &str) -> bool {
+        let (dropped, mut added) = changed_comments(original, formatted);
+        if self.config.normalize_doc_attributes() {
+            // Doc attributes turned into doc comments would show up as added comments.
+            added.clear();
+        }
+        let errors: Vec<_> = dropped
+            .into_iter()
+            .map(|comment| (comment, original))
+            .chain(added.into_iter().map(|comment| (comment, formatted)))
+            .map(|(comment, text)| FormattingError {
+                line: comment.line,
+                kind: ErrorKind::CommentLoss,
+                is_comment: false,
+                is_string: false,
+                line_buffer: text.lines().nth(comment.line - 1).unwrap_or("").to_owned(),
+            })
+            .collect();
+        if errors.is_empty() {
+            return false;
+        }
+        self.report.append(path.clone(), errors);
+        true
+    }
+
+    // Formats a single file/module of `krate`.
+    fn format_file(
+        &mut self,
+        path: FileName,
+        module: &Module<'_>,
+        krate: &ast::Crate,
+    ) -> Result<(), ErrorKind> {
+        #[cfg(test)]
+        PANIC_WHILE_FORMATTING.with(|panic| assert!(!panic.get(), "formatting panicked"));
+
         let snippet_provider = self.parse_session.snippet_provider(module.as_ref().inner);
         let mut visitor = FmtVisitor::from_parse_sess(
             &self.parse_session,
@@ -142,7 +396,9 @@ impl<'a, T: FormatHandler + 'a> FormatContext<'a, T> {
             &snippet_provider,
             self.report.clone(),
         );
-        visitor.skip_context.update_with_attrs(&self.krate.attrs);
+        visitor.skip_context.update_with_attrs(&krate.attrs);
+        visitor.rewrite_hooks = Rc::from(self.handler.rewrite_hooks());
+        visitor.cancellation = self.handler.cancellation();
 
         visitor.last_pos = snippet_provider.start_pos();
         visitor.skip_empty_lines(snippet_provider.end_pos());
@@ -154,18 +410,58 @@ impl<'a, T: FormatHandler + 'a> FormatContext<'a, T> {
             "failed in format_file visitor.buffer:\n {:?}",
             &visitor.buffer
         );
+        visitor.align_trailing_comments();
 
         // For some reason, the source_map does not include terminating
         // newlines so we must add one on for each file. This is sad.
         source_file::append_newline(&mut visitor.buffer);
 
-        format_lines(
+        let mut line_mapping = LineMapping::new(mem::take(&mut visitor.line_anchors));
+
+        // The parser skips a leading shebang line, make sure it is kept as is.
+        if let Some(shebang) = source_file::shebang_line(snippet_provider.entire_snippet()) {
+            if !visitor.buffer.starts_with(shebang) {
+                visitor.buffer.insert_str(0, &format!("{}\n", shebang));
+                line_mapping.shift_formatted_lines(2, 1);
+            }
+        }
+
+        // Both keep original lines in the place of formatted ones, so the line mapping and the
+        // skipped ranges are recomputed for the text which is actually written.
+        let keeps_original_lines = self.config.formatting_profile()
+            == FormattingProfile::Conservative
+            || self.config.minimal_diff();
+        if keeps_original_lines {
+            let original = snippet_provider.entire_snippet();
+            let formatted = visitor.buffer.clone();
+            if self.config.formatting_profile() == FormattingProfile::Conservative {
+                apply_conservative_profile(&mut visitor.buffer, original);
+            }
+            if self.config.minimal_diff() {
+                apply_minimal_diff(&mut visitor.buffer, original);
+            }
+            let kept_lines = LineMapping::from_diff(&formatted, &visitor.buffer);
+            for (lo, hi) in visitor.skipped_range.borrow_mut().iter_mut() {
+                *lo = kept_lines.formatted_line(*lo);
+                *hi = kept_lines.formatted_line(*hi);
+            }
+            line_mapping = LineMapping::from_diff(original, &visitor.buffer);
+        }
+
+        let inserted_lines = format_lines(
             &mut visitor.buffer,
             &path,
             &visitor.skipped_range.borrow(),
             &self.config,
             &self.report,
         );
+        line_mapping.shift_formatted_lines(1, inserted_lines);
+        check_final_newline(
+            snippet_provider.entire_snippet(),
+            &path,
+            &self.config,
+            &self.report,
+        );
 
         apply_newline_style(
             self.config.newline_style(),
@@ -173,11 +469,40 @@ impl<'a, T: FormatHandler + 'a> FormatContext<'a, T> {
             snippet_provider.entire_snippet(),
         );
 
+        if self.config.verify_comments()
+            && self.report_changed_comments(
+                &path,
+                snippet_provider.entire_snippet(),
+                &visitor.buffer,
+            )
+        {
+            // The visitor borrows from `self` until it's dropped.
+            drop(visitor);
+            return self.leave_unformatted(path, snippet_provider.entire_snippet());
+        }
+
+        if self.config.verify_semantics() {
+            let original = snippet_provider.entire_snippet();
+            if let Some(line) = changed_item_line(&self.config, original, &visitor.buffer) {
+                let error = FormattingError {
+                    line,
+                    kind: ErrorKind::SemanticChange,
+                    is_comment: false,
+                    is_string: false,
+                    line_buffer: original.lines().nth(line - 1).unwrap_or("").to_owned(),
+                };
+                self.report.append(path.clone(), vec![error]);
+                drop(visitor);
+                return self.leave_unformatted(path, original);
+            }
+        }
+
         if visitor.macro_rewrite_failure {
             self.report.add_macro_format_failure();
         }
         self.report
-            .add_non_formatted_ranges(visitor.skipped_range.borrow().clone());
+            .add_non_formatted_ranges(path.clone(), visitor.skipped_range.borrow().clone());
+        self.report.add_line_mapping(path.clone(), line_mapping);
 
         self.handler.handle_formatted_file(
             &self.parse_session,
@@ -197,6 +522,19 @@ trait FormatHandler {
         result: String,
         report: &mut FormatReport,
     ) -> Result<(), ErrorKind>;
+
+    // Called once all files are formatted.
+    fn record_stats(&mut self, stats: RunStats);
+
+    // The hooks to call around the rewrite of every item.
+    fn rewrite_hooks(&self) -> &[Rc<dyn RewriteHook>];
+
+    // The conditions under which formatting stops early.
+    fn cancellation(&self) -> Cancellation;
+
+    // Returns `false` if the file with the canonical path `path` was already formatted and must
+    // be skipped, and records that it is formatted otherwise.
+    fn claim_file(&mut self, path: &Path) -> bool;
 }
 
 impl<'b, T: Write + 'b> FormatHandler for Session<'b, T> {
@@ -215,21 +553,47 @@ impl<'b, T: Write + 'b> FormatHandler for Session<'b, T> {
                 &result,
                 out,
                 &mut *self.emitter,
-                self.config.newline_style(),
+                &self.config,
             ) {
-                Ok(ref result) if result.has_diff => report.add_diff(),
+                Ok(ref result) => {
+                    if result.has_diff {
+                        report.add_diff();
+                    }
+                    report.add_formatted_file(result.changed);
+                }
                 Err(e) => {
                     // Create a new error with path_str to help users see which files failed
                     let err_msg = format!("{}: {}", path, e);
                     return Err(io::Error::new(e.kind(), err_msg).into());
                 }
-                _ => {}
             }
         }
 
-        self.source_file.push((path, result));
+        self.file_digests
+            .push((path.clone(), format_cache::hash_str(&result)));
+        if !self.stream_output {
+            self.source_file.push((path, result));
+        }
         Ok(())
     }
+
+    fn record_stats(&mut self, stats: RunStats) {
+        self.emitter.record_stats(stats);
+    }
+
+    fn rewrite_hooks(&self) -> &[Rc<dyn RewriteHook>] {
+        &self.rewrite_hooks
+    }
+
+    fn cancellation(&self) -> Cancellation {
+        self.cancellation.clone()
+    }
+
+    fn claim_file(&mut self, path: &Path) -> bool {
+        self.formatted_files
+            .as_mut()
+            .map_or(true, |files| files.insert(path.to_path_buf()))
+    }
 }
 
 pub(crate) struct FormattingError {
@@ -261,7 +625,10 @@ impl FormattingError {
             | ErrorKind::TrailingWhitespace
             | ErrorKind::IoError(_)
             | ErrorKind::ParseError
-            | ErrorKind::LostComment => true,
+            | ErrorKind::LostComment
+            | ErrorKind::CommentLoss
+            | ErrorKind::SemanticChange
+            | ErrorKind::FormatPanic => true,
             _ => false,
         }
     }
@@ -279,12 +646,34 @@ impl FormattingError {
     pub(crate) fn format_len(&self) -> (usize, usize) {
         match self.kind {
             ErrorKind::LineOverflow(found, max) => (max, found - max),
+            ErrorKind::FormatPanic => (0, 0),
+            ErrorKind::CommentLoss
+            | ErrorKind::SemanticChange
+            | ErrorKind::RewriteLimitExceeded
+            | ErrorKind::FileTimeLimitExceeded => {
+                // Point at the line of the comment, item or expression.
+                let text = self.line_buffer.trim_end();
+                let start = text.len() - text.trim_start().len();
+                (start, text.len() - start)
+            }
+            ErrorKind::MixedIndentation => {
+                // Point at the indentation.
+                let indent = self.line_buffer.len() - self.line_buffer.trim_start().len();
+                (0, indent)
+            }
+            ErrorKind::BadIssue(ref issue) => {
+                // Point at the issue and the text following it.
+                let text = self.line_buffer.trim_end();
+                let keyword = issue.keyword().to_ascii_lowercase();
+                let start = text.to_ascii_lowercase().find(&keyword).unwrap_or(0);
+                (start, text.len() - start)
+            }
             ErrorKind::TrailingWhitespace
             | ErrorKind::DeprecatedAttr
-            | ErrorKind::BadIssue(_)
             | ErrorKind::BadAttr
             | ErrorKind::LostComment
-            | ErrorKind::LicenseCheck => {
+            | ErrorKind::LicenseCheck
+            | ErrorKind::MissingNewlineAtEof => {
                 let trailing_ws_start = self
                     .line_buffer
                     .rfind(|c: char| !c.is_whitespace())
@@ -300,13 +689,16 @@ impl FormattingError {
     }
 }
 
-pub(crate) type FormatErrorMap = HashMap<FileName, Vec<FormattingError>>;
+pub(crate) type FormatErrorMap = BTreeMap<FileName, Vec<FormattingError>>;
 
 #[derive(Default, Debug, PartialEq)]
 pub(crate) struct ReportedErrors {
     // Encountered e.g., an IO error.
     pub(crate) has_operational_errors: bool,
 
+    // Rustfmt panicked while formatting a file.
+    pub(crate) has_internal_errors: bool,
+
     // Failed to reformat code because of parsing errors.
     pub(crate) has_parsing_errors: bool,
 
@@ -321,17 +713,41 @@ pub(crate) struct ReportedErrors {
 
     /// Formatted code differs from existing code (--check only).
     pub(crate) has_diff: bool,
+
+    /// Formatting was cancelled before all items were formatted.
+    pub(crate) was_cancelled: bool,
+
+    /// Number of files whose formatted code differs from the existing code.
+    pub(crate) files_changed: usize,
+
+    /// Number of files which were already formatted.
+    pub(crate) files_unchanged: usize,
 }
 
 impl ReportedErrors {
+    /// Whether nothing is to be reported, i.e. the code is formatted and passed every check.
+    pub(crate) fn is_clean(&self) -> bool {
+        !(self.has_operational_errors
+            || self.has_parsing_errors
+            || self.has_formatting_errors
+            || self.has_macro_format_failure
+            || self.has_check_errors
+            || self.has_diff
+            || self.was_cancelled)
+    }
+
     /// Combine two summaries together.
     pub(crate) fn add(&mut self, other: &ReportedErrors) {
         self.has_operational_errors |= other.has_operational_errors;
+        self.has_internal_errors |= other.has_internal_errors;
         self.has_parsing_errors |= other.has_parsing_errors;
         self.has_formatting_errors |= other.has_formatting_errors;
         self.has_macro_format_failure |= other.has_macro_format_failure;
         self.has_check_errors |= other.has_check_errors;
         self.has_diff |= other.has_diff;
+        self.was_cancelled |= other.was_cancelled;
+        self.files_changed += other.files_changed;
+        self.files_unchanged += other.files_unchanged;
     }
 }
 
@@ -369,6 +785,17 @@ impl Timer {
         }
     }
 
+    /// Returns the time elapsed since the timer was started in seconds, or 0 if this platform
+    /// cannot time execution.
+    fn get_elapsed_time(&self) -> f32 {
+        match *self {
+            Timer::Disabled => 0.0,
+            Timer::Initialized(init)
+            | Timer::DoneParsing(init, _)
+            | Timer::DoneFormatting(init, _, _) => Self::duration_to_f32(init.elapsed()),
+        }
+    }
+
     /// Returns the time it took to parse the source files in seconds.
     fn get_parse_time(&self) -> f32 {
         match *self {
@@ -400,24 +827,62 @@ impl Timer {
 
 // Formatting done on a char by char or line by line basis.
 // FIXME(#20): other stuff for parity with make tidy.
+// Checks the lines of the formatted text and fixes their indentation. Returns the number of lines
+// inserted at the start of the text, i.e. of the license header.
 fn format_lines(
     text: &mut String,
     name: &FileName,
     skipped_range: &[(usize, usize)],
     config: &Config,
     report: &FormatReport,
-) {
+) -> usize {
     let mut formatter = FormatLines::new(name, skipped_range, config);
-    formatter.check_license(text);
+    let header = formatter.check_license(text);
     formatter.iterate(text);
 
+    for (start, len, indent) in mem::take(&mut formatter.indent_fixes).into_iter().rev() {
+        text.replace_range(start..start + len, &indent);
+    }
+
     if formatter.newline_count > 1 {
         debug!("track truncate: {} {}", text.len(), formatter.newline_count);
         let line = text.len() - formatter.newline_count + 1;
         text.truncate(line);
     }
 
+    // The header is inserted once the lines are checked, since the skipped ranges and the file
+    // lines refer to the text without it. The reported lines refer to the text with it.
+    let inserted_lines = header.map_or(0, |header| {
+        text.insert_str(0, &header);
+        count_newlines(&header)
+    });
+    for error in &mut formatter.errors {
+        error.line += inserted_lines;
+    }
+
     report.append(name.clone(), formatter.errors);
+    inserted_lines
+}
+
+// In check mode, reports a file which lacks the final newline that `require_final_newline`
+// asks for, since the diff alone hardly shows it.
+fn check_final_newline(original: &str, name: &FileName, config: &Config, report: &FormatReport) {
+    if config.require_final_newline() != FinalNewline::Always
+        || config.emit_mode() != EmitMode::Diff
+        || original.is_empty()
+        || original.ends_with('\n')
+    {
+        return;
+    }
+
+    let error = FormattingError {
+        line: count_newlines(original) + 1,
+        kind: ErrorKind::MissingNewlineAtEof,
+        is_comment: false,
+        is_string: false,
+        line_buffer: original.lines().last().unwrap_or("").to_owned(),
+    };
+    report.append(name.clone(), vec![error]);
 }
 
 struct FormatLines<'a> {
@@ -429,8 +894,22 @@ struct FormatLines<'a> {
     newline_count: usize,
     errors: Vec<FormattingError>,
     issue_seeker: BadIssueSeeker,
+    // Bad issues found on the current line, reported once the whole line is known.
+    pending_issues: Vec<Issue>,
     line_buffer: String,
     current_line_contains_string_literal: bool,
+    current_line_contains_raw_string: bool,
+    // Whether the previous character was part of a string literal.
+    last_was_string: bool,
+    // Whether the string literal being scanned is a raw or byte string literal.
+    in_raw_string: bool,
+    // The byte position of the next character, and of the start of the current line.
+    pos: usize,
+    line_start: usize,
+    // Whether the current line starts within a string literal, whose content is left as is.
+    line_starts_in_string: bool,
+    // The indentations mixing tabs and spaces to replace, as byte positions and lengths.
+    indent_fixes: Vec<(usize, usize, String)>,
     format_line: bool,
     allow_issue_seek: bool,
     config: &'a Config,
@@ -442,7 +921,8 @@ impl<'a> FormatLines<'a> {
         skipped_range: &'a [(usize, usize)],
         config: &'a Config,
     ) -> FormatLines<'a> {
-        let issue_seeker = BadIssueSeeker::new(config.report_todo(), config.report_fixme());
+        let issue_seeker = BadIssueSeeker::new(config.report_todo(), config.report_fixme())
+            .with_issue_pattern(config.issue_pattern().regex().cloned());
         FormatLines {
             name,
             skipped_range,
@@ -453,16 +933,41 @@ impl<'a> FormatLines<'a> {
             errors: vec![],
             allow_issue_seek: !issue_seeker.is_disabled(),
             issue_seeker,
+            pending_issues: vec![],
             line_buffer: String::with_capacity(config.max_width() * 2),
             current_line_contains_string_literal: false,
+            current_line_contains_raw_string: false,
+            last_was_string: false,
+            in_raw_string: false,
+            pos: 0,
+            line_start: 0,
+            line_starts_in_string: false,
+            indent_fixes: vec![],
             format_line: config.file_lines().contains_line(name, 1),
             config,
         }
     }
 
-    fn check_license(&mut self, text: &mut String) {
+    // Returns the header to insert at the start of the text, if it lacks one.
+    fn check_license(&mut self, text: &str) -> Option<String> {
+        // The header precedes the items of the file, so a skipped first item doesn't exempt it.
+        // Only a file whose lines are all skipped is left unchecked.
+        let last_line = count_newlines(text.trim_end()) + 1;
+        if self
+            .skipped_range
+            .iter()
+            .any(|&(lo, hi)| lo <= 1 && last_line <= hi)
+        {
+            return None;
+        }
         if let Some(ref license_template) = self.config.license_template {
             if !license_template.is_match(text) {
+                // Inserting the header before a shebang line would break the script.
+                if self.config.insert_license() && source_file::shebang_line(text).is_none() {
+                    if let Some(header) = license_template.render() {
+                        return Some(header);
+                    }
+                }
                 self.errors.push(FormattingError {
                     line: self.cur_line,
                     kind: ErrorKind::LicenseCheck,
@@ -472,11 +977,13 @@ impl<'a> FormatLines<'a> {
                 });
             }
         }
+        None
     }
 
     // Iterate over the chars in the file map.
     fn iterate(&mut self, text: &mut String) {
         for (kind, c) in CharClasses::new(text.chars()) {
+            self.pos += c.len_utf8();
             if c == '\r' {
                 continue;
             }
@@ -484,7 +991,7 @@ impl<'a> FormatLines<'a> {
             if self.allow_issue_seek && self.format_line {
                 // Add warnings for bad todos/ fixmes
                 if let Some(issue) = self.issue_seeker.inspect(c) {
-                    self.push_err(ErrorKind::BadIssue(issue), false, false);
+                    self.pending_issues.push(issue);
                 }
             }
 
@@ -497,7 +1004,15 @@ impl<'a> FormatLines<'a> {
     }
 
     fn new_line(&mut self, kind: FullCodeCharKind) {
+        for issue in mem::take(&mut self.pending_issues) {
+            if !self.is_skipped_line() {
+                self.push_err(ErrorKind::BadIssue(issue), false, false);
+            }
+        }
+
         if self.format_line {
+            self.fix_mixed_indentation();
+
             // Check for (and record) trailing whitespace.
             if self.last_was_space {
                 if self.should_report_error(kind, &ErrorKind::TrailingWhitespace)
@@ -517,6 +1032,7 @@ impl<'a> FormatLines<'a> {
             if self.line_len > self.config.max_width()
                 && !self.is_skipped_line()
                 && self.should_report_error(kind, &error_kind)
+                && !self.overflows_because_of_url(kind)
             {
                 let is_string = self.current_line_contains_string_literal;
                 self.push_err(error_kind, kind.is_comment(), is_string);
@@ -533,9 +1049,14 @@ impl<'a> FormatLines<'a> {
         self.last_was_space = false;
         self.line_buffer.clear();
         self.current_line_contains_string_literal = false;
+        self.current_line_contains_raw_string = false;
     }
 
     fn char(&mut self, c: char, kind: FullCodeCharKind) {
+        if self.line_buffer.is_empty() {
+            self.line_start = self.pos - c.len_utf8();
+            self.line_starts_in_string = kind.is_string();
+        }
         self.newline_count = 0;
         self.line_len += if c == '\t' {
             self.config.tab_spaces()
@@ -543,10 +1064,33 @@ impl<'a> FormatLines<'a> {
             1
         };
         self.last_was_space = c.is_whitespace();
-        self.line_buffer.push(c);
         if kind.is_string() {
+            if !self.last_was_string {
+                // Raw string literals start with `r` (or `br`), byte strings with `b"`.
+                self.in_raw_string = c == 'r' || self.line_buffer.ends_with('b');
+            }
             self.current_line_contains_string_literal = true;
+            self.current_line_contains_raw_string |= self.in_raw_string;
+        }
+        self.last_was_string = kind.is_string();
+        self.line_buffer.push(c);
+    }
+
+    // Normalizes the indentation of the current line according to `hard_tabs` if it mixes tabs
+    // and spaces, unless the line is skipped or starts within a string literal.
+    fn fix_mixed_indentation(&mut self) {
+        let indent_len = self.line_buffer.len() - self.line_buffer.trim_start().len();
+        let indent = &self.line_buffer[..indent_len];
+        if self.line_starts_in_string
+            || indent_len == self.line_buffer.len()
+            || !is_mixed_indentation(indent, self.config.hard_tabs())
+            || self.is_skipped_line()
+        {
+            return;
         }
+        let fixed = normalize_indentation(indent, self.config);
+        self.indent_fixes.push((self.line_start, indent_len, fixed));
+        self.push_err(ErrorKind::MixedIndentation, false, false);
     }
 
     fn push_err(&mut self, kind: ErrorKind, is_comment: bool, is_string: bool) {
@@ -571,13 +1115,46 @@ impl<'a> FormatLines<'a> {
 
         match error_kind {
             ErrorKind::LineOverflow(..) => {
-                self.config.error_on_line_overflow() && allow_error_report
+                self.config.error_on_line_overflow()
+                    && allow_error_report
+                    && (!self.current_line_contains_raw_string
+                        || self.config.error_on_raw_string_overflow())
             }
             ErrorKind::TrailingWhitespace | ErrorKind::LostComment => allow_error_report,
             _ => true,
         }
     }
 
+    /// Returns `true` if `ignore_long_urls` is set and the current line exceeds `max_width` because
+    /// of a URL or a path in a comment or string, which can't be broken.
+    fn overflows_because_of_url(&self, kind: FullCodeCharKind) -> bool {
+        if !self.config.ignore_long_urls()
+            || !(kind.is_comment() || self.current_line_contains_string_literal)
+        {
+            return false;
+        }
+        // Find the token which crosses `max_width`.
+        let mut column = 0;
+        for token in self.line_buffer.split(' ') {
+            column += token
+                .chars()
+                .map(|c| {
+                    if c == '\t' {
+                        self.config.tab_spaces()
+                    } else {
+                        1
+                    }
+                })
+                .sum::<usize>();
+            if column > self.config.max_width() {
+                return is_url_or_path(token);
+            }
+            // 1 = the space after the token
+            column += 1;
+        }
+        false
+    }
+
     /// Returns `true` if the line with the given line number was skipped by `#[rustfmt::skip]`.
     fn is_skipped_line(&self) -> bool {
         self.skipped_range
@@ -586,6 +1163,42 @@ impl<'a> FormatLines<'a> {
     }
 }
 
+// Returns `true` if `indent` mixes tabs and spaces. With hard tabs, spaces may follow the tabs
+// to align code.
+fn is_mixed_indentation(indent: &str, hard_tabs: bool) -> bool {
+    match indent.find(' ') {
+        Some(first_space) if hard_tabs => indent[first_space..].contains('\t'),
+        Some(_) => indent.contains('\t'),
+        None => false,
+    }
+}
+
+// Returns the indentation as wide as `indent`, counting a tab as `tab_spaces` columns: tabs
+// followed by the remaining spaces with hard tabs, or else only spaces.
+fn normalize_indentation(indent: &str, config: &Config) -> String {
+    let tab_spaces = config.tab_spaces();
+    let width: usize = indent
+        .chars()
+        .map(|c| if c == '\t' { tab_spaces } else { 1 })
+        .sum();
+    if config.hard_tabs() {
+        let mut fixed = "\t".repeat(width / tab_spaces);
+        fixed.push_str(&" ".repeat(width % tab_spaces));
+        fixed
+    } else {
+        " ".repeat(width)
+    }
+}
+
+// Returns `true` if `token`, e.g. a word of a comment, is a URL or a file path.
+fn is_url_or_path(token: &str) -> bool {
+    let token = token.trim_matches(|c: char| "<>()[]{}\"'`,.;:".contains(c));
+    let is_path = (token.contains('/') || token.contains('\\'))
+        && !token.starts_with("//")
+        && token.chars().any(char::is_alphanumeric);
+    token.contains("://") || is_path
+}
+
 fn should_emit_verbose<F>(forbid_verbose_output: bool, config: &Config, f: F)
 where
     F: Fn(),
@@ -594,3 +1207,58 @@ where
         f();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::ReportTactic;
+
+    #[test]
+    fn mixed_indentation_is_normalized() {
+        let mut config = Config::default();
+        let report = FormatReport::new();
+        let mut text = "fn foo() {\n \tfoo();\n    let s = \"a\n \tb\";\n}\n".to_owned();
+        format_lines(&mut text, &FileName::Stdin, &[], &config, &report);
+        assert_eq!(
+            text,
+            "fn foo() {\n     foo();\n    let s = \"a\n \tb\";\n}\n"
+        );
+        let internal = report.internal.borrow();
+        let errors = &internal.0[&FileName::Stdin];
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 2);
+
+        config.set().hard_tabs(true);
+        let report = FormatReport::new();
+        let mut text = "fn foo() {\n \tfoo();\n\t  bar();\n}\n".to_owned();
+        format_lines(&mut text, &FileName::Stdin, &[], &config, &report);
+        assert_eq!(text, "fn foo() {\n\t foo();\n\t  bar();\n}\n");
+    }
+
+    #[test]
+    fn lines_are_checked_before_the_license_header_is_inserted() {
+        let mut config = Config::default();
+        config.set().insert_license(true);
+        config.set().report_todo(ReportTactic::Always);
+        config.override_value("license_owner", "The rustfmt developers");
+        config.override_value(
+            "license_template_path",
+            "tests/license-template/lt.txt,tests/license-template/owner.txt",
+        );
+        let report = FormatReport::new();
+        let mut text = "fn foo() {\n \tfoo();\n}\nfn bar() {\n    // TODO\n}\n".to_owned();
+        let inserted_lines = format_lines(&mut text, &FileName::Stdin, &[(4, 6)], &config, &report);
+        assert_eq!(inserted_lines, 1);
+        assert_eq!(
+            text,
+            "// Copyright The rustfmt developers.\nfn foo() {\n     foo();\n}\nfn bar() {\n    \
+             // TODO\n}\n"
+        );
+        // The TODO of the skipped item isn't reported, and the mixed indentation is reported at
+        // its line in the text with the header.
+        let internal = report.internal.borrow();
+        let errors = &internal.0[&FileName::Stdin];
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 3);
+    }
+}
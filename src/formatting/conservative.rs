@@ -0,0 +1,199 @@
+use std::borrow::Cow;
+
+use super::minimal_diff::non_whitespace;
+use crate::comment::LineClasses;
+use crate::rustfmt_diff::get_modified_lines;
+
+/// Reverts the changes to the formatted text which join or split lines, for the `Conservative`
+/// formatting profile. The changes within lines, e.g. to the indentation, the spacing or the
+/// order of imports, are kept, as are the changes to blank lines. The original lines which are
+/// kept are reindented along with their first line, and lose their trailing whitespace unless it
+/// is part of a string literal or a block comment.
+pub(crate) fn apply_conservative_profile(formatted_text: &mut String, raw_input_text: &str) {
+    let original_lines = original_lines(raw_input_text);
+    let modified_lines = get_modified_lines(raw_input_text, formatted_text);
+
+    let mut result: Vec<Cow<'_, str>> = Vec::with_capacity(original_lines.len());
+    let mut next_line = 0;
+    for chunk in &modified_lines.chunks {
+        let start = (chunk.line_number_orig as usize).saturating_sub(1);
+        let end = start + chunk.lines_removed as usize;
+        result.extend(
+            original_lines[next_line..start]
+                .iter()
+                .map(|l| l.text.into()),
+        );
+        merge_chunk(&original_lines[start..end], &chunk.lines, &mut result);
+        next_line = end;
+    }
+    result.extend(original_lines[next_line..].iter().map(|l| l.text.into()));
+
+    let mut text = result.join("\n");
+    if formatted_text.ends_with('\n') {
+        text.push('\n');
+    }
+    *formatted_text = text;
+}
+
+struct OriginalLine<'a> {
+    text: &'a str,
+    // Whether the line starts within a string literal, so that its indentation is part of it.
+    starts_in_string: bool,
+    // Whether the line ends within a string literal or a block comment, so that its trailing
+    // whitespace is part of it.
+    ends_in_literal: bool,
+}
+
+fn original_lines(raw_input_text: &str) -> Vec<OriginalLine<'_>> {
+    let mut in_string = false;
+    raw_input_text
+        .lines()
+        .zip(LineClasses::new(raw_input_text))
+        .map(|(text, (kind, _))| {
+            let starts_in_string = in_string;
+            in_string = kind.is_string();
+            OriginalLine {
+                text,
+                starts_in_string,
+                ends_in_literal: kind.is_string() || kind.inside_comment(),
+            }
+        })
+        .collect()
+}
+
+// Merges the original lines removed by a chunk with the formatted lines added by it, keeping the
+// original lines wherever formatting changes how many lines the same content takes.
+fn merge_chunk<'a>(
+    removed: &[OriginalLine<'a>],
+    added: &'a [String],
+    result: &mut Vec<Cow<'a, str>>,
+) {
+    let (mut i, mut j) = (0, 0);
+    // The indentation of the last original line starting with code, and of its formatted line.
+    let mut indents = ("", "");
+    loop {
+        while i < removed.len() && removed[i].text.trim().is_empty() {
+            i += 1;
+        }
+        while j < added.len() && added[j].trim().is_empty() {
+            result.push(added[j].as_str().into());
+            j += 1;
+        }
+        if i == removed.len() || j == added.len() {
+            break;
+        }
+        if !removed[i].starts_in_string {
+            indents = (indentation(removed[i].text), indentation(&added[j]));
+        }
+
+        // Find the fewest lines on both sides with the same non-whitespace content.
+        let (mut i_end, mut j_end) = (i + 1, j + 1);
+        let mut original = non_whitespace(removed[i].text);
+        let mut formatted = non_whitespace(&added[j]);
+        while original != formatted {
+            if original.len() < formatted.len()
+                && formatted.starts_with(&original)
+                && i_end < removed.len()
+            {
+                original.push_str(&non_whitespace(removed[i_end].text));
+                i_end += 1;
+            } else if formatted.len() < original.len()
+                && original.starts_with(&formatted)
+                && j_end < added.len()
+            {
+                formatted.push_str(&non_whitespace(&added[j_end]));
+                j_end += 1;
+            } else {
+                break;
+            }
+        }
+        if original != formatted {
+            break;
+        }
+        push_lines(&removed[i..i_end], &added[j..j_end], indents, result);
+        i = i_end;
+        j = j_end;
+    }
+    // The content of the rest of the chunk is changed by formatting.
+    push_lines(&removed[i..], &added[j..], indents, result);
+}
+
+// Pushes the formatted lines if they replace the original lines one for one, or else the
+// original lines, reindented from the first to the second of `indents`.
+fn push_lines<'a>(
+    removed: &[OriginalLine<'a>],
+    added: &'a [String],
+    (old_indent, new_indent): (&'a str, &'a str),
+    result: &mut Vec<Cow<'a, str>>,
+) {
+    if removed.len() == added.len() {
+        result.extend(added.iter().map(|line| line.as_str().into()));
+        return;
+    }
+    for line in removed {
+        let text = if line.ends_in_literal {
+            line.text
+        } else {
+            line.text.trim_end()
+        };
+        if line.starts_in_string || text.is_empty() || old_indent == new_indent {
+            result.push(text.into());
+            continue;
+        }
+        let content = text.trim_start();
+        let indent = text.len() - content.len();
+        let reindented = match text.get(old_indent.len()..) {
+            Some(rest) if indent >= old_indent.len() => format!("{}{}", new_indent, rest),
+            _ => {
+                // The line is less indented than the first one, e.g. a closing bracket.
+                let outdent = old_indent.len().saturating_sub(indent);
+                let indent = &new_indent[..new_indent.len().saturating_sub(outdent)];
+                format!("{}{}", indent, content)
+            }
+        };
+        result.push(reindented.into());
+    }
+}
+
+fn indentation(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_lines_which_formatting_joins_or_splits() {
+        let original = "fn foo(a: u32,\n       b: u32) {}\nfn bar () {\n  let x=1 ;   \n}\n";
+        let mut formatted =
+            "fn foo(a: u32, b: u32) {}\n\nfn bar() {\n    let x = 1;\n}\n".to_owned();
+        apply_conservative_profile(&mut formatted, original);
+        assert_eq!(
+            formatted,
+            "fn foo(a: u32,\n       b: u32) {}\n\nfn bar() {\n    let x = 1;\n}\n"
+        );
+    }
+
+    #[test]
+    fn reindents_the_lines_it_keeps() {
+        let original = "fn foo() {\n  let x = bar(a,\n              b);\n}\n";
+        let mut formatted = "fn foo() {\n    let x = bar(a, b);\n}\n".to_owned();
+        apply_conservative_profile(&mut formatted, original);
+        assert_eq!(
+            formatted,
+            "fn foo() {\n    let x = bar(a,\n                b);\n}\n"
+        );
+    }
+
+    #[test]
+    fn keeps_the_whitespace_of_multiline_literals() {
+        let original = "fn foo() {\n  let x = bar(a,  \n              \"b  \n  c\");\n}\n";
+        let mut formatted = "fn foo() {\n    let x = bar(a, \"b  \n  c\");\n}\n".to_owned();
+        apply_conservative_profile(&mut formatted, original);
+        assert_eq!(
+            formatted,
+            "fn foo() {\n    let x = bar(a,\n                \"b  \n  c\");\n}\n"
+        );
+    }
+}
@@ -0,0 +1,104 @@
+use crate::rustfmt_diff::get_modified_lines;
+
+/// Reverts the changes to the formatted text which only change whitespace, so that the lines of
+/// the original text whose non-whitespace content formatting leaves alone are kept byte for byte.
+/// Lines which formatting merely reindents, joins or splits, and blank lines, are thus left as
+/// they are.
+pub(crate) fn apply_minimal_diff(formatted_text: &mut String, raw_input_text: &str) {
+    let original_lines: Vec<&str> = raw_input_text.lines().collect();
+    let modified_lines = get_modified_lines(raw_input_text, formatted_text);
+
+    let mut result: Vec<&str> = Vec::with_capacity(original_lines.len());
+    let mut next_line = 0;
+    for chunk in &modified_lines.chunks {
+        let start = (chunk.line_number_orig as usize).saturating_sub(1);
+        let end = start + chunk.lines_removed as usize;
+        result.extend(&original_lines[next_line..start]);
+        merge_chunk(&original_lines[start..end], &chunk.lines, &mut result);
+        next_line = end;
+    }
+    result.extend(&original_lines[next_line..]);
+
+    let mut text = result.join("\n");
+    if formatted_text.ends_with('\n') {
+        text.push('\n');
+    }
+    *formatted_text = text;
+}
+
+// Merges the original lines removed by a chunk with the formatted lines added by it, keeping the
+// original lines as long as they only differ from the formatted ones in whitespace.
+fn merge_chunk<'a>(removed: &[&'a str], added: &'a [String], result: &mut Vec<&'a str>) {
+    let (mut i, mut j) = (0, 0);
+    loop {
+        while i < removed.len() && removed[i].trim().is_empty() {
+            result.push(removed[i]);
+            i += 1;
+        }
+        while j < added.len() && added[j].trim().is_empty() {
+            j += 1;
+        }
+        if i == removed.len() || j == added.len() {
+            break;
+        }
+
+        // Find the fewest lines on both sides with the same non-whitespace content.
+        let (mut i_end, mut j_end) = (i + 1, j + 1);
+        let mut original = non_whitespace(removed[i]);
+        let mut formatted = non_whitespace(&added[j]);
+        while original != formatted {
+            if original.len() < formatted.len()
+                && formatted.starts_with(&original)
+                && i_end < removed.len()
+            {
+                original.push_str(&non_whitespace(removed[i_end]));
+                i_end += 1;
+            } else if formatted.len() < original.len()
+                && original.starts_with(&formatted)
+                && j_end < added.len()
+            {
+                formatted.push_str(&non_whitespace(&added[j_end]));
+                j_end += 1;
+            } else {
+                break;
+            }
+        }
+        if original != formatted {
+            break;
+        }
+        result.extend(&removed[i..i_end]);
+        i = i_end;
+        j = j_end;
+    }
+    // The rest of the chunk is changed by formatting.
+    result.extend(added[j..].iter().map(String::as_str));
+}
+
+pub(super) fn non_whitespace(line: &str) -> String {
+    line.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_lines_with_whitespace_only_changes() {
+        let original = "fn foo(a: u32,\n          b: u32) {}\n\n\n\nfn bar () {\n  let x=1 ;\n}\n";
+        let mut formatted =
+            "fn foo(a: u32, b: u32) {}\n\nfn bar() {\n    let x = 1;\n    baz();\n}\n".to_owned();
+        apply_minimal_diff(&mut formatted, original);
+        assert_eq!(
+            formatted,
+            "fn foo(a: u32,\n          b: u32) {}\n\n\n\nfn bar () {\n  let x=1 ;\n    baz();\n}\n"
+        );
+    }
+
+    #[test]
+    fn replaces_lines_whose_content_changes() {
+        let original = "use b;\nuse a;\n";
+        let mut formatted = "use a;\nuse b;\n".to_owned();
+        apply_minimal_diff(&mut formatted, original);
+        assert_eq!(formatted, "use a;\nuse b;\n");
+    }
+}
@@ -1,7 +1,7 @@
 use crate::NewlineStyle;
 
 /// Apply this newline style to the formatted text. When the style is set
-/// to `Auto`, the `raw_input_text` is used to detect the existing line
+/// to `Auto`, the `raw_input_text` is used to detect the dominant line
 /// endings.
 ///
 /// If the style is set to `Auto` and `raw_input_text` contains no
@@ -40,18 +40,35 @@ const CARRIAGE_RETURN: char = '\r';
 const WINDOWS_NEWLINE: &str = "\r\n";
 const UNIX_NEWLINE: &str = "\n";
 
+/// Detects the line endings used by most lines of `raw_input_text`. When both
+/// styles are used equally often, the style of the first line ending wins.
 fn auto_detect_newline_style(raw_input_text: &str) -> EffectiveNewlineStyle {
-    let first_line_feed_pos = raw_input_text.chars().position(|ch| ch == LINE_FEED);
-    match first_line_feed_pos {
-        Some(first_line_feed_pos) => {
-            let char_before_line_feed_pos = first_line_feed_pos.saturating_sub(1);
-            let char_before_line_feed = raw_input_text.chars().nth(char_before_line_feed_pos);
-            match char_before_line_feed {
-                Some(CARRIAGE_RETURN) => EffectiveNewlineStyle::Windows,
-                _ => EffectiveNewlineStyle::Unix,
+    let mut first_style = None;
+    let mut windows_count = 0;
+    let mut unix_count = 0;
+    let mut prev_char = None;
+    for ch in raw_input_text.chars() {
+        if ch == LINE_FEED {
+            let style = if prev_char == Some(CARRIAGE_RETURN) {
+                windows_count += 1;
+                EffectiveNewlineStyle::Windows
+            } else {
+                unix_count += 1;
+                EffectiveNewlineStyle::Unix
+            };
+            if first_style.is_none() {
+                first_style = Some(style);
             }
         }
-        None => native_newline_style(),
+        prev_char = Some(ch);
+    }
+
+    if windows_count > unix_count {
+        EffectiveNewlineStyle::Windows
+    } else if unix_count > windows_count {
+        EffectiveNewlineStyle::Unix
+    } else {
+        first_style.unwrap_or_else(native_newline_style)
     }
 }
 
@@ -109,6 +126,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn auto_detects_dominant_newlines() {
+        assert_eq!(
+            EffectiveNewlineStyle::Windows,
+            auto_detect_newline_style("One\nTwo\r\nThree\r\nFour")
+        );
+        assert_eq!(
+            EffectiveNewlineStyle::Unix,
+            auto_detect_newline_style("One\r\nTwo\nThree\nFour")
+        );
+    }
+
+    #[test]
+    fn auto_detects_first_newlines_on_tie() {
+        assert_eq!(
+            EffectiveNewlineStyle::Windows,
+            auto_detect_newline_style("One\r\nTwo\nThree")
+        );
+        assert_eq!(
+            EffectiveNewlineStyle::Unix,
+            auto_detect_newline_style("One\nTwo\r\nThree")
+        );
+    }
+
     #[test]
     fn falls_back_to_native_newlines_if_no_newlines_are_found() {
         let expected_newline_style = if cfg!(windows) {
@@ -1,17 +1,23 @@
 #[macro_use]
 extern crate log;
 
+use std::collections::BTreeMap;
 use std::env;
-use std::io::stdout;
+use std::fs;
+use std::io::{self, stdout, Write};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Stdio};
 use std::str::FromStr;
 
 use env_logger;
 use getopts::{Matches, Options};
 use rustfmt_nightly as rustfmt;
 
-use crate::rustfmt::{load_config, CliOptions, FormatReportFormatterBuilder, Input, Session};
+use crate::rustfmt::rustfmt_diff::{parse_unified_diff, DiffLine};
+use crate::rustfmt::{
+    load_config, CliOptions, EmitMode, FileLines, FileName, FormatReportFormatterBuilder, Input,
+    ModuleTraversal, Range, Session, Verbosity,
+};
 
 fn prune_files(files: Vec<&str>) -> Vec<&str> {
     let prefixes: Vec<_> = files
@@ -39,10 +45,16 @@ fn prune_files(files: Vec<&str>) -> Vec<&str> {
         .collect()
 }
 
-fn git_diff(commits: &str) -> String {
+fn git_diff(commits: &str, staged: bool, whole_files: bool) -> String {
     let mut cmd = Command::new("git");
     cmd.arg("diff");
-    if commits != "0" {
+    if !whole_files {
+        // Without context lines, the hunks only cover the changed lines.
+        cmd.arg("--unified=0");
+    }
+    if staged {
+        cmd.arg("--cached");
+    } else if commits != "0" {
         cmd.arg(format!("HEAD~{}", commits));
     }
     let output = cmd.output().expect("Couldn't execute `git diff`");
@@ -57,15 +69,72 @@ fn get_files(input: &str) -> Vec<&str> {
         .collect()
 }
 
-fn fmt_files(files: &[&str]) -> i32 {
-    let (config, _) =
+/// Returns the lines added or changed by the diff, as `(file, first line, last line)`.
+fn get_ranges(input: &str) -> Vec<(String, usize, usize)> {
+    let files = parse_unified_diff(input).expect("Couldn't parse `git diff`");
+    let mut ranges = vec![];
+    for file in files.iter().filter(|file| file.path.ends_with(".rs")) {
+        for mismatch in &file.mismatches {
+            let mut line = mismatch.line_number as usize;
+            let mut start = None;
+            for diff_line in &mismatch.lines {
+                match diff_line {
+                    DiffLine::Expected(_) => {
+                        start.get_or_insert(line);
+                        line += 1;
+                    }
+                    DiffLine::Context(_) => {
+                        if let Some(start) = start.take() {
+                            ranges.push((file.path.clone(), start, line - 1));
+                        }
+                        line += 1;
+                    }
+                    DiffLine::Resulting(_) => {}
+                }
+            }
+            if let Some(start) = start {
+                ranges.push((file.path.clone(), start, line - 1));
+            }
+        }
+    }
+    ranges
+}
+
+fn get_file_lines(ranges: &[(String, usize, usize)]) -> FileLines {
+    let mut file_lines: BTreeMap<FileName, Vec<Range>> = BTreeMap::new();
+    for &(ref file, lo, hi) in ranges {
+        // File lines are matched against canonical paths.
+        let path = match fs::canonicalize(file) {
+            Ok(path) => path,
+            Err(_) => continue,
+        };
+        file_lines
+            .entry(FileName::Real(path))
+            .or_insert_with(Vec::new)
+            .push(Range::new(lo, hi));
+    }
+    FileLines::from_ranges(file_lines)
+}
+
+fn fmt_files(files: &[&str], file_lines: Option<FileLines>) -> i32 {
+    let (mut config, _) =
         load_config::<NullOptions>(Some(Path::new(".")), None).expect("couldn't load config");
+    if let Some(file_lines) = file_lines {
+        config.set().file_lines(file_lines);
+    }
 
     let mut exit_code = 0;
     let mut out = stdout();
     let mut session = Session::new(config, Some(&mut out));
     for file in files {
-        let report = session.format(Input::File(PathBuf::from(file))).unwrap();
+        let report = match session.format(Input::File(PathBuf::from(file))) {
+            Ok(report) => report,
+            Err(e) => {
+                eprintln!("Couldn't format `{}`: {}", file, e);
+                exit_code = 1;
+                continue;
+            }
+        };
         if report.has_warnings() {
             eprintln!("{}", FormatReportFormatterBuilder::new(&report).build());
         }
@@ -76,6 +145,117 @@ fn fmt_files(files: &[&str]) -> i32 {
     exit_code
 }
 
+/// Formats the staged versions of `files`, read from the index, and stages the results.
+/// The working tree copy of a file is only updated if it has no unstaged changes.
+fn fmt_staged_files(files: &[&str], ranges: Option<&[(String, usize, usize)]>) -> i32 {
+    let (mut config, _) =
+        load_config::<NullOptions>(Some(Path::new(".")), None).expect("couldn't load config");
+    config.set().emit_mode(EmitMode::Stdout);
+    config.set().verbose(Verbosity::Quiet);
+    // The staged versions of child modules are formatted on their own.
+    config.set().module_traversal(ModuleTraversal::FormatRoots);
+
+    let mut exit_code = 0;
+    for file in files {
+        if let Some(ranges) = ranges {
+            let file_ranges = ranges
+                .iter()
+                .filter(|&(f, _, _)| f == file)
+                .map(|&(_, lo, hi)| Range::new(lo, hi))
+                .collect();
+            let mut file_lines = BTreeMap::new();
+            file_lines.insert(FileName::Stdin, file_ranges);
+            config.set().file_lines(FileLines::from_ranges(file_lines));
+        }
+
+        let staged = git_show_staged(file);
+        let mut out = vec![];
+        let mut session = Session::new(config.clone(), Some(&mut out));
+        let report = match session.format(Input::Text(staged.clone())) {
+            Ok(report) => report,
+            Err(e) => {
+                eprintln!("Couldn't format `{}`: {}", file, e);
+                exit_code = 1;
+                continue;
+            }
+        };
+        if report.has_warnings() {
+            eprintln!("{}", FormatReportFormatterBuilder::new(&report).build());
+        }
+        if !session.has_no_errors() {
+            exit_code = 1;
+        }
+        if session.has_parsing_errors() {
+            continue;
+        }
+        drop(session);
+
+        let formatted = String::from_utf8_lossy(&out);
+        if formatted != staged {
+            if let Err(e) = restage(file, &staged, &formatted) {
+                eprintln!("Couldn't stage `{}`: {}", file, e);
+                exit_code = 1;
+            }
+        }
+    }
+    exit_code
+}
+
+fn git_show_staged(file: &str) -> String {
+    let output = Command::new("git")
+        .arg("show")
+        .arg(format!(":{}", file))
+        .output()
+        .expect("Couldn't execute `git show`");
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn restage(file: &str, staged: &str, formatted: &str) -> io::Result<()> {
+    let mut hash_object = Command::new("git")
+        .args(&["hash-object", "-w", "--stdin", "--path", file])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    hash_object
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(formatted.as_bytes())?;
+    let output = hash_object.wait_with_output()?;
+    if !output.status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            "`git hash-object` failed",
+        ));
+    }
+    let hash = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+
+    // Keep the mode of the staged file, e.g. whether it is executable.
+    let output = Command::new("git")
+        .args(&["ls-files", "--stage", file])
+        .output()?;
+    let stage = String::from_utf8_lossy(&output.stdout);
+    let mode = stage.split_whitespace().next().unwrap_or("100644");
+
+    let status = Command::new("git")
+        .arg("update-index")
+        .arg("--cacheinfo")
+        .arg(format!("{},{},{}", mode, hash, file))
+        .status()?;
+    if !status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            "`git update-index` failed",
+        ));
+    }
+
+    // Unstaged changes would be lost by writing the file.
+    if fs::read_to_string(file)? == staged {
+        fs::write(file, formatted)?;
+    }
+    Ok(())
+}
+
 struct NullOptions;
 
 impl CliOptions for NullOptions {
@@ -121,6 +301,16 @@ fn make_opts() -> Options {
     opts.optflag("h", "help", "show this message");
     opts.optflag("c", "check", "check only, don't format (unimplemented)");
     opts.optflag("u", "uncommitted", "format uncommitted files");
+    opts.optflag(
+        "s",
+        "staged",
+        "format the staged versions of the staged files and stage the results",
+    );
+    opts.optflag(
+        "",
+        "whole-files",
+        "format the whole of the changed files, not only the changed lines",
+    );
     opts
 }
 
@@ -128,6 +318,8 @@ struct Config {
     commits: String,
     uncommitted: bool,
     check: bool,
+    staged: bool,
+    whole_files: bool,
 }
 
 impl Config {
@@ -147,6 +339,8 @@ impl Config {
             commits: "1".to_owned(),
             uncommitted: false,
             check: false,
+            staged: false,
+            whole_files: false,
         };
 
         if matches.opt_present("c") {
@@ -158,6 +352,14 @@ impl Config {
             config.uncommitted = true;
         }
 
+        if matches.opt_present("s") {
+            config.staged = true;
+        }
+
+        if matches.opt_present("whole-files") {
+            config.whole_files = true;
+        }
+
         if matches.free.len() > 1 {
             panic!("unknown arguments, use `-h` for usage");
         }
@@ -182,15 +384,64 @@ fn main() {
         .expect("Couldn't parse command line");
     let config = Config::from_args(&matches, &opts);
 
-    if !config.uncommitted {
+    // Staged changes are uncommitted by definition.
+    if !config.uncommitted && !config.staged {
         check_uncommitted();
     }
 
-    let stdout = git_diff(&config.commits);
+    let stdout = git_diff(&config.commits, config.staged, config.whole_files);
     let files = get_files(&stdout);
     debug!("files: {:?}", files);
+    if config.staged {
+        let ranges = if config.whole_files {
+            None
+        } else {
+            Some(get_ranges(&stdout))
+        };
+        debug!("ranges: {:?}", ranges);
+        let exit_code = fmt_staged_files(&files, ranges.as_ref().map(|r| &r[..]));
+        std::process::exit(exit_code);
+    }
     let files = prune_files(files);
     debug!("pruned files: {:?}", files);
-    let exit_code = fmt_files(&files);
+    let file_lines = if config.whole_files {
+        None
+    } else {
+        let ranges = get_ranges(&stdout);
+        debug!("ranges: {:?}", ranges);
+        Some(get_file_lines(&ranges))
+    };
+    let exit_code = fmt_files(&files, file_lines);
     std::process::exit(exit_code);
 }
+
+#[test]
+fn get_ranges_of_changed_lines() {
+    const DIFF: &str = "\
+diff --git a/src/lib.rs b/src/lib.rs
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -3 +3 @@ fn foo() {
+-    let x=1;
++    let x = 1;
+@@ -10,0 +11,2 @@ fn bar() {
++fn baz() {
++}
+@@ -20,2 +22,0 @@ fn qux() {
+-fn quux() {
+-}
+diff --git a/README.md b/README.md
+--- a/README.md
++++ b/README.md
+@@ -1 +1 @@
+-foo
++bar
+";
+    assert_eq!(
+        get_ranges(DIFF),
+        vec![
+            ("src/lib.rs".to_owned(), 3, 3),
+            ("src/lib.rs".to_owned(), 11, 12)
+        ]
+    );
+}
@@ -4,6 +4,8 @@
 
 use std::fmt;
 
+use regex::Regex;
+
 use crate::config::ReportTactic;
 
 const TO_DO_CHARS: &[char] = &['t', 'o', 'd', 'o'];
@@ -39,10 +41,7 @@ pub struct Issue {
 
 impl fmt::Display for Issue {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
-        let msg = match self.issue_type {
-            IssueType::Todo => "TODO",
-            IssueType::Fixme => "FIXME",
-        };
+        let msg = self.keyword();
         let details = if self.missing_number {
             " without issue number"
         } else {
@@ -53,6 +52,16 @@ impl fmt::Display for Issue {
     }
 }
 
+impl Issue {
+    /// Returns the keyword which starts this kind of issue.
+    pub(crate) fn keyword(&self) -> &'static str {
+        match self.issue_type {
+            IssueType::Todo => "TODO",
+            IssueType::Fixme => "FIXME",
+        }
+    }
+}
+
 #[derive(PartialEq, Eq, Debug, Clone, Copy)]
 enum IssueType {
     Todo,
@@ -69,6 +78,11 @@ pub(crate) struct BadIssueSeeker {
     state: Seeking,
     report_todo: ReportTactic,
     report_fixme: ReportTactic,
+    // If set, the rest of the line after an unnumbered issue must match this pattern instead of
+    // starting with `(#123)`.
+    issue_pattern: Option<Regex>,
+    // The text following the issue being inspected against `issue_pattern`.
+    reference: String,
 }
 
 impl BadIssueSeeker {
@@ -80,9 +94,16 @@ impl BadIssueSeeker {
             },
             report_todo,
             report_fixme,
+            issue_pattern: None,
+            reference: String::new(),
         }
     }
 
+    pub(crate) fn with_issue_pattern(mut self, issue_pattern: Option<Regex>) -> BadIssueSeeker {
+        self.issue_pattern = issue_pattern;
+        self
+    }
+
     pub(crate) fn is_disabled(&self) -> bool {
         !is_enabled(self.report_todo) && !is_enabled(self.report_fixme)
     }
@@ -98,7 +119,11 @@ impl BadIssueSeeker {
                 self.state = self.inspect_issue(c, todo_idx, fixme_idx);
             }
             Seeking::Number { issue, part } => {
-                let result = self.inspect_number(c, issue, part);
+                let result = if issue.missing_number && self.issue_pattern.is_some() {
+                    self.inspect_reference(c, issue)
+                } else {
+                    self.inspect_number(c, issue, part)
+                };
 
                 if let IssueClassification::None = result {
                     return None;
@@ -209,6 +234,24 @@ impl BadIssueSeeker {
 
         IssueClassification::None
     }
+
+    fn inspect_reference(&mut self, c: char, issue: Issue) -> IssueClassification {
+        if c != '\n' {
+            self.reference.push(c);
+            return IssueClassification::None;
+        }
+
+        let is_match = self
+            .issue_pattern
+            .as_ref()
+            .map_or(true, |re| re.is_match(&self.reference));
+        self.reference.clear();
+        if is_match {
+            IssueClassification::Good
+        } else {
+            IssueClassification::Bad(issue)
+        }
+    }
 }
 
 #[test]
@@ -239,6 +282,22 @@ fn find_unnumbered_issue() {
     check_fail("TODO(#22) FIXME\n", 15);
 }
 
+#[test]
+fn find_issue_with_pattern() {
+    fn is_bad_issue(text: &str) -> bool {
+        let pattern = Regex::new(r"\bJIRA-\d+\b").unwrap();
+        let mut seeker = BadIssueSeeker::new(ReportTactic::Unnumbered, ReportTactic::Unnumbered)
+            .with_issue_pattern(Some(pattern));
+        text.chars().any(|c| seeker.inspect(c).is_some())
+    }
+
+    assert!(!is_bad_issue("TODO(JIRA-123): do it\n"));
+    assert!(!is_bad_issue("FIXME: see JIRA-7\n"));
+    assert!(is_bad_issue("TODO(#123): do it\n"));
+    assert!(is_bad_issue("FIXME\n"));
+    assert!(is_bad_issue("TODO(JIRA-1) done\nFIXME(JIRA-)\n"));
+}
+
 #[test]
 fn find_issue() {
     fn is_bad_issue(text: &str, report_todo: ReportTactic, report_fixme: ReportTactic) -> bool {
@@ -22,6 +22,7 @@ use crate::expr::{
 use crate::lists::{definitive_tactic, itemize_list, write_list, ListFormatting, Separator};
 use crate::macros::{rewrite_macro, MacroPosition};
 use crate::overflow;
+use crate::patterns::rewrite_top_level_pattern;
 use crate::rewrite::{Rewrite, RewriteContext};
 use crate::shape::{Indent, Shape};
 use crate::source_map::{LineRangeUtils, SpanUtils};
@@ -77,7 +78,7 @@ impl Rewrite for ast::Local {
         let pat_shape = shape.offset_left(4)?;
         // 1 = ;
         let pat_shape = pat_shape.sub_width(1)?;
-        let pat_str = self.pat.rewrite(context, pat_shape)?;
+        let pat_str = rewrite_top_level_pattern(context, &self.pat, pat_shape)?;
         result.push_str(&pat_str);
 
         // String that is placed within the assignment pattern and expression.
@@ -128,6 +129,8 @@ impl Rewrite for ast::Local {
 struct Item<'a> {
     unsafety: ast::Unsafe,
     abi: Cow<'static, str>,
+    // The span of the ABI string, if any.
+    abi_span: Option<Span>,
     vis: Option<&'a ast::Visibility>,
     body: Vec<BodyElement<'a>>,
     span: Span,
@@ -142,6 +145,7 @@ impl<'a> Item<'a> {
                 config.force_explicit_abi(),
                 true,
             ),
+            abi_span: fm.abi.map(|abi| abi.span),
             vis: None,
             body: fm
                 .items
@@ -161,6 +165,79 @@ enum BodyElement<'a> {
     ForeignItem(&'a ast::ForeignItem),
 }
 
+impl BodyElement<'_> {
+    // The start of the element, including its attributes.
+    fn lo(&self) -> BytePos {
+        match *self {
+            BodyElement::ForeignItem(item) => match item.attrs.first() {
+                Some(attr) => attr.span.lo(),
+                None => item.span.lo(),
+            },
+        }
+    }
+}
+
+/// The widths to pad the signatures of single line foreign functions and statics to, so that the
+/// return types of the functions, and the types of the statics, are vertically aligned.
+#[derive(Clone, Copy, Default)]
+struct ForeignItemAlignment {
+    fn_width: usize,
+    static_width: usize,
+}
+
+impl ForeignItemAlignment {
+    fn pad(
+        &self,
+        context: &RewriteContext<'_>,
+        item: &ast::ForeignItem,
+        rewrite: String,
+        shape: Shape,
+    ) -> String {
+        let (head, padding) = match split_foreign_item_type(context, item, &rewrite) {
+            Some((is_fn, head)) => {
+                let width = if is_fn {
+                    self.fn_width
+                } else {
+                    self.static_width
+                };
+                (head, width.saturating_sub(unicode_str_width(head)))
+            }
+            None => return rewrite,
+        };
+        if padding == 0 || unicode_str_width(&rewrite) + padding > shape.width {
+            return rewrite;
+        }
+        format!("{}{}{}", head, " ".repeat(padding), &rewrite[head.len()..])
+    }
+}
+
+// Splits off the part of the single line rewrite of a foreign function or static before its return
+// type or type, i.e. before ` -> ` or after `:`. Returns whether the item is a function, and that
+// part.
+fn split_foreign_item_type<'a>(
+    context: &RewriteContext<'_>,
+    item: &ast::ForeignItem,
+    rewrite: &'a str,
+) -> Option<(bool, &'a str)> {
+    let (is_fn, ty, separator) = match item.kind {
+        ast::ForeignItemKind::Fn(_, ref fn_sig, ..) => match fn_sig.decl.output {
+            ast::FnRetTy::Ty(ref ty) => (true, ty, " -> "),
+            ast::FnRetTy::Default(..) => return None,
+        },
+        ast::ForeignItemKind::Static(ref ty, ..) => (false, ty, " "),
+        _ => return None,
+    };
+    let ty_str = ty.rewrite(
+        context,
+        Shape::legacy(context.config.max_width(), Indent::empty()),
+    )?;
+    let tail = format!("{}{};", separator, ty_str);
+    if rewrite.contains('\n') || !rewrite.ends_with(&tail) {
+        return None;
+    }
+    Some((is_fn, &rewrite[..rewrite.len() - tail.len()]))
+}
+
 /// Represents a fn's signature.
 pub(crate) struct FnSig<'a> {
     decl: &'a ast::FnDecl,
@@ -261,10 +338,27 @@ impl<'a> FmtVisitor<'a> {
         let snippet = self.snippet(item.span);
         let brace_pos = snippet.find_uncommented("{").unwrap();
 
+        // Keep the comments between the `extern` keyword, or the ABI, and the opening brace.
+        let header_hi = match item.abi_span {
+            Some(span) => span.hi(),
+            None => {
+                item.span.lo() + BytePos(snippet.find_uncommented("extern").unwrap() as u32 + 6)
+            }
+        };
+        let header_span = mk_sp(header_hi, item.span.lo() + BytePos(brace_pos as u32));
+        let comment = rewrite_missing_comment(header_span, self.shape(), &self.get_context());
+        if let Some(comment) = comment.filter(|comment| !comment.is_empty()) {
+            self.push_str(&comment);
+            if comment.starts_with("//") {
+                let indent_str = self.block_indent.to_string_with_newline(self.config);
+                self.push_str(&indent_str);
+            } else {
+                self.push_str(" ");
+            }
+        }
+
         self.push_str("{");
         if !item.body.is_empty() || contains_comment(&snippet[brace_pos..]) {
-            // FIXME: this skips comments between the extern keyword and the opening
-            // brace.
             self.last_pos = item.span.lo() + BytePos(brace_pos as u32 + 1);
             self.block_indent = self.block_indent.block_indent(self.config);
 
@@ -274,22 +368,156 @@ impl<'a> FmtVisitor<'a> {
                 let indent_str = self.block_indent.to_string(self.config);
                 self.push_str(&indent_str);
             } else {
-                for item in &item.body {
-                    self.format_body_element(item);
+                let alignment = self.foreign_item_alignment(&item.body);
+                if self.config.reorder_foreign_items() {
+                    let body_hi = item.span.hi() - BytePos(1);
+                    self.format_sorted_body_elements(&item.body, alignment, body_hi);
+                } else {
+                    for item in &item.body {
+                        self.format_body_element(item, alignment);
+                    }
                 }
 
                 self.block_indent = self.block_indent.block_unindent(self.config);
                 self.format_missing_with_indent(item.span.hi() - BytePos(1));
             }
+        } else if !is_empty_item_single_line(self.config, Indent::empty(), last_line(&self.buffer))
+        {
+            let indent_str = self.block_indent.to_string_with_newline(self.config);
+            self.push_str(&indent_str);
         }
 
         self.push_str("}");
         self.last_pos = item.span.hi();
     }
 
-    fn format_body_element(&mut self, element: &BodyElement<'_>) {
+    fn format_body_element(&mut self, element: &BodyElement<'_>, alignment: ForeignItemAlignment) {
         match *element {
-            BodyElement::ForeignItem(item) => self.format_foreign_item(item),
+            BodyElement::ForeignItem(item) => self.format_foreign_item(item, alignment),
+        }
+    }
+
+    // Formats the elements sorted by name, each one along with the comments before it and the
+    // comment after it on the same line. Blank lines before the elements are kept, except before
+    // the first one. The lines recorded in the line anchors and the skipped ranges while
+    // formatting an element are moved along with it.
+    fn format_sorted_body_elements(
+        &mut self,
+        elements: &[BodyElement<'_>],
+        alignment: ForeignItemAlignment,
+        body_hi: BytePos,
+    ) {
+        let mut rewrites = Vec::with_capacity(elements.len());
+        for (i, element) in elements.iter().enumerate() {
+            let start = self.buffer.len();
+            let line_anchors_start = self.line_anchors.len();
+            let skipped_range_start = self.skipped_range.borrow().len();
+            self.format_body_element(element, alignment);
+            let next_lo = elements.get(i + 1).map_or(body_hi, BodyElement::lo);
+            self.push_trailing_comment(next_lo);
+            let rewrite = self.buffer.split_off(start);
+            self.line_number -= count_newlines(&rewrite);
+            let name = match *element {
+                BodyElement::ForeignItem(item) => item.ident.as_str(),
+            };
+            let body = rewrite.trim_start();
+            let leading_newlines = count_newlines(&rewrite[..rewrite.len() - body.len()]);
+
+            // Make the recorded lines relative to the first line of the element.
+            let first_line = self.line_number + 1 + leading_newlines;
+            let line_anchors: Vec<_> = self
+                .line_anchors
+                .drain(line_anchors_start..)
+                .map(|(original, line)| (original, line.saturating_sub(first_line)))
+                .collect();
+            let skipped_range: Vec<_> = self
+                .skipped_range
+                .borrow_mut()
+                .drain(skipped_range_start..)
+                .map(|(lo, hi)| (lo.saturating_sub(first_line), hi.saturating_sub(first_line)))
+                .collect();
+            rewrites.push((
+                name,
+                leading_newlines > 1,
+                body.trim_end().to_owned(),
+                line_anchors,
+                skipped_range,
+            ));
+        }
+
+        rewrites.sort_by(|(a, ..), (b, ..)| a.cmp(b));
+        for (i, (_, blank_line, rewrite, line_anchors, skipped_range)) in
+            rewrites.into_iter().enumerate()
+        {
+            if blank_line && i > 0 {
+                self.push_str("\n");
+            }
+            let indent_str = self.block_indent.to_string_with_newline(self.config);
+            self.push_str(&indent_str);
+            let first_line = self.line_number + 1;
+            self.line_anchors.extend(
+                line_anchors
+                    .into_iter()
+                    .map(|(original, line)| (original, first_line + line)),
+            );
+            self.skipped_range.borrow_mut().extend(
+                skipped_range
+                    .into_iter()
+                    .map(|(lo, hi)| (first_line + lo, first_line + hi)),
+            );
+            self.push_str(&rewrite);
+        }
+    }
+
+    // Appends the comment following the last element on the same line, if any, so that it stays
+    // with the element.
+    fn push_trailing_comment(&mut self, next_lo: BytePos) {
+        let snippet = self.snippet(mk_sp(self.last_pos, next_lo));
+        let line = snippet.split('\n').next().unwrap_or("");
+        let comment = line.trim();
+        if comment.starts_with("//") || (comment.starts_with("/*") && comment.ends_with("*/")) {
+            self.push_str(" ");
+            self.push_str(comment);
+            self.last_pos = self.last_pos + BytePos(line.len() as u32);
+        }
+    }
+
+    // Computes the widths to pad the foreign functions and statics to, so that their return types
+    // and types are aligned. The functions, or the statics, are only aligned if the widths of their
+    // signatures differ by at most `foreign_item_align_threshold`.
+    fn foreign_item_alignment(&self, elements: &[BodyElement<'_>]) -> ForeignItemAlignment {
+        let threshold = self.config.foreign_item_align_threshold();
+        if threshold == 0 {
+            return ForeignItemAlignment::default();
+        }
+        let context = self.get_context();
+        let mut fn_widths = vec![];
+        let mut static_widths = vec![];
+        for element in elements {
+            let item = match *element {
+                BodyElement::ForeignItem(item) => item,
+            };
+            let head = item
+                .rewrite(&context, self.shape())
+                .and_then(|rewrite| split_foreign_item_type(&context, item, &rewrite));
+            match head {
+                Some((true, ref head)) => fn_widths.push(unicode_str_width(head)),
+                Some((false, ref head)) => static_widths.push(unicode_str_width(head)),
+                None => (),
+            }
+        }
+        let aligned_width = |widths: &[usize]| {
+            let max_width = widths.iter().copied().max().unwrap_or(0);
+            let min_width = widths.iter().copied().min().unwrap_or(0);
+            if max_width - min_width > threshold {
+                0
+            } else {
+                max_width
+            }
+        };
+        ForeignItemAlignment {
+            fn_width: aligned_width(&fn_widths),
+            static_width: aligned_width(&static_widths),
         }
     }
 
@@ -298,8 +526,12 @@ impl<'a> FmtVisitor<'a> {
         self.format_item(&item);
     }
 
-    fn format_foreign_item(&mut self, item: &ast::ForeignItem) {
-        let rewrite = item.rewrite(&self.get_context(), self.shape());
+    fn format_foreign_item(&mut self, item: &ast::ForeignItem, alignment: ForeignItemAlignment) {
+        let context = self.get_context();
+        let shape = self.shape();
+        let rewrite = item
+            .rewrite(&context, shape)
+            .map(|rewrite| alignment.pad(&context, item, rewrite, shape));
         let hi = item.span.hi();
         let span = if item.attrs.is_empty() {
             item.span
@@ -373,10 +605,9 @@ impl<'a> FmtVisitor<'a> {
 
         let context = self.get_context();
 
-        if self.config.empty_item_single_line()
-            && is_empty_block(&context, block, None)
-            && self.block_indent.width() + fn_str.len() + 3 <= self.config.max_width()
+        if is_empty_block(&context, block, None)
             && !last_line_contains_single_line_comment(fn_str)
+            && is_empty_item_single_line(self.config, self.block_indent, &format!("{} {{", fn_str))
         {
             return Some(format!("{} {{}}", fn_str));
         }
@@ -750,7 +981,14 @@ pub(crate) fn format_impl(
             }
         }
 
-        if is_impl_single_line(context, items.as_slice(), &result, &where_clause_str, item)? {
+        if is_impl_single_line(
+            context,
+            items.as_slice(),
+            &result,
+            &where_clause_str,
+            item,
+            offset,
+        )? {
             result.push_str(&where_clause_str);
             if where_clause_str.contains('\n') || last_line_contains_single_line_comment(&result) {
                 // if the where_clause contains extra comments AND
@@ -805,7 +1043,9 @@ pub(crate) fn format_impl(
             result.push_str(&inner_indent_str);
             result.push_str(visitor.buffer.trim());
             result.push_str(&outer_indent_str);
-        } else if need_newline || !context.config.empty_item_single_line() {
+        } else if need_newline
+            || !is_empty_item_single_line(context.config, offset, last_line(&result))
+        {
             result.push_str(&sep);
         }
 
@@ -823,19 +1063,29 @@ fn is_impl_single_line(
     result: &str,
     where_clause_str: &str,
     item: &ast::Item,
+    offset: Indent,
 ) -> Option<bool> {
     let snippet = context.snippet(item.span);
     let open_pos = snippet.find_uncommented("{")? + 1;
 
     Some(
-        context.config.empty_item_single_line()
-            && items.is_empty()
-            && !result.contains('\n')
+        items.is_empty()
             && result.len() + where_clause_str.len() <= context.config.max_width()
-            && !contains_comment(&snippet[open_pos..]),
+            && !contains_comment(&snippet[open_pos..])
+            && is_empty_item_single_line(context.config, offset, &format!("{} {{", result)),
     )
 }
 
+/// Returns `true` if the body of an empty item is closed on the line of its opening brace, as in
+/// `mod foo {}`. `header` is the item up to and including that brace, written at `indent`; it
+/// must be a single line leaving room for the closing brace, and `empty_item_single_line` must
+/// be set. Functions, impls, traits, modules and extern blocks all decide this here.
+pub(crate) fn is_empty_item_single_line(config: &Config, indent: Indent, header: &str) -> bool {
+    config.empty_item_single_line()
+        && !header.contains('\n')
+        && indent.width() + unicode_str_width(header) + 1 <= config.max_width()
+}
+
 fn format_impl_ref_and_type(
     context: &RewriteContext<'_>,
     item: &ast::Item,
@@ -922,7 +1172,7 @@ fn format_impl_ref_and_type(
             result.push_str("for ");
         }
         let budget = context.budget(last_line_width(&result));
-        let type_offset = match context.config.indent_style() {
+        let type_offset = match context.indent_style() {
             IndentStyle::Visual => new_line_offset + trait_ref_overhead,
             IndentStyle::Block => new_line_offset,
         };
@@ -1070,7 +1320,11 @@ pub(crate) fn format_trait(
 
         // Rewrite where-clause.
         if !generics.where_clause.predicates.is_empty() {
-            let where_on_new_line = context.config.indent_style() != IndentStyle::Block;
+            let where_on_new_line = context
+                .config
+                .where_indent_style()
+                .resolve(context.config.indent_style())
+                != IndentStyle::Block;
 
             let where_budget = context.budget(last_line_width(&result));
             let pos_before_where = if generic_bounds.is_empty() {
@@ -1133,6 +1387,13 @@ pub(crate) fn format_trait(
                 result.push_str(&offset.to_string_with_newline(context.config));
             }
             BraceStyle::PreferSameLine => result.push(' '),
+            // Like functions, keep the brace after a single-line where-clause.
+            BraceStyle::SameLineWhere
+                if context.config.where_single_line()
+                    && generics.where_clause.predicates.len() == 1 =>
+            {
+                result.push(' ')
+            }
             BraceStyle::SameLineWhere => {
                 if result.contains('\n')
                     || (!generics.where_clause.predicates.is_empty() && !trait_items.is_empty())
@@ -1165,7 +1426,7 @@ pub(crate) fn format_trait(
             result.push_str(&inner_indent_str);
             result.push_str(visitor.buffer.trim());
             result.push_str(&outer_indent_str);
-        } else if result.contains('\n') {
+        } else if !is_empty_item_single_line(context.config, offset, &result) {
             result.push_str(&outer_indent_str);
         }
 
@@ -1531,8 +1792,21 @@ fn rewrite_type<R: Rewrite>(
     if let Some(bounds) = generic_bounds_opt {
         if !bounds.is_empty() {
             // 2 = `: `
-            let shape = Shape::indented(indent, context.config).offset_left(result.len() + 2)?;
-            let type_bounds = bounds.rewrite(context, shape).map(|s| format!(": {}", s))?;
+            let type_bounds = Shape::indented(indent, context.config)
+                .offset_left(last_line_width(&result) + 2)
+                .and_then(|shape| bounds.rewrite(context, shape))
+                .map(|s| format!(": {}", s))
+                .or_else(|| {
+                    // Put the bounds on the next line if they don't fit after the generics.
+                    let shape =
+                        Shape::indented(indent.block_indent(context.config), context.config);
+                    let bounds_str = bounds.rewrite(context, shape)?;
+                    Some(format!(
+                        ":{}{}",
+                        shape.indent.to_string_with_newline(context.config),
+                        bounds_str
+                    ))
+                })?;
             result.push_str(&type_bounds);
         }
     }
@@ -1891,7 +2165,7 @@ impl Rewrite for ast::FnRetTy {
             ast::FnRetTy::Default(_) => Some(String::new()),
             ast::FnRetTy::Ty(ref ty) => {
                 if context.config.version() == Version::One
-                    || context.config.indent_style() == IndentStyle::Visual
+                    || context.indent_style() == IndentStyle::Visual
                 {
                     let inner_width = shape.width.checked_sub(3)?;
                     return ty
@@ -2146,6 +2420,9 @@ fn rewrite_fn_base(
     )?;
     result.push_str(&generics_str);
 
+    // Params and return type. The where-clause overrides this with its own indent style.
+    let _indent_style = context.override_indent_style(context.config.fn_args_indent_style());
+
     let snuggle_angle_bracket = generics_str
         .lines()
         .last()
@@ -2174,12 +2451,18 @@ fn rewrite_fn_base(
         "rewrite_fn_base: one_line_budget: {}, multi_line_budget: {}, param_indent: {:?}",
         one_line_budget, multi_line_budget, param_indent
     );
+    // 2 = the spaces within `( ` and ` )`
+    let one_line_budget = if context.config.spaces_within_parens() {
+        one_line_budget.saturating_sub(2)
+    } else {
+        one_line_budget
+    };
 
     result.push('(');
     // Check if vertical layout was forced.
     if one_line_budget == 0
         && !snuggle_angle_bracket
-        && context.config.indent_style() == IndentStyle::Visual
+        && context.indent_style() == IndentStyle::Visual
     {
         result.push_str(&param_indent.to_string_with_newline(context.config));
     }
@@ -2218,7 +2501,7 @@ fn rewrite_fn_base(
         fd.c_variadic(),
     )?;
 
-    let put_params_in_block = match context.config.indent_style() {
+    let put_params_in_block = match context.indent_style() {
         IndentStyle::Block => param_str.contains('\n') || param_str.len() > one_line_budget,
         _ => false,
     } && !fd.inputs.is_empty();
@@ -2233,7 +2516,18 @@ fn rewrite_fn_base(
         result.push_str(&indent.to_string_with_newline(context.config));
         result.push(')');
     } else {
-        result.push_str(&param_str);
+        let spaces_within_parens = context.config.spaces_within_parens()
+            && result.ends_with('(')
+            && !param_str.is_empty()
+            && !param_str.contains('\n')
+            && !param_str.contains("//");
+        if spaces_within_parens {
+            result.push(' ');
+            result.push_str(&param_str);
+            result.push(' ');
+        } else {
+            result.push_str(&param_str);
+        }
         let used_width = last_line_used_width(&result, indent.width()) + first_line_width(&ret_str);
         // Put the closing brace on the next line if it overflows the max width.
         // 1 = `)`
@@ -2268,7 +2562,7 @@ fn rewrite_fn_base(
 
     // Return type.
     if let ast::FnRetTy::Ty(..) = fd.output {
-        let ret_should_indent = match context.config.indent_style() {
+        let ret_should_indent = match context.indent_style() {
             // If our params are block layout then we surely must have space.
             IndentStyle::Block if put_params_in_block || fd.inputs.is_empty() => false,
             _ if params_last_line_contains_comment => false,
@@ -2290,7 +2584,7 @@ fn rewrite_fn_base(
         };
         let ret_shape = if ret_should_indent {
             if context.config.version() == Version::One
-                || context.config.indent_style() == IndentStyle::Visual
+                || context.indent_style() == IndentStyle::Visual
             {
                 let indent = if param_str.is_empty() {
                     // Aligning with non-existent params looks silly.
@@ -2522,27 +2816,28 @@ fn rewrite_params(
     )
     .collect();
 
-    let tactic = definitive_tactic(
-        &param_items,
+    let force_multiline_threshold = context.config.fn_params_force_multiline_threshold();
+    let list_tactic = if force_multiline_threshold > 0 && params.len() > force_multiline_threshold {
+        ListTactic::Vertical
+    } else {
         context
             .config
             .fn_args_layout()
-            .to_list_tactic(param_items.len()),
-        Separator::Comma,
-        one_line_budget,
-    );
+            .to_list_tactic(param_items.len())
+    };
+    let tactic = definitive_tactic(&param_items, list_tactic, Separator::Comma, one_line_budget);
     let budget = match tactic {
         DefinitiveListTactic::Horizontal => one_line_budget,
         _ => multi_line_budget,
     };
-    let indent = match context.config.indent_style() {
+    let indent = match context.indent_style() {
         IndentStyle::Block => indent.block_indent(context.config),
         IndentStyle::Visual => param_indent,
     };
     let trailing_separator = if variadic {
         SeparatorTactic::Never
     } else {
-        match context.config.indent_style() {
+        match context.indent_style() {
             IndentStyle::Block => context.config.trailing_comma(),
             IndentStyle::Visual => SeparatorTactic::Never,
         }
@@ -2550,7 +2845,7 @@ fn rewrite_params(
     let fmt = ListFormatting::new(Shape::legacy(budget, indent), context.config)
         .tactic(tactic)
         .trailing_separator(trailing_separator)
-        .ends_with_newline(tactic.ends_with_newline(context.config.indent_style()))
+        .ends_with_newline(tactic.ends_with_newline(context.indent_style()))
         .preserve_newline(true);
     write_list(&param_items, &fmt)
 }
@@ -2584,7 +2879,7 @@ fn compute_budgets_for_params(
 
         if one_line_budget > 0 {
             // 4 = "() {".len()
-            let (indent, multi_line_budget) = match context.config.indent_style() {
+            let (indent, multi_line_budget) = match context.indent_style() {
                 IndentStyle::Block => {
                     let indent = indent.block_indent(context.config);
                     (indent, context.budget(indent.width() + 1))
@@ -2605,7 +2900,7 @@ fn compute_budgets_for_params(
 
     // Didn't work. we must force vertical layout and put params on a newline.
     let new_indent = indent.block_indent(context.config);
-    let used_space = match context.config.indent_style() {
+    let used_space = match context.indent_style() {
         // 1 = `,`
         IndentStyle::Block => new_indent.width() + 1,
         // Account for `)` and possibly ` {`.
@@ -2645,7 +2940,70 @@ fn rewrite_generics(
     }
 
     let params = generics.params.iter();
-    overflow::rewrite_with_angle_brackets(context, ident, params, shape, generics.span)
+    let result =
+        overflow::rewrite_with_angle_brackets(context, ident, params, shape, generics.span)?;
+    if context.config.group_lifetime_params() && result.contains('\n') {
+        if let Some(grouped) =
+            rewrite_generics_with_grouped_lifetimes(context, ident, generics, shape)
+        {
+            return Some(grouped);
+        }
+    }
+    Some(result)
+}
+
+// Rewrites the generic parameters one per line, except for the lifetimes which are put first and
+// together on a line if they fit. Returns `None` if the parameters have comments, which would be
+// lost.
+fn rewrite_generics_with_grouped_lifetimes(
+    context: &RewriteContext<'_>,
+    ident: &str,
+    generics: &ast::Generics,
+    shape: Shape,
+) -> Option<String> {
+    let config = context.config;
+    if config.indent_style() != IndentStyle::Block
+        || contains_comment(context.snippet(generics.span))
+    {
+        return None;
+    }
+
+    let nested_shape = generics_shape_from_config(config, shape, 0)?;
+    let (lifetimes, others): (Vec<_>, Vec<_>) =
+        generics.params.iter().partition(|param| match param.kind {
+            ast::GenericParamKind::Lifetime => true,
+            _ => false,
+        });
+    let lifetimes = lifetimes
+        .iter()
+        .map(|param| param.rewrite(context, nested_shape))
+        .collect::<Option<Vec<_>>>()?;
+
+    let mut lines = vec![];
+    let lifetimes_line = lifetimes.join(", ");
+    if lifetimes_line.len() <= nested_shape.width {
+        lines.push(lifetimes_line);
+    } else {
+        lines.extend(lifetimes);
+    }
+    lines.retain(|line| !line.is_empty());
+    for param in others {
+        lines.push(param.rewrite(context, nested_shape)?);
+    }
+
+    let nested_indent_str = nested_shape.indent.to_string_with_newline(config);
+    let trailing_comma = match config.trailing_comma() {
+        SeparatorTactic::Never => "",
+        _ => ",",
+    };
+    Some(format!(
+        "{}<{}{}{}{}>",
+        ident,
+        nested_indent_str,
+        lines.join(&format!(",{}", nested_indent_str)),
+        trailing_comma,
+        shape.block().indent.to_string_with_newline(config)
+    ))
 }
 
 fn generics_shape_from_config(config: &Config, shape: Shape, offset: usize) -> Option<Shape> {
@@ -2689,6 +3047,25 @@ fn rewrite_where_clause_rfc_style(
         && where_clause.predicates.len() == 1
         && !where_clause_option.veto_single_line;
 
+    let compress = context.config.where_compressed()
+        && !where_clause_option.veto_single_line
+        && !contains_comment(context.snippet(where_clause.span));
+    if compress {
+        let preds_str = rewrite_bounds_on_where_clause(
+            context,
+            where_clause,
+            clause_shape,
+            terminator,
+            span_end,
+            where_clause_option,
+            true,
+        )?;
+        // 6 = `where `
+        if !preds_str.contains('\n') && 6 + preds_str.len() <= shape.width {
+            return Some(format!("{} {}", where_keyword, preds_str));
+        }
+    }
+
     let preds_str = rewrite_bounds_on_where_clause(
         context,
         where_clause,
@@ -2740,7 +3117,11 @@ fn rewrite_where_keyword(
         rewrite_comments_before_after_where(context, span_before, span_after, shape)?;
 
     let starting_newline = match where_clause_option.snuggle {
-        WhereClauseSpace::Space if comment_before.is_empty() => Cow::from(" "),
+        WhereClauseSpace::Space
+            if comment_before.is_empty() && !context.config.where_always_on_new_line() =>
+        {
+            Cow::from(" ")
+        }
         WhereClauseSpace::None => Cow::from(""),
         _ => block_shape.indent.to_string_with_newline(context.config),
     };
@@ -2786,7 +3167,10 @@ fn rewrite_bounds_on_where_clause(
         span_end,
         false,
     );
-    let comma_tactic = if where_clause_option.suppress_comma || force_single_line {
+    let comma_tactic = if where_clause_option.suppress_comma
+        || force_single_line
+        || !context.config.where_trailing_comma()
+    {
         SeparatorTactic::Never
     } else {
         context.config.trailing_comma()
@@ -2822,7 +3206,8 @@ fn rewrite_where_clause(
         return Some(String::new());
     }
 
-    if context.config.indent_style() == IndentStyle::Block {
+    let _indent_style = context.override_indent_style(context.config.where_indent_style());
+    if context.indent_style() == IndentStyle::Block {
         return rewrite_where_clause_rfc_style(
             context,
             where_clause,
@@ -2836,7 +3221,7 @@ fn rewrite_where_clause(
 
     let extra_indent = Indent::new(context.config.tab_spaces(), 0);
 
-    let offset = match context.config.indent_style() {
+    let offset = match context.indent_style() {
         IndentStyle::Block => shape.indent + extra_indent.block_indent(context.config),
         // 6 = "where ".len()
         IndentStyle::Visual => shape.indent + extra_indent + 6,
@@ -2869,14 +3254,17 @@ fn rewrite_where_clause(
 
     let mut comma_tactic = context.config.trailing_comma();
     // Kind of a hack because we don't usually have trailing commas in where-clauses.
-    if comma_tactic == SeparatorTactic::Vertical || where_clause_option.suppress_comma {
+    if comma_tactic == SeparatorTactic::Vertical
+        || where_clause_option.suppress_comma
+        || !context.config.where_trailing_comma()
+    {
         comma_tactic = SeparatorTactic::Never;
     }
 
     let fmt = ListFormatting::new(Shape::legacy(budget, offset), context.config)
         .tactic(tactic)
         .trailing_separator(comma_tactic)
-        .ends_with_newline(tactic.ends_with_newline(context.config.indent_style()))
+        .ends_with_newline(tactic.ends_with_newline(context.indent_style()))
         .preserve_newline(true);
     let preds_str = write_list(&item_vec, &fmt)?;
 
@@ -2893,6 +3281,7 @@ fn rewrite_where_clause(
         terminator.len()
     };
     if on_new_line
+        || context.config.where_always_on_new_line()
         || preds_str.contains('\n')
         || shape.indent.width() + " where ".len() + preds_str.len() + end_length > shape.width
     {
@@ -3167,6 +3556,17 @@ pub(crate) fn rewrite_mod(
     rewrite_attrs(context, item, &result, attrs_shape)
 }
 
+/// Rewrite an impl block with its attributes, e.g. to reorder it.
+/// The given shape is used to format the impl's attributes.
+pub(crate) fn rewrite_impl(
+    context: &RewriteContext<'_>,
+    item: &ast::Item,
+    attrs_shape: Shape,
+) -> Option<String> {
+    let impl_str = format_impl(context, item, attrs_shape.indent)?;
+    rewrite_attrs(context, item, &impl_str, attrs_shape)
+}
+
 /// Rewrite `extern crate foo;`.
 /// The given shape is used to format the extern crate's attributes.
 pub(crate) fn rewrite_extern_crate(
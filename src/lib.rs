@@ -9,41 +9,49 @@ extern crate lazy_static;
 extern crate log;
 
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashSet};
 use std::fmt;
 use std::io::{self, Write};
 use std::mem;
 use std::panic;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::time::Instant;
 
 use ignore;
 use rustc_ast::ast;
 use rustc_span::symbol;
 use thiserror::Error;
 
+use crate::cancellation::Cancellation;
 use crate::comment::LineClasses;
-use crate::emitter::Emitter;
+use crate::format_cache::FormatCache;
 use crate::formatting::{FormatErrorMap, FormattingError, ReportedErrors, SourceFile};
 use crate::issues::Issue;
 use crate::modules::ModuleResolutionError;
 use crate::shape::Indent;
-use crate::syntux::parser::DirectoryOwnership;
+use crate::syntux::parser::{DirectoryOwnership, Parser};
+use crate::syntux::session::ParseSess;
 use crate::utils::indent_next_line;
 
 pub use crate::config::{
-    load_config, CliOptions, Color, Config, Edition, EmitMode, FileLines, FileName, NewlineStyle,
-    Range, Verbosity,
+    load_config, CliOptions, Color, Config, DiffStyle, Edition, EmitMode, FileLines, FileName,
+    LicenseTemplate, ModuleTraversal, NewlineStyle, Range, SymlinkPolicy, Verbosity,
 };
 
+pub use crate::cancellation::CancellationToken;
+pub use crate::emitter::{Emitter, EmitterResult, FileStats, FormattedFile, RunStats};
 pub use crate::format_report_formatter::{FormatReportFormatter, FormatReportFormatterBuilder};
+pub use crate::line_mapping::LineMapping;
+pub use crate::rewrite::{RewriteContext, RewriteHook};
 
-pub use crate::rustfmt_diff::{ModifiedChunk, ModifiedLines};
+pub use crate::rustfmt_diff::{get_modified_lines, ModifiedChunk, ModifiedLines};
 
 #[macro_use]
 mod utils;
 
 mod attr;
+mod cancellation;
 mod chains;
 mod closures;
 mod comment;
@@ -51,12 +59,15 @@ pub(crate) mod config;
 mod coverage;
 mod emitter;
 mod expr;
+mod failure_report;
+mod format_cache;
 mod format_report_formatter;
 pub(crate) mod formatting;
 mod ignore_path;
 mod imports;
 mod issues;
 mod items;
+mod line_mapping;
 mod lists;
 mod macros;
 mod matches;
@@ -68,17 +79,20 @@ mod patterns;
 mod release_channel;
 mod reorder;
 mod rewrite;
-pub(crate) mod rustfmt_diff;
+pub mod rustfmt_diff;
+mod semantic_check;
 mod shape;
 mod skip;
 pub(crate) mod source_file;
 pub(crate) mod source_map;
+mod spacing;
 mod spanned;
 mod stmt;
 mod string;
 mod syntux;
 #[cfg(test)]
 mod test;
+mod trace;
 mod types;
 mod vertical;
 pub(crate) mod visitor;
@@ -124,6 +138,27 @@ pub enum ErrorKind {
     /// If we had formatted the given node, then we would have lost a comment.
     #[error("not formatted because a comment would be lost")]
     LostComment,
+    /// Formatting dropped or duplicated a comment.
+    #[error("formatting would drop or duplicate this comment, so the file was left unformatted")]
+    CommentLoss,
+    /// The formatted code doesn't parse to the same syntax tree as the original code.
+    #[error("formatting would change the meaning of this code, so the file was left unformatted")]
+    SemanticChange,
+    /// Rustfmt panicked while formatting the file.
+    #[error("rustfmt panicked while formatting this file, which was left unformatted")]
+    FormatPanic,
+    /// Rewriting an expression took too long, e.g. because it is nested too deeply.
+    #[error("expression too complex to format in reasonable time, so it was left as it is")]
+    RewriteLimitExceeded,
+    /// Formatting the file took too long.
+    #[error("formatting this file took too long, so the code from here on was left as it is")]
+    FileTimeLimitExceeded,
+    /// The file does not end with a newline.
+    #[error("missing newline at the end of the file")]
+    MissingNewlineAtEof,
+    /// The indentation of a line mixed tabs and spaces, and was normalized.
+    #[error("indentation mixed tabs and spaces, and was normalized according to `hard_tabs`")]
+    MixedIndentation,
     /// Invalid glob pattern in `ignore` configuration option.
     #[error("Invalid glob pattern found in ignore list: {0}")]
     InvalidGlobPattern(ignore::Error),
@@ -180,18 +215,76 @@ pub struct FormatReport {
     // Maps stringified file paths to their associated formatting errors.
     internal: Rc<RefCell<(FormatErrorMap, ReportedErrors)>>,
     non_formatted_ranges: Vec<(usize, usize)>,
+    skipped_ranges: BTreeMap<FileName, Vec<(usize, usize)>>,
+    line_mappings: BTreeMap<FileName, LineMapping>,
+    // The statistics of the formatted files, and of the file being formatted.
+    file_stats: Rc<RefCell<(BTreeMap<FileName, FileStats>, FileStats)>>,
+    run_stats: RunStats,
 }
 
 impl FormatReport {
     fn new() -> FormatReport {
         FormatReport {
-            internal: Rc::new(RefCell::new((HashMap::new(), ReportedErrors::default()))),
+            internal: Rc::new(RefCell::new((BTreeMap::new(), ReportedErrors::default()))),
             non_formatted_ranges: Vec::new(),
+            skipped_ranges: BTreeMap::new(),
+            line_mappings: BTreeMap::new(),
+            file_stats: Rc::new(RefCell::new((BTreeMap::new(), FileStats::default()))),
+            run_stats: RunStats::default(),
         }
     }
 
-    fn add_non_formatted_ranges(&mut self, mut ranges: Vec<(usize, usize)>) {
-        self.non_formatted_ranges.append(&mut ranges);
+    fn add_non_formatted_ranges(&mut self, file: FileName, ranges: Vec<(usize, usize)>) {
+        self.non_formatted_ranges.extend(ranges.iter().cloned());
+        self.skipped_ranges.insert(file, ranges);
+    }
+
+    /// Returns the ranges of lines of the given file which were left as they are, e.g. because
+    /// of `#[rustfmt::skip]`. No warnings are reported for these lines.
+    pub fn skipped_ranges(&self, file: &FileName) -> &[(usize, usize)] {
+        self.skipped_ranges
+            .get(file)
+            .map_or(&[], |ranges| ranges.as_slice())
+    }
+
+    fn count_rewrite(&self, succeeded: bool) {
+        let current = &mut self.file_stats.borrow_mut().1;
+        if succeeded {
+            current.items_formatted += 1;
+        } else {
+            current.rewrite_fallbacks += 1;
+        }
+    }
+
+    fn finish_file_stats(&self, file: FileName, format_time: f32) {
+        let mut file_stats = self.file_stats.borrow_mut();
+        let mut stats = mem::take(&mut file_stats.1);
+        stats.format_time = format_time;
+        file_stats.0.insert(file, stats);
+    }
+
+    /// Returns the statistics of formatting each file.
+    pub fn file_stats(&self) -> BTreeMap<FileName, FileStats> {
+        self.file_stats.borrow().0.clone()
+    }
+
+    fn set_run_stats(&mut self, stats: RunStats) {
+        self.run_stats = stats;
+    }
+
+    /// Returns the statistics of the whole run, e.g. the time spent parsing.
+    pub fn run_stats(&self) -> RunStats {
+        self.run_stats
+    }
+
+    fn add_line_mapping(&mut self, file: FileName, mapping: LineMapping) {
+        self.line_mappings.insert(file, mapping);
+    }
+
+    /// Returns the mapping of the lines of the given file before formatting to its lines after
+    /// formatting, if the file was formatted.
+    pub fn line_mapping(&self, file: &FileName) -> Option<&LineMapping> {
+        self.line_mappings.get(file)
     }
 
     fn append(&self, f: FileName, mut v: Vec<FormattingError>) {
@@ -214,11 +307,18 @@ impl FormatReport {
         }
         for err in new_errors {
             match err.kind {
-                ErrorKind::LineOverflow(..) | ErrorKind::TrailingWhitespace => {
+                ErrorKind::LineOverflow(..)
+                | ErrorKind::TrailingWhitespace
+                | ErrorKind::CommentLoss => {
                     errs.has_operational_errors = true;
                 }
+                ErrorKind::FormatPanic | ErrorKind::SemanticChange => {
+                    errs.has_operational_errors = true;
+                    errs.has_internal_errors = true;
+                }
                 ErrorKind::BadIssue(_)
                 | ErrorKind::LicenseCheck
+                | ErrorKind::MissingNewlineAtEof
                 | ErrorKind::DeprecatedAttr
                 | ErrorKind::BadAttr
                 | ErrorKind::VersionMismatch => {
@@ -233,10 +333,29 @@ impl FormatReport {
         self.internal.borrow_mut().1.has_diff = true;
     }
 
+    fn add_formatted_file(&self, changed: bool) {
+        let errs = &mut self.internal.borrow_mut().1;
+        if changed {
+            errs.files_changed += 1;
+        } else {
+            errs.files_unchanged += 1;
+        }
+    }
+
     fn add_macro_format_failure(&mut self) {
         self.internal.borrow_mut().1.has_macro_format_failure = true;
     }
 
+    fn add_cancellation(&mut self) {
+        self.internal.borrow_mut().1.was_cancelled = true;
+    }
+
+    /// Whether formatting was cancelled before all items were formatted. The items visited after
+    /// the cancellation are left as they are.
+    pub fn was_cancelled(&self) -> bool {
+        self.internal.borrow().1.was_cancelled
+    }
+
     fn add_parsing_error(&mut self) {
         self.internal.borrow_mut().1.has_parsing_errors = true;
     }
@@ -317,17 +436,124 @@ fn format_snippet(snippet: &str, config: &Config) -> Option<FormattedSnippet> {
     .ok()?
 }
 
+const FN_MAIN_PREFIX: &str = "fn main() {\n";
+const TRAIT_PREFIX: &str = "trait Dummy {\n";
+const IMPL_PREFIX: &str = "impl Dummy {\n";
+
 /// Format the given code block. Mainly targeted for code block in comment.
 /// The code block may be incomplete (i.e., parser may be unable to parse it).
 /// To avoid panic in parser, we wrap the code block with a dummy function.
 /// The returned code block does **not** end with newline.
 fn format_code_block(code_snippet: &str, config: &Config) -> Option<FormattedSnippet> {
-    const FN_MAIN_PREFIX: &str = "fn main() {\n";
+    format_enclosed_code(code_snippet, config, FN_MAIN_PREFIX)
+}
+
+/// Format the given expression, as the tail expression of a block.
+///
+/// This is meant for tools formatting fragments of code, e.g. the code inserted by a code action.
+/// The returned code does **not** end with newline. Returns `None` if the code isn't a single
+/// expression or couldn't be formatted.
+pub fn format_expr_snippet(expr: &str, config: &Config) -> Option<String> {
+    if !is_single_expr(expr, config) {
+        return None;
+    }
+    format_enclosed_code(expr, config, FN_MAIN_PREFIX).map(|formatted| formatted.snippet)
+}
+
+/// Returns `true` if the given code parses as a single expression, i.e. as the only content of a
+/// block, without a trailing semicolon.
+fn is_single_expr(code: &str, config: &Config) -> bool {
+    rustc_span::with_session_globals(config.edition().to_libsyntax_pos_edition(), || {
+        let mut sess = match ParseSess::new(config) {
+            Ok(sess) => sess,
+            Err(_) => return false,
+        };
+        sess.set_silent_emitter();
+        let source = format!("{}{}\n}}", FN_MAIN_PREFIX, code);
+        let items = match Parser::parse_str_as_module(&sess, source) {
+            Ok((module, _)) => module.items,
+            Err(_) => return false,
+        };
+        let stmts = match items.first().map(|item| &item.kind) {
+            Some(ast::ItemKind::Fn(_, _, _, Some(ref body))) if items.len() == 1 => &body.stmts,
+            _ => return false,
+        };
+        match stmts.first().map(|stmt| &stmt.kind) {
+            Some(ast::StmtKind::Expr(..)) if stmts.len() == 1 => true,
+            Some(ast::StmtKind::MacCall(ref mac_stmt)) if stmts.len() == 1 => {
+                mac_stmt.style != ast::MacStmtStyle::Semicolon
+            }
+            _ => false,
+        }
+    })
+}
 
-    fn enclose_in_main_block(s: &str, config: &Config) -> String {
+/// Format the given statements, as the statements of a block.
+///
+/// The returned code does **not** end with newline. Returns `None` if the code couldn't be
+/// formatted.
+pub fn format_stmt_snippet(stmts: &str, config: &Config) -> Option<String> {
+    format_enclosed_code(stmts, config, FN_MAIN_PREFIX).map(|formatted| formatted.snippet)
+}
+
+/// Format the given associated items, as the items of a trait or an impl, e.g. functions, types
+/// or constants.
+///
+/// The returned code does **not** end with newline. Returns `None` if the code couldn't be
+/// formatted.
+pub fn format_trait_items_snippet(items: &str, config: &Config) -> Option<String> {
+    format_enclosed_code(items, config, TRAIT_PREFIX).map(|formatted| formatted.snippet)
+}
+
+/// The kind of code generated by a procedural macro, i.e. where the code is going to be put.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MacroContext {
+    /// Items, e.g. the output of a derive macro.
+    Items,
+    /// Associated items, as the items of an impl.
+    ImplItems,
+    /// An expression, e.g. the output of a function-like macro used as an expression.
+    Expr,
+}
+
+/// Format the code generated by a procedural macro, given as the string representation of its
+/// token stream, using the default config.
+///
+/// The returned code does **not** end with newline. Returns `None` if the code couldn't be
+/// parsed or formatted as `ctx`.
+pub fn format_tokens(tokens: &str, ctx: MacroContext) -> Option<String> {
+    format_tokens_with_config(tokens, ctx, &Config::default())
+}
+
+/// Like `format_tokens`, but using the given config.
+pub fn format_tokens_with_config(
+    tokens: &str,
+    ctx: MacroContext,
+    config: &Config,
+) -> Option<String> {
+    match ctx {
+        // A macro may generate no items at all.
+        MacroContext::Items if tokens.trim().is_empty() => Some(String::new()),
+        MacroContext::Items => format_snippet(tokens, config)
+            .map(|formatted| formatted.snippet.trim_end_matches('\n').to_owned()),
+        MacroContext::ImplItems => {
+            format_enclosed_code(tokens, config, IMPL_PREFIX).map(|formatted| formatted.snippet)
+        }
+        MacroContext::Expr => format_expr_snippet(tokens, config),
+    }
+}
+
+/// Format the given code enclosed in a dummy block opened by `prefix`, a single line such as
+/// `fn main() {`, and return it without the enclosing block.
+fn format_enclosed_code(
+    code_snippet: &str,
+    config: &Config,
+    prefix: &str,
+) -> Option<FormattedSnippet> {
+    fn enclose_in_block(s: &str, config: &Config, prefix: &str) -> String {
         let indent = Indent::from_width(config, config.tab_spaces());
         let mut result = String::with_capacity(s.len() * 2);
-        result.push_str(FN_MAIN_PREFIX);
+        result.push_str(prefix);
         let mut need_indent = true;
         for (kind, line) in LineClasses::new(s) {
             if need_indent {
@@ -341,8 +567,16 @@ fn format_code_block(code_snippet: &str, config: &Config) -> Option<FormattedSni
         result
     }
 
-    // Wrap the given code block with `fn main()` if it does not have one.
-    let snippet = enclose_in_main_block(code_snippet, config);
+    // There is nothing to format, and the formatted dummy block would be shorter than `prefix`.
+    if code_snippet.trim().is_empty() {
+        return Some(FormattedSnippet {
+            snippet: String::new(),
+            non_formatted_ranges: vec![],
+        });
+    }
+
+    // Wrap the given code in the dummy block.
+    let snippet = enclose_in_block(code_snippet, config, prefix);
     let mut result = String::with_capacity(snippet.len());
     let mut is_first = true;
 
@@ -355,17 +589,18 @@ fn format_code_block(code_snippet: &str, config: &Config) -> Option<FormattedSni
         .set()
         .newline_style(NewlineStyle::Unix);
     let mut formatted = format_snippet(&snippet, &config_with_unix_newline)?;
-    // Remove wrapping main block
+    // Remove wrapping block
     formatted.unwrap_code_block();
 
-    // Trim "fn main() {" on the first line and "}" on the last line,
+    // Trim the prefix on the first line and "}" on the last line,
     // then unindent the whole code block.
     let block_len = formatted
         .snippet
         .rfind('}')
         .unwrap_or_else(|| formatted.snippet.len());
     let mut is_indented = true;
-    for (kind, ref line) in LineClasses::new(&formatted.snippet[FN_MAIN_PREFIX.len()..block_len]) {
+    let block = formatted.snippet.get(prefix.len()..block_len)?;
+    for (kind, ref line) in LineClasses::new(block) {
         if !is_first {
             result.push('\n');
         } else {
@@ -404,29 +639,68 @@ fn format_code_block(code_snippet: &str, config: &Config) -> Option<FormattedSni
     })
 }
 
+/// The number of lines of a formatted file which change when an option is overridden, as
+/// returned by [`Session::explain`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiffStat {
+    pub file: FileName,
+    pub lines_removed: usize,
+    pub lines_added: usize,
+}
+
 /// A session is a run of rustfmt across a single or multiple inputs.
 pub struct Session<'b, T: Write> {
     pub config: Config,
     pub out: Option<&'b mut T>,
     pub(crate) errors: ReportedErrors,
     source_file: SourceFile,
+    // The digests of the formatted files, which are kept even when their text isn't.
+    file_digests: Vec<(FileName, u64)>,
+    // The canonical paths of the files formatted so far, if these files are skipped when they
+    // are reached again.
+    formatted_files: Option<HashSet<PathBuf>>,
+    stream_output: bool,
+    cancellation: Cancellation,
     emitter: Box<dyn Emitter + 'b>,
+    format_cache: Option<FormatCache>,
+    rewrite_hooks: Vec<Rc<dyn RewriteHook>>,
 }
 
 impl<'b, T: Write + 'b> Session<'b, T> {
-    pub fn new(config: Config, mut out: Option<&'b mut T>) -> Session<'b, T> {
+    pub fn new(config: Config, out: Option<&'b mut T>) -> Session<'b, T> {
         let emitter = create_emitter(&config);
+        Session::with_emitter(config, out, emitter)
+    }
 
+    /// Creates a session which emits the formatted files with the given emitter rather than the
+    /// one for the configured `emit_mode`.
+    pub fn with_emitter(
+        config: Config,
+        mut out: Option<&'b mut T>,
+        emitter: Box<dyn Emitter + 'b>,
+    ) -> Session<'b, T> {
         if let Some(ref mut out) = out {
             let _ = emitter.emit_header(out);
         }
 
+        let format_cache = if config.format_cache().is_empty() {
+            None
+        } else {
+            Some(FormatCache::load(Path::new(config.format_cache())))
+        };
+
         Session {
             config,
             out,
             emitter,
             errors: ReportedErrors::default(),
             source_file: SourceFile::new(),
+            file_digests: vec![],
+            formatted_files: None,
+            stream_output: false,
+            cancellation: Cancellation::default(),
+            format_cache,
+            rewrite_hooks: vec![],
         }
     }
 
@@ -436,6 +710,20 @@ impl<'b, T: Write + 'b> Session<'b, T> {
         self.format_input_inner(input)
     }
 
+    /// Formats several crate roots, e.g. the library, tests and examples of a package, in one
+    /// pass. The roots are parsed with the same parse session and their modules are resolved
+    /// from each root, but a module shared by several roots is only formatted once. The
+    /// returned report covers all the roots.
+    pub fn format_roots(&mut self, roots: Vec<PathBuf>) -> Result<FormatReport, ErrorKind> {
+        self.format_roots_inner(roots)
+    }
+
+    /// Registers a hook which is called around the rewrite of every item. Hooks are called in
+    /// the order they were added.
+    pub fn add_rewrite_hook<H: RewriteHook + 'static>(&mut self, hook: H) {
+        self.rewrite_hooks.push(Rc::new(hook));
+    }
+
     pub fn override_config<F, U>(&mut self, mut config: Config, f: F) -> U
     where
         F: FnOnce(&mut Session<'b, T>) -> U,
@@ -446,6 +734,80 @@ impl<'b, T: Write + 'b> Session<'b, T> {
         result
     }
 
+    /// Formats `input` with the session's config and again with `config`, without emitting
+    /// anything, and returns how many lines of each formatted file differ between the two. This
+    /// shows the impact of changing an option before committing to it.
+    pub fn explain(&mut self, input: Input, config: Config) -> Result<Vec<DiffStat>, ErrorKind> {
+        // Nothing is emitted without an output, and cached files would be skipped rather than
+        // formatted.
+        let out = self.out.take();
+        let format_cache = self.format_cache.take();
+        let formatted_files = self.formatted_files.take();
+        let stream_output = mem::replace(&mut self.stream_output, false);
+        let start = self.source_file.len();
+        let result = self.format_twice(input, config);
+        self.out = out;
+        self.format_cache = format_cache;
+        self.formatted_files = formatted_files;
+        self.stream_output = stream_output;
+
+        let mut current = self.source_file.split_off(start);
+        let overridden = current.split_off(result? - start);
+        Ok(current
+            .into_iter()
+            .map(|(file, text)| {
+                let other = overridden
+                    .iter()
+                    .find(|(other_file, _)| *other_file == file)
+                    .map_or("", |(_, other)| other.as_str());
+                let (lines_removed, lines_added) = rustfmt_diff::diff_stat(&text, other);
+                DiffStat {
+                    file,
+                    lines_removed,
+                    lines_added,
+                }
+            })
+            .collect())
+    }
+
+    // Formats `input` with the session's config and with `config`, and returns the index of the
+    // first file formatted with `config`.
+    fn format_twice(&mut self, input: Input, config: Config) -> Result<usize, ErrorKind> {
+        self.format(input.clone())?;
+        let middle = self.source_file.len();
+        self.override_config(config, |session| session.format(input))?;
+        Ok(middle)
+    }
+
+    /// Sets whether the formatted files are only written to the output rather than also kept
+    /// in memory until the session ends. This cuts the memory used to format large crates, and
+    /// only makes sense for sessions which have an output.
+    pub fn set_stream_output(&mut self, stream_output: bool) {
+        self.stream_output = stream_output;
+    }
+
+    /// Sets whether the files which the session already formatted are skipped when they are
+    /// reached again, e.g. a module given along with its crate root, or a file given through two
+    /// paths.
+    pub fn set_skip_formatted_files(&mut self, skip: bool) {
+        self.formatted_files = if skip { Some(HashSet::new()) } else { None };
+    }
+
+    /// Sets a token which stops formatting when it is cancelled, e.g. by another thread.
+    pub fn set_cancellation_token(&mut self, token: CancellationToken) {
+        self.cancellation.token = Some(token);
+    }
+
+    /// Sets the time after which formatting stops.
+    pub fn set_deadline(&mut self, deadline: Instant) {
+        self.cancellation.deadline = Some(deadline);
+    }
+
+    /// Whether formatting was cancelled by the token or the deadline of the session.
+    pub fn was_cancelled(&self) -> bool {
+        self.errors.was_cancelled
+    }
+
     pub fn add_operational_error(&mut self) {
         self.errors.has_operational_errors = true;
     }
@@ -454,6 +816,12 @@ impl<'b, T: Write + 'b> Session<'b, T> {
         self.errors.has_operational_errors
     }
 
+    /// Whether rustfmt panicked while formatting a file, or found that formatting a file would
+    /// change its meaning. Such files are also counted as operational errors.
+    pub fn has_internal_errors(&self) -> bool {
+        self.errors.has_internal_errors
+    }
+
     pub fn has_parsing_errors(&self) -> bool {
         self.errors.has_parsing_errors
     }
@@ -470,6 +838,17 @@ impl<'b, T: Write + 'b> Session<'b, T> {
         self.errors.has_diff
     }
 
+    /// The number of files whose formatting changed. In `Files` mode, these are the only
+    /// files that were written.
+    pub fn files_changed(&self) -> usize {
+        self.errors.files_changed
+    }
+
+    /// The number of files which were already formatted correctly.
+    pub fn files_unchanged(&self) -> usize {
+        self.errors.files_unchanged
+    }
+
     pub fn has_no_errors(&self) -> bool {
         !(self.has_operational_errors()
             || self.has_parsing_errors()
@@ -480,8 +859,13 @@ impl<'b, T: Write + 'b> Session<'b, T> {
     }
 }
 
-pub(crate) fn create_emitter<'a>(config: &Config) -> Box<dyn Emitter + 'a> {
+/// Creates the emitter for the `emit_mode` of the given config.
+pub fn create_emitter<'a>(config: &Config) -> Box<dyn Emitter + 'a> {
     match config.emit_mode() {
+        EmitMode::Files if !config.out_dir().is_empty() => Box::new(
+            emitter::FilesEmitter::new(config.print_misformatted_file_names())
+                .with_out_dir(PathBuf::from(config.out_dir())),
+        ),
         EmitMode::Files if config.make_backup() => {
             Box::new(emitter::FilesWithBackupEmitter::default())
         }
@@ -491,9 +875,11 @@ pub(crate) fn create_emitter<'a>(config: &Config) -> Box<dyn Emitter + 'a> {
         EmitMode::Stdout | EmitMode::Coverage => {
             Box::new(emitter::StdoutEmitter::new(config.verbose()))
         }
-        EmitMode::Json => Box::new(emitter::JsonEmitter::default()),
+        EmitMode::Json => Box::new(emitter::JsonEmitter::new(config.diff_summary())),
         EmitMode::ModifiedLines => Box::new(emitter::ModifiedLinesEmitter::default()),
-        EmitMode::Checkstyle => Box::new(emitter::CheckstyleEmitter::default()),
+        EmitMode::Checkstyle => Box::new(emitter::CheckstyleEmitter::new(config.diff_summary())),
+        EmitMode::Stats => Box::new(emitter::StatsEmitter::new(false)),
+        EmitMode::StatsJson => Box::new(emitter::StatsEmitter::new(true)),
         EmitMode::Diff => Box::new(emitter::DiffEmitter::new(config.clone())),
     }
 }
@@ -503,10 +889,15 @@ impl<'b, T: Write + 'b> Drop for Session<'b, T> {
         if let Some(ref mut out) = self.out {
             let _ = self.emitter.emit_footer(out);
         }
+        if let Some(ref mut format_cache) = self.format_cache {
+            if let Err(e) = format_cache.save() {
+                warn!("Failed to save the format cache: {}", e);
+            }
+        }
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum Input {
     File(PathBuf),
     Text(String),
@@ -632,4 +1023,94 @@ false,
 };";
         assert!(test_format_inner(format_code_block, code_block, expected));
     }
+
+    #[test]
+    fn test_format_fragment_snippets() {
+        let config = Config::default();
+        assert_eq!(
+            format_expr_snippet("if x {a} else {b}", &config).as_deref(),
+            Some("if x {\n    a\n} else {\n    b\n}")
+        );
+        assert_eq!(format_expr_snippet("foo(a,b);", &config), None);
+        assert_eq!(format_expr_snippet("a; b", &config), None);
+        assert_eq!(format_expr_snippet("let x = 1; x", &config), None);
+        assert_eq!(format_expr_snippet("fn f() {}", &config), None);
+        assert_eq!(
+            format_expr_snippet("vec![1,2]", &config).as_deref(),
+            Some("vec![1, 2]")
+        );
+        assert_eq!(
+            format_stmt_snippet("let x=3;\nfoo(x)", &config).as_deref(),
+            Some("let x = 3;\nfoo(x)")
+        );
+        assert_eq!(
+            format_trait_items_snippet("type T;\nfn foo(&self)->u32 {1}", &config).as_deref(),
+            Some("type T;\nfn foo(&self) -> u32 {\n    1\n}")
+        );
+    }
+
+    #[test]
+    fn test_format_empty_fragment_snippets() {
+        let config = Config::default();
+        for input in &["", "  ", "\n", " \n\t\n"] {
+            assert_eq!(format_expr_snippet(input, &config), None);
+            assert_eq!(format_stmt_snippet(input, &config).as_deref(), Some(""));
+            assert_eq!(
+                format_trait_items_snippet(input, &config).as_deref(),
+                Some("")
+            );
+            assert_eq!(
+                format_code_block(input, &config).map(|formatted| formatted.snippet),
+                Some(String::new())
+            );
+        }
+    }
+
+    #[test]
+    fn test_format_tokens() {
+        assert_eq!(
+            format_tokens(
+                "# [derive (Debug)] struct Foo { a : u32 , } impl :: std :: fmt :: Display for \
+                 Foo { fn fmt (& self , f : & mut :: std :: fmt :: Formatter) -> :: std :: fmt \
+                 :: Result { write ! (f , \"{}\" , self . a) } }",
+                MacroContext::Items
+            )
+            .as_deref(),
+            Some(
+                "#[derive(Debug)]\nstruct Foo {\n    a: u32,\n}\n\
+                 impl ::std::fmt::Display for Foo {\n    \
+                 fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {\n        \
+                 write!(f, \"{}\", self.a)\n    }\n}"
+            )
+        );
+        assert_eq!(
+            format_tokens(
+                "pub fn new (a : u32) -> Self { Self { a } }",
+                MacroContext::ImplItems
+            )
+            .as_deref(),
+            Some("pub fn new(a: u32) -> Self {\n    Self { a }\n}")
+        );
+        assert_eq!(
+            format_tokens("Foo { a : 1 + 2 }", MacroContext::Expr).as_deref(),
+            Some("Foo { a: 1 + 2 }")
+        );
+        assert_eq!(format_tokens("struct", MacroContext::Items), None);
+        assert_eq!(format_tokens("a ; b", MacroContext::Expr), None);
+    }
+
+    #[test]
+    fn test_format_empty_tokens() {
+        for tokens in &["", "  ", "\n", " \n\t\n"] {
+            assert_eq!(
+                format_tokens(tokens, MacroContext::Items).as_deref(),
+                Some("")
+            );
+            assert_eq!(
+                format_tokens(tokens, MacroContext::ImplItems).as_deref(),
+                Some("")
+            );
+            assert_eq!(format_tokens(tokens, MacroContext::Expr), None);
+        }
+    }
 }
@@ -0,0 +1,107 @@
+use crate::rustfmt_diff::get_modified_lines;
+
+/// Maps the lines of an original file to the lines of the formatted file, e.g. so that tools can
+/// move the diagnostics they hold against the original file onto the formatted file.
+///
+/// The mapping is exact for the lines where items, statements and comments start. Lines within
+/// rewritten code map to the same line relative to the start of the rewritten code, as far as
+/// the rewritten code has as many lines.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineMapping {
+    // Pairs of 1-based lines of the original and of the formatted file, sorted by original line.
+    anchors: Vec<(usize, usize)>,
+}
+
+impl LineMapping {
+    /// Creates a mapping from pairs of corresponding original and formatted lines. If several
+    /// pairs have the same original line, the first one is kept.
+    pub(crate) fn new(mut anchors: Vec<(usize, usize)>) -> LineMapping {
+        anchors.push((1, 1));
+        anchors.sort_by_key(|&(original, _)| original);
+        anchors.dedup_by_key(|&mut (original, _)| original);
+        LineMapping { anchors }
+    }
+
+    /// Creates a mapping from the diff of the original and the formatted text, for formatted text
+    /// which isn't only made of the output of the visitor, e.g. because original lines were kept.
+    pub(crate) fn from_diff(original: &str, formatted: &str) -> LineMapping {
+        let mut anchors = vec![];
+        let (mut original_line, mut formatted_line) = (1, 1);
+        for chunk in get_modified_lines(original, formatted).chunks {
+            let chunk_line = chunk.line_number_orig as usize;
+            // The lines before the chunk are unchanged.
+            anchors.push((original_line, formatted_line));
+            formatted_line += chunk_line - original_line;
+            // The original line of a chunk which only inserts lines comes after them.
+            if chunk.lines_removed > 0 {
+                anchors.push((chunk_line, formatted_line));
+            }
+            original_line = chunk_line + chunk.lines_removed as usize;
+            formatted_line += chunk.lines.len();
+        }
+        anchors.push((original_line, formatted_line));
+        LineMapping::new(anchors)
+    }
+
+    /// Shifts the formatted lines of the original lines from `first` on down by `count` lines,
+    /// because lines were inserted at the start of the formatted file.
+    pub(crate) fn shift_formatted_lines(&mut self, first: usize, count: usize) {
+        for (original, formatted) in &mut self.anchors {
+            if *original >= first {
+                *formatted += count;
+            }
+        }
+    }
+
+    /// Returns the 1-based line of the formatted file corresponding to the 1-based `line` of
+    /// the original file.
+    pub fn formatted_line(&self, line: usize) -> usize {
+        let i = match self
+            .anchors
+            .binary_search_by_key(&line, |&(original, _)| original)
+        {
+            Ok(i) => i,
+            // There is always an anchor for the first line.
+            Err(i) => i.saturating_sub(1),
+        };
+        let (original, formatted) = self.anchors[i];
+        let mapped = formatted + line.saturating_sub(original);
+        match self.anchors.get(i + 1) {
+            // Don't go past the code formatted from the next anchor.
+            Some(&(_, next)) if next > formatted && mapped >= next => next - 1,
+            _ => mapped,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn maps_lines_between_anchors() {
+        // Lines 3-6 were rewritten to lines 3-4, and line 10 moved to line 6.
+        let mapping = LineMapping::new(vec![(3, 3), (7, 5), (10, 6), (3, 4)]);
+        assert_eq!(mapping.formatted_line(1), 1);
+        assert_eq!(mapping.formatted_line(3), 3);
+        assert_eq!(mapping.formatted_line(4), 4);
+        assert_eq!(mapping.formatted_line(6), 4);
+        assert_eq!(mapping.formatted_line(7), 5);
+        assert_eq!(mapping.formatted_line(10), 6);
+        assert_eq!(mapping.formatted_line(12), 8);
+    }
+
+    #[test]
+    fn maps_lines_from_diff() {
+        let original = "a\nb\nc\nd\ne\nf\n";
+        // `b` and `c` were merged, and a line was inserted before `f`.
+        let formatted = "a\nbc\nd\ne\nx\nf\n";
+        let mapping = LineMapping::from_diff(original, formatted);
+        assert_eq!(mapping.formatted_line(1), 1);
+        assert_eq!(mapping.formatted_line(2), 2);
+        assert_eq!(mapping.formatted_line(3), 2);
+        assert_eq!(mapping.formatted_line(4), 3);
+        assert_eq!(mapping.formatted_line(5), 4);
+        assert_eq!(mapping.formatted_line(6), 6);
+    }
+}
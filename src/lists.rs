@@ -31,6 +31,8 @@ pub(crate) struct ListFormatting<'a> {
     nested: bool,
     // Whether comments should be visually aligned.
     align_comments: bool,
+    // The maximum number of items on each line with the "Mixed" list type, if any.
+    items_per_line: Option<usize>,
     config: &'a Config,
 }
 
@@ -46,6 +48,7 @@ impl<'a> ListFormatting<'a> {
             preserve_newline: false,
             nested: false,
             align_comments: true,
+            items_per_line: None,
             config,
         }
     }
@@ -90,6 +93,11 @@ impl<'a> ListFormatting<'a> {
         self
     }
 
+    pub(crate) fn items_per_line(mut self, items_per_line: Option<usize>) -> Self {
+        self.items_per_line = items_per_line;
+        self
+    }
+
     pub(crate) fn needs_trailing_separator(&self) -> bool {
         match self.trailing_separator {
             // We always put separator in front.
@@ -281,6 +289,7 @@ where
     let mut prev_item_is_nested_import = false;
 
     let mut line_len = 0;
+    let mut line_items = 0;
     let indent_str = &formatting.shape.indent.to_string(formatting.config);
     while let Some((i, item)) = iter.next() {
         let item = item.as_ref();
@@ -336,6 +345,7 @@ where
 
                 // 1 is space between separator and item.
                 if (line_len > 0 && line_len + 1 + total_width > formatting.shape.width)
+                    || formatting.items_per_line.map_or(false, |n| line_items >= n)
                     || prev_item_had_post_comment
                     || (formatting.nested
                         && (prev_item_is_nested_import || (!first && inner_item.contains("::"))))
@@ -343,6 +353,7 @@ where
                     result.push('\n');
                     result.push_str(indent_str);
                     line_len = 0;
+                    line_items = 0;
                     if formatting.ends_with_newline {
                         trailing_separator = true;
                     }
@@ -356,6 +367,7 @@ where
                 }
 
                 line_len += total_width;
+                line_items += 1;
             }
             _ => {}
         }
@@ -848,7 +860,7 @@ pub(crate) fn struct_lit_shape(
     prefix_width: usize,
     suffix_width: usize,
 ) -> Option<(Option<Shape>, Shape)> {
-    let v_shape = match context.config.indent_style() {
+    let v_shape = match context.indent_style() {
         IndentStyle::Visual => shape
             .visual_indent(0)
             .shrink_left(prefix_width)?
@@ -877,7 +889,7 @@ pub(crate) fn struct_lit_tactic(
     items: &[ListItem],
 ) -> DefinitiveListTactic {
     if let Some(h_shape) = h_shape {
-        let prelim_tactic = match (context.config.indent_style(), items.len()) {
+        let prelim_tactic = match (context.indent_style(), items.len()) {
             (IndentStyle::Visual, 1) => ListTactic::HorizontalVertical,
             _ if context.config.struct_lit_single_line() => ListTactic::HorizontalVertical,
             _ => ListTactic::Vertical,
@@ -909,8 +921,8 @@ pub(crate) fn struct_lit_formatting<'a>(
     context: &'a RewriteContext<'_>,
     force_no_trailing_comma: bool,
 ) -> ListFormatting<'a> {
-    let ends_with_newline = context.config.indent_style() != IndentStyle::Visual
-        && tactic == DefinitiveListTactic::Vertical;
+    let ends_with_newline =
+        context.indent_style() != IndentStyle::Visual && tactic == DefinitiveListTactic::Vertical;
     ListFormatting {
         tactic,
         separator: ",",
@@ -925,6 +937,7 @@ pub(crate) fn struct_lit_formatting<'a>(
         preserve_newline: true,
         nested: false,
         align_comments: true,
+        items_per_line: None,
         config: context.config,
     }
 }
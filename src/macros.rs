@@ -41,6 +41,24 @@ use crate::visitor::FmtVisitor;
 
 const FORCED_BRACKET_MACROS: &[&str] = &["vec!"];
 
+type SpecialMacroFormatter = fn(&RewriteContext<'_>, &ast::MacCall, &str, Shape) -> Option<String>;
+
+/// Well-known macros whose bodies are not a list of arguments, together with
+/// the function used to format them. The macro is looked up by the last
+/// segment of its path, so `cfg_if::cfg_if!` is handled like `cfg_if!`.
+const SPECIAL_MACRO_FORMATTERS: &[(&str, SpecialMacroFormatter)] = &[
+    ("cfg_if", format_cfg_if),
+    ("lazy_static", format_lazy_static),
+];
+
+fn special_macro_formatter(path: &ast::Path) -> Option<SpecialMacroFormatter> {
+    let name = path.segments.last()?.ident.name;
+    SPECIAL_MACRO_FORMATTERS
+        .iter()
+        .find(|(special_name, _)| &*name.as_str() == *special_name)
+        .map(|&(_, formatter)| formatter)
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) enum MacroPosition {
     Item,
@@ -196,9 +214,11 @@ pub(crate) fn rewrite_macro(
     shape: Shape,
     position: MacroPosition,
 ) -> Option<String> {
-    let should_skip = context
-        .skip_context
-        .skip_macro(&context.snippet(mac.path.span).to_owned());
+    let macro_path = context.snippet(mac.path.span);
+    if context.config.skip_macro_invocations().contains(macro_path) {
+        return Some(context.snippet(mac.span()).to_owned());
+    }
+    let should_skip = context.skip_context.skip_macro(&macro_path.to_owned());
     if should_skip {
         None
     } else {
@@ -284,8 +304,8 @@ fn rewrite_macro_inner(
         };
     }
     // Format well-known macros which cannot be parsed as a valid AST.
-    if macro_name == "lazy_static!" && !has_comment {
-        if let success @ Some(..) = format_lazy_static(context, shape, &ts) {
+    if let Some(format_special_macro) = special_macro_formatter(&mac.path) {
+        if let success @ Some(..) = format_special_macro(context, mac, &macro_name, shape) {
             return success;
         }
     }
@@ -1411,6 +1431,26 @@ impl MacroBranch {
     }
 }
 
+macro_rules! parse_or {
+    ($parser:ident, $method:ident $(,)* $($arg:expr),* $(,)*) => {
+        match $parser.$method($($arg,)*) {
+            Ok(val) => {
+                if $parser.sess.span_diagnostic.has_errors() {
+                    $parser.sess.span_diagnostic.reset_err_count();
+                    return None;
+                } else {
+                    val
+                }
+            }
+            Err(mut err) => {
+                err.cancel();
+                $parser.sess.span_diagnostic.reset_err_count();
+                return None;
+            }
+        }
+    }
+}
+
 /// Format `lazy_static!` from https://crates.io/crates/lazy_static.
 ///
 /// # Expected syntax
@@ -1425,51 +1465,42 @@ impl MacroBranch {
 /// ```
 fn format_lazy_static(
     context: &RewriteContext<'_>,
+    mac: &ast::MacCall,
+    macro_name: &str,
     shape: Shape,
-    ts: &TokenStream,
 ) -> Option<String> {
+    if contains_comment(context.snippet(mac.span())) {
+        return None;
+    }
+
+    let ts = mac.args.inner_tokens();
     let mut result = String::with_capacity(1024);
     let mut parser = new_parser_from_tts(context.parse_sess.inner(), ts.trees().collect());
     let nested_shape = shape
         .block_indent(context.config.tab_spaces())
         .with_max_width(context.config);
 
-    result.push_str("lazy_static! {");
+    result.push_str(macro_name);
+    result.push_str(" {");
     result.push_str(&nested_shape.indent.to_string_with_newline(context.config));
 
-    macro_rules! parse_or {
-        ($method:ident $(,)* $($arg:expr),* $(,)*) => {
-            match parser.$method($($arg,)*) {
-                Ok(val) => {
-                    if parser.sess.span_diagnostic.has_errors() {
-                        parser.sess.span_diagnostic.reset_err_count();
-                        return None;
-                    } else {
-                        val
-                    }
-                }
-                Err(mut err) => {
-                    err.cancel();
-                    parser.sess.span_diagnostic.reset_err_count();
-                    return None;
-                }
-            }
-        }
-    }
-
     while parser.token.kind != TokenKind::Eof {
         // Parse a `lazy_static!` item.
         let vis = crate::utils::format_visibility(
             context,
-            &parse_or!(parse_visibility, rustc_parse::parser::FollowedByType::No),
+            &parse_or!(
+                parser,
+                parse_visibility,
+                rustc_parse::parser::FollowedByType::No
+            ),
         );
         parser.eat_keyword(kw::Static);
         parser.eat_keyword(kw::Ref);
-        let id = parse_or!(parse_ident);
+        let id = parse_or!(parser, parse_ident);
         parser.eat(&TokenKind::Colon);
-        let ty = parse_or!(parse_ty);
+        let ty = parse_or!(parser, parse_ty);
         parser.eat(&TokenKind::Eq);
-        let expr = parse_or!(parse_expr);
+        let expr = parse_or!(parser, parse_expr);
         parser.eat(&TokenKind::Semi);
 
         // Rewrite as a static item.
@@ -1498,6 +1529,94 @@ fn format_lazy_static(
     Some(result)
 }
 
+/// Formats `cfg_if! { if #[cfg(..)] { items } else if #[cfg(..)] { items } else { items } }`
+/// by formatting the items of every branch with `FmtVisitor`.
+fn format_cfg_if(
+    context: &RewriteContext<'_>,
+    mac: &ast::MacCall,
+    macro_name: &str,
+    shape: Shape,
+) -> Option<String> {
+    let ts = mac.args.inner_tokens();
+    let mut parser = new_parser_from_tts(context.parse_sess.inner(), ts.trees().collect());
+    let branch_shape = shape
+        .block_indent(context.config.tab_spaces())
+        .with_max_width(context.config);
+    let item_indent = branch_shape.indent.block_indent(context.config);
+
+    let mut result = String::with_capacity(1024);
+    result.push_str(macro_name);
+    result.push_str(" {");
+    result.push_str(&branch_shape.indent.to_string_with_newline(context.config));
+
+    // Comments outside of the branch bodies have no place in the rewritten
+    // output, so leave the macro as is if there are any.
+    let mut last_hi = context.snippet_provider.span_after(mac.span(), "{");
+    let mut is_first_branch = true;
+    let mut has_cfg = true;
+    while parser.token.kind != TokenKind::Eof {
+        if !is_first_branch {
+            if !parser.eat_keyword(kw::Else) {
+                return None;
+            }
+            result.push_str(" else ");
+            has_cfg = parser.token.is_keyword(kw::If);
+        }
+        if has_cfg {
+            if !parser.eat_keyword(kw::If) {
+                return None;
+            }
+            let attr = parse_or!(parser, parse_attribute, false);
+            result.push_str("if ");
+            result.push_str(&attr.rewrite(context, branch_shape.offset_left(3)?)?);
+            result.push(' ');
+        }
+
+        let open_brace = parser.token.span;
+        if !parser.eat(&TokenKind::OpenDelim(DelimToken::Brace))
+            || contains_comment(context.snippet(mk_sp(last_hi, open_brace.lo())))
+        {
+            return None;
+        }
+
+        let mut visitor = FmtVisitor::from_context(context);
+        visitor.block_indent = item_indent;
+        visitor.last_pos = open_brace.hi();
+        while parser.token != TokenKind::CloseDelim(DelimToken::Brace)
+            && parser.token.kind != TokenKind::Eof
+        {
+            let item = parse_or!(parser, parse_item)?;
+            visitor.visit_item(&item);
+        }
+
+        let close_brace = parser.token.span;
+        if !parser.eat(&TokenKind::CloseDelim(DelimToken::Brace))
+            || visitor.macro_rewrite_failure
+            || contains_comment(context.snippet(mk_sp(visitor.last_pos, close_brace.lo())))
+        {
+            return None;
+        }
+
+        result.push('{');
+        if !visitor.buffer.trim().is_empty() {
+            result.push_str(&item_indent.to_string_with_newline(context.config));
+            result.push_str(visitor.buffer.trim());
+        }
+        result.push_str(&branch_shape.indent.to_string_with_newline(context.config));
+        result.push('}');
+        last_hi = close_brace.hi();
+        is_first_branch = false;
+    }
+
+    if contains_comment(context.snippet(mk_sp(last_hi, mac.span().hi()))) {
+        return None;
+    }
+    result.push_str(&shape.indent.to_string_with_newline(context.config));
+    result.push('}');
+
+    Some(result)
+}
+
 fn rewrite_macro_with_items(
     context: &RewriteContext<'_>,
     items: &[MacroArg],
@@ -7,12 +7,15 @@ use rustc_span::{BytePos, Span};
 
 use crate::comment::{combine_strs_with_missing_comments, rewrite_comment};
 use crate::config::lists::*;
-use crate::config::{Config, ControlBraceStyle, IndentStyle, MatchArmLeadingPipe, Version};
+use crate::config::{
+    Config, ControlBraceStyle, IndentStyle, MatchArmLeadingPipe, MatchArmWrapping, Version,
+};
 use crate::expr::{
     format_expr, is_empty_block, is_simple_block, is_unsafe_block, prefer_next_line, rewrite_cond,
     ExprType, RhsTactics,
 };
 use crate::lists::{itemize_list, write_list, ListFormatting};
+use crate::patterns::rewrite_top_level_pattern;
 use crate::rewrite::{Rewrite, RewriteContext};
 use crate::shape::Shape;
 use crate::source_map::SpanUtils;
@@ -79,12 +82,14 @@ pub(crate) fn rewrite_match(
         width: context.budget(shape.used_width()),
         ..shape
     };
+    let indent_style = context.override_indent_style(context.config.control_flow_indent_style());
     // 6 = `match `
-    let cond_shape = match context.config.indent_style() {
+    let cond_shape = match context.indent_style() {
         IndentStyle::Visual => cond_shape.shrink_left(6)?,
         IndentStyle::Block => cond_shape.offset_left(6)?,
     };
     let cond_str = cond.rewrite(context, cond_shape)?;
+    drop(indent_style);
     let alt_block_sep = &shape.indent.to_string_with_newline(context.config);
     let block_sep = match context.config.control_brace_style() {
         ControlBraceStyle::AlwaysNextLine => alt_block_sep,
@@ -250,7 +255,7 @@ fn rewrite_match_arm(
     // Patterns
     // 5 = ` => {`
     let pat_shape = shape.sub_width(5)?.offset_left(pipe_offset)?;
-    let pats_str = arm.pat.rewrite(context, pat_shape)?;
+    let pats_str = rewrite_top_level_pattern(context, &arm.pat, pat_shape)?;
 
     // Guard
     let block_like_pat = trimmed_last_line_width(&pats_str) <= context.config.tab_spaces();
@@ -311,6 +316,10 @@ fn flatten_arm_body<'a>(
     let can_extend =
         |expr| !context.config.force_multiline_blocks() && can_flatten_block_around_this(expr);
 
+    if context.config.match_arm_wrapping() == MatchArmWrapping::Preserve {
+        return (can_extend(body), body);
+    }
+
     if let Some(ref block) = block_can_be_flattened(context, body) {
         if let ast::StmtKind::Expr(ref expr) = block.stmts[0].kind {
             if let ast::ExprKind::Block(..) = expr.kind {
@@ -405,26 +414,30 @@ fn rewrite_match_body(
         }
 
         let indent_str = shape.indent.to_string_with_newline(context.config);
-        let (body_prefix, body_suffix) =
-            if context.config.match_arm_blocks() && !context.inside_macro() {
-                let comma = if context.config.match_block_trailing_comma() {
-                    ","
+        let wrap_in_block = match context.config.match_arm_wrapping() {
+            MatchArmWrapping::Default => context.config.match_arm_blocks(),
+            MatchArmWrapping::Always => true,
+            MatchArmWrapping::Never | MatchArmWrapping::Preserve => false,
+        };
+        let (body_prefix, body_suffix) = if wrap_in_block && !context.inside_macro() {
+            let comma = if context.config.match_block_trailing_comma() {
+                ","
+            } else {
+                ""
+            };
+            let semicolon = if context.config.version() == Version::One {
+                ""
+            } else {
+                if semicolon_for_expr(context, body) {
+                    ";"
                 } else {
                     ""
-                };
-                let semicolon = if context.config.version() == Version::One {
-                    ""
-                } else {
-                    if semicolon_for_expr(context, body) {
-                        ";"
-                    } else {
-                        ""
-                    }
-                };
-                ("{", format!("{}{}}}{}", semicolon, indent_str, comma))
-            } else {
-                ("", String::from(","))
+                }
             };
+            ("{", format!("{}{}}}{}", semicolon, indent_str, comma))
+        } else {
+            ("", String::from(","))
+        };
 
         let block_sep = match context.config.control_brace_style() {
             ControlBraceStyle::AlwaysNextLine => format!("{}{}", alt_block_sep, body_prefix),
@@ -481,7 +494,14 @@ fn rewrite_match_body(
         format_expr(body, ExprType::Statement, context, next_line_body_shape),
         next_line_body_shape.width,
     );
+    let wrap_multi_line_body =
+        context.config.match_arm_wrapping() == MatchArmWrapping::Always && !is_block;
     match (orig_body, next_line_body) {
+        (Some(ref orig_str), Some(ref next_line_str))
+            if wrap_multi_line_body && orig_str.contains('\n') =>
+        {
+            combine_next_line_body(next_line_str)
+        }
         (Some(ref orig_str), Some(ref next_line_str))
             if prefer_next_line(orig_str, next_line_str, RhsTactics::Default) =>
         {
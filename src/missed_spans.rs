@@ -1,13 +1,18 @@
+use std::borrow::Cow;
+
 use rustc_span::{BytePos, Pos, Span};
 
-use crate::comment::{is_last_comment_block, rewrite_comment, CodeCharKind, CommentCodeSlices};
+use crate::comment::{
+    is_last_comment_block, rewrite_comment, CharClasses, CodeCharKind, CommentCodeSlices,
+    FullCodeCharKind,
+};
 use crate::config::file_lines::FileLines;
-use crate::config::FileName;
 use crate::config::Version;
+use crate::config::{Config, FileName};
 use crate::coverage::transform_missing_snippet;
 use crate::shape::{Indent, Shape};
 use crate::source_map::LineRangeUtils;
-use crate::utils::{count_lf_crlf, count_newlines, last_line_width, mk_sp};
+use crate::utils::{count_lf_crlf, count_newlines, mk_sp, unicode_str_width};
 use crate::visitor::FmtVisitor;
 
 struct SnippetStatus {
@@ -73,6 +78,16 @@ impl<'a> FmtVisitor<'a> {
         &mut self,
         end: BytePos,
         process_last_snippet: F,
+    ) {
+        self.write_missing(end, process_last_snippet);
+        // The code starting at `end` is written from here on.
+        self.record_line_anchor_at(end);
+    }
+
+    fn write_missing<F: Fn(&mut FmtVisitor<'_>, &str, &str)>(
+        &mut self,
+        end: BytePos,
+        process_last_snippet: F,
     ) {
         let start = self.last_pos;
 
@@ -182,7 +197,11 @@ impl<'a> FmtVisitor<'a> {
                 );
                 (lf_count, crlf_count, within_file_lines_range)
             };
-        for (kind, offset, subslice) in CommentCodeSlices::new(snippet) {
+        let mut slices = CommentCodeSlices::new(snippet).peekable();
+        // Whether the last slice is a comment on a line of its own. A comment following code on
+        // its line, or starting the snippet, e.g. the header of a file, is not.
+        let mut follows_own_line_comment = false;
+        while let Some((kind, offset, subslice)) = slices.next() {
             debug!("{:?}: {:?}", kind, subslice);
 
             let (lf_count, crlf_count, within_file_lines_range) =
@@ -199,13 +218,30 @@ impl<'a> FmtVisitor<'a> {
                 );
             } else if subslice.trim().is_empty() && newline_count > 0 && within_file_lines_range {
                 // 2: blank lines.
-                self.push_vertical_spaces(newline_count);
+                let precedes_code = slices
+                    .peek()
+                    .map_or(true, |&(next_kind, ..)| next_kind != CodeCharKind::Comment);
+                if self.config.remove_blank_lines_after_comments()
+                    && follows_own_line_comment
+                    && precedes_code
+                {
+                    // Keep the comment attached to the code it documents.
+                    if !self.buffer.ends_with('\n') {
+                        self.push_str("\n");
+                    }
+                } else {
+                    self.push_vertical_spaces(newline_count);
+                }
                 status.cur_line += newline_count;
                 status.line_start = offset + lf_count + crlf_count * 2;
             } else {
                 // 3: code which we failed to format or which is not within file-lines range.
                 self.process_missing_code(&mut status, snippet, subslice, offset, file_name);
             }
+            follows_own_line_comment = kind == CodeCharKind::Comment
+                && snippet[..offset]
+                    .rfind('\n')
+                    .map_or(false, |i| snippet[i + 1..offset].trim().is_empty());
         }
 
         let last_snippet = &snippet[status.line_start..];
@@ -252,9 +288,12 @@ impl<'a> FmtVisitor<'a> {
             self.block_indent
         } else {
             self.push_str(" ");
-            Indent::from_width(self.config, last_line_width(&self.buffer))
+            let last_line = self.buffer.rsplitn(2, '\n').next().unwrap_or("");
+            Indent::end_of_line(self.config, last_line)
         };
 
+        self.record_line_anchor(status.cur_line);
+
         let comment_width = ::std::cmp::min(
             self.config.comment_width(),
             self.config.max_width() - self.block_indent.width(),
@@ -344,6 +383,7 @@ impl<'a> FmtVisitor<'a> {
 
                 status.cur_line += 1;
                 status.line_start = i + 1;
+                self.record_line_anchor(status.cur_line);
             } else if c.is_whitespace() && status.last_wspace.is_none() {
                 status.last_wspace = Some(i);
             } else {
@@ -358,3 +398,103 @@ impl<'a> FmtVisitor<'a> {
         }
     }
 }
+
+impl<'a> FmtVisitor<'a> {
+    /// Aligns the trailing `//` comments of consecutive lines to a common column, as long as the
+    /// widths of the code before them differ by at most `trailing_comment_align_threshold` and
+    /// the aligned comments fit within `max_width`.
+    pub(crate) fn align_trailing_comments(&mut self) {
+        if self.config.trailing_comment_align_threshold() > 0 && self.config.file_lines().is_all() {
+            self.buffer =
+                align_trailing_comments(&self.buffer, &self.skipped_range.borrow(), self.config);
+        }
+    }
+}
+
+fn align_trailing_comments(
+    text: &str,
+    skipped_range: &[(usize, usize)],
+    config: &Config,
+) -> String {
+    let threshold = config.trailing_comment_align_threshold();
+    let tab_spaces = config.tab_spaces();
+    let lines: Vec<&str> = text.split('\n').collect();
+    // The start of the trailing comment of each line, with the width of the code before it.
+    let comments: Vec<Option<(usize, usize)>> = trailing_comment_starts(text)
+        .into_iter()
+        .enumerate()
+        .map(|(i, start)| {
+            let start = start?;
+            let line = i + 1;
+            if skipped_range
+                .iter()
+                .any(|&(lo, hi)| lo <= line && line <= hi)
+            {
+                return None;
+            }
+            let code = lines[i][..start].trim_end();
+            let width = unicode_str_width(code) + code.matches('\t').count() * tab_spaces;
+            Some((start, width))
+        })
+        .collect();
+
+    let mut aligned: Vec<Cow<'_, str>> = lines.iter().map(|&line| Cow::from(line)).collect();
+    let mut i = 0;
+    while i < lines.len() {
+        // Find the longest run of lines from `i` on whose comments can be aligned.
+        let mut run = vec![];
+        let (mut min, mut max) = (usize::max_value(), 0);
+        while let Some(&Some((start, width))) = comments.get(i + run.len()) {
+            if max.max(width) - min.min(width) > threshold {
+                break;
+            }
+            min = min.min(width);
+            max = max.max(width);
+            run.push((i + run.len(), start, width));
+        }
+        if run.len() < 2 {
+            i += 1;
+            continue;
+        }
+        i += run.len();
+
+        let fits = run.iter().all(|&(j, start, _)| {
+            max + 1 + unicode_str_width(&lines[j][start..]) <= config.max_width()
+        });
+        if !fits {
+            continue;
+        }
+        for (j, start, width) in run {
+            let code = lines[j][..start].trim_end();
+            let padding = " ".repeat(max + 1 - width);
+            aligned[j] = Cow::from(format!("{}{}{}", code, padding, &lines[j][start..]));
+        }
+    }
+
+    aligned.join("\n")
+}
+
+// Returns, for every line of `text`, the byte offset within the line of the `//` comment which
+// follows code on that line, if any.
+fn trailing_comment_starts(text: &str) -> Vec<Option<usize>> {
+    let mut starts = vec![];
+    let (mut line_start, mut has_code, mut start) = (0, false, None);
+    for (kind, (i, c)) in CharClasses::new(text.char_indices()) {
+        match kind {
+            _ if c == '\n' => {
+                starts.push(start.take());
+                line_start = i + 1;
+                has_code = false;
+            }
+            FullCodeCharKind::StartComment => {
+                if has_code && start.is_none() && text[i + 1..].starts_with('/') {
+                    start = Some(i - line_start);
+                }
+            }
+            _ if !kind.is_comment() && !c.is_whitespace() => has_code = true,
+            _ => (),
+        }
+    }
+    starts.push(start);
+    starts
+}
@@ -1,15 +1,18 @@
 use std::borrow::Cow;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
 use std::path::{Path, PathBuf};
 
 use rustc_ast::ast;
 use rustc_ast::attr::HasAttrs;
+use rustc_ast::token::{self, Token, TokenKind};
+use rustc_ast::tokenstream::TokenTree;
 use rustc_ast::visit::Visitor;
 use rustc_span::symbol::{self, sym, Symbol};
 use thiserror::Error;
 
 use crate::attr::MetaVisitor;
-use crate::config::FileName;
+use crate::config::{Config, FileName, ModuleTraversal, SymlinkPolicy};
 use crate::items::is_mod_decl;
 use crate::syntux::parser::{
     Directory, DirectoryOwnership, ModulePathSuccess, Parser, ParserError,
@@ -23,6 +26,7 @@ type FileModMap<'ast> = BTreeMap<FileName, Module<'ast>>;
 
 lazy_static! {
     static ref CFG_IF: Symbol = Symbol::intern("cfg_if");
+    static ref INCLUDE: Symbol = Symbol::intern("include");
 }
 
 /// Represents module with its inner attributes.
@@ -61,12 +65,47 @@ impl<'a> AsRef<ast::Mod> for Module<'a> {
     }
 }
 
+impl ModuleTraversal {
+    /// Returns the traversal used for `main_file`, the file of an input.
+    ///
+    /// This is the `module_traversal` option, except that `skip_children` restricts it to
+    /// `FormatRoots`, as does input from stdin, whose modules cannot be found relative to a
+    /// file.
+    pub(crate) fn for_input(config: &Config, main_file: &FileName) -> ModuleTraversal {
+        match main_file {
+            FileName::Stdin => ModuleTraversal::FormatRoots,
+            _ if config.skip_children() => ModuleTraversal::FormatRoots,
+            _ => config.module_traversal(),
+        }
+    }
+
+    /// Whether the out of line modules of the input file are looked for and parsed.
+    pub(crate) fn resolves_modules(self) -> bool {
+        self != ModuleTraversal::FormatRoots
+    }
+
+    /// Whether `path`, a file of the input `main_file`, is formatted.
+    pub(crate) fn formats(self, path: &FileName, main_file: &FileName) -> bool {
+        match self {
+            ModuleTraversal::FormatReachable => true,
+            ModuleTraversal::FormatRoots | ModuleTraversal::FormatExplicitOnly => path == main_file,
+        }
+    }
+}
+
 /// Maps each module to the corresponding file.
 pub(crate) struct ModResolver<'ast, 'sess> {
     parse_sess: &'sess ParseSess,
     directory: Directory,
     file_map: FileModMap<'ast>,
-    recursive: bool,
+    traversal: ModuleTraversal,
+    include_files: bool,
+    symlinks: SymlinkPolicy,
+    // The directory of the root file, below which symbolic links are looked for.
+    root_directory: PathBuf,
+    // The canonical paths of the files in `file_map`, so that a file reached through several
+    // paths is only formatted once.
+    canonical_paths: HashSet<PathBuf>,
 }
 
 /// Represents errors while trying to resolve modules.
@@ -102,7 +141,9 @@ impl<'ast, 'sess, 'c> ModResolver<'ast, 'sess> {
     pub(crate) fn new(
         parse_sess: &'sess ParseSess,
         directory_ownership: DirectoryOwnership,
-        recursive: bool,
+        traversal: ModuleTraversal,
+        include_files: bool,
+        symlinks: SymlinkPolicy,
     ) -> Self {
         ModResolver {
             directory: Directory {
@@ -111,7 +152,11 @@ impl<'ast, 'sess, 'c> ModResolver<'ast, 'sess> {
             },
             file_map: BTreeMap::new(),
             parse_sess,
-            recursive,
+            traversal,
+            include_files,
+            symlinks,
+            root_directory: PathBuf::new(),
+            canonical_paths: HashSet::new(),
         }
     }
 
@@ -125,9 +170,13 @@ impl<'ast, 'sess, 'c> ModResolver<'ast, 'sess> {
             FileName::Real(ref p) => p.parent().unwrap_or(Path::new("")).to_path_buf(),
             _ => PathBuf::new(),
         };
+        self.root_directory = self.directory.path.clone();
+        // A module which turns out to be the root file is formatted as the root.
+        if let FileName::Real(ref path) = root_filename {
+            self.canonical_paths.insert(canonical_path(path));
+        }
 
-        // Skip visiting sub modules when the input is from stdin.
-        if self.recursive {
+        if self.traversal.resolves_modules() {
             self.visit_mod_from_ast(&krate.module)?;
         }
 
@@ -161,6 +210,10 @@ impl<'ast, 'sess, 'c> ModResolver<'ast, 'sess> {
                 continue;
             }
 
+            if self.include_files {
+                self.visit_include(&item)?;
+            }
+
             if let ast::ItemKind::Mod(ref sub_mod) = item.kind {
                 self.visit_sub_mod(&item, Module::new(Cow::Owned(sub_mod.clone()), &item.attrs))?;
             }
@@ -175,6 +228,10 @@ impl<'ast, 'sess, 'c> ModResolver<'ast, 'sess> {
                 self.visit_cfg_if(Cow::Borrowed(item))?;
             }
 
+            if self.include_files {
+                self.visit_include(item)?;
+            }
+
             if let ast::ItemKind::Mod(ref sub_mod) = item.kind {
                 self.visit_sub_mod(item, Module::new(Cow::Borrowed(sub_mod), &item.attrs))?;
             }
@@ -182,6 +239,39 @@ impl<'ast, 'sess, 'c> ModResolver<'ast, 'sess> {
         Ok(())
     }
 
+    /// Visit the file included by an `include!("...")` item, which is parsed as a module.
+    fn visit_include(&mut self, item: &ast::Item) -> Result<(), ModuleResolutionError> {
+        let included_path = match include_path(item) {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        // The path is relative to the file containing the `include!` call.
+        let path = match self.parse_sess.span_to_filename(item.span) {
+            FileName::Real(ref file) => file.parent().unwrap_or(Path::new("")).join(included_path),
+            FileName::Stdin => return Ok(()),
+        };
+        if self.parse_sess.is_file_parsed(&path) {
+            return Ok(());
+        }
+
+        let module = format!("include!(\"{}\")", path.display());
+        match Parser::parse_file_as_module(self.parse_sess, &path, item.span) {
+            Ok((_, ref attrs)) if contains_skip(attrs) => Ok(()),
+            Ok((ast_mod, attrs)) => {
+                self.insert_file(path, Module::new(Cow::Owned(ast_mod.clone()), &attrs));
+                self.visit_mod_outside_ast(ast_mod)
+            }
+            Err(ParserError::ParseError) => Err(ModuleResolutionError {
+                module,
+                kind: ModuleResolutionErrorKind::ParseError { file: path },
+            }),
+            Err(..) => Err(ModuleResolutionError {
+                module,
+                kind: ModuleResolutionErrorKind::NotFound { file: path },
+            }),
+        }
+    }
+
     fn visit_sub_mod(
         &mut self,
         item: &'c ast::Item,
@@ -222,16 +312,10 @@ impl<'ast, 'sess, 'c> ModResolver<'ast, 'sess> {
         sub_mod_kind: SubModKind<'c, 'ast>,
     ) -> Result<(), ModuleResolutionError> {
         match sub_mod_kind {
-            SubModKind::External(mod_path, _, sub_mod) => {
-                self.file_map
-                    .entry(FileName::Real(mod_path))
-                    .or_insert(sub_mod);
-            }
+            SubModKind::External(mod_path, _, sub_mod) => self.insert_file(mod_path, sub_mod),
             SubModKind::MultiExternal(mods) => {
                 for (mod_path, _, sub_mod) in mods {
-                    self.file_map
-                        .entry(FileName::Real(mod_path))
-                        .or_insert(sub_mod);
+                    self.insert_file(mod_path, sub_mod);
                 }
             }
             _ => (),
@@ -239,6 +323,17 @@ impl<'ast, 'sess, 'c> ModResolver<'ast, 'sess> {
         Ok(())
     }
 
+    /// Adds the file at `path` to the files to format, unless it is already one of them, possibly
+    /// under another path, or it is reached through a symbolic link which isn't followed.
+    fn insert_file(&mut self, path: PathBuf, module: Module<'ast>) {
+        if self.symlinks == SymlinkPolicy::Skip && is_symlinked(&path, &self.root_directory) {
+            return;
+        }
+        if self.canonical_paths.insert(canonical_path(&path)) {
+            self.file_map.entry(FileName::Real(path)).or_insert(module);
+        }
+    }
+
     fn visit_sub_mod_inner(
         &mut self,
         sub_mod: Module<'ast>,
@@ -476,6 +571,41 @@ fn find_path_value(attrs: &[ast::Attribute]) -> Option<Symbol> {
     attrs.iter().flat_map(path_value).next()
 }
 
+/// Returns the path of an `include!("...")` item, if it is a literal.
+fn include_path(item: &ast::Item) -> Option<PathBuf> {
+    let mac = match item.kind {
+        ast::ItemKind::MacCall(ref mac) => mac,
+        _ => return None,
+    };
+    if mac.path.segments.len() != 1 || mac.path.segments[0].ident.name != *INCLUDE {
+        return None;
+    }
+
+    let mut trees = mac.args.inner_tokens().trees();
+    let path = match trees.next() {
+        Some(TokenTree::Token(Token {
+            kind: TokenKind::Literal(lit),
+            ..
+        })) => match lit.kind {
+            token::LitKind::Str | token::LitKind::StrRaw(_) => lit.symbol,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    // Allow a trailing comma.
+    match (trees.next(), trees.next()) {
+        (None, _)
+        | (
+            Some(TokenTree::Token(Token {
+                kind: TokenKind::Comma,
+                ..
+            })),
+            None,
+        ) => Some(PathBuf::from(&*path.as_str())),
+        _ => None,
+    }
+}
+
 fn is_cfg_if(item: &ast::Item) -> bool {
     match item.kind {
         ast::ItemKind::MacCall(ref mac) => {
@@ -489,3 +619,23 @@ fn is_cfg_if(item: &ast::Item) -> bool {
         _ => false,
     }
 }
+
+/// Returns `path` with `..` and symbolic links resolved, to find the files which are reached
+/// through several paths.
+pub(crate) fn canonical_path(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Whether `path` is a symbolic link or is in a symlinked directory below `root_directory`.
+fn is_symlinked(path: &Path, root_directory: &Path) -> bool {
+    let is_symlink = |path: &Path| {
+        fs::symlink_metadata(path).map_or(false, |metadata| metadata.file_type().is_symlink())
+    };
+    match path.strip_prefix(root_directory) {
+        Ok(relative) => relative
+            .ancestors()
+            .filter(|ancestor| !ancestor.as_os_str().is_empty())
+            .any(|ancestor| is_symlink(&root_directory.join(ancestor))),
+        Err(_) => is_symlink(path),
+    }
+}
@@ -23,8 +23,11 @@ use crate::rewrite::{Rewrite, RewriteContext};
 use crate::shape::Shape;
 use crate::source_map::SpanUtils;
 use crate::spanned::Spanned;
+use crate::trace;
 use crate::types::{can_be_overflowed_type, SegmentParam};
-use crate::utils::{count_newlines, extra_offset, first_line_width, last_line_width, mk_sp};
+use crate::utils::{
+    count_newlines, extra_offset, first_line_width, last_line_width, mk_sp, spaces_within_delims,
+};
 
 const SHORT_ITEM_THRESHOLD: usize = 10;
 
@@ -73,6 +76,7 @@ pub(crate) enum OverflowableItem<'a> {
     GenericParam(&'a ast::GenericParam),
     MacroArg(&'a MacroArg),
     NestedMetaItem(&'a ast::NestedMetaItem),
+    Pat(&'a ast::Pat),
     SegmentParam(&'a SegmentParam<'a>),
     StructField(&'a ast::StructField),
     TuplePatField(&'a TuplePatField<'a>),
@@ -112,6 +116,7 @@ impl<'a> OverflowableItem<'a> {
             OverflowableItem::GenericParam(gp) => f(*gp),
             OverflowableItem::MacroArg(macro_arg) => f(*macro_arg),
             OverflowableItem::NestedMetaItem(nmi) => f(*nmi),
+            OverflowableItem::Pat(pat) => f(*pat),
             OverflowableItem::SegmentParam(sp) => f(*sp),
             OverflowableItem::StructField(sf) => f(*sf),
             OverflowableItem::TuplePatField(pat) => f(*pat),
@@ -238,7 +243,7 @@ macro_rules! impl_into_overflowable_item_for_rustfmt_types {
     }
 }
 
-impl_into_overflowable_item_for_ast_node!(Expr, GenericParam, NestedMetaItem, StructField, Ty);
+impl_into_overflowable_item_for_ast_node!(Expr, GenericParam, NestedMetaItem, Pat, StructField, Ty);
 impl_into_overflowable_item_for_rustfmt_types!([MacroArg], [SegmentParam, TuplePatField]);
 
 pub(crate) fn into_overflowable_list<'a, T>(
@@ -338,6 +343,8 @@ struct Context<'a> {
     one_line_width: usize,
     force_separator_tactic: Option<SeparatorTactic>,
     custom_delims: Option<(&'a str, &'a str)>,
+    // Whether a single-line list gets spaces just inside its delimiters.
+    spaces_within: bool,
 }
 
 impl<'a> Context<'a> {
@@ -353,16 +360,23 @@ impl<'a> Context<'a> {
         force_separator_tactic: Option<SeparatorTactic>,
         custom_delims: Option<(&'a str, &'a str)>,
     ) -> Context<'a> {
+        let (lhs, _) = custom_delims.unwrap_or((prefix, suffix));
+        let spaces_within = spaces_within_delims(context.config, lhs);
+        // 1 = "(" or ")", 2 = "( " or " )"
+        let delim_width = if spaces_within { 2 } else { 1 };
         let used_width = extra_offset(ident, shape);
-        // 1 = `()`
-        let one_line_width = shape.width.saturating_sub(used_width + 2);
+        let one_line_width = shape.width.saturating_sub(used_width + 2 * delim_width);
 
-        // 1 = "(" or ")"
         let one_line_shape = shape
-            .offset_left(last_line_width(ident) + 1)
-            .and_then(|shape| shape.sub_width(1))
+            .offset_left(last_line_width(ident) + delim_width)
+            .and_then(|shape| shape.sub_width(delim_width))
             .unwrap_or(Shape { width: 0, ..shape });
-        let nested_shape = shape_from_indent_style(context, shape, used_width + 2, used_width + 1);
+        let nested_shape = shape_from_indent_style(
+            context,
+            shape,
+            used_width + 2 * delim_width,
+            used_width + delim_width,
+        );
         Context {
             context,
             items: into_overflowable_list(items).collect(),
@@ -376,6 +390,7 @@ impl<'a> Context<'a> {
             one_line_width,
             force_separator_tactic,
             custom_delims,
+            spaces_within,
         }
     }
 
@@ -608,6 +623,17 @@ impl<'a> Context<'a> {
         // indentation. If its first line fits on one line with the other arguments,
         // we format the function arguments horizontally.
         let tactic = self.try_overflow_last_item(&mut list_items);
+        if trace::is_tracing() {
+            let construct = format!("list in {}{}", self.prefix, self.suffix);
+            let tactic = format!("{:?}", tactic);
+            trace::trace_decision(
+                self.context.parse_sess,
+                self.span,
+                &construct,
+                &tactic,
+                self.nested_shape,
+            );
+        }
         let trailing_separator = if let Some(tactic) = self.force_separator_tactic {
             tactic
         } else if !self.context.use_block_indent() {
@@ -622,10 +648,17 @@ impl<'a> Context<'a> {
             _ => false,
         };
 
+        // Lay out tables, e.g. of bytes, with a fixed number of elements per line.
+        let items_per_line = match self.context.config.array_element_per_line_threshold() {
+            0 => None,
+            n if self.custom_delims == Some(("[", "]")) => Some(n),
+            _ => None,
+        };
         let fmt = ListFormatting::new(self.nested_shape, self.context.config)
             .tactic(tactic)
             .trailing_separator(trailing_separator)
-            .ends_with_newline(ends_with_newline);
+            .ends_with_newline(ends_with_newline)
+            .items_per_line(items_per_line);
 
         write_list(&list_items, &fmt)
             .map(|items_str| (tactic == DefinitiveListTactic::Horizontal, items_str))
@@ -642,8 +675,11 @@ impl<'a> Context<'a> {
             _ => (self.prefix, self.suffix),
         };
 
+        let spaces_within = self.spaces_within && !items_str.is_empty();
         let extend_width = if items_str.is_empty() {
             2
+        } else if spaces_within {
+            first_line_width(items_str) + 2
         } else {
             first_line_width(items_str) + 1
         };
@@ -670,7 +706,13 @@ impl<'a> Context<'a> {
                 || (is_extendable && extend_width <= shape.width)
         };
         if force_single_line {
-            result.push_str(items_str);
+            if spaces_within {
+                result.push(' ');
+                result.push_str(items_str);
+                result.push(' ');
+            } else {
+                result.push_str(items_str);
+            }
         } else {
             if !items_str.is_empty() {
                 result.push_str(&nested_indent_str);
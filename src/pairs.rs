@@ -4,6 +4,7 @@ use crate::config::lists::*;
 use crate::config::IndentStyle;
 use crate::rewrite::{Rewrite, RewriteContext};
 use crate::shape::Shape;
+use crate::spacing::Spacing;
 use crate::utils::{
     first_line_width, is_single_line, last_line_width, trimmed_last_line_width, wrap_str,
 };
@@ -52,6 +53,7 @@ fn rewrite_pairs_one_line<T: Rewrite>(
 
     let mut result = String::new();
     let base_shape = shape.block();
+    let spacing = Spacing::new(context.config);
 
     for ((_, rewrite), s) in list.list.iter().zip(list.separators.iter()) {
         if let Some(rewrite) = rewrite {
@@ -60,9 +62,7 @@ fn rewrite_pairs_one_line<T: Rewrite>(
             }
 
             result.push_str(&rewrite);
-            result.push(' ');
-            result.push_str(s);
-            result.push(' ');
+            result.push_str(&spacing.binop(s));
         } else {
             return None;
         }
@@ -95,7 +95,7 @@ fn rewrite_pairs_multiline<T: Rewrite>(
     context: &RewriteContext<'_>,
 ) -> Option<String> {
     let rhs_offset = shape.rhs_overhead(&context.config);
-    let nested_shape = (match context.config.indent_style() {
+    let nested_shape = (match context.indent_style() {
         IndentStyle::Visual => shape.visual_indent(0),
         IndentStyle::Block => shape.block_indent(context.config.tab_spaces()),
     })
@@ -206,7 +206,7 @@ where
 
     // We have to use multiple lines.
     // Re-evaluate the rhs because we have more space now:
-    let mut rhs_shape = match context.config.indent_style() {
+    let mut rhs_shape = match context.indent_style() {
         IndentStyle::Visual => shape
             .sub_width(pp.suffix.len() + pp.prefix.len())?
             .visual_indent(pp.prefix.len()),
@@ -265,7 +265,7 @@ impl FlattenPair for ast::Expr {
             }
             let nested_overhead = sep + 1;
             let rhs_offset = shape.rhs_overhead(&context.config);
-            let nested_shape = (match context.config.indent_style() {
+            let nested_shape = (match context.indent_style() {
                 IndentStyle::Visual => shape.visual_indent(0),
                 IndentStyle::Block => shape.block_indent(context.config.tab_spaces()),
             })
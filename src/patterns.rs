@@ -4,6 +4,7 @@ use rustc_span::{BytePos, Span};
 
 use crate::comment::{combine_strs_with_missing_comments, FindUncommented};
 use crate::config::lists::*;
+use crate::config::{MatchArmLeadingPipe, Version};
 use crate::expr::{can_be_overflowed_expr, rewrite_unary_prefix, wrap_struct_field};
 use crate::lists::{
     definitive_tactic, itemize_list, shape_for_tactic, struct_lit_formatting, struct_lit_shape,
@@ -15,9 +16,12 @@ use crate::pairs::{rewrite_pair, PairParts};
 use crate::rewrite::{Rewrite, RewriteContext};
 use crate::shape::Shape;
 use crate::source_map::SpanUtils;
+use crate::spacing::Spacing;
 use crate::spanned::Spanned;
 use crate::types::{rewrite_path, PathContext};
-use crate::utils::{format_mutability, mk_sp, rewrite_ident};
+use crate::utils::{
+    format_mutability, last_line_contains_single_line_comment, mk_sp, rewrite_ident,
+};
 
 /// Returns `true` if the given pattern is "short".
 /// A short pattern is defined by the following grammar:
@@ -55,6 +59,81 @@ fn is_short_pattern_inner(pat: &ast::Pat) -> bool {
     }
 }
 
+/// Rewrites the pattern of a match arm or of a `let`, `if let` or `while let`. Unlike nested
+/// or-patterns with `version = Two`, the alternatives of a top-level or-pattern don't get a
+/// leading `|`: match arms take care of their own, and `let` never has one.
+pub(crate) fn rewrite_top_level_pattern(
+    context: &RewriteContext<'_>,
+    pat: &Pat,
+    shape: Shape,
+) -> Option<String> {
+    match pat.kind {
+        PatKind::Or(ref pats) => rewrite_or_pattern(context, pat, pats, shape, false),
+        _ => pat.rewrite(context, shape),
+    }
+}
+
+// Rewrites the alternatives of an or-pattern. When they don't fit on a single line, the first
+// alternative gets a leading `|` as well if `match_arm_leading_pipes` asks for one.
+fn rewrite_or_pattern(
+    context: &RewriteContext<'_>,
+    pat: &Pat,
+    pats: &[ptr::P<Pat>],
+    shape: Shape,
+    allow_leading_pipe: bool,
+) -> Option<String> {
+    let leading_pipe = allow_leading_pipe
+        && context.config.binop_separator() == SeparatorPlace::Front
+        && match context.config.match_arm_leading_pipes() {
+            MatchArmLeadingPipe::Never => false,
+            MatchArmLeadingPipe::Always => true,
+            MatchArmLeadingPipe::Preserve => context
+                .snippet(mk_sp(pat.span.lo(), pats[0].span.lo()))
+                .contains('|'),
+        };
+
+    let result = write_or_pattern(context, pats, shape)?;
+    if !leading_pipe || !result.contains('\n') {
+        return Some(result);
+    }
+    // 2 = `| `
+    let result = write_or_pattern(context, pats, shape.offset_left(2)?)?;
+    Some(format!("| {}", result))
+}
+
+fn write_or_pattern(
+    context: &RewriteContext<'_>,
+    pats: &[ptr::P<Pat>],
+    shape: Shape,
+) -> Option<String> {
+    let pat_strs = pats
+        .iter()
+        .map(|p| p.rewrite(context, shape))
+        .collect::<Option<Vec<_>>>()?;
+
+    let use_mixed_layout = pats
+        .iter()
+        .zip(pat_strs.iter())
+        .all(|(pat, pat_str)| is_short_pattern(pat, pat_str));
+    let items: Vec<_> = pat_strs.into_iter().map(ListItem::from_str).collect();
+    let tactic = if use_mixed_layout {
+        DefinitiveListTactic::Mixed
+    } else {
+        definitive_tactic(
+            &items,
+            ListTactic::HorizontalVertical,
+            Separator::VerticalBar,
+            shape.width,
+        )
+    };
+    let fmt = ListFormatting::new(shape, context.config)
+        .tactic(tactic)
+        .separator(" |")
+        .separator_place(context.config.binop_separator())
+        .ends_with_newline(false);
+    write_list(&items, &fmt)
+}
+
 struct RangeOperand<'a>(&'a Option<ptr::P<ast::Expr>>);
 
 impl<'a> Rewrite for RangeOperand<'a> {
@@ -70,32 +149,8 @@ impl Rewrite for Pat {
     fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String> {
         match self.kind {
             PatKind::Or(ref pats) => {
-                let pat_strs = pats
-                    .iter()
-                    .map(|p| p.rewrite(context, shape))
-                    .collect::<Option<Vec<_>>>()?;
-
-                let use_mixed_layout = pats
-                    .iter()
-                    .zip(pat_strs.iter())
-                    .all(|(pat, pat_str)| is_short_pattern(pat, pat_str));
-                let items: Vec<_> = pat_strs.into_iter().map(ListItem::from_str).collect();
-                let tactic = if use_mixed_layout {
-                    DefinitiveListTactic::Mixed
-                } else {
-                    definitive_tactic(
-                        &items,
-                        ListTactic::HorizontalVertical,
-                        Separator::VerticalBar,
-                        shape.width,
-                    )
-                };
-                let fmt = ListFormatting::new(shape, context.config)
-                    .tactic(tactic)
-                    .separator(" |")
-                    .separator_place(context.config.binop_separator())
-                    .ends_with_newline(false);
-                write_list(&items, &fmt)
+                let allow_leading_pipe = context.config.version() == Version::Two;
+                rewrite_or_pattern(context, self, pats, shape, allow_leading_pipe)
             }
             PatKind::Box(ref pat) => rewrite_unary_prefix(context, "box ", &**pat, shape),
             PatKind::Ident(binding_mode, ident, ref sub_pat) => {
@@ -196,19 +251,9 @@ impl Rewrite for Pat {
                     RangeEnd::Included(RangeSyntax::DotDotEq) => "..=",
                     RangeEnd::Excluded => "..",
                 };
-                let infix = if context.config.spaces_around_ranges() {
-                    let lhs_spacing = match lhs {
-                        None => "",
-                        Some(_) => " ",
-                    };
-                    let rhs_spacing = match rhs {
-                        None => "",
-                        Some(_) => " ",
-                    };
-                    format!("{}{}{}", lhs_spacing, infix, rhs_spacing)
-                } else {
-                    infix.to_owned()
-                };
+                let infix = Spacing::new(context.config)
+                    .range(infix, lhs.is_some(), rhs.is_some())
+                    .unwrap_or_else(|| infix.to_owned());
                 rewrite_pair(
                     &RangeOperand(lhs),
                     &RangeOperand(rhs),
@@ -231,7 +276,7 @@ impl Rewrite for Pat {
                 rewrite_tuple_pat(pat_vec, Some(path_str), self.span, context, shape)
             }
             PatKind::Lit(ref expr) => expr.rewrite(context, shape),
-            PatKind::Slice(ref slice_pat) => {
+            PatKind::Slice(ref slice_pat) if context.config.version() == Version::One => {
                 let rw: Vec<String> = slice_pat
                     .iter()
                     .map(|p| {
@@ -244,15 +289,30 @@ impl Rewrite for Pat {
                     .collect();
                 Some(format!("[{}]", rw.join(", ")))
             }
+            PatKind::Slice(ref slice_pat) => overflow::rewrite_with_square_brackets(
+                context,
+                "",
+                slice_pat.iter(),
+                shape,
+                self.span,
+                None,
+                None,
+            ),
             PatKind::Struct(ref path, ref fields, ellipsis) => {
                 rewrite_struct_pat(path, fields, ellipsis, self.span, context, shape)
             }
             PatKind::MacCall(ref mac) => {
                 rewrite_macro(mac, None, context, shape, MacroPosition::Pat)
             }
-            PatKind::Paren(ref pat) => pat
-                .rewrite(context, shape.offset_left(1)?.sub_width(1)?)
-                .map(|inner_pat| format!("({})", inner_pat)),
+            PatKind::Paren(ref pat) => {
+                let (lhs, rhs) = if context.config.spaces_within_parens() {
+                    ("( ", " )")
+                } else {
+                    ("(", ")")
+                };
+                pat.rewrite(context, shape.offset_left(lhs.len())?.sub_width(rhs.len())?)
+                    .map(|inner_pat| format!("{}{}{}", lhs, inner_pat, rhs))
+            }
         }
     }
 }
@@ -278,6 +338,10 @@ fn rewrite_struct_pat(
     // 3 = ` { `, 2 = ` }`.
     let (h_shape, v_shape) =
         struct_lit_shape(shape, context, path_str.len() + 3, ellipsis_str.len() + 2)?;
+    // Struct patterns with too many fields are always formatted vertically.
+    let force_multiline_fields = context.config.struct_pat_force_multiline_fields();
+    let h_shape =
+        h_shape.filter(|_| force_multiline_fields == 0 || fields.len() <= force_multiline_fields);
 
     let items = itemize_list(
         context.snippet_provider,
@@ -297,9 +361,19 @@ fn rewrite_struct_pat(
         span.hi(),
         false,
     );
-    let item_vec = items.collect::<Vec<_>>();
+    let mut item_vec = items.collect::<Vec<_>>();
 
     let tactic = struct_lit_tactic(h_shape, context, &item_vec);
+    if tactic == DefinitiveListTactic::Vertical {
+        let prefix_max_width = field_pat_prefix_max_width(context, fields);
+        if prefix_max_width > 0 {
+            for (field, item) in fields.iter().zip(item_vec.iter_mut()) {
+                if item.item.is_some() {
+                    item.item = rewrite_field_pat(field, context, v_shape, prefix_max_width);
+                }
+            }
+        }
+    }
     let nested_shape = shape_for_tactic(tactic, h_shape, v_shape);
     let fmt = struct_lit_formatting(nested_shape, tactic, context, false);
 
@@ -312,8 +386,14 @@ fn rewrite_struct_pat(
             if context.config.trailing_comma() == SeparatorTactic::Never {
                 fields_str.push_str(",");
             }
-            fields_str.push_str("\n");
-            fields_str.push_str(&nested_shape.indent.to_string(context.config));
+            if context.config.struct_pat_rest_own_line()
+                || last_line_contains_single_line_comment(&fields_str)
+            {
+                fields_str.push_str("\n");
+                fields_str.push_str(&nested_shape.indent.to_string(context.config));
+            } else {
+                fields_str.push_str(" ");
+            }
             fields_str.push_str("..");
         } else {
             if !fields_str.is_empty() {
@@ -334,53 +414,92 @@ fn rewrite_struct_pat(
     Some(format!("{} {{{}}}", path_str, fields_str))
 }
 
+// Returns the width to which the names of the fields of a vertical struct pattern are padded, so
+// that their bindings are aligned, or 0 if they are not aligned.
+fn field_pat_prefix_max_width(context: &RewriteContext<'_>, fields: &[ast::FieldPat]) -> usize {
+    let threshold = context.config.struct_pat_field_align_threshold();
+    if threshold == 0 {
+        return 0;
+    }
+    let widths = fields
+        .iter()
+        .filter(|field| !field.is_shorthand)
+        .map(|field| rewrite_ident(context, field.ident).len());
+    let (max_width, min_width) = widths.fold((0, usize::max_value()), |(max, min), width| {
+        (max.max(width), min.min(width))
+    });
+    if max_width.saturating_sub(min_width) > threshold {
+        0
+    } else {
+        max_width
+    }
+}
+
 impl Rewrite for FieldPat {
     fn rewrite(&self, context: &RewriteContext<'_>, shape: Shape) -> Option<String> {
-        let hi_pos = if let Some(last) = self.attrs.last() {
-            last.span.hi()
-        } else {
-            self.pat.span.lo()
-        };
+        rewrite_field_pat(self, context, shape, 0)
+    }
+}
 
-        let attrs_str = if self.attrs.is_empty() {
-            String::from("")
-        } else {
-            self.attrs.rewrite(context, shape)?
-        };
+fn rewrite_field_pat(
+    field: &FieldPat,
+    context: &RewriteContext<'_>,
+    shape: Shape,
+    prefix_max_width: usize,
+) -> Option<String> {
+    let hi_pos = if let Some(last) = field.attrs.last() {
+        last.span.hi()
+    } else {
+        field.pat.span.lo()
+    };
 
-        let pat_str = self.pat.rewrite(context, shape)?;
-        if self.is_shorthand {
-            combine_strs_with_missing_comments(
-                context,
-                &attrs_str,
-                &pat_str,
-                mk_sp(hi_pos, self.pat.span.lo()),
-                shape,
-                false,
+    let attrs_str = if field.attrs.is_empty() {
+        String::from("")
+    } else {
+        field.attrs.rewrite(context, shape)?
+    };
+
+    let pat_str = field.pat.rewrite(context, shape)?;
+    if field.is_shorthand {
+        combine_strs_with_missing_comments(
+            context,
+            &attrs_str,
+            &pat_str,
+            mk_sp(hi_pos, field.pat.span.lo()),
+            shape,
+            false,
+        )
+    } else {
+        let nested_shape = shape.block_indent(context.config.tab_spaces());
+        let id_str = rewrite_ident(context, field.ident);
+        let spacing = Spacing::new(context.config);
+        let colon = spacing.colon_before_padding();
+        let padding = " ".repeat(prefix_max_width.saturating_sub(id_str.len()));
+        let space_after_colon = spacing.space_after_colon();
+        let one_line_width =
+            id_str.len() + colon.len() + padding.len() + space_after_colon.len() + pat_str.len();
+        let pat_and_id_str = if one_line_width <= shape.width {
+            format!(
+                "{}{}{}{}{}",
+                id_str, colon, padding, space_after_colon, pat_str
             )
         } else {
-            let nested_shape = shape.block_indent(context.config.tab_spaces());
-            let id_str = rewrite_ident(context, self.ident);
-            let one_line_width = id_str.len() + 2 + pat_str.len();
-            let pat_and_id_str = if one_line_width <= shape.width {
-                format!("{}: {}", id_str, pat_str)
-            } else {
-                format!(
-                    "{}:\n{}{}",
-                    id_str,
-                    nested_shape.indent.to_string(context.config),
-                    self.pat.rewrite(context, nested_shape)?
-                )
-            };
-            combine_strs_with_missing_comments(
-                context,
-                &attrs_str,
-                &pat_and_id_str,
-                mk_sp(hi_pos, self.pat.span.lo()),
-                nested_shape,
-                false,
+            format!(
+                "{}{}\n{}{}",
+                id_str,
+                colon,
+                nested_shape.indent.to_string(context.config),
+                field.pat.rewrite(context, nested_shape)?
             )
-        }
+        };
+        combine_strs_with_missing_comments(
+            context,
+            &attrs_str,
+            &pat_and_id_str,
+            mk_sp(hi_pos, field.pat.span.lo()),
+            nested_shape,
+            false,
+        )
     }
 }
 
@@ -1,8 +1,9 @@
 //! Reorder items.
 //!
 //! `mod`, `extern crate` and `use` declarations are reordered in alphabetical
-//! order. Trait items are reordered in pre-determined order (associated types
-//! and constants comes before methods).
+//! order. Impl blocks are reordered by trait and then type name when
+//! `reorder_impls` is set. Trait items are reordered in pre-determined order
+//! (associated types and constants comes before methods).
 
 // FIXME(#2455): Reorder trait items.
 
@@ -13,7 +14,7 @@ use rustc_span::{symbol::sym, Span};
 
 use crate::config::Config;
 use crate::imports::{merge_use_trees, UseTree};
-use crate::items::{is_mod_decl, rewrite_extern_crate, rewrite_mod};
+use crate::items::{is_mod_decl, rewrite_extern_crate, rewrite_impl, rewrite_mod};
 use crate::lists::{itemize_list, write_list, ListFormatting, ListItem};
 use crate::rewrite::RewriteContext;
 use crate::shape::Shape;
@@ -23,8 +24,11 @@ use crate::utils::{contains_skip, mk_sp};
 use crate::visitor::FmtVisitor;
 
 /// Choose the ordering between the given two items.
-fn compare_items(a: &ast::Item, b: &ast::Item) -> Ordering {
+fn compare_items(context: &RewriteContext<'_>, a: &ast::Item, b: &ast::Item) -> Ordering {
     match (&a.kind, &b.kind) {
+        (&ast::ItemKind::Impl { .. }, &ast::ItemKind::Impl { .. }) => {
+            impl_sort_key(context, a).cmp(&impl_sort_key(context, b))
+        }
         (&ast::ItemKind::Mod(..), &ast::ItemKind::Mod(..)) => {
             a.ident.as_str().cmp(&b.ident.as_str())
         }
@@ -51,6 +55,29 @@ fn compare_items(a: &ast::Item, b: &ast::Item) -> Ordering {
     }
 }
 
+/// Returns the key impls are sorted by: the trait, with inherent impls first, then the self type,
+/// then whether the impl is negative, so that `impl !Send for Foo` follows `impl Send for Bar`.
+fn impl_sort_key<'a>(
+    context: &'a RewriteContext<'_>,
+    item: &ast::Item,
+) -> (Option<&'a str>, &'a str, bool) {
+    match item.kind {
+        ast::ItemKind::Impl {
+            polarity,
+            ref of_trait,
+            ref self_ty,
+            ..
+        } => (
+            of_trait
+                .as_ref()
+                .map(|trait_ref| context.snippet(trait_ref.path.span)),
+            context.snippet(self_ty.span),
+            polarity != ast::ImplPolarity::Positive,
+        ),
+        _ => unreachable!(),
+    }
+}
+
 fn wrap_reorderable_items(
     context: &RewriteContext<'_>,
     list_items: &[ListItem],
@@ -70,6 +97,7 @@ fn rewrite_reorderable_item(
     match item.kind {
         ast::ItemKind::ExternCrate(..) => rewrite_extern_crate(context, item, shape),
         ast::ItemKind::Mod(..) => rewrite_mod(context, item, shape),
+        ast::ItemKind::Impl { .. } => rewrite_impl(context, item, shape),
         _ => None,
     }
 }
@@ -138,9 +166,20 @@ fn rewrite_reorderable_items(
             );
 
             let mut item_pair_vec: Vec<_> = list_items.zip(reorderable_items.iter()).collect();
-            item_pair_vec.sort_by(|a, b| compare_items(a.1, b.1));
-            let item_vec: Vec<_> = item_pair_vec.into_iter().map(|pair| pair.0).collect();
+            item_pair_vec.sort_by(|a, b| compare_items(context, a.1, b.1));
+            let mut item_vec: Vec<_> = item_pair_vec.into_iter().map(|pair| pair.0).collect();
 
+            if let ast::ItemKind::Impl { .. } = reorderable_items[0].kind {
+                // Impls are separated by a blank line wherever they end up.
+                for item in &mut item_vec {
+                    item.new_lines = true;
+                }
+                let fmt = ListFormatting::new(shape, context.config)
+                    .separator("")
+                    .align_comments(false)
+                    .preserve_newline(true);
+                return write_list(&item_vec, &fmt);
+            }
             wrap_reorderable_items(context, &item_vec, shape)
         }
     }
@@ -156,6 +195,7 @@ enum ReorderableItemKind {
     ExternCrate,
     Mod,
     Use,
+    Impl,
     /// An item that cannot be reordered. Either has an unreorderable item kind
     /// or an `macro_use` attribute.
     Other,
@@ -170,6 +210,7 @@ impl ReorderableItemKind {
             ast::ItemKind::ExternCrate(..) => ReorderableItemKind::ExternCrate,
             ast::ItemKind::Mod(..) if is_mod_decl(item) => ReorderableItemKind::Mod,
             ast::ItemKind::Use(..) => ReorderableItemKind::Use,
+            ast::ItemKind::Impl { .. } => ReorderableItemKind::Impl,
             _ => ReorderableItemKind::Other,
         }
     }
@@ -183,16 +224,19 @@ impl ReorderableItemKind {
             ReorderableItemKind::ExternCrate => config.reorder_imports(),
             ReorderableItemKind::Mod => config.reorder_modules(),
             ReorderableItemKind::Use => config.reorder_imports(),
+            ReorderableItemKind::Impl => config.reorder_impls(),
             ReorderableItemKind::Other => false,
         }
     }
 
-    fn in_group(self) -> bool {
+    fn in_group(self, config: &Config) -> bool {
         match self {
-            ReorderableItemKind::ExternCrate
-            | ReorderableItemKind::Mod
-            | ReorderableItemKind::Use => true,
-            ReorderableItemKind::Other => false,
+            ReorderableItemKind::ExternCrate | ReorderableItemKind::Use => {
+                config.reorder_imports_in_group()
+            }
+            ReorderableItemKind::Mod => true,
+            // Impls are usually separated by blank lines.
+            ReorderableItemKind::Impl | ReorderableItemKind::Other => false,
         }
     }
 }
@@ -231,7 +275,14 @@ impl<'b, 'a: 'b> FmtVisitor<'a> {
             let hi = items.last().unwrap().span().hi();
             let span = mk_sp(lo, hi);
             let rw = rewrite_reorderable_items(&self.get_context(), items, self.shape(), span);
-            self.push_rewrite(span, rw);
+            if rw.is_none() && item_kind == ReorderableItemKind::Impl {
+                // Don't leave all the impls unformatted because one of them can't be rewritten.
+                for item in items {
+                    self.visit_item(item);
+                }
+            } else {
+                self.push_rewrite(span, rw);
+            }
         } else {
             for item in items {
                 self.push_rewrite(item.span, None);
@@ -251,7 +302,7 @@ impl<'b, 'a: 'b> FmtVisitor<'a> {
             let item_kind = ReorderableItemKind::from(items[0]);
             if item_kind.is_reorderable(self.config) {
                 let visited_items_num =
-                    self.walk_reorderable_items(items, item_kind, item_kind.in_group());
+                    self.walk_reorderable_items(items, item_kind, item_kind.in_group(self.config));
                 let (_, rest) = items.split_at(visited_items_num);
                 items = rest;
             } else {
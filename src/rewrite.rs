@@ -1,17 +1,25 @@
 // A generic trait to abstract the rewriting of an element (of the AST).
 
 use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
-use rustc_ast::ptr;
+use rustc_ast::{ast, ptr};
 use rustc_span::Span;
 
-use crate::config::{Config, IndentStyle};
+use crate::config::{Config, ConstructIndentStyle, IndentStyle};
+use crate::formatting::FormattingError;
 use crate::shape::Shape;
 use crate::skip::SkipContext;
 use crate::syntux::session::ParseSess;
 use crate::visitor::SnippetProvider;
-use crate::FormatReport;
+use crate::{ErrorKind, FormatReport};
+
+// The number of expression rewrites, and the depth of nested expression rewrites, after which
+// the rewrite of an outermost expression gives up.
+const MAX_EXPR_REWRITES: usize = 1_000_000;
+const MAX_EXPR_DEPTH: usize = 512;
 
 pub(crate) trait Rewrite {
     /// Rewrite self into shape.
@@ -24,13 +32,46 @@ impl<T: Rewrite> Rewrite for ptr::P<T> {
     }
 }
 
+/// Callbacks run around the rewrite of every item, which library users can register with
+/// `Session::add_rewrite_hook` to adjust the formatting of items, e.g. to enforce the layouts
+/// required by their organization.
+///
+/// The text given to the callbacks starts at the outer attributes of the item and has no leading
+/// indentation. Items with a skip attribute are not given to the callbacks.
+pub trait RewriteHook {
+    /// Called with the original text of `item` before it is rewritten. Returning `Some` uses the
+    /// returned text as the rewrite of the item instead of rewriting it.
+    fn pre_rewrite_item(
+        &self,
+        _item: &ast::Item,
+        _context: &RewriteContext<'_>,
+        _text: &str,
+    ) -> Option<String> {
+        None
+    }
+
+    /// Called with the rewrite of `item`. Returning `Some` replaces the rewrite with the returned
+    /// text.
+    fn post_rewrite_item(
+        &self,
+        _item: &ast::Item,
+        _context: &RewriteContext<'_>,
+        _text: &str,
+    ) -> Option<String> {
+        None
+    }
+}
+
+/// The context in which the code is rewritten.
 #[derive(Clone)]
-pub(crate) struct RewriteContext<'a> {
+pub struct RewriteContext<'a> {
     pub(crate) parse_sess: &'a ParseSess,
     pub(crate) config: &'a Config,
     pub(crate) inside_macro: Rc<Cell<bool>>,
     // Force block indent style even if we are using visual indent style.
     pub(crate) use_block: Cell<bool>,
+    // The indent style of the construct being rewritten, if it overrides `indent_style`.
+    pub(crate) indent_style: Cell<Option<IndentStyle>>,
     // When `is_if_else_block` is true, unindent the comment on top
     // of the `else` or `else if`.
     pub(crate) is_if_else_block: Cell<bool>,
@@ -42,6 +83,44 @@ pub(crate) struct RewriteContext<'a> {
     pub(crate) report: FormatReport,
     pub(crate) skip_context: SkipContext,
     pub(crate) skipped_range: Rc<RefCell<Vec<(usize, usize)>>>,
+    pub(crate) rewrite_hooks: Rc<[Rc<dyn RewriteHook>]>,
+    pub(crate) rewrite_budget: Rc<RewriteBudget>,
+}
+
+// The shape and the state of the context an expression is rewritten in, which determine its
+// rewrite.
+type ExprRewriteKey = (Span, Shape, bool, [bool; 5]);
+
+/// Limits the work done to rewrite the expressions of a file. Parent expressions retry their
+/// children with different shapes, which can take exponential time on deeply nested code.
+pub(crate) struct RewriteBudget {
+    // The rewrites of the expressions within the current outermost expression, and whether they
+    // failed to rewrite a macro, as parents often try the same shape for a child several times.
+    cache: RefCell<HashMap<ExprRewriteKey, (Option<String>, bool)>>,
+    deadline: Instant,
+    // The number of rewrites for the current outermost expression.
+    rewrites: Cell<usize>,
+    depth: Cell<usize>,
+    outermost: Cell<Option<Span>>,
+    // The outermost expressions whose rewrite gave up, which are not retried with other shapes.
+    given_up: RefCell<HashSet<Span>>,
+    exhausted: Cell<bool>,
+    timed_out: Cell<bool>,
+}
+
+impl RewriteBudget {
+    pub(crate) fn new(time_limit: Duration) -> RewriteBudget {
+        RewriteBudget {
+            cache: RefCell::new(HashMap::new()),
+            deadline: Instant::now() + time_limit,
+            rewrites: Cell::new(0),
+            depth: Cell::new(0),
+            outermost: Cell::new(None),
+            given_up: RefCell::new(HashSet::new()),
+            exhausted: Cell::new(false),
+            timed_out: Cell::new(false),
+        }
+    }
 }
 
 pub(crate) struct InsideMacroGuard {
@@ -61,14 +140,55 @@ impl Drop for InsideMacroGuard {
     }
 }
 
+/// Restores the indent style of the context when a construct with its own indent style has been
+/// rewritten.
+pub(crate) struct IndentStyleGuard<'a> {
+    indent_style: &'a Cell<Option<IndentStyle>>,
+    previous: Option<IndentStyle>,
+}
+
+impl Drop for IndentStyleGuard<'_> {
+    fn drop(&mut self) {
+        self.indent_style.set(self.previous);
+    }
+}
+
 impl<'a> RewriteContext<'a> {
-    pub(crate) fn snippet(&self, span: Span) -> &str {
+    /// The config used for rewriting.
+    pub fn config(&self) -> &Config {
+        self.config
+    }
+
+    /// Returns the original text of `span`.
+    pub fn snippet(&self, span: Span) -> &str {
         self.snippet_provider.span_to_snippet(span).unwrap()
     }
 
+    /// The indent style of the construct being rewritten.
+    pub(crate) fn indent_style(&self) -> IndentStyle {
+        self.indent_style
+            .get()
+            .unwrap_or_else(|| self.config.indent_style())
+    }
+
+    /// Rewrites with the given indent style, e.g. `fn_args_indent_style`, until the returned
+    /// guard is dropped.
+    pub(crate) fn override_indent_style(
+        &self,
+        style: ConstructIndentStyle,
+    ) -> IndentStyleGuard<'_> {
+        let previous = self
+            .indent_style
+            .replace(Some(style.resolve(self.config.indent_style())));
+        IndentStyleGuard {
+            indent_style: &self.indent_style,
+            previous,
+        }
+    }
+
     /// Returns `true` if we should use block indent style for rewriting function call.
     pub(crate) fn use_block_indent(&self) -> bool {
-        self.config.indent_style() == IndentStyle::Block || self.use_block.get()
+        self.indent_style() == IndentStyle::Block || self.use_block.get()
     }
 
     pub(crate) fn budget(&self, used_width: usize) -> usize {
@@ -94,4 +214,102 @@ impl<'a> RewriteContext<'a> {
     pub(crate) fn is_if_else_block(&self) -> bool {
         self.is_if_else_block.get()
     }
+
+    /// Rewrites the expression at `span` with `rewrite`, or reuses the result of rewriting it
+    /// with the same shape before. Once the rewrite budget of the outermost expression is
+    /// exhausted, or the time limit of the file is reached, this returns `None` without
+    /// rewriting, so that the enclosing statement or item is left as it is, and reports a
+    /// warning.
+    pub(crate) fn budgeted_expr_rewrite<F>(
+        &self,
+        span: Span,
+        shape: Shape,
+        is_statement: bool,
+        rewrite: F,
+    ) -> Option<String>
+    where
+        F: FnOnce() -> Option<String>,
+    {
+        let budget = &self.rewrite_budget;
+        if budget.timed_out.get() {
+            return None;
+        }
+        if budget.depth.get() == 0 {
+            if budget.given_up.borrow().contains(&span) {
+                return None;
+            }
+            // The statement or item may retry its outermost expression with other shapes, which
+            // reuse the rewrites of its children, but the rewrites of the previous outermost
+            // expression aren't needed anymore.
+            if budget.outermost.replace(Some(span)) != Some(span) {
+                budget.cache.borrow_mut().clear();
+            }
+            budget.rewrites.set(0);
+            budget.exhausted.set(false);
+        }
+        if budget.exhausted.get() {
+            return None;
+        }
+        let key = (
+            span,
+            shape,
+            is_statement,
+            [
+                self.inside_macro(),
+                self.use_block.get(),
+                self.is_if_else_block(),
+                self.force_one_line_chain.get(),
+                self.indent_style() == IndentStyle::Block,
+            ],
+        );
+        if let Some((result, macro_failed)) = budget.cache.borrow().get(&key) {
+            if *macro_failed {
+                self.macro_rewrite_failure.replace(true);
+            }
+            return result.clone();
+        }
+
+        let rewrites = budget.rewrites.get() + 1;
+        let depth = budget.depth.get() + 1;
+        if rewrites > MAX_EXPR_REWRITES || depth > MAX_EXPR_DEPTH {
+            budget.exhausted.set(true);
+            if let Some(outermost) = budget.outermost.get() {
+                budget.given_up.borrow_mut().insert(outermost);
+            }
+            self.report_rewrite_budget_error(span, ErrorKind::RewriteLimitExceeded);
+            return None;
+        }
+        if Instant::now() > budget.deadline {
+            budget.timed_out.set(true);
+            self.report_rewrite_budget_error(span, ErrorKind::FileTimeLimitExceeded);
+            return None;
+        }
+
+        // A failed macro rewrite is signaled through the context, so the failure of this rewrite
+        // is tracked on its own to be replayed with its cached result.
+        let failed_before = self.macro_rewrite_failure.replace(false);
+        budget.rewrites.set(rewrites);
+        budget.depth.set(depth);
+        let result = rewrite();
+        budget.depth.set(depth - 1);
+        let macro_failed = self.macro_rewrite_failure.get();
+        self.macro_rewrite_failure
+            .replace(failed_before || macro_failed);
+
+        // Results of rewrites that gave up depend on the budget, not on the shape.
+        if !budget.exhausted.get() && !budget.timed_out.get() {
+            budget
+                .cache
+                .borrow_mut()
+                .insert(key, (result.clone(), macro_failed));
+        }
+        result
+    }
+
+    fn report_rewrite_budget_error(&self, span: Span, kind: ErrorKind) {
+        self.report.append(
+            self.parse_sess.span_to_filename(span),
+            vec![FormattingError::from_span(span, self.parse_sess, kind)],
+        );
+    }
 }
@@ -1,10 +1,21 @@
+//! Computing, parsing and applying diffs between an original and a formatted text, so that
+//! tools built on rustfmt can handle diffs the same way as rustfmt.
+
 use std::collections::VecDeque;
+use std::env;
 use std::fmt;
 use std::io;
 use std::io::Write;
 
-use crate::config::{Color, Config, Verbosity};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use unicode_width::UnicodeWidthStr;
+
+use crate::config::{Color, Config, DiffStyle, Verbosity};
 
+/// A line of a diff. The diffs made by rustfmt are from the original text to the formatted one,
+/// so the `Expected` lines are those of the formatted text, and the `Resulting` lines those of
+/// the original text.
 #[derive(Debug, PartialEq)]
 pub enum DiffLine {
     Context(String),
@@ -12,6 +23,7 @@ pub enum DiffLine {
     Resulting(String),
 }
 
+/// A hunk of a diff.
 #[derive(Debug, PartialEq)]
 pub struct Mismatch {
     /// The line number in the formatted version.
@@ -34,7 +46,7 @@ impl Mismatch {
 
 /// A single span of changed lines, with 0 or more removed lines
 /// and a vector of 0 or more inserted lines.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ModifiedChunk {
     /// The first to be removed from the original text
     pub line_number_orig: u32,
@@ -45,7 +57,7 @@ pub struct ModifiedChunk {
 }
 
 /// Set of changed sections of a file.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ModifiedLines {
     /// The set of changed chunks.
     pub chunks: Vec<ModifiedChunk>,
@@ -53,33 +65,70 @@ pub struct ModifiedLines {
 
 impl From<Vec<Mismatch>> for ModifiedLines {
     fn from(mismatches: Vec<Mismatch>) -> ModifiedLines {
-        let chunks = mismatches.into_iter().map(|mismatch| {
-            let lines = mismatch.lines.iter();
-            let num_removed = lines
-                .filter(|line| match line {
-                    DiffLine::Resulting(_) => true,
-                    _ => false,
-                })
-                .count();
-
-            let new_lines = mismatch.lines.into_iter().filter_map(|line| match line {
-                DiffLine::Context(_) | DiffLine::Resulting(_) => None,
-                DiffLine::Expected(str) => Some(str),
-            });
+        let mut chunks = vec![];
+        for mismatch in mismatches {
+            // The context lines split a mismatch into several chunks.
+            let mut line_number_orig = mismatch.line_number_orig;
+            let mut chunk: Option<ModifiedChunk> = None;
+            for line in mismatch.lines {
+                if let DiffLine::Context(_) = line {
+                    chunks.extend(chunk.take());
+                    line_number_orig += 1;
+                    continue;
+                }
+                let chunk = chunk.get_or_insert_with(|| ModifiedChunk {
+                    line_number_orig,
+                    lines_removed: 0,
+                    lines: vec![],
+                });
+                match line {
+                    DiffLine::Expected(str) => chunk.lines.push(str),
+                    _ => {
+                        chunk.lines_removed += 1;
+                        line_number_orig += 1;
+                    }
+                }
+            }
+            chunks.extend(chunk);
+        }
 
-            ModifiedChunk {
-                line_number_orig: mismatch.line_number_orig,
-                lines_removed: num_removed as u32,
-                lines: new_lines.collect(),
+        ModifiedLines { chunks }
+    }
+}
+
+impl ModifiedLines {
+    /// Applies the changes to `original`, which must be the text the changes were made from.
+    /// Returns `None` if the chunks don't fit `original`.
+    pub fn apply(&self, original: &str) -> Option<String> {
+        let original_lines: Vec<&str> = original.lines().collect();
+        let mut result: Vec<&str> = Vec::with_capacity(original_lines.len());
+        let mut next_line = 0;
+        for chunk in &self.chunks {
+            let start = (chunk.line_number_orig as usize).saturating_sub(1);
+            let end = start + chunk.lines_removed as usize;
+            if start < next_line || end > original_lines.len() {
+                return None;
             }
-        });
+            result.extend(&original_lines[next_line..start]);
+            result.extend(chunk.lines.iter().map(String::as_str));
+            next_line = end;
+        }
+        result.extend(&original_lines[next_line..]);
 
-        ModifiedLines {
-            chunks: chunks.collect(),
+        let mut text = result.join("\n");
+        if !text.is_empty() && (original.is_empty() || original.ends_with('\n')) {
+            text.push('\n');
         }
+        Some(text)
     }
 }
 
+/// Returns the chunks of lines which have to be replaced in `original` to turn it into
+/// `formatted`, e.g. so that an editor can apply only the changes made by formatting.
+pub fn get_modified_lines(original: &str, formatted: &str) -> ModifiedLines {
+    ModifiedLines::from(make_diff(original, formatted, 0))
+}
+
 // Converts a `Mismatch` into a serialized form, which just includes
 // enough information to modify the original file.
 // Each section starts with a line with three integers, space separated:
@@ -146,6 +195,9 @@ impl std::str::FromStr for ModifiedLines {
 // of printing in color, if it's possible in the executing environment.
 pub(crate) struct OutputWriter {
     terminal: Option<Box<dyn term::Terminal<Output = io::Stdout>>>,
+    // Whether the terminal announces 24-bit color support through `COLORTERM`,
+    // which the terminfo database used by `term` does not describe.
+    true_color: bool,
 }
 
 impl OutputWriter {
@@ -154,33 +206,146 @@ impl OutputWriter {
     pub(crate) fn new(color: Color) -> Self {
         if let Some(t) = term::stdout() {
             if color.use_colored_tty() && t.supports_color() {
-                return OutputWriter { terminal: Some(t) };
+                let true_color = env::var("COLORTERM")
+                    .map_or(false, |value| value == "truecolor" || value == "24bit");
+                return OutputWriter {
+                    terminal: Some(t),
+                    true_color,
+                };
             }
         }
-        OutputWriter { terminal: None }
+        OutputWriter {
+            terminal: None,
+            true_color: false,
+        }
     }
 
     // Write output in the optionally specified color. The output is written
     // in the specified color if this OutputWriter instance contains a
     // Terminal in its `terminal` field.
     pub(crate) fn writeln(&mut self, msg: &str, color: Option<term::color::Color>) {
+        self.write(msg, color);
+        self.write("\n", None);
+    }
+
+    // Like `writeln`, without ending the line, so that a line can be made of
+    // several colors.
+    pub(crate) fn write(&mut self, msg: &str, color: Option<term::color::Color>) {
         match &mut self.terminal {
-            Some(ref mut t) => {
-                if let Some(color) = color {
-                    t.fg(color).unwrap();
+            Some(ref mut t) => match color.and_then(true_color_escape) {
+                Some(escape) if self.true_color => write!(t, "{}{}\x1b[0m", escape, msg).unwrap(),
+                _ => {
+                    if let Some(color) = color {
+                        t.fg(color).unwrap();
+                    }
+                    write!(t, "{}", msg).unwrap();
+                    if color.is_some() {
+                        t.reset().unwrap();
+                    }
+                }
+            },
+            None => print!("{}", msg),
+        }
+    }
+}
+
+// The 24-bit foreground escape sequence used in place of the 16 standard terminal colors
+// for the removed and added lines of a diff.
+fn true_color_escape(color: term::color::Color) -> Option<&'static str> {
+    match color {
+        term::color::RED => Some("\x1b[38;2;220;50;47m"),
+        term::color::GREEN => Some("\x1b[38;2;64;160;43m"),
+        _ => None,
+    }
+}
+
+/// A row of a side-by-side diff, with a line of the original text on the left and a line of the
+/// formatted text on the right.
+#[derive(Debug, PartialEq)]
+enum SideBySideRow<'a> {
+    Context(&'a str),
+    /// A removed line, an added line, or a line replaced by another one.
+    Changed(Option<&'a str>, Option<&'a str>),
+}
+
+// Pairs the removed and added lines of each run of changed lines, so that a line replaced by
+// another one is shown next to it.
+fn side_by_side_rows(lines: &[DiffLine]) -> Vec<SideBySideRow<'_>> {
+    fn flush<'a>(
+        rows: &mut Vec<SideBySideRow<'a>>,
+        removed: &mut Vec<&'a str>,
+        added: &mut Vec<&'a str>,
+    ) {
+        for i in 0..removed.len().max(added.len()) {
+            rows.push(SideBySideRow::Changed(
+                removed.get(i).cloned(),
+                added.get(i).cloned(),
+            ));
+        }
+        removed.clear();
+        added.clear();
+    }
+
+    let mut rows = vec![];
+    let mut removed = vec![];
+    let mut added = vec![];
+    for line in lines {
+        match line {
+            DiffLine::Context(ref s) => {
+                flush(&mut rows, &mut removed, &mut added);
+                rows.push(SideBySideRow::Context(s));
+            }
+            DiffLine::Resulting(ref s) => removed.push(s.as_str()),
+            DiffLine::Expected(ref s) => added.push(s.as_str()),
+        }
+    }
+    flush(&mut rows, &mut removed, &mut added);
+    rows
+}
+
+fn print_side_by_side(writer: &mut OutputWriter, lines: &[DiffLine], line_terminator: &str) {
+    let rows = side_by_side_rows(lines);
+    let left_width = rows
+        .iter()
+        .map(|row| match row {
+            SideBySideRow::Context(s) | SideBySideRow::Changed(Some(s), _) => s.width(),
+            SideBySideRow::Changed(None, _) => 0,
+        })
+        .max()
+        .unwrap_or(0)
+        + line_terminator.width();
+    let pad = |s: &str| {
+        let s = format!("{}{}", s, line_terminator);
+        let padding = " ".repeat(left_width.saturating_sub(s.width()));
+        s + &padding
+    };
+
+    for row in rows {
+        match row {
+            SideBySideRow::Context(s) => {
+                writer.writeln(&format!(" {} |  {}{}", pad(s), s, line_terminator), None)
+            }
+            SideBySideRow::Changed(removed, added) => {
+                match removed {
+                    Some(s) => writer.write(&format!("-{}", pad(s)), Some(term::color::RED)),
+                    None => writer.write(&format!(" {}", " ".repeat(left_width)), None),
                 }
-                writeln!(t, "{}", msg).unwrap();
-                if color.is_some() {
-                    t.reset().unwrap();
+                writer.write(" | ", None);
+                match added {
+                    Some(s) => writer.writeln(
+                        &format!("+{}{}", s, line_terminator),
+                        Some(term::color::GREEN),
+                    ),
+                    None => writer.writeln("", None),
                 }
             }
-            None => println!("{}", msg),
         }
     }
 }
 
-// Produces a diff between the expected output and actual output of rustfmt.
-pub(crate) fn make_diff(expected: &str, actual: &str, context_size: usize) -> Vec<Mismatch> {
+/// Produces a diff between the expected output and actual output of rustfmt, with
+/// `context_size` lines of context around the changed lines.
+pub fn make_diff(expected: &str, actual: &str, context_size: usize) -> Vec<Mismatch> {
     let mut line_number = 1;
     let mut line_number_orig = 1;
     let mut context_queue: VecDeque<&str> = VecDeque::with_capacity(context_size);
@@ -248,6 +413,161 @@ pub(crate) fn make_diff(expected: &str, actual: &str, context_size: usize) -> Ve
     results
 }
 
+/// The changes a unified diff makes to a file.
+#[derive(Debug, PartialEq)]
+pub struct FileDiff {
+    /// The path of the changed file, without the `b/` prefix of git diffs. The path of the
+    /// original file is used for deleted files.
+    pub path: String,
+    /// The hunks of the diff, whose `Resulting` lines are the removed lines and `Expected` lines
+    /// the added lines.
+    pub mismatches: Vec<Mismatch>,
+}
+
+/// An error parsing a unified diff.
+#[error("invalid unified diff at line {line}")]
+#[derive(Debug, Error, PartialEq)]
+pub struct UnifiedDiffError {
+    /// The 1-based line of the diff which is invalid.
+    pub line: usize,
+}
+
+/// Parses a unified diff, e.g. the output of `git diff` or `diff -u`, into the changes it makes
+/// to each file. The lines which are not part of the file headers or hunks are ignored.
+pub fn parse_unified_diff(input: &str) -> Result<Vec<FileDiff>, UnifiedDiffError> {
+    let mut files = vec![];
+    let mut original_path = None;
+    let mut lines = input.lines().enumerate();
+    while let Some((i, line)) = lines.next() {
+        let error = move || UnifiedDiffError { line: i + 1 };
+        if line.starts_with("--- ") {
+            original_path = Some(diff_path(&line[4..], "a/"));
+            continue;
+        }
+        if line.starts_with("+++ ") {
+            let path = match diff_path(&line[4..], "b/") {
+                "/dev/null" => original_path.take().ok_or_else(error)?,
+                path => path,
+            };
+            files.push(FileDiff {
+                path: path.to_owned(),
+                mismatches: vec![],
+            });
+            continue;
+        }
+        if !line.starts_with("@@ ") {
+            continue;
+        }
+
+        let file = files.last_mut().ok_or_else(error)?;
+        let mut header = line.split_whitespace().skip(1);
+        let (orig_start, orig_count) = header
+            .next()
+            .filter(|range| range.starts_with('-'))
+            .and_then(|range| parse_hunk_range(&range[1..]))
+            .ok_or_else(error)?;
+        let (start, count) = header
+            .next()
+            .filter(|range| range.starts_with('+'))
+            .and_then(|range| parse_hunk_range(&range[1..]))
+            .ok_or_else(error)?;
+        // An empty range starts at the line before it.
+        let mut mismatch = Mismatch::new(
+            if count == 0 { start + 1 } else { start },
+            if orig_count == 0 {
+                orig_start + 1
+            } else {
+                orig_start
+            },
+        );
+
+        let (mut removed, mut added) = (0, 0);
+        while removed < orig_count || added < count {
+            let (j, line) = lines.next().ok_or_else(error)?;
+            let text = line.get(1..).unwrap_or("").to_owned();
+            match line.chars().next() {
+                // Some tools trim the space of empty context lines.
+                Some(' ') | None => {
+                    mismatch.lines.push(DiffLine::Context(text));
+                    removed += 1;
+                    added += 1;
+                }
+                Some('-') => {
+                    mismatch.lines.push(DiffLine::Resulting(text));
+                    removed += 1;
+                }
+                Some('+') => {
+                    mismatch.lines.push(DiffLine::Expected(text));
+                    added += 1;
+                }
+                // `\ No newline at end of file`
+                Some('\\') => {}
+                _ => return Err(UnifiedDiffError { line: j + 1 }),
+            }
+            if removed > orig_count || added > count {
+                return Err(UnifiedDiffError { line: j + 1 });
+            }
+        }
+        file.mismatches.push(mismatch);
+    }
+    Ok(files)
+}
+
+// Returns the path of a file header, without the timestamp of `diff -u` and the prefix of git.
+fn diff_path<'a>(header: &'a str, prefix: &str) -> &'a str {
+    let path = header.split('\t').next().unwrap_or(header);
+    if path.starts_with(prefix) {
+        &path[prefix.len()..]
+    } else {
+        path
+    }
+}
+
+// Parses the `start,count` range of a hunk header, where the count defaults to 1.
+fn parse_hunk_range(range: &str) -> Option<(u32, u32)> {
+    let mut parts = range.splitn(2, ',');
+    let start = parts.next()?.parse().ok()?;
+    let count = match parts.next() {
+        Some(count) => count.parse().ok()?,
+        None => 1,
+    };
+    Some((start, count))
+}
+
+/// Returns the number of lines changed by a diff made without context lines. A changed line
+/// shows up both as an original and as a formatted line, but is counted once.
+pub(crate) fn count_changed_lines(diff: &[Mismatch]) -> usize {
+    diff.iter()
+        .map(|mismatch| {
+            let (mut original, mut formatted) = (0, 0);
+            for line in &mismatch.lines {
+                match line {
+                    DiffLine::Resulting(_) => original += 1,
+                    DiffLine::Expected(_) => formatted += 1,
+                    DiffLine::Context(_) => {}
+                }
+            }
+            original.max(formatted)
+        })
+        .sum()
+}
+
+/// Returns the number of lines removed from `original` and added to `formatted`.
+pub(crate) fn diff_stat(original: &str, formatted: &str) -> (usize, usize) {
+    let mut removed = 0;
+    let mut added = 0;
+    for mismatch in make_diff(original, formatted, 0) {
+        for line in mismatch.lines {
+            match line {
+                DiffLine::Resulting(_) => removed += 1,
+                DiffLine::Expected(_) => added += 1,
+                DiffLine::Context(_) => {}
+            }
+        }
+    }
+    (removed, added)
+}
+
 pub(crate) fn print_diff<F>(diff: Vec<Mismatch>, get_section_title: F, config: &Config)
 where
     F: Fn(u32) -> String,
@@ -265,6 +585,11 @@ where
         let title = get_section_title(mismatch.line_number_orig);
         writer.writeln(&title, None);
 
+        if config.diff_style() == DiffStyle::SideBySide {
+            print_side_by_side(&mut writer, &mismatch.lines, line_terminator);
+            continue;
+        }
+
         for line in mismatch.lines {
             match line {
                 DiffLine::Context(ref str) => {
@@ -286,8 +611,17 @@ where
 #[cfg(test)]
 mod test {
     use super::DiffLine::*;
-    use super::{make_diff, Mismatch};
-    use super::{ModifiedChunk, ModifiedLines};
+    use super::{diff_stat, make_diff, side_by_side_rows, Mismatch, SideBySideRow};
+    use super::{get_modified_lines, ModifiedChunk, ModifiedLines};
+    use super::{parse_unified_diff, true_color_escape, FileDiff, UnifiedDiffError};
+
+    #[test]
+    fn diff_stat_counts_removed_and_added_lines() {
+        let original = "one\ntwo\nthree\nfour\n";
+        let formatted = "one\n2\nthree\nfour\nfive\nsix\n";
+        assert_eq!(diff_stat(original, formatted), (1, 3));
+        assert_eq!(diff_stat(original, original), (0, 0));
+    }
 
     #[test]
     fn diff_simple() {
@@ -309,6 +643,22 @@ mod test {
         );
     }
 
+    #[test]
+    fn side_by_side_pairs_changed_lines() {
+        let src = "one\ntwo\nthree\nfour\n";
+        let dest = "one\n2\nfour\n";
+        let diff = make_diff(src, dest, 1);
+        assert_eq!(
+            side_by_side_rows(&diff[0].lines),
+            vec![
+                SideBySideRow::Context("one"),
+                SideBySideRow::Changed(Some("two"), Some("2")),
+                SideBySideRow::Changed(Some("three"), None),
+                SideBySideRow::Context("four"),
+            ]
+        );
+    }
+
     #[test]
     fn diff_simple2() {
         let src = "one\ntwo\nthree\nfour\nfive\nsix\nseven\n";
@@ -400,4 +750,140 @@ mod test {
         let src = "1 5 3\na\nb";
         assert_eq!(ModifiedLines::from_str(src), Err(()));
     }
+
+    #[test]
+    fn modified_lines_round_trip_through_json() {
+        let lines = get_modified_lines(
+            "fn main () {
+}
+struct A;
+",
+            "fn main() {}
+struct A;
+",
+        );
+        assert_eq!(
+            lines,
+            ModifiedLines {
+                chunks: vec![ModifiedChunk {
+                    line_number_orig: 1,
+                    lines_removed: 2,
+                    lines: vec!["fn main() {}".to_owned()],
+                }]
+            }
+        );
+
+        let json = serde_json::to_string(&lines).unwrap();
+        assert_eq!(
+            json,
+            r#"{"chunks":[{"line_number_orig":1,"lines_removed":2,"lines":["fn main() {}"]}]}"#
+        );
+        assert_eq!(serde_json::from_str::<ModifiedLines>(&json).unwrap(), lines);
+    }
+
+    #[test]
+    fn modified_lines_with_context_apply_to_original() {
+        let src = "one\ntwo\nthree\nfour\nfive\nsix\nseven\n";
+        let dest = "zero\none\ntwo\ntrois\nfour\ncinq\nsix\n";
+        let modified_lines = ModifiedLines::from(make_diff(src, dest, 3));
+        assert_eq!(modified_lines.apply(src).as_deref(), Some(dest));
+        assert_eq!(
+            get_modified_lines(src, dest).apply(src).as_deref(),
+            Some(dest)
+        );
+        assert_eq!(modified_lines.apply("one\n"), None);
+    }
+
+    #[test]
+    fn parse_unified_diff_of_files() {
+        let diff = "\
+diff --git a/src/lib.rs b/src/lib.rs
+index 0123456..789abcd 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,4 +1,4 @@ mod foo;
+ fn main() {
+-    let x=1;
++    let x = 1;
+ }
+
+@@ -10,0 +11 @@
++fn bar() {}
+--- a/old.rs
++++ /dev/null
+@@ -1 +0,0 @@
+-fn old() {}
+\\ No newline at end of file
+";
+        assert_eq!(
+            parse_unified_diff(diff),
+            Ok(vec![
+                FileDiff {
+                    path: "src/lib.rs".to_owned(),
+                    mismatches: vec![
+                        Mismatch {
+                            line_number: 1,
+                            line_number_orig: 1,
+                            lines: vec![
+                                Context("fn main() {".to_owned()),
+                                Resulting("    let x=1;".to_owned()),
+                                Expected("    let x = 1;".to_owned()),
+                                Context("}".to_owned()),
+                                Context("".to_owned()),
+                            ],
+                        },
+                        Mismatch {
+                            line_number: 11,
+                            line_number_orig: 11,
+                            lines: vec![Expected("fn bar() {}".to_owned())],
+                        },
+                    ],
+                },
+                FileDiff {
+                    path: "old.rs".to_owned(),
+                    mismatches: vec![Mismatch {
+                        line_number: 1,
+                        line_number_orig: 1,
+                        lines: vec![Resulting("fn old() {}".to_owned())],
+                    }],
+                },
+            ])
+        );
+        assert_eq!(
+            parse_unified_diff("--- a/foo.rs\n+++ b/foo.rs\n@@ -1,2 +1,2 @@\n-a\n+b\n"),
+            Err(UnifiedDiffError { line: 3 })
+        );
+    }
+
+    #[test]
+    fn parsed_unified_diff_applies_to_original() {
+        let original = "fn main() {\n    let x=1;\n}\n";
+        let diff = "\
+--- a/main.rs
++++ b/main.rs
+@@ -2 +2,2 @@
+-    let x=1;
++    let x = 1;
++    let y = 2;
+";
+        let mut files = parse_unified_diff(diff).unwrap();
+        let modified_lines = ModifiedLines::from(files.remove(0).mismatches);
+        assert_eq!(
+            modified_lines.apply(original).as_deref(),
+            Some("fn main() {\n    let x = 1;\n    let y = 2;\n}\n")
+        );
+    }
+
+    #[test]
+    fn true_colors_of_diff_lines() {
+        assert_eq!(
+            true_color_escape(term::color::RED),
+            Some("\x1b[38;2;220;50;47m")
+        );
+        assert_eq!(
+            true_color_escape(term::color::GREEN),
+            Some("\x1b[38;2;64;160;43m")
+        );
+        assert_eq!(true_color_escape(term::color::BLUE), None);
+    }
 }
@@ -0,0 +1,395 @@
+//! Checks that formatting a file didn't change its meaning, by comparing the syntax trees of the
+//! original and the formatted text, as printed without their spans.
+
+use std::collections::HashMap;
+
+use rustc_ast::mut_visit::{self, MutVisitor};
+use rustc_ast::{ast, ptr};
+use rustc_ast_pretty::pprust;
+use rustc_span::symbol::{sym, Symbol};
+
+use crate::config::Config;
+use crate::expr::is_unsafe_block;
+use crate::syntux::parser::Parser;
+use crate::syntux::session::ParseSess;
+
+/// Returns `None` if `formatted` has the same syntax tree as `original`, and otherwise the 1-based
+/// line of the first item of `original` which formatting changed. Line 1 is returned if the change
+/// can't be attributed to an item, e.g. because `formatted` fails to parse.
+///
+/// Items are compared as a multiset, since rustfmt reorders items and imports, but the tree of each
+/// item has to be the same, up to the changes rustfmt makes on purpose.
+pub(crate) fn changed_item_line(config: &Config, original: &str, formatted: &str) -> Option<usize> {
+    // The original text has been parsed before, so a failure here isn't the formatter's fault.
+    let original_items = canonical_items(config, original)?;
+    let formatted_items = match canonical_items(config, formatted) {
+        Some(items) => items,
+        None => return Some(1),
+    };
+
+    let mut available: HashMap<String, usize> = HashMap::new();
+    for (_, item) in formatted_items {
+        *available.entry(item).or_insert(0) += 1;
+    }
+    for (line, item) in original_items {
+        match available.get_mut(&item) {
+            Some(n) if *n > 0 => *n -= 1,
+            _ => return Some(line),
+        }
+    }
+    if available.values().any(|n| *n > 0) {
+        // Formatting added code.
+        Some(1)
+    } else {
+        None
+    }
+}
+
+// Parses `text` and prints its inner attributes and items in a canonical form, along with their
+// lines.
+fn canonical_items(config: &Config, text: &str) -> Option<Vec<(usize, String)>> {
+    // Every text gets its own session, as their source files would have the same name.
+    let mut sess = ParseSess::new(config).ok()?;
+    sess.set_silent_emitter();
+    let (module, attrs) = Parser::parse_str_as_module(&sess, text.to_owned()).ok()?;
+
+    let mut items: Vec<_> = attrs
+        .iter()
+        .filter(|attr| !is_doc_attr(attr))
+        .map(|attr| {
+            (
+                sess.line_of_byte_pos(attr.span.lo()),
+                pprust::attribute_to_string(attr),
+            )
+        })
+        .collect();
+    push_canonical_items(&sess, config, "", &module.items, &mut items);
+    Some(items)
+}
+
+// Adds the canonical form of each of `items` to `out`. The items of inline modules are added one by
+// one as well, prefixed with the path of their module, since they are reordered too.
+fn push_canonical_items(
+    sess: &ParseSess,
+    config: &Config,
+    prefix: &str,
+    items: &[ptr::P<ast::Item>],
+    out: &mut Vec<(usize, String)>,
+) {
+    for item in items {
+        let line = sess.line_of_byte_pos(item.span.lo());
+        let mut item = (**item).clone();
+        let attrs = canonical_attrs(&std::mem::take(&mut item.attrs));
+
+        match item.kind {
+            ast::ItemKind::Use(ref tree) => {
+                // Imports are flattened, so that nesting and merging them is ignored.
+                let vis = pprust::vis_to_string(&item.vis);
+                let mut paths = vec![];
+                flatten_use_tree("", tree, &mut paths);
+                for path in paths {
+                    out.push((line, format!("{}{}{}use {};", prefix, attrs, vis, path)));
+                }
+            }
+            ast::ItemKind::Mod(ref mut module) => {
+                let module_items = std::mem::take(&mut module.items);
+                let header = canonical_item(config, &mut item);
+                out.push((line, format!("{}{}{}", prefix, attrs, header)));
+                let prefix = format!("{}mod {}::", prefix, item.ident);
+                push_canonical_items(sess, config, &prefix, &module_items, out);
+            }
+            _ => {
+                let item = canonical_item(config, &mut item);
+                out.push((line, format!("{}{}{}", prefix, attrs, item)));
+            }
+        }
+    }
+}
+
+// Prints the attributes of an item, one per line, without doc comments and with each derived trait
+// in its own attribute, as rustfmt merges derives and may sort them.
+fn canonical_attrs(attrs: &[ast::Attribute]) -> String {
+    let mut canonical = vec![];
+    for attr in attrs.iter().filter(|attr| !is_doc_attr(attr)) {
+        let derives = if attr.has_name(sym::derive) {
+            attr.meta_item_list().and_then(|list| {
+                list.iter()
+                    .map(|nested| {
+                        nested.meta_item().map(|meta_item| {
+                            format!("#[derive({})]", pprust::path_to_string(&meta_item.path))
+                        })
+                    })
+                    .collect::<Option<Vec<_>>>()
+            })
+        } else {
+            None
+        };
+        match derives {
+            Some(derives) => canonical.extend(derives),
+            None => canonical.push(pprust::attribute_to_string(attr)),
+        }
+    }
+    canonical.sort();
+    canonical.iter().map(|attr| format!("{}\n", attr)).collect()
+}
+
+fn is_doc_attr(attr: &ast::Attribute) -> bool {
+    attr.is_doc_comment() || attr.has_name(sym::doc)
+}
+
+fn flatten_use_tree(prefix: &str, tree: &ast::UseTree, paths: &mut Vec<String>) {
+    let path = join_paths(prefix, &pprust::path_to_string(&tree.prefix));
+    match tree.kind {
+        ast::UseTreeKind::Simple(ref rename, ..) => match rename {
+            Some(rename) => paths.push(format!("{} as {}", path, rename)),
+            None => paths.push(path),
+        },
+        ast::UseTreeKind::Glob => paths.push(join_paths(&path, "*")),
+        ast::UseTreeKind::Nested(ref trees) => {
+            for (tree, _) in trees {
+                flatten_use_tree(&path, tree, paths);
+            }
+        }
+    }
+}
+
+fn join_paths(prefix: &str, path: &str) -> String {
+    if prefix.is_empty() {
+        path.to_owned()
+    } else if path.is_empty() {
+        prefix.to_owned()
+    } else {
+        format!("{}::{}", prefix, path)
+    }
+}
+
+// Prints an item without its attributes, after undoing the changes rustfmt makes on purpose.
+fn canonical_item(config: &Config, item: &mut ast::Item) -> String {
+    Normalizer { config }.visit_item_kind(&mut item.kind);
+    pprust::item_to_string(item)
+        .lines()
+        .filter(|line| !is_doc_comment(line.trim_start()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Doc comments may be normalized or wrapped, and aren't part of the meaning of the code.
+fn is_doc_comment(line: &str) -> bool {
+    ["///", "//!", "/**", "/*!", "#[doc", "#![doc"]
+        .iter()
+        .any(|prefix| line.starts_with(prefix))
+}
+
+/// Rewrites the parts of a syntax tree that rustfmt is allowed to change into a canonical form:
+/// redundant nested parentheses, the case and digit grouping of literals, blocks around the bodies
+/// of match arms and closures, and the order of items within blocks, impls and extern blocks.
+struct Normalizer<'a> {
+    config: &'a Config,
+}
+
+impl MutVisitor for Normalizer<'_> {
+    fn visit_expr(&mut self, expr: &mut ptr::P<ast::Expr>) {
+        loop {
+            let inner = match expr.kind {
+                ast::ExprKind::Paren(ref inner) => match inner.kind {
+                    ast::ExprKind::Paren(..) => inner.clone(),
+                    _ => break,
+                },
+                _ => break,
+            };
+            *expr = inner;
+        }
+        match expr.kind {
+            ast::ExprKind::Lit(ref mut lit) => {
+                if let ast::LitKind::Float(ref mut symbol, _) = lit.kind {
+                    *symbol = Symbol::intern(&symbol.as_str().to_lowercase());
+                }
+                lit.token = lit.kind.to_lit_token();
+            }
+            ast::ExprKind::Match(_, ref mut arms) => {
+                for arm in arms.iter_mut() {
+                    unwrap_block(&mut arm.body);
+                }
+            }
+            ast::ExprKind::Closure(_, _, _, ref fn_decl, ref mut body, _) => {
+                if let ast::FnRetTy::Default(_) = fn_decl.output {
+                    unwrap_block(body);
+                }
+            }
+            _ => {}
+        }
+        mut_visit::noop_visit_expr(expr, self);
+    }
+
+    fn visit_block(&mut self, block: &mut ptr::P<ast::Block>) {
+        mut_visit::noop_visit_block(block, self);
+        for items in block.stmts.split_mut(|stmt| !is_item_stmt(stmt)) {
+            items.sort_by_cached_key(|stmt| pprust::stmt_to_string(stmt));
+        }
+    }
+
+    fn visit_item_kind(&mut self, kind: &mut ast::ItemKind) {
+        mut_visit::noop_visit_item_kind(kind, self);
+        match *kind {
+            ast::ItemKind::Impl { ref mut items, .. } if self.config.reorder_impl_items() => {
+                items.sort_by_cached_key(|item| {
+                    let rank = match item.kind {
+                        ast::AssocItemKind::TyAlias(..) => 0,
+                        ast::AssocItemKind::Const(..) => 1,
+                        ast::AssocItemKind::MacCall(..) => 2,
+                        ast::AssocItemKind::Fn(..) => 3,
+                    };
+                    (rank, item.ident.to_string())
+                });
+            }
+            ast::ItemKind::ForeignMod(ref mut foreign_mod)
+                if self.config.reorder_foreign_items() =>
+            {
+                foreign_mod.items.sort_by_cached_key(|item| {
+                    let rank = match item.kind {
+                        ast::ForeignItemKind::TyAlias(..) => 0,
+                        ast::ForeignItemKind::Static(..) => 1,
+                        ast::ForeignItemKind::MacCall(..) => 2,
+                        ast::ForeignItemKind::Fn(..) => 3,
+                    };
+                    (rank, item.ident.to_string())
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+fn is_item_stmt(stmt: &ast::Stmt) -> bool {
+    if let ast::StmtKind::Item(..) = stmt.kind {
+        true
+    } else {
+        false
+    }
+}
+
+// Replaces a block holding a single expression by that expression.
+fn unwrap_block(expr: &mut ptr::P<ast::Expr>) {
+    loop {
+        let inner = match expr.kind {
+            ast::ExprKind::Block(ref block, None)
+                if expr.attrs.is_empty() && !is_unsafe_block(block) && block.stmts.len() == 1 =>
+            {
+                match block.stmts[0].kind {
+                    ast::StmtKind::Expr(ref inner) => inner.clone(),
+                    _ => break,
+                }
+            }
+            _ => break,
+        };
+        *expr = inner;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn check(config: &Config, original: &str, formatted: &str) -> Option<usize> {
+        rustc_span::with_session_globals(config.edition().to_libsyntax_pos_edition(), || {
+            changed_item_line(config, original, formatted)
+        })
+    }
+
+    #[test]
+    fn allowed_changes_are_ignored() {
+        let config = Config::default();
+        let unchanged = |original, formatted| check(&config, original, formatted).is_none();
+
+        assert!(unchanged(
+            "const X: u32 = 0xFF_FF;",
+            "const X: u32 = 0xffff;"
+        ));
+        assert!(unchanged(
+            "const X: f64 = 1_0.5E3;",
+            "const X: f64 = 10.5e3;"
+        ));
+        assert!(unchanged("fn f() { g(((a))); }", "fn f() { g((a)); }"));
+        assert!(unchanged(
+            "pub use a::{c, b::{d}};\nuse e;",
+            "use e;\npub use a::b::d;\npub use a::c;",
+        ));
+        assert!(unchanged(
+            "#[derive(Debug)]\n#[derive(Clone)]\nstruct S;",
+            "#[derive(Clone, Debug)]\nstruct S;",
+        ));
+        assert!(unchanged(
+            "#[doc = \"Foo\"]\nfn foo() {}",
+            "/// Foo\n/// and more\nfn foo() {}",
+        ));
+        assert!(unchanged(
+            "fn f() { use b; use a; match x { A => { 1 } _ => 2 } }",
+            "fn f() { use a; use b; match x { A => 1, _ => { 2 } } }",
+        ));
+        assert!(unchanged(
+            "mod m { fn b() {} fn a() {} }",
+            "mod m { fn a() {} fn b() {} }",
+        ));
+
+        let mut config = Config::default();
+        config.set().reorder_impl_items(true);
+        assert_eq!(
+            check(
+                &config,
+                "impl S { fn f() {} type T = u8; }",
+                "impl S { type T = u8; fn f() {} }",
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn changes_are_detected() {
+        let config = Config::default();
+        let changed = |original, formatted| check(&config, original, formatted);
+
+        // The case of identifiers matters.
+        assert_eq!(changed("fn Foo() {}", "fn foo() {}"), Some(1));
+        // Only nested parentheses are redundant, not those of tuples.
+        assert_eq!(
+            changed("fn f() { g((a, b)); }", "fn f() { g(a, b); }"),
+            Some(1)
+        );
+        assert_eq!(changed("fn f() { g((a)); }", "fn f() { g(a); }"), Some(1));
+        // Imports are compared by path.
+        assert_eq!(changed("use a::{b, c};", "use a::{bc};"), Some(1));
+        assert_eq!(changed("use a::b;", "use b::a;"), Some(1));
+        // Underscores are only insignificant within number literals.
+        assert_eq!(changed("const X: u8 = 1_0;", "const X: u8 = 10;"), None);
+        assert_eq!(changed("fn f() { x_1; }", "fn f() { x1; }"), Some(1));
+        // The order of statements matters.
+        assert_eq!(
+            changed(
+                "fn f() {\n    a();\n    b();\n}",
+                "fn f() {\n    b();\n    a();\n}"
+            ),
+            Some(1)
+        );
+        assert_eq!(
+            changed(
+                "fn f() {}\n\nfn g(a: u8) {}",
+                "fn f() {}\n\nfn g(a: u16) {}"
+            ),
+            Some(3)
+        );
+        assert_eq!(
+            changed("mod a { fn f() {} }", "mod b { fn f() {} }"),
+            Some(1)
+        );
+        assert_eq!(changed("fn f() {}", "fn f() {}\nfn g() {}"), Some(1));
+        assert_eq!(changed("fn f() {}", "fn f() {"), Some(1));
+        // Impl items are only reordered with `reorder_impl_items`.
+        assert_eq!(
+            changed(
+                "impl S { fn f() {} type T = u8; }",
+                "impl S { type T = u8; fn f() {} }",
+            ),
+            Some(1)
+        );
+    }
+}
@@ -2,9 +2,10 @@ use std::borrow::Cow;
 use std::cmp::min;
 use std::ops::{Add, Sub};
 
+use crate::utils::unicode_str_width;
 use crate::Config;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub(crate) struct Indent {
     // Width of the block indent, in characters. Must be a multiple of
     // Config::tab_spaces.
@@ -36,6 +37,38 @@ impl Indent {
         }
     }
 
+    /// Returns an indent of `width` characters which extends `self`.
+    ///
+    /// With `smart_tabs`, the last `alignment` characters added to `self` stay alignment and
+    /// the others are block indentation; otherwise this is the same as `Indent::from_width`.
+    pub(crate) fn extend_to_width(
+        &self,
+        config: &Config,
+        width: usize,
+        alignment: usize,
+    ) -> Indent {
+        if config.hard_tabs() && config.smart_tabs() && width >= self.width() {
+            let alignment = self.alignment + min(alignment, width - self.width());
+            Indent::new(width - alignment, alignment)
+        } else {
+            Indent::from_width(config, width)
+        }
+    }
+
+    /// Returns the indent of the end of `line`.
+    ///
+    /// With `smart_tabs`, the leading tabs of `line` are its block indentation and the rest
+    /// of the line is alignment; otherwise this is the same as `Indent::from_width`.
+    pub(crate) fn end_of_line(config: &Config, line: &str) -> Indent {
+        if config.hard_tabs() && config.smart_tabs() {
+            let aligned = line.trim_start_matches('\t');
+            let num_tabs = line.len() - aligned.len();
+            Indent::new(num_tabs * config.tab_spaces(), unicode_str_width(aligned))
+        } else {
+            Indent::from_width(config, unicode_str_width(line))
+        }
+    }
+
     pub(crate) fn empty() -> Indent {
         Indent::new(0, 0)
     }
@@ -74,7 +107,13 @@ impl Indent {
     }
 
     fn to_string_inner(&self, config: &Config, offset: usize) -> Cow<'static, str> {
-        let (num_tabs, num_spaces) = if config.hard_tabs() {
+        let (num_tabs, num_spaces) = if config.hard_tabs() && config.smart_tabs() {
+            // Any part of the block indent that does not fill a whole tab is alignment.
+            (
+                self.block_indent / config.tab_spaces(),
+                self.block_indent % config.tab_spaces() + self.alignment,
+            )
+        } else if config.hard_tabs() {
             (self.block_indent / config.tab_spaces(), self.alignment)
         } else {
             (0, self.width())
@@ -139,7 +178,7 @@ impl Sub<usize> for Indent {
 // 8096 is close enough to infinite for rustfmt.
 const INFINITE_SHAPE_WIDTH: usize = 8096;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub(crate) struct Shape {
     pub(crate) width: usize,
     // The current indentation of code.
@@ -332,6 +371,51 @@ mod test {
         assert_eq!("\t\t    ", indent.to_string(&config));
     }
 
+    #[test]
+    fn indent_to_string_smart_tabs() {
+        let mut config = Config::default();
+        config.set().hard_tabs(true);
+        config.set().smart_tabs(true);
+
+        // 2 tabs + 4 spaces
+        assert_eq!("\t\t    ", Indent::new(8, 4).to_string(&config));
+        // 1 tab + 2 spaces
+        assert_eq!("\t  ", Indent::new(6, 0).to_string(&config));
+    }
+
+    #[test]
+    fn indent_extend_to_width() {
+        let mut config = Config::default();
+        config.set().hard_tabs(true);
+        let indent = Indent::new(4, 0).extend_to_width(&config, 13, 9);
+        assert_eq!(12, indent.block_indent);
+        assert_eq!(1, indent.alignment);
+
+        config.set().smart_tabs(true);
+        let indent = Indent::new(4, 0).extend_to_width(&config, 13, 9);
+        assert_eq!(4, indent.block_indent);
+        assert_eq!(9, indent.alignment);
+        assert_eq!("\t         ", indent.to_string(&config));
+
+        let indent = Indent::new(4, 0).extend_to_width(&config, 13, 1);
+        assert_eq!("\t\t\t ", indent.to_string(&config));
+    }
+
+    #[test]
+    fn indent_end_of_line() {
+        let mut config = Config::default();
+        config.set().hard_tabs(true);
+        let indent = Indent::end_of_line(&config, "let a = 1; ");
+        assert_eq!(8, indent.block_indent);
+        assert_eq!(3, indent.alignment);
+
+        config.set().smart_tabs(true);
+        let indent = Indent::end_of_line(&config, "\tlet a = 1; ");
+        assert_eq!(4, indent.block_indent);
+        assert_eq!(11, indent.alignment);
+        assert_eq!("\t           ", indent.to_string(&config));
+    }
+
     #[test]
     fn shape_visual_indent() {
         let config = Config::default();
@@ -1,25 +1,79 @@
+use std::borrow::Cow;
 use std::fs;
-use std::io::{self, Write};
+use std::io::{self, Read, Write};
 use std::path::Path;
 
-use crate::config::FileName;
+use crate::config::{Config, FileName, FinalNewline};
 use crate::emitter::{self, Emitter};
 use crate::syntux::session::ParseSess;
 use crate::NewlineStyle;
 
-#[cfg(test)]
-use crate::config::Config;
 #[cfg(test)]
 use crate::create_emitter;
 #[cfg(test)]
 use crate::formatting::FileRecord;
 use std::rc::Rc;
 
+/// The UTF-8 byte order mark.
+const BOM: &str = "\u{feff}";
+
 // Append a newline to the end of each file.
 pub(crate) fn append_newline(s: &mut String) {
     s.push_str("\n");
 }
 
+// Remove the line ending at the end of a file, if any.
+fn trim_final_newline(s: &str) -> &str {
+    if s.ends_with("\r\n") {
+        &s[..s.len() - 2]
+    } else if s.ends_with('\n') {
+        &s[..s.len() - 1]
+    } else {
+        s
+    }
+}
+
+/// Returns the shebang line at the start of `text`, without its line ending.
+///
+/// Like the parser, this does not mistake an inner attribute (`#![...]`) for a shebang.
+pub(crate) fn shebang_line(text: &str) -> Option<&str> {
+    let text = text.trim_start_matches(BOM);
+    if !text.starts_with("#!") || text[2..].trim_start().starts_with('[') {
+        return None;
+    }
+    text.lines().next()
+}
+
+// The source map drops a leading byte order mark, so the file itself has to be checked for one.
+fn file_has_bom(filename: &FileName, original: &str) -> Result<bool, io::Error> {
+    match *filename {
+        FileName::Real(ref path) => Ok(original.starts_with(BOM) || starts_with_bom(path)?),
+        FileName::Stdin => Ok(false),
+    }
+}
+
+/// Adds the byte order mark of `filename` back to its formatted text, like `write_file` does.
+#[cfg(test)]
+pub(crate) fn restore_bom(
+    filename: &FileName,
+    formatted_text: &mut String,
+    config: &Config,
+) -> Result<(), io::Error> {
+    if file_has_bom(filename, "")? && !config.strip_bom() {
+        formatted_text.insert_str(0, BOM);
+    }
+    Ok(())
+}
+
+fn starts_with_bom(path: &Path) -> Result<bool, io::Error> {
+    let mut buf = [0; 3];
+    match fs::File::open(path)?.read_exact(&mut buf) {
+        Ok(()) => Ok(buf == BOM.as_bytes()),
+        Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
 #[cfg(test)]
 pub(crate) fn write_all_files<T>(
     source_file: &[FileRecord],
@@ -33,14 +87,7 @@ where
 
     emitter.emit_header(out)?;
     for &(ref filename, ref text) in source_file {
-        write_file(
-            None,
-            filename,
-            text,
-            out,
-            &mut *emitter,
-            config.newline_style(),
-        )?;
+        write_file(None, filename, text, out, &mut *emitter, config)?;
     }
     emitter.emit_footer(out)?;
 
@@ -53,7 +100,7 @@ pub(crate) fn write_file<T>(
     formatted_text: &str,
     out: &mut T,
     emitter: &mut dyn Emitter,
-    newline_style: NewlineStyle,
+    config: &Config,
 ) -> Result<emitter::EmitterResult, io::Error>
 where
     T: Write,
@@ -85,7 +132,8 @@ where
     // left as the default value, then try getting source from the parse session
     // source map instead of hitting the file system. This also supports getting
     // original text for `FileName::Stdin`.
-    let original_text = if newline_style != NewlineStyle::Auto && *filename != FileName::Stdin {
+    let newline_style = config.newline_style();
+    let original = if newline_style != NewlineStyle::Auto && *filename != FileName::Stdin {
         Rc::new(fs::read_to_string(ensure_real_path(filename))?)
     } else {
         match parse_sess.and_then(|sess| sess.get_original_snippet(filename)) {
@@ -94,11 +142,36 @@ where
         }
     };
 
+    // Unless asked to strip it, the byte order mark is preserved.
+    let has_bom = file_has_bom(filename, &original)?;
+    let original_text = if has_bom && !original.starts_with(BOM) {
+        Cow::Owned(format!("{}{}", BOM, original))
+    } else {
+        Cow::Borrowed(original.as_str())
+    };
+    let formatted_text = if has_bom && !config.strip_bom() {
+        Cow::Owned(format!("{}{}", BOM, formatted_text))
+    } else {
+        Cow::Borrowed(formatted_text)
+    };
+    let keep_final_newline = match config.require_final_newline() {
+        FinalNewline::Always => true,
+        FinalNewline::Never => false,
+        FinalNewline::Preserve => original_text.is_empty() || original_text.ends_with('\n'),
+    };
+    let formatted_text = if keep_final_newline {
+        formatted_text
+    } else {
+        Cow::Owned(trim_final_newline(&formatted_text).to_owned())
+    };
+
     let formatted_file = emitter::FormattedFile {
         filename,
-        original_text: original_text.as_str(),
-        formatted_text,
+        original_text: &original_text,
+        formatted_text: &formatted_text,
     };
 
-    emitter.emit_formatted_file(out, formatted_file)
+    let mut result = emitter.emit_formatted_file(out, formatted_file)?;
+    result.changed = original_text != formatted_text;
+    Ok(result)
 }
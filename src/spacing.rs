@@ -0,0 +1,102 @@
+//! The spaces put around colons, range operators and binary operators.
+//!
+//! | Punctuation                                  | Options                                    | Default  |
+//! |----------------------------------------------|--------------------------------------------|----------|
+//! | `:` of annotations, bounds and struct fields | `space_before_colon`, `space_after_colon`  | `x: u32` |
+//! | `..`, `..=` and `...`                        | `spaces_around_ranges`                     | `0..10`  |
+//! | binary operators                             | none, they are always surrounded by spaces | `a + b`  |
+//!
+//! The table applies to expressions, types, patterns and generics alike, e.g. the colon of a type
+//! ascription is spaced like the colon of a `let` statement.
+
+use crate::config::Config;
+
+/// The spacing policy of a config.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Spacing {
+    space_before_colon: bool,
+    space_after_colon: bool,
+    spaces_around_ranges: bool,
+}
+
+impl Spacing {
+    pub(crate) fn new(config: &Config) -> Spacing {
+        Spacing {
+            space_before_colon: config.space_before_colon(),
+            space_after_colon: config.space_after_colon(),
+            spaces_around_ranges: config.spaces_around_ranges(),
+        }
+    }
+
+    /// Returns the colon with its surrounding spaces, e.g. `: `.
+    pub(crate) fn colon(self) -> &'static str {
+        match (self.space_before_colon, self.space_after_colon) {
+            (true, true) => " : ",
+            (true, false) => " :",
+            (false, true) => ": ",
+            (false, false) => ":",
+        }
+    }
+
+    /// Returns the colon with the space before it only, for a colon which is followed by
+    /// alignment padding or by a newline.
+    pub(crate) fn colon_before_padding(self) -> &'static str {
+        if self.space_before_colon {
+            " :"
+        } else {
+            ":"
+        }
+    }
+
+    /// Returns the space after a colon which is followed by alignment padding.
+    pub(crate) fn space_after_colon(self) -> &'static str {
+        if self.space_after_colon {
+            " "
+        } else {
+            ""
+        }
+    }
+
+    /// Returns the range operator `op` with spaces on the sides which have an operand, or `None`
+    /// if `spaces_around_ranges` is off, in which case ranges are written without spaces as
+    /// far as that is valid.
+    pub(crate) fn range(self, op: &str, has_lhs: bool, has_rhs: bool) -> Option<String> {
+        if !self.spaces_around_ranges {
+            return None;
+        }
+        let space_if = |b: bool| if b { " " } else { "" };
+        Some(format!("{}{}{}", space_if(has_lhs), op, space_if(has_rhs)))
+    }
+
+    /// Returns the binary operator `op` with its surrounding spaces.
+    pub(crate) fn binop(self, op: &str) -> String {
+        format!(" {} ", op)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn spacing_follows_config() {
+        let mut config = Config::default();
+        let spacing = Spacing::new(&config);
+        assert_eq!(spacing.colon(), ": ");
+        assert_eq!(spacing.colon_before_padding(), ":");
+        assert_eq!(spacing.space_after_colon(), " ");
+        assert_eq!(spacing.range("..=", true, true), None);
+        assert_eq!(spacing.binop("+"), " + ");
+
+        config.set().space_before_colon(true);
+        config.set().space_after_colon(false);
+        config.set().spaces_around_ranges(true);
+        let spacing = Spacing::new(&config);
+        assert_eq!(spacing.colon(), " :");
+        assert_eq!(spacing.colon_before_padding(), " :");
+        assert_eq!(spacing.space_after_colon(), "");
+        assert_eq!(spacing.range("..=", true, true).as_deref(), Some(" ..= "));
+        assert_eq!(spacing.range("..", false, true).as_deref(), Some(".. "));
+        assert_eq!(spacing.range("..", true, false).as_deref(), Some(" .."));
+    }
+}
@@ -1,14 +1,17 @@
 use rustc_ast::ast;
 use rustc_span::Span;
 
+use std::ptr;
+
+use crate::closures::expr_requires_semi_to_be_stmt;
 use crate::comment::recover_comment_removed;
-use crate::config::Version;
+use crate::config::{ReturnStyle, Version};
 use crate::expr::{format_expr, ExprType};
 use crate::rewrite::{Rewrite, RewriteContext};
 use crate::shape::Shape;
 use crate::source_map::LineRangeUtils;
 use crate::spanned::Spanned;
-use crate::utils::semicolon_for_stmt;
+use crate::utils::{left_most_sub_expr, semicolon_for_stmt};
 
 pub(crate) struct Stmt<'a> {
     inner: &'a ast::Stmt,
@@ -110,3 +113,98 @@ fn format_stmt(
     };
     result.and_then(|res| recover_comment_removed(res, stmt.span(), context))
 }
+
+/// Returns the statement ending the body of a function which `return_style` converts, i.e. a
+/// `return expr;` for `Implicit` and a tail expression for `Explicit`, if converting it is known
+/// to keep the meaning of the code.
+pub(crate) fn convertible_fn_body_tail<'a>(
+    return_style: ReturnStyle,
+    decl: &ast::FnDecl,
+    block: &'a ast::Block,
+) -> Option<&'a ast::Stmt> {
+    if let ast::FnRetTy::Default(..) = decl.output {
+        return None;
+    }
+    let stmt = block.stmts.last()?;
+    let expr = match (return_style, &stmt.kind) {
+        (ReturnStyle::Implicit, ast::StmtKind::Expr(expr))
+        | (ReturnStyle::Implicit, ast::StmtKind::Semi(expr)) => match expr.kind {
+            ast::ExprKind::Ret(Some(ref value)) if can_be_tail_expr(value, block) => expr,
+            _ => return None,
+        },
+        (ReturnStyle::Explicit, ast::StmtKind::Expr(expr)) => match expr.kind {
+            ast::ExprKind::Ret(..)
+            | ast::ExprKind::Break(..)
+            | ast::ExprKind::Continue(..)
+            | ast::ExprKind::Assign(..)
+            | ast::ExprKind::AssignOp(..)
+            | ast::ExprKind::MacCall(..) => return None,
+            // Keep `if`, `match` and the like as they are.
+            _ if !expr_requires_semi_to_be_stmt(expr) => return None,
+            _ if !keeps_drop_order(expr, block) => return None,
+            _ => expr,
+        },
+        _ => return None,
+    };
+    if expr.attrs.is_empty() {
+        Some(stmt)
+    } else {
+        None
+    }
+}
+
+// A tail expression starting with a block-like expression would end at the block.
+fn can_be_tail_expr(value: &ast::Expr, block: &ast::Block) -> bool {
+    !starts_with_block_like(value) && keeps_drop_order(value, block)
+}
+
+// The temporaries of a tail expression are dropped after the local variables of the block, but
+// those of a returned expression before them. So converting between the two is only known to be
+// fine if the expression has no temporaries, or if the block has no local variables.
+fn keeps_drop_order(value: &ast::Expr, block: &ast::Block) -> bool {
+    let is_simple = match value.kind {
+        ast::ExprKind::Lit(..) | ast::ExprKind::Path(..) => true,
+        _ => false,
+    };
+    let has_locals = block.stmts.iter().any(|stmt| match stmt.kind {
+        ast::StmtKind::Local(..) => true,
+        _ => false,
+    });
+    is_simple || !has_locals
+}
+
+fn starts_with_block_like(expr: &ast::Expr) -> bool {
+    let left_most = match expr.kind {
+        ast::ExprKind::MethodCall(_, ref args, _) => &args[0],
+        _ => left_most_sub_expr(expr),
+    };
+    !ptr::eq(left_most, expr)
+        && (!expr_requires_semi_to_be_stmt(left_most) || starts_with_block_like(left_most))
+}
+
+/// Rewrites a statement returned by `convertible_fn_body_tail` in the other style.
+pub(crate) fn rewrite_fn_body_tail(
+    context: &RewriteContext<'_>,
+    shape: Shape,
+    stmt: &ast::Stmt,
+) -> Option<String> {
+    let result = match stmt.kind {
+        ast::StmtKind::Expr(ref expr) | ast::StmtKind::Semi(ref expr) => match expr.kind {
+            ast::ExprKind::Ret(Some(ref value)) => {
+                format_expr(value, ExprType::Statement, context, shape)
+            }
+            _ => {
+                let suffix = if context.config.trailing_semicolon() {
+                    ";"
+                } else {
+                    ""
+                };
+                let shape = shape.offset_left(7)?.sub_width(suffix.len())?;
+                format_expr(expr, ExprType::SubExpression, context, shape)
+                    .map(|s| format!("return {}{}", s, suffix))
+            }
+        },
+        _ => None,
+    }?;
+    recover_comment_removed(result, stmt.span(), context)
+}
@@ -4,7 +4,7 @@ use regex::Regex;
 use unicode_categories::UnicodeCategories;
 use unicode_segmentation::UnicodeSegmentation;
 
-use crate::config::Config;
+use crate::config::{Config, HexEscapeStyle, LiteralCase};
 use crate::shape::Shape;
 use crate::utils::{unicode_str_width, wrap_str};
 
@@ -153,6 +153,73 @@ pub(crate) fn rewrite_string<'a>(
     wrap_str(result, fmt.config.max_width(), fmt.shape)
 }
 
+/// Rewrites the `\x` and `\u{..}` escape sequences of a char, byte or (byte) string literal as
+/// configured by `hex_escape_style` and `escape_hex_case`. `lit` includes the quotes.
+pub(crate) fn normalize_escapes(lit: &str, is_byte: bool, config: &Config) -> String {
+    if config.hex_escape_style() == HexEscapeStyle::Preserve
+        && config.escape_hex_case() == LiteralCase::Preserve
+    {
+        return lit.to_owned();
+    }
+
+    let mut result = String::with_capacity(lit.len());
+    let mut rest = lit;
+    while let Some(pos) = rest.find('\\') {
+        result.push_str(&rest[..pos]);
+        let escape = &rest[pos..];
+        let (len, rewrite) = match escape[1..].chars().next() {
+            Some('x') if escape.get(2..4).is_some() => (
+                4,
+                normalize_hex_escape(&escape[2..4], false, is_byte, config),
+            ),
+            Some('u') if escape[2..].starts_with('{') && escape.contains('}') => {
+                let len = escape.find('}').unwrap() + 1;
+                (
+                    len,
+                    normalize_hex_escape(&escape[3..len - 1], true, is_byte, config),
+                )
+            }
+            Some(c) => (1 + c.len_utf8(), None),
+            None => (1, None),
+        };
+        match rewrite {
+            Some(rewrite) => result.push_str(&rewrite),
+            None => result.push_str(&escape[..len]),
+        }
+        rest = &escape[len..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Rewrites a single hex escape given its digits, or returns `None` if they are not valid.
+fn normalize_hex_escape(
+    digits: &str,
+    is_unicode: bool,
+    is_byte: bool,
+    config: &Config,
+) -> Option<String> {
+    let value = u32::from_str_radix(&digits.replace('_', ""), 16).ok()?;
+    // `\x` escapes above 0x7f are only allowed in byte literals, which in turn have no `\u{..}`.
+    let fits_hex = value <= 0x7f || (is_byte && value <= 0xff);
+    let to_unicode = match config.hex_escape_style() {
+        HexEscapeStyle::Preserve => is_unicode,
+        HexEscapeStyle::Hex => !fits_hex,
+        HexEscapeStyle::Unicode => !is_byte,
+    };
+    let digits = match (is_unicode, to_unicode) {
+        (true, true) | (false, false) => digits.to_owned(),
+        (false, true) => format!("{:x}", value),
+        (true, false) => format!("{:02x}", value),
+    };
+    let digits = config.escape_hex_case().apply(&digits);
+    if to_unicode {
+        Some(format!("\\u{{{}}}", digits))
+    } else {
+        Some(format!("\\x{}", digits))
+    }
+}
+
 /// Returns the index to the end of the URL if the split at index of the given string includes an
 /// URL or alike. Otherwise, returns `None`.
 fn detect_url(s: &[&str], index: usize) -> Option<usize> {
@@ -279,11 +346,14 @@ fn break_string(max_width: usize, trim_end: bool, line_end: &str, input: &[&str]
         cur_index
     };
 
+    let units = BreakUnits::new(input);
+
     // Find the position in input for breaking the string
     if line_end.is_empty()
         && trim_end
         && !is_whitespace(input[max_width_index_in_input - 1])
         && is_whitespace(input[max_width_index_in_input])
+        && units.can_break_after(max_width_index_in_input - 1)
     {
         // At a breaking point already
         // The line won't invalidate the rewriting because:
@@ -305,27 +375,31 @@ fn break_string(max_width: usize, trim_end: bool, line_end: &str, input: &[&str]
         };
     }
 
-    match input[0..max_width_index_in_input]
-        .iter()
-        .rposition(|grapheme| is_whitespace(grapheme))
+    let is_whitespace_at = |i: usize| is_whitespace(input[i]) && units.can_break_after(i);
+    let is_boundary_at = |i: usize| {
+        (is_punctuation(input[i]) || units.is_unit_boundary(i)) && units.can_break_after(i)
+    };
+    match (0..max_width_index_in_input)
+        .rev()
+        .find(|&i| is_whitespace_at(i))
     {
         // Found a whitespace and what is on its left side is big enough.
         Some(index) if index >= MIN_STRING => break_at(index),
-        // No whitespace found, try looking for a punctuation instead
-        _ => match input[0..max_width_index_in_input]
-            .iter()
-            .rposition(|grapheme| is_punctuation(grapheme))
+        // No whitespace found, try looking for a punctuation, an escape sequence or a format
+        // specifier instead
+        _ => match (0..max_width_index_in_input)
+            .rev()
+            .find(|&i| is_boundary_at(i))
         {
-            // Found a punctuation and what is on its left side is big enough.
+            // Found a boundary and what is on its left side is big enough.
             Some(index) if index >= MIN_STRING => break_at(index),
             // Either no boundary character was found to the left of `input[max_chars]`, or the line
             // got too small. We try searching for a boundary character to the right.
-            _ => match input[max_width_index_in_input..]
-                .iter()
-                .position(|grapheme| is_whitespace(grapheme) || is_punctuation(grapheme))
+            _ => match (max_width_index_in_input..input.len())
+                .find(|&i| is_whitespace_at(i) || is_boundary_at(i))
             {
                 // A boundary was found after the line limit
-                Some(index) => break_at(max_width_index_in_input + index),
+                Some(index) => break_at(index),
                 // No boundary to the right, the input cannot be broken
                 None => SnippetState::EndOfInput(input.concat()),
             },
@@ -333,6 +407,89 @@ fn break_string(max_width: usize, trim_end: bool, line_end: &str, input: &[&str]
     }
 }
 
+/// Escape sequences (e.g., `\n`, `\x41` or `\u{1F600}`) and format specifiers (e.g., `{}` or
+/// `{:>8.2}`) found in a piece of text, which must not be split across lines.
+struct BreakUnits {
+    /// `starts[i]` is `true` if a unit starts at `input[i]`.
+    starts: Vec<bool>,
+    /// `ends[i]` is `true` if a unit ends at `input[i]`.
+    ends: Vec<bool>,
+    /// `inside[i]` is `true` if `input[i]` is part of a unit but not its last grapheme.
+    inside: Vec<bool>,
+}
+
+impl BreakUnits {
+    fn new(input: &[&str]) -> BreakUnits {
+        let mut starts = vec![false; input.len()];
+        let mut ends = vec![false; input.len()];
+        let mut inside = vec![false; input.len()];
+        let mut i = 0;
+        while i < input.len() {
+            let len = escape_sequence_len(&input[i..]).or_else(|| format_spec_len(&input[i..]));
+            match len {
+                Some(len) if len > 1 => {
+                    starts[i] = true;
+                    ends[i + len - 1] = true;
+                    for flag in &mut inside[i..i + len - 1] {
+                        *flag = true;
+                    }
+                    i += len;
+                }
+                _ => i += 1,
+            }
+        }
+        BreakUnits {
+            starts,
+            ends,
+            inside,
+        }
+    }
+
+    /// Returns `true` if the text may be broken right after `input[index]`.
+    fn can_break_after(&self, index: usize) -> bool {
+        !self.inside[index]
+    }
+
+    /// Returns `true` if `input[index]` ends a unit or the next grapheme starts one.
+    fn is_unit_boundary(&self, index: usize) -> bool {
+        self.ends[index] || self.starts.get(index + 1).cloned().unwrap_or(false)
+    }
+}
+
+/// Returns the number of graphemes of the escape sequence at the start of `input`.
+fn escape_sequence_len(input: &[&str]) -> Option<usize> {
+    if input.first() != Some(&"\\") {
+        return None;
+    }
+    let len = match input.get(1) {
+        // `\x7F`
+        Some(&"x") => 4,
+        // `\u{10FFFF}`
+        Some(&"u") => input
+            .iter()
+            .position(|g| *g == "}")
+            .map_or(2, |pos| pos + 1),
+        Some(_) => 2,
+        None => 1,
+    };
+    Some(len.min(input.len()))
+}
+
+/// Returns the number of graphemes of the format specifier (or escaped brace) at the start of
+/// `input`.
+fn format_spec_len(input: &[&str]) -> Option<usize> {
+    match (input.first(), input.get(1)) {
+        (Some(&"{"), Some(&"{")) | (Some(&"}"), Some(&"}")) => Some(2),
+        (Some(&"{"), _) => input
+            .iter()
+            .skip(1)
+            .take_while(|g| !is_whitespace(g) && **g != "{")
+            .position(|g| *g == "}")
+            .map(|pos| pos + 2),
+        _ => None,
+    }
+}
+
 fn is_new_line(grapheme: &str) -> bool {
     let bytes = grapheme.as_bytes();
     bytes.starts_with(b"\n") || bytes.starts_with(b"\r\n")
@@ -355,7 +512,7 @@ fn graphemes_width(graphemes: &[&str]) -> usize {
 #[cfg(test)]
 mod test {
     use super::{break_string, detect_url, rewrite_string, SnippetState, StringFormat};
-    use crate::config::Config;
+    use crate::config::{Config, HexEscapeStyle, LiteralCase};
     use crate::shape::{Indent, Shape};
     use unicode_segmentation::UnicodeSegmentation;
 
@@ -404,6 +561,33 @@ mod test {
         );
     }
 
+    #[test]
+    fn should_not_break_escape_sequences() {
+        let string = "Loremipsumdolorsit\\x41amet";
+        let graphemes = UnicodeSegmentation::graphemes(&*string, false).collect::<Vec<&str>>();
+        assert_eq!(
+            break_string(20, false, "\\", &graphemes[..]),
+            SnippetState::LineEnd("Loremipsumdolorsit".to_string(), 18)
+        );
+
+        let string = "Loremipsumdolor\\nsitametconsectetur";
+        let graphemes = UnicodeSegmentation::graphemes(&*string, false).collect::<Vec<&str>>();
+        assert_eq!(
+            break_string(20, false, "\\", &graphemes[..]),
+            SnippetState::LineEnd("Loremipsumdolor\\n".to_string(), 17)
+        );
+    }
+
+    #[test]
+    fn should_not_break_format_specifiers() {
+        let string = "Loremipsumdolorsit{:>8}amet";
+        let graphemes = UnicodeSegmentation::graphemes(&*string, false).collect::<Vec<&str>>();
+        assert_eq!(
+            break_string(20, false, "\\", &graphemes[..]),
+            SnippetState::LineEnd("Loremipsumdolorsit".to_string(), 18)
+        );
+    }
+
     #[test]
     fn nothing_to_break() {
         let string = "Venenatis_tellus_vel_tellus";
@@ -133,7 +133,7 @@ impl<'a> Parser<'a> {
                     return Ok(m);
                 }
 
-                if sess.can_reset_errors() {
+                if sess.can_reset_errors() || sess.can_recover_errors() {
                     sess.reset_errors();
                     return Ok(m);
                 }
@@ -145,6 +145,28 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Parses `text` as the items of a module, without resolving its out of line modules.
+    pub(crate) fn parse_str_as_module(
+        sess: &'a ParseSess,
+        text: String,
+    ) -> Result<(ast::Mod, Vec<ast::Attribute>), ParserError> {
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            let mut parser = ParserBuilder::parser(sess.inner(), Input::Text(text)).ok()?;
+            match parser.parse_mod(&TokenKind::Eof, ast::Unsafe::No) {
+                Ok(result) => Some(result),
+                Err(mut e) => {
+                    e.cancel();
+                    None
+                }
+            }
+        }));
+        match result {
+            Ok(Some(m)) if !sess.has_errors() => Ok(m),
+            Ok(_) => Err(ParserError::ParseError),
+            Err(_) => Err(ParserError::ParsePanicError),
+        }
+    }
+
     pub(crate) fn parse_crate(
         config: &'a Config,
         input: Input,
@@ -170,7 +192,7 @@ impl<'a> Parser<'a> {
                     return Ok(krate);
                 }
 
-                if self.sess.can_reset_errors() {
+                if self.sess.can_reset_errors() || self.sess.can_recover_errors() {
                     self.sess.reset_errors();
                     return Ok(krate);
                 }
@@ -23,6 +23,9 @@ pub(crate) struct ParseSess {
     parse_sess: RawParseSess,
     ignore_path_set: Rc<IgnorePathSet>,
     can_reset_errors: Rc<RefCell<bool>>,
+    // The spans of the errors reported by the parser, except in ignored files.
+    error_spans: Rc<RefCell<Vec<Span>>>,
+    recover_errors: bool,
 }
 
 /// Emitter which discards every error.
@@ -46,12 +49,16 @@ struct SilentOnIgnoredFilesEmitter {
     emitter: Box<dyn Emitter + Send>,
     has_non_ignorable_parser_errors: bool,
     can_reset: Rc<RefCell<bool>>,
+    error_spans: Rc<RefCell<Vec<Span>>>,
 }
 
 impl SilentOnIgnoredFilesEmitter {
     fn handle_non_ignoreable_error(&mut self, db: &Diagnostic) {
         self.has_non_ignorable_parser_errors = true;
         *self.can_reset.borrow_mut() = false;
+        if let Some(primary_span) = db.span.primary_span() {
+            self.error_spans.borrow_mut().push(primary_span);
+        }
         self.emitter.emit_diagnostic(db);
     }
 }
@@ -87,6 +94,7 @@ fn default_handler(
     source_map: Rc<SourceMap>,
     ignore_path_set: Rc<IgnorePathSet>,
     can_reset: Rc<RefCell<bool>>,
+    error_spans: Rc<RefCell<Vec<Span>>>,
     hide_parse_errors: bool,
 ) -> Handler {
     let supports_color = term::stderr().map_or(false, |term| term.supports_color());
@@ -117,6 +125,7 @@ fn default_handler(
             emitter,
             ignore_path_set,
             can_reset,
+            error_spans,
         }),
     )
 }
@@ -129,11 +138,13 @@ impl ParseSess {
         };
         let source_map = Rc::new(SourceMap::new(FilePathMapping::empty()));
         let can_reset_errors = Rc::new(RefCell::new(false));
+        let error_spans = Rc::new(RefCell::new(vec![]));
 
         let handler = default_handler(
             Rc::clone(&source_map),
             Rc::clone(&ignore_path_set),
             Rc::clone(&can_reset_errors),
+            Rc::clone(&error_spans),
             config.hide_parse_errors(),
         );
         let parse_sess = RawParseSess::with_span_handler(handler, source_map);
@@ -142,9 +153,26 @@ impl ParseSess {
             parse_sess,
             ignore_path_set,
             can_reset_errors,
+            error_spans,
+            recover_errors: config.recover_parse_errors(),
         })
     }
 
+    /// Whether the parser recovered from errors, in which case the code with errors is left as
+    /// it is.
+    pub(crate) fn has_recovered_errors(&self) -> bool {
+        self.recover_errors && !self.error_spans.borrow().is_empty()
+    }
+
+    /// Whether `span` contains an error the parser recovered from which isn't contained in one
+    /// of the `children` spans.
+    pub(crate) fn has_recovered_errors_outside(&self, span: Span, children: &[Span]) -> bool {
+        self.recover_errors
+            && self.error_spans.borrow().iter().any(|error| {
+                span.contains(*error) && !children.iter().any(|child| child.contains(*error))
+            })
+    }
+
     pub(crate) fn default_submod_path(
         &self,
         id: symbol::Ident,
@@ -238,6 +266,11 @@ impl ParseSess {
         self.parse_sess.span_diagnostic.has_errors()
     }
 
+    /// Whether the code which is parsed despite errors is formatted.
+    pub(super) fn can_recover_errors(&self) -> bool {
+        self.recover_errors
+    }
+
     pub(super) fn reset_errors(&self) {
         self.parse_sess.span_diagnostic.reset_err_count();
     }
@@ -325,6 +358,7 @@ mod tests {
                 emitter: Box::new(emitter_writer),
                 ignore_path_set,
                 can_reset,
+                error_spans: Rc::new(RefCell::new(vec![])),
             }
         }
 
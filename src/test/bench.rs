@@ -0,0 +1,173 @@
+//! Benchmarks of the formatting of large generated files, run with
+//! `cargo test -- --ignored formatting_performance`.
+//!
+//! Each case fails if formatting it takes longer than its time limit. The limits are generous so
+//! that only regressions in the complexity of the heuristics trip them, and they can be scaled
+//! for slow machines with the `RUSTFMT_BENCH_SCALE` environment variable, e.g. `2.0`.
+
+use std::cell::RefCell;
+use std::env;
+use std::fmt::Write as _;
+use std::io;
+use std::rc::Rc;
+
+use crate::config::{Config, EmitMode, Verbosity};
+use crate::{Emitter, EmitterResult, FormattedFile, Input, RunStats, Session};
+
+// The number of times each case is formatted. The fastest run is compared to the limit.
+const RUNS: usize = 3;
+
+struct BenchCase {
+    name: &'static str,
+    source: fn() -> String,
+    /// The time formatting may take, in seconds.
+    format_time_limit: f32,
+}
+
+const CASES: &[BenchCase] = &[
+    BenchCase {
+        name: "deep chains",
+        source: deep_chains,
+        format_time_limit: 2.0,
+    },
+    BenchCase {
+        name: "giant match",
+        source: giant_match,
+        format_time_limit: 2.0,
+    },
+    BenchCase {
+        name: "huge struct",
+        source: huge_struct,
+        format_time_limit: 2.0,
+    },
+    BenchCase {
+        name: "nested blocks",
+        source: nested_blocks,
+        format_time_limit: 2.0,
+    },
+];
+
+// Records the statistics of the runs of a session, without emitting anything.
+struct StatsRecorder(Rc<RefCell<Vec<RunStats>>>);
+
+impl Emitter for StatsRecorder {
+    fn emit_formatted_file(
+        &mut self,
+        _output: &mut dyn io::Write,
+        _formatted_file: FormattedFile<'_>,
+    ) -> Result<EmitterResult, io::Error> {
+        Ok(EmitterResult::default())
+    }
+
+    fn record_stats(&mut self, stats: RunStats) {
+        self.0.borrow_mut().push(stats);
+    }
+}
+
+fn deep_chains() -> String {
+    let mut source = String::new();
+    for i in 0..200 {
+        let _ = write!(source, "fn chain_{}() {{\n    let x = foo", i);
+        for j in 0..30 {
+            let _ = write!(source, ".method_{}(arg_{}, |x| x + {})", j, j, j);
+        }
+        source.push_str(".collect::<Vec<_>>();\n}\n\n");
+    }
+    source
+}
+
+fn giant_match() -> String {
+    let mut source = String::from("fn giant_match(x: u32) -> u32 {\n    match x {\n");
+    for i in 0..5000 {
+        let _ = write!(
+            source,
+            "        {} => {{ let y = x * {}; if y > {} {{ y - {} }} else {{ y + {} }} }}\n",
+            i, i, i, i, i
+        );
+    }
+    source.push_str("        _ => 0,\n    }\n}\n");
+    source
+}
+
+fn huge_struct() -> String {
+    let mut source = String::from("pub struct Huge {\n");
+    for i in 0..5000 {
+        let _ = write!(
+            source,
+            "    /// Field {}.\n    pub field_{}: Vec<u8>,\n",
+            i, i
+        );
+    }
+    source.push_str("}\n\nfn huge() -> Huge {\n    Huge {\n");
+    for i in 0..5000 {
+        let _ = write!(source, "        field_{}: vec![{}],\n", i, i);
+    }
+    source.push_str("    }\n}\n");
+    source
+}
+
+fn nested_blocks() -> String {
+    let depth = 60;
+    let mut source = String::from("fn nested() {\n");
+    for i in 0..depth {
+        let _ = write!(source, "if a_{} {{ let b = call({}, |c| {{ ", i, i);
+    }
+    source.push_str("done();");
+    for _ in 0..depth {
+        source.push_str(" }); }");
+    }
+    source.push_str("\n}\n");
+    source
+}
+
+// Formats `source` and returns the fastest of the recorded runs.
+fn bench(source: &str) -> RunStats {
+    let mut config = Config::default();
+    config.set().emit_mode(EmitMode::Stdout);
+    config.set().verbose(Verbosity::Quiet);
+
+    let stats = Rc::new(RefCell::new(vec![]));
+    let mut out: Vec<u8> = vec![];
+    {
+        let emitter = Box::new(StatsRecorder(Rc::clone(&stats)));
+        let mut session = Session::with_emitter(config, Some(&mut out), emitter);
+        for _ in 0..RUNS {
+            session.format(Input::Text(source.to_owned())).unwrap();
+        }
+    }
+    let stats = stats.borrow();
+    *stats
+        .iter()
+        .min_by(|a, b| a.format_time.partial_cmp(&b.format_time).unwrap())
+        .unwrap()
+}
+
+#[ignore]
+#[test]
+fn formatting_performance() {
+    let scale = env::var("RUSTFMT_BENCH_SCALE")
+        .ok()
+        .and_then(|scale| scale.parse::<f32>().ok())
+        .unwrap_or(1.0);
+
+    let mut failures = vec![];
+    for case in CASES {
+        let source = (case.source)();
+        let stats = bench(&source);
+        println!(
+            "{}: {} lines, parsed in {:.3}s, formatted in {:.3}s",
+            case.name,
+            source.lines().count(),
+            stats.parse_time,
+            stats.format_time
+        );
+        let limit = case.format_time_limit * scale;
+        if stats.format_time > limit {
+            failures.push(format!(
+                "{} took {:.3}s to format, more than the limit of {:.3}s",
+                case.name, stats.format_time, limit
+            ));
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
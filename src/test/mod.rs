@@ -1,3 +1,4 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::env;
 use std::fs;
@@ -9,15 +10,21 @@ use std::process::{Command, Stdio};
 use std::str::Chars;
 use std::thread;
 
-use crate::config::{Color, Config, EmitMode, FileName, NewlineStyle, ReportTactic};
+use rustc_ast::ast;
+
+use crate::config::{
+    Color, Config, EmitMode, FileName, FinalNewline, NewlineStyle, ReportTactic, Verbosity,
+};
 use crate::formatting::{ReportedErrors, SourceFile};
 use crate::modules::{ModuleResolutionError, ModuleResolutionErrorKind};
 use crate::rustfmt_diff::{make_diff, print_diff, DiffLine, Mismatch, ModifiedChunk, OutputWriter};
 use crate::source_file;
 use crate::{
-    is_nightly_channel, ErrorKind, FormatReport, FormatReportFormatterBuilder, Input, Session,
+    is_nightly_channel, CancellationToken, Emitter, EmitterResult, ErrorKind, FormatReport,
+    FormatReportFormatterBuilder, FormattedFile, Input, RewriteContext, RewriteHook, Session,
 };
 
+mod bench;
 mod configuration_snippet;
 
 const DIFF_CONTEXT_SIZE: usize = 3;
@@ -27,6 +34,7 @@ const SKIP_FILE_WHITE_LIST: &[&str] = &[
     // We want to make sure that the `skip_children` is correctly working,
     // so we do not want to test this file directly.
     "configs/skip_children/foo/mod.rs",
+    "configs/module_traversal/mods",
     "issue-3434/no_entry.rs",
     "issue-3665/sub_mod.rs",
     // Testing for issue-3779
@@ -43,6 +51,8 @@ const SKIP_FILE_WHITE_LIST: &[&str] = &[
     "cfg_mod/foo.rs",
     "cfg_mod/wasm32.rs",
     "skip/foo.rs",
+    // This directory is a part of the files included with `include!`.
+    "configs/format_included_files/included",
 ];
 
 fn init_log() {
@@ -424,6 +434,7 @@ fn stdin_works_with_modified_lines() {
         session.format(input).unwrap();
         let errors = ReportedErrors {
             has_diff: true,
+            files_changed: 1,
             ..Default::default()
         };
         assert_eq!(session.errors, errors);
@@ -431,6 +442,258 @@ fn stdin_works_with_modified_lines() {
     assert_eq!(buf, output.as_bytes());
 }
 
+#[test]
+fn counts_changed_and_unchanged_files() {
+    init_log();
+    let mut config = Config::default();
+    config.set().emit_mode(EmitMode::Stdout);
+    config.set().verbose(Verbosity::Quiet);
+    let mut buf: Vec<u8> = vec![];
+    let mut session = Session::new(config, Some(&mut buf));
+    for input in &["fn main() {}\n", "fn main () {}\n", "fn main() {}\n"] {
+        session.format(Input::Text(input.to_string())).unwrap();
+    }
+    assert_eq!(session.files_changed(), 1);
+    assert_eq!(session.files_unchanged(), 2);
+}
+
+#[test]
+fn maps_original_lines_to_formatted_lines() {
+    init_log();
+    let input = "fn foo(a: u32,\n       b: u32) {}\n\n// Comment\n\n\nfn bar() {}\n";
+    let mut config = Config::default();
+    config.set().emit_mode(EmitMode::Stdout);
+    config.set().verbose(Verbosity::Quiet);
+    let mut buf: Vec<u8> = vec![];
+    let report = Session::new(config, Some(&mut buf))
+        .format(Input::Text(input.to_owned()))
+        .unwrap();
+    let mapping = report.line_mapping(&FileName::Stdin).unwrap();
+    assert_eq!(mapping.formatted_line(1), 1);
+    assert_eq!(mapping.formatted_line(4), 3);
+    assert_eq!(mapping.formatted_line(7), 5);
+}
+
+#[test]
+fn maps_lines_and_checks_the_text_kept_by_minimal_diff() {
+    init_log();
+    let input = "fn foo(a: u32,\n       b: u32) {}\n\n\n\n// TODO: bar\nfn bar() {}\n";
+    let mut config = Config::default();
+    config.set().emit_mode(EmitMode::Stdout);
+    config.set().verbose(Verbosity::Quiet);
+    config.set().minimal_diff(true);
+    config.set().report_todo(ReportTactic::Always);
+    let mut buf: Vec<u8> = vec![];
+    let report = Session::new(config, Some(&mut buf))
+        .format(Input::Text(input.to_owned()))
+        .unwrap();
+    // Only whitespace changes, so every original line is kept.
+    assert_eq!(String::from_utf8(buf).unwrap(), input);
+    let mapping = report.line_mapping(&FileName::Stdin).unwrap();
+    assert_eq!(mapping.formatted_line(2), 2);
+    assert_eq!(mapping.formatted_line(6), 6);
+    assert_eq!(mapping.formatted_line(7), 7);
+    assert!(report.to_string().contains("stdin:6"));
+}
+
+#[test]
+fn skipped_items_are_not_checked() {
+    init_log();
+    let input = "#[rustfmt::skip]\nfn foo() {   \n    // TODO: bar\n}\n";
+    let mut config = Config::default();
+    config.set().emit_mode(EmitMode::Stdout);
+    config.set().verbose(Verbosity::Quiet);
+    config.set().report_todo(ReportTactic::Always);
+    let mut buf: Vec<u8> = vec![];
+    let report = Session::new(config, Some(&mut buf))
+        .format(Input::Text(input.to_owned()))
+        .unwrap();
+    assert!(!report.has_warnings());
+    assert!(!report.skipped_ranges(&FileName::Stdin).is_empty());
+}
+
+#[test]
+fn file_stats_count_rewrites() {
+    init_log();
+    let input = "fn foo() {\n    bar();\n}\n\nstruct Baz;\n";
+    let mut config = Config::default();
+    config.set().emit_mode(EmitMode::Stdout);
+    config.set().verbose(Verbosity::Quiet);
+    let mut buf: Vec<u8> = vec![];
+    let report = Session::new(config, Some(&mut buf))
+        .format(Input::Text(input.to_owned()))
+        .unwrap();
+    let file_stats = report.file_stats();
+    let stats = &file_stats[&FileName::Stdin];
+    assert_eq!(stats.items_formatted, 3);
+    assert_eq!(stats.rewrite_fallbacks, 0);
+    assert_eq!(report.run_stats().items_formatted, 3);
+}
+
+struct RecordingEmitter<'a> {
+    formatted: &'a RefCell<Vec<String>>,
+}
+
+impl Emitter for RecordingEmitter<'_> {
+    fn emit_formatted_file(
+        &mut self,
+        _output: &mut dyn Write,
+        formatted_file: FormattedFile<'_>,
+    ) -> Result<EmitterResult, io::Error> {
+        let has_diff = formatted_file.original_text != formatted_file.formatted_text;
+        self.formatted
+            .borrow_mut()
+            .push(formatted_file.formatted_text.to_owned());
+        Ok(EmitterResult {
+            has_diff,
+            changed: has_diff,
+        })
+    }
+}
+
+#[test]
+fn session_uses_custom_emitter() {
+    init_log();
+    let formatted = RefCell::new(vec![]);
+    let mut buf: Vec<u8> = vec![];
+    {
+        let emitter = Box::new(RecordingEmitter {
+            formatted: &formatted,
+        });
+        let mut session = Session::with_emitter(Config::default(), Some(&mut buf), emitter);
+        session
+            .format(Input::Text("fn main () {}\n".to_owned()))
+            .unwrap();
+        assert!(session.has_diff());
+    }
+    assert_eq!(formatted.into_inner(), vec!["fn main() {}\n".to_owned()]);
+    assert!(buf.is_empty());
+}
+
+// Leaves structs as they are, and puts constants in upper case.
+struct ShoutingConsts;
+
+impl RewriteHook for ShoutingConsts {
+    fn pre_rewrite_item(
+        &self,
+        item: &ast::Item,
+        _context: &RewriteContext<'_>,
+        text: &str,
+    ) -> Option<String> {
+        match item.kind {
+            ast::ItemKind::Struct(..) => Some(text.to_owned()),
+            _ => None,
+        }
+    }
+
+    fn post_rewrite_item(
+        &self,
+        item: &ast::Item,
+        _context: &RewriteContext<'_>,
+        text: &str,
+    ) -> Option<String> {
+        match item.kind {
+            ast::ItemKind::Const(..) => Some(text.to_uppercase()),
+            _ => None,
+        }
+    }
+}
+
+#[test]
+fn session_calls_rewrite_hooks() {
+    init_log();
+    let input = "#[derive(Debug)]\nstruct  A { a:u32 }\nconst a:u32=1;\nfn foo() {\n    const b:u32=2;\n}\n";
+    let mut config = Config::default();
+    config.set().emit_mode(EmitMode::Stdout);
+    config.set().verbose(Verbosity::Quiet);
+    let mut buf: Vec<u8> = vec![];
+    {
+        let mut session = Session::new(config, Some(&mut buf));
+        session.add_rewrite_hook(ShoutingConsts);
+        session.format(Input::Text(input.to_owned())).unwrap();
+    }
+    assert_eq!(
+        String::from_utf8(buf).unwrap(),
+        "#[derive(Debug)]\nstruct  A { a:u32 }\nCONST A: U32 = 1;\nfn foo() {\n    CONST B: U32 = 2;\n}\n"
+    );
+}
+
+#[test]
+fn stdin_final_newline() {
+    init_log();
+    let cases = [
+        (FinalNewline::Always, "fn main () {}", "fn main() {}\n"),
+        (FinalNewline::Never, "fn main () {}\n", "fn main() {}"),
+        (FinalNewline::Preserve, "fn main () {}", "fn main() {}"),
+        (FinalNewline::Preserve, "fn main () {}\n", "fn main() {}\n"),
+    ];
+    for &(final_newline, input, output) in &cases {
+        let mut config = Config::default();
+        config.set().newline_style(NewlineStyle::Unix);
+        config.set().emit_mode(EmitMode::Stdout);
+        config.set().verbose(Verbosity::Quiet);
+        config.set().require_final_newline(final_newline);
+        let mut buf: Vec<u8> = vec![];
+        {
+            let mut session = Session::new(config, Some(&mut buf));
+            session.format(Input::Text(input.to_owned())).unwrap();
+        }
+        assert_eq!(buf, output.as_bytes());
+    }
+}
+
+#[test]
+fn missing_final_newline_is_reported_in_check_mode() {
+    init_log();
+    let mut config = Config::default();
+    config.set().emit_mode(EmitMode::Diff);
+    let mut buf: Vec<u8> = vec![];
+    let mut session = Session::new(config, Some(&mut buf));
+    session
+        .format(Input::Text("fn main() {}".to_owned()))
+        .unwrap();
+    assert!(session.has_check_errors());
+}
+
+#[test]
+fn license_template_is_inserted() {
+    init_log();
+    let mut config = Config::default();
+    config.set().newline_style(NewlineStyle::Unix);
+    config.set().emit_mode(EmitMode::Stdout);
+    config.set().verbose(Verbosity::Quiet);
+    config.set().insert_license(true);
+    config.override_value("license_owner", "The rustfmt developers");
+    config.override_value(
+        "license_template_path",
+        "tests/license-template/lt.txt,tests/license-template/owner.txt",
+    );
+    let cases = [
+        (
+            "fn main () {}\n",
+            "// Copyright The rustfmt developers.\nfn main() {}\n",
+        ),
+        (
+            "// Copyright 2019 The rustfmt developers.\n\nfn main() {}\n",
+            "// Copyright 2019 The rustfmt developers.\n\nfn main() {}\n",
+        ),
+        // A skipped first item doesn't exempt the file from its header.
+        (
+            "#[rustfmt::skip]\nfn main () {}\nfn foo () {}\n",
+            "// Copyright The rustfmt developers.\n#[rustfmt::skip]\nfn main () {}\nfn foo() {}\n",
+        ),
+    ];
+    for &(input, output) in &cases {
+        let mut buf: Vec<u8> = vec![];
+        {
+            let mut session = Session::new(config.clone(), Some(&mut buf));
+            session.format(Input::Text(input.to_owned())).unwrap();
+            assert!(session.has_no_errors());
+        }
+        assert_eq!(buf, output.as_bytes());
+    }
+}
+
 #[test]
 fn stdin_disable_all_formatting_test() {
     init_log();
@@ -485,6 +748,213 @@ fn format_lines_errors_are_reported_with_tabs() {
     assert!(session.has_formatting_errors());
 }
 
+#[test]
+fn format_lines_raw_string_errors_can_be_suppressed() {
+    init_log();
+    let long_string = String::from_utf8(vec![b'a'; 100]).unwrap();
+    for literal in &[
+        format!("r\"{}\"", long_string),
+        format!("r#\"{}\"#", long_string),
+        format!("b\"{}\"", long_string),
+    ] {
+        let input = format!("fn a() {{\n    let x = {};\n}}\n", literal);
+        let mut config = Config::default();
+        config.set().error_on_line_overflow(true);
+        config.set().error_on_unformatted(true);
+
+        let mut session = Session::<io::Stdout>::new(config.clone(), None);
+        session.format(Input::Text(input.clone())).unwrap();
+        assert!(session.has_formatting_errors());
+
+        config.set().error_on_raw_string_overflow(false);
+        let mut session = Session::<io::Stdout>::new(config, None);
+        session.format(Input::Text(input)).unwrap();
+        assert!(!session.has_formatting_errors());
+    }
+}
+
+#[test]
+fn format_lines_url_errors_can_be_suppressed() {
+    init_log();
+    let url = format!(
+        "https://example.com/{}",
+        String::from_utf8(vec![b'a'; 100]).unwrap()
+    );
+    for line in &[
+        format!("    // See {}", url),
+        format!("    let x = \"{}\";", url),
+        format!(
+            "    let x = \"src/{}.rs\";",
+            String::from_utf8(vec![b'a'; 100]).unwrap()
+        ),
+    ] {
+        let input = format!("fn a() {{\n{}\n}}\n", line);
+        let mut config = Config::default();
+        config.set().error_on_line_overflow(true);
+        config.set().error_on_unformatted(true);
+
+        let mut session = Session::<io::Stdout>::new(config.clone(), None);
+        session.format(Input::Text(input.clone())).unwrap();
+        assert!(session.has_formatting_errors());
+
+        config.set().ignore_long_urls(true);
+        let mut session = Session::<io::Stdout>::new(config, None);
+        session.format(Input::Text(input)).unwrap();
+        assert!(!session.has_formatting_errors());
+    }
+}
+
+#[test]
+fn streamed_output_is_not_kept() {
+    init_log();
+    let mut config = Config::default();
+    config.set().emit_mode(EmitMode::Stdout);
+    config.set().verbose(Verbosity::Quiet);
+    let mut out = vec![];
+    {
+        let mut session = Session::new(config, Some(&mut out));
+        session.set_stream_output(true);
+        session
+            .format(Input::Text("fn main () {}".to_owned()))
+            .unwrap();
+        assert!(session.source_file.is_empty());
+        assert_eq!(session.file_digests.len(), 1);
+    }
+    assert_eq!(String::from_utf8(out).unwrap(), "fn main() {}\n");
+}
+
+#[test]
+fn cancelled_formatting_leaves_items_as_they_are() {
+    init_log();
+    let input = "fn a () {}\nfn b () {}\n";
+    let mut config = Config::default();
+    config.set().emit_mode(EmitMode::Stdout);
+    config.set().verbose(Verbosity::Quiet);
+    let mut out = vec![];
+    {
+        let token = CancellationToken::new();
+        let mut session = Session::new(config, Some(&mut out));
+        session.set_cancellation_token(token.clone());
+        token.cancel();
+        let report = session.format(Input::Text(input.to_owned())).unwrap();
+        assert!(report.was_cancelled());
+        assert!(session.was_cancelled());
+    }
+    assert_eq!(String::from_utf8(out).unwrap(), input);
+}
+
+#[test]
+fn panics_while_formatting_are_internal_errors() {
+    init_log();
+    let input = "fn a () {}\n";
+    let mut config = Config::default();
+    config.set().emit_mode(EmitMode::Stdout);
+    config.set().verbose(Verbosity::Quiet);
+    let mut out = vec![];
+    {
+        let mut session = Session::new(config, Some(&mut out));
+        crate::formatting::PANIC_WHILE_FORMATTING.with(|panic| panic.set(true));
+        let report = session.format(Input::Text(input.to_owned()));
+        crate::formatting::PANIC_WHILE_FORMATTING.with(|panic| panic.set(false));
+        assert_eq!(report.unwrap().warning_count(), 1);
+        // The binary exits with `INTERNAL_ERROR` (4) for internal errors.
+        assert!(session.has_internal_errors());
+        assert!(session.has_operational_errors());
+    }
+    // The original text is emitted in place of the formatted one.
+    assert_eq!(String::from_utf8(out).unwrap(), input);
+}
+
+#[test]
+fn too_deeply_nested_expressions_are_left_as_they_are() {
+    init_log();
+    // Parsing and rewriting the nested calls recurses deeper than the default stack allows.
+    let thread = std::thread::Builder::new().stack_size(256 * 1024 * 1024);
+    let handle = thread.spawn(|| {
+        let nested = format!("{}1{}", "f(".repeat(600), ")".repeat(600));
+        let input = format!("fn a() {{\n    let x = {};\n}}\nfn b () {{}}\n", nested);
+        let mut config = Config::default();
+        config.set().emit_mode(EmitMode::Stdout);
+        config.set().verbose(Verbosity::Quiet);
+        let mut out = vec![];
+        let report = {
+            let mut session = Session::new(config, Some(&mut out));
+            session.format(Input::Text(input)).unwrap().to_string()
+        };
+        // The rewrite gives up once, and the statement is not retried with other shapes.
+        assert_eq!(report.matches("expression too complex").count(), 1);
+        let expected = format!("fn a() {{\n    let x = {};\n}}\nfn b() {{}}\n", nested);
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    });
+    handle.unwrap().join().unwrap();
+}
+
+#[test]
+fn files_exceeding_the_time_limit_are_left_as_they_are() {
+    init_log();
+    let input = "fn main() {\n    let x = foo(a,\n        b);\n}\n";
+    let mut config = Config::default();
+    config.set().file_rewrite_time_limit(0);
+    config.set().emit_mode(EmitMode::Stdout);
+    config.set().verbose(Verbosity::Quiet);
+    let mut out = vec![];
+    let report = {
+        let mut session = Session::new(config, Some(&mut out));
+        session
+            .format(Input::Text(input.to_owned()))
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(
+        report.matches("formatting this file took too long").count(),
+        1
+    );
+    assert_eq!(String::from_utf8(out).unwrap(), input);
+}
+
+#[test]
+fn recovered_parse_errors_are_left_as_they_are() {
+    init_log();
+    let input = "fn a () {}\nfn b() {\n    let x = ;\n}\nfn c () {}\n";
+    let mut config = Config::default();
+    config.set().emit_mode(EmitMode::Stdout);
+    config.set().verbose(Verbosity::Quiet);
+    config.set().hide_parse_errors(true);
+    config.set().recover_parse_errors(true);
+    let mut out = vec![];
+    {
+        let mut session = Session::new(config, Some(&mut out));
+        session.format(Input::Text(input.to_owned())).unwrap();
+        assert!(session.has_parsing_errors());
+    }
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "fn a() {}\nfn b() {\n    let x = ;\n}\nfn c() {}\n"
+    );
+}
+
+#[test]
+fn shared_modules_of_crate_roots_are_formatted_once() {
+    init_log();
+    let formatted = RefCell::new(vec![]);
+    let mut buf: Vec<u8> = vec![];
+    {
+        let emitter = Box::new(RecordingEmitter {
+            formatted: &formatted,
+        });
+        let mut session = Session::with_emitter(Config::default(), Some(&mut buf), emitter);
+        let report = session
+            .format_roots(vec![
+                PathBuf::from("tests/crate-roots/main.rs"),
+                PathBuf::from("tests/crate-roots/other.rs"),
+            ])
+            .unwrap();
+        assert_eq!(report.file_stats().len(), 3);
+        assert!(!session.has_diff());
+    }
+    assert_eq!(formatted.into_inner().len(), 3);
+}
+
 #[test]
 fn parser_errors_in_submods_are_surfaced() {
     // See also https://github.com/rust-lang/rustfmt/issues/4126
@@ -630,13 +1100,14 @@ fn idempotent_check(
     } else {
         read_config(filename)
     };
-    let (parsing_errors, source_file, format_report) = format_file(filename, config);
+    let (parsing_errors, source_file, format_report) = format_file(filename, config.clone());
     if parsing_errors {
         return Err(IdempotentCheckError::Parse);
     }
 
     let mut write_result = HashMap::new();
-    for (filename, text) in source_file {
+    for (filename, mut text) in source_file {
+        source_file::restore_bom(&filename, &mut text, &config).expect("couldn't read file");
         if let FileName::Real(ref filename) = filename {
             write_result.insert(filename.to_owned(), text);
         }
@@ -687,6 +1158,7 @@ fn read_significant_comments(file_name: &Path) -> HashMap<String, String> {
     reader
         .lines()
         .map(|line| line.expect("failed getting line"))
+        .map(|line| line.trim_start_matches('\u{feff}').to_owned())
         .take_while(|line| line_regex.is_match(line))
         .filter_map(|line| {
             regex.captures_iter(&line).next().map(|capture| {
@@ -0,0 +1,60 @@
+//! Tracing of the layout decisions made while rewriting, e.g. whether a list is laid out
+//! horizontally or vertically, to help understand why some code is formatted the way it is.
+//!
+//! Tracing is enabled by setting the `RUSTFMT_TRACE` environment variable to `1`, which
+//! `--trace-decisions` does. Every decision is printed to stderr as a line of JSON. A construct
+//! is often rewritten several times with different shapes, so it can have several decisions.
+
+use std::env;
+
+use rustc_span::Span;
+use serde::Serialize;
+
+use crate::config::FileName;
+use crate::shape::Shape;
+use crate::syntux::session::ParseSess;
+
+lazy_static! {
+    static ref TRACE_ENABLED: bool = env::var("RUSTFMT_TRACE").map_or(false, |value| value == "1");
+}
+
+#[derive(Serialize)]
+struct Decision<'a> {
+    file: FileName,
+    line: usize,
+    construct: &'a str,
+    tactic: &'a str,
+    width: usize,
+    indent: usize,
+    offset: usize,
+}
+
+/// Returns `true` if decisions are traced, so that callers can skip preparing them otherwise.
+pub(crate) fn is_tracing() -> bool {
+    *TRACE_ENABLED
+}
+
+/// Traces that the construct at `span` was rewritten into `shape` with the given tactic.
+pub(crate) fn trace_decision(
+    parse_sess: &ParseSess,
+    span: Span,
+    construct: &str,
+    tactic: &str,
+    shape: Shape,
+) {
+    if !is_tracing() {
+        return;
+    }
+    let decision = Decision {
+        file: parse_sess.span_to_filename(span),
+        line: parse_sess.line_of_byte_pos(span.lo()),
+        construct,
+        tactic,
+        width: shape.width,
+        indent: shape.indent.width(),
+        offset: shape.offset,
+    };
+    if let Ok(json) = serde_json::to_string(&decision) {
+        eprintln!("{}", json);
+    }
+}
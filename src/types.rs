@@ -309,7 +309,7 @@ where
 {
     debug!("format_function_type {:#?}", shape);
 
-    let ty_shape = match context.config.indent_style() {
+    let ty_shape = match context.indent_style() {
         // 4 = " -> "
         IndentStyle::Block => shape.offset_left(4)?,
         IndentStyle::Visual => shape.block_left(4)?,
@@ -380,7 +380,7 @@ where
         let fmt = ListFormatting::new(list_shape, context.config)
             .tactic(tactic)
             .trailing_separator(trailing_separator)
-            .ends_with_newline(tactic.ends_with_newline(context.config.indent_style()))
+            .ends_with_newline(tactic.ends_with_newline(context.indent_style()))
             .preserve_newline(true);
         (write_list(&item_vec, &fmt)?, tactic)
     };
@@ -563,7 +563,7 @@ impl Rewrite for ast::GenericParam {
         if let rustc_ast::ast::GenericParamKind::Const { ref ty, .. } = &self.kind {
             result.push_str("const ");
             result.push_str(rewrite_ident(context, self.ident));
-            result.push_str(": ");
+            result.push_str(colon_spaces(context.config));
             result.push_str(&ty.rewrite(context, shape)?);
         } else {
             result.push_str(rewrite_ident(context, self.ident));
@@ -683,20 +683,24 @@ impl Rewrite for ast::Ty {
             // FIXME: we drop any comments here, even though it's a silly place to put
             // comments.
             ast::TyKind::Paren(ref ty) => {
+                let (lhs, rhs) = if context.config.spaces_within_parens() {
+                    ("( ", " )")
+                } else {
+                    ("(", ")")
+                };
                 if context.config.version() == Version::One
-                    || context.config.indent_style() == IndentStyle::Visual
+                    || context.indent_style() == IndentStyle::Visual
                 {
-                    let budget = shape.width.checked_sub(2)?;
+                    let budget = shape.width.checked_sub(lhs.len() + rhs.len())?;
                     return ty
-                        .rewrite(context, Shape::legacy(budget, shape.indent + 1))
-                        .map(|ty_str| format!("({})", ty_str));
+                        .rewrite(context, Shape::legacy(budget, shape.indent + lhs.len()))
+                        .map(|ty_str| format!("{}{}{}", lhs, ty_str, rhs));
                 }
 
-                // 2 = ()
-                if let Some(sh) = shape.sub_width(2) {
+                if let Some(sh) = shape.sub_width(lhs.len() + rhs.len()) {
                     if let Some(ref s) = ty.rewrite(context, sh) {
                         if !s.contains('\n') {
-                            return Some(format!("({})", s));
+                            return Some(format!("{}{}{}", lhs, s, rhs));
                         }
                     }
                 }
@@ -714,9 +718,14 @@ impl Rewrite for ast::Ty {
                 ))
             }
             ast::TyKind::Slice(ref ty) => {
+                let (lbr, rbr) = if context.config.spaces_within_brackets() {
+                    ("[ ", " ]")
+                } else {
+                    ("[", "]")
+                };
                 let budget = shape.width.checked_sub(4)?;
-                ty.rewrite(context, Shape::legacy(budget, shape.indent + 1))
-                    .map(|ty_str| format!("[{}]", ty_str))
+                ty.rewrite(context, Shape::legacy(budget, shape.indent + lbr.len()))
+                    .map(|ty_str| format!("{}{}{}", lbr, ty_str, rbr))
             }
             ast::TyKind::Tup(ref items) => {
                 rewrite_tuple(context, items.iter(), self.span, shape, items.len() == 1)
@@ -724,14 +733,21 @@ impl Rewrite for ast::Ty {
             ast::TyKind::Path(ref q_self, ref path) => {
                 rewrite_path(context, PathContext::Type, q_self.as_ref(), path, shape)
             }
-            ast::TyKind::Array(ref ty, ref repeats) => rewrite_pair(
-                &**ty,
-                &*repeats.value,
-                PairParts::new("[", "; ", "]"),
-                context,
-                shape,
-                SeparatorPlace::Back,
-            ),
+            ast::TyKind::Array(ref ty, ref repeats) => {
+                let pair_parts = if context.config.spaces_within_brackets() {
+                    PairParts::new("[ ", "; ", " ]")
+                } else {
+                    PairParts::new("[", "; ", "]")
+                };
+                rewrite_pair(
+                    &**ty,
+                    &*repeats.value,
+                    pair_parts,
+                    context,
+                    shape,
+                    SeparatorPlace::Back,
+                )
+            }
             ast::TyKind::Infer => {
                 if shape.width >= 1 {
                     Some("_".to_owned())
@@ -13,6 +13,7 @@ use crate::comment::{filter_normal_code, CharClasses, FullCodeCharKind, LineClas
 use crate::config::{Config, Version};
 use crate::rewrite::RewriteContext;
 use crate::shape::{Indent, Shape};
+use crate::spacing::Spacing;
 
 #[inline]
 pub(crate) fn depr_skip_annotation() -> Symbol {
@@ -207,10 +208,16 @@ pub(crate) fn first_line_width(s: &str) -> usize {
     unicode_str_width(s.splitn(2, '\n').next().unwrap_or(""))
 }
 
+/// The last line in s.
+#[inline]
+pub(crate) fn last_line(s: &str) -> &str {
+    s.rsplitn(2, '\n').next().unwrap_or("")
+}
+
 /// The width of the last line in s.
 #[inline]
 pub(crate) fn last_line_width(s: &str) -> usize {
-    unicode_str_width(s.rsplitn(2, '\n').next().unwrap_or(""))
+    unicode_str_width(last_line(s))
 }
 
 /// The total used width of the last line.
@@ -247,16 +254,21 @@ pub(crate) fn last_line_extendable(s: &str) -> bool {
     true
 }
 
+/// Returns `true` for `rustfmt::skip`, `::rustfmt::skip` and the legacy `rustfmt_skip`, and for
+/// a `cfg_attr` with any of them among its attributes, e.g.
+/// `cfg_attr(rustfmt, allow(unused), rustfmt::skip)`.
 #[inline]
 fn is_skip(meta_item: &MetaItem) -> bool {
     match meta_item.kind {
         MetaItemKind::Word => {
             let path_str = pprust::path_to_string(&meta_item.path);
+            let path_str = path_str.trim_start_matches("::");
             path_str == &*skip_annotation().as_str()
                 || path_str == &*depr_skip_annotation().as_str()
         }
         MetaItemKind::List(ref l) => {
-            meta_item.has_name(sym::cfg_attr) && l.len() == 2 && is_skip_nested(&l[1])
+            // The first item of a `cfg_attr` is its predicate.
+            meta_item.has_name(sym::cfg_attr) && l.iter().skip(1).any(is_skip_nested)
         }
         _ => false,
     }
@@ -283,6 +295,10 @@ pub(crate) fn semicolon_for_expr(context: &RewriteContext<'_>, expr: &ast::Expr)
         ast::ExprKind::Ret(..) | ast::ExprKind::Continue(..) | ast::ExprKind::Break(..) => {
             context.config.trailing_semicolon()
         }
+        // Assignments are always unit-valued, so the semicolon doesn't change the type.
+        ast::ExprKind::Assign(..) | ast::ExprKind::AssignOp(..) => {
+            context.config.semicolon_if_nothing_returned()
+        }
         _ => false,
     }
 }
@@ -297,6 +313,9 @@ pub(crate) fn semicolon_for_stmt(context: &RewriteContext<'_>, stmt: &ast::Stmt)
             ast::ExprKind::Break(..) | ast::ExprKind::Continue(..) | ast::ExprKind::Ret(..) => {
                 context.config.trailing_semicolon()
             }
+            ast::ExprKind::If(..) | ast::ExprKind::Block(..) => {
+                !context.config.semicolon_if_nothing_returned() || !is_unit_block_like(expr)
+            }
             _ => true,
         },
         ast::StmtKind::Expr(..) => false,
@@ -304,6 +323,23 @@ pub(crate) fn semicolon_for_stmt(context: &RewriteContext<'_>, stmt: &ast::Stmt)
     }
 }
 
+/// Returns `true` if `expr` is an `if` or a block which is known to be unit-valued without
+/// type information, i.e. none of its blocks end with an expression.
+fn is_unit_block_like(expr: &ast::Expr) -> bool {
+    let is_unit_block = |block: &ast::Block| match block.stmts.last().map(|stmt| &stmt.kind) {
+        Some(ast::StmtKind::Expr(..)) | Some(ast::StmtKind::MacCall(..)) => false,
+        _ => true,
+    };
+    match expr.kind {
+        ast::ExprKind::If(_, ref if_block, ref else_block) => {
+            is_unit_block(if_block) && else_block.as_ref().map_or(true, |e| is_unit_block_like(e))
+        }
+        // Labeled blocks may break with a value.
+        ast::ExprKind::Block(ref block, None) => is_unit_block(block),
+        _ => false,
+    }
+}
+
 #[inline]
 pub(crate) fn stmt_expr(stmt: &ast::Stmt) -> Option<&ast::Expr> {
     match stmt.kind {
@@ -412,13 +448,17 @@ fn is_valid_str(snippet: &str, max_width: usize, shape: Shape) -> bool {
 
 #[inline]
 pub(crate) fn colon_spaces(config: &Config) -> &'static str {
-    let before = config.space_before_colon();
-    let after = config.space_after_colon();
-    match (before, after) {
-        (true, true) => " : ",
-        (true, false) => " :",
-        (false, true) => ": ",
-        (false, false) => ":",
+    Spacing::new(config).colon()
+}
+
+/// Returns `true` if there should be spaces just inside the given opening delimiter and its
+/// closing counterpart, as configured by the `spaces_within_*` options.
+pub(crate) fn spaces_within_delims(config: &Config, opening: &str) -> bool {
+    match opening {
+        "(" => config.spaces_within_parens(),
+        "[" => config.spaces_within_brackets(),
+        "<" => config.spaces_within_angle_brackets(),
+        _ => false,
     }
 }
 
@@ -575,6 +615,7 @@ pub(crate) fn trim_left_preserve_layout(
             } else {
                 Some(get_prefix_space_width(config, &line))
             };
+            let prefix_alignment = get_prefix_alignment(config, &line);
 
             // just InString{Commented} in order to allow the start of a string to be indented
             let new_veto_trim_value = (kind == FullCodeCharKind::InString
@@ -588,7 +629,7 @@ pub(crate) fn trim_left_preserve_layout(
             } else {
                 line.trim().to_owned()
             };
-            trimmed_lines.push((trimmed, line, prefix_space_width));
+            trimmed_lines.push((trimmed, line, prefix_space_width, prefix_alignment));
 
             // Because there is a veto against trimming and indenting lines within a string,
             // such lines should not be taken into account when computing the minimum.
@@ -610,15 +651,21 @@ pub(crate) fn trim_left_preserve_layout(
             + &trimmed_lines
                 .iter()
                 .map(
-                    |&(trimmed, ref line, prefix_space_width)| match prefix_space_width {
-                        _ if !trimmed => line.to_owned(),
-                        Some(original_indent_width) => {
-                            let new_indent_width = indent.width()
-                                + original_indent_width.saturating_sub(min_prefix_space_width);
-                            let new_indent = Indent::from_width(config, new_indent_width);
-                            format!("{}{}", new_indent.to_string(config), line)
+                    |&(trimmed, ref line, prefix_space_width, prefix_alignment)| {
+                        match prefix_space_width {
+                            _ if !trimmed => line.to_owned(),
+                            Some(original_indent_width) => {
+                                let new_indent_width = indent.width()
+                                    + original_indent_width.saturating_sub(min_prefix_space_width);
+                                let new_indent = indent.extend_to_width(
+                                    config,
+                                    new_indent_width,
+                                    prefix_alignment,
+                                );
+                                format!("{}{}", new_indent.to_string(config), line)
+                            }
+                            None => String::new(),
                         }
-                        None => String::new(),
                     },
                 )
                 .collect::<Vec<_>>()
@@ -648,6 +695,18 @@ fn get_prefix_space_width(config: &Config, s: &str) -> usize {
     width
 }
 
+// Returns the width of the spaces which follow the leading tabs of `s`, or of the part of its
+// indentation which does not fill a whole tab if it has none.
+fn get_prefix_alignment(config: &Config, s: &str) -> usize {
+    let aligned = s.trim_start_matches('\t');
+    let alignment = aligned.len() - aligned.trim_start_matches(' ').len();
+    if aligned.len() < s.len() {
+        alignment
+    } else {
+        alignment % config.tab_spaces()
+    }
+}
+
 pub(crate) trait NodeIdExt {
     fn root() -> Self;
 }
@@ -665,6 +724,8 @@ pub(crate) fn unicode_str_width(s: &str) -> usize {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::syntux::parser::Parser;
+    use crate::syntux::session::ParseSess;
 
     #[test]
     fn test_remove_trailing_white_spaces() {
@@ -682,4 +743,34 @@ mod test {
             Some("aaa\n    bbb\n    ccc".to_string())
         );
     }
+
+    #[test]
+    fn test_contains_skip() {
+        let config = Config::default();
+        let skipped = |item: &str| {
+            rustc_span::with_session_globals(config.edition().to_libsyntax_pos_edition(), || {
+                let sess = ParseSess::new(&config).unwrap();
+                let (module, _) = Parser::parse_str_as_module(&sess, item.to_owned()).unwrap();
+                contains_skip(&module.items[0].attrs)
+            })
+        };
+
+        assert!(skipped("#[rustfmt::skip] fn f() {}"));
+        assert!(skipped("#[::rustfmt::skip] fn f() {}"));
+        assert!(skipped("#[rustfmt_skip] fn f() {}"));
+        assert!(skipped("#[cfg_attr(rustfmt, rustfmt::skip)] fn f() {}"));
+        assert!(skipped(
+            "#[cfg_attr(all(), allow(dead_code), rustfmt::skip)] fn f() {}"
+        ));
+        assert!(skipped(
+            "#[cfg_attr(a, cfg_attr(b, rustfmt::skip))] fn f() {}"
+        ));
+
+        assert!(!skipped("fn f() {}"));
+        assert!(!skipped("#[allow(rustfmt::skip)] fn f() {}"));
+        assert!(!skipped(
+            "#[cfg_attr(rustfmt::skip, allow(dead_code))] fn f() {}"
+        ));
+        assert!(!skipped("#[rustfmt::skip::macros(m)] fn f() {}"));
+    }
 }
@@ -1,31 +1,36 @@
 use std::cell::{Cell, RefCell};
+use std::mem;
 use std::rc::Rc;
+use std::time::Duration;
 
 use rustc_ast::{ast, attr::HasAttrs, token::DelimToken, visit};
 use rustc_span::{symbol, BytePos, Pos, Span, DUMMY_SP};
 
 use crate::attr::*;
+use crate::cancellation::Cancellation;
 use crate::comment::{rewrite_comment, CodeCharKind, CommentCodeSlices};
 use crate::config::Version;
-use crate::config::{BraceStyle, Config};
+use crate::config::{BraceStyle, Config, ReturnStyle};
 use crate::coverage::transform_missing_snippet;
+use crate::failure_report::{report_failure, Failure};
 use crate::items::{
-    format_impl, format_trait, format_trait_alias, is_mod_decl, is_use_item,
-    rewrite_associated_impl_type, rewrite_extern_crate, rewrite_opaque_impl_type,
+    format_impl, format_trait, format_trait_alias, is_empty_item_single_line, is_mod_decl,
+    is_use_item, rewrite_associated_impl_type, rewrite_extern_crate, rewrite_opaque_impl_type,
     rewrite_opaque_type, rewrite_type_alias, FnBraceStyle, FnSig, StaticParts, StructParts,
 };
 use crate::macros::{macro_style, rewrite_macro, rewrite_macro_def, MacroPosition};
 use crate::modules::Module;
-use crate::rewrite::{Rewrite, RewriteContext};
+use crate::rewrite::{Rewrite, RewriteBudget, RewriteContext, RewriteHook};
 use crate::shape::{Indent, Shape};
 use crate::skip::{is_skip_attr, SkipContext};
 use crate::source_map::{LineRangeUtils, SpanUtils};
 use crate::spanned::Spanned;
-use crate::stmt::Stmt;
+use crate::stmt::{convertible_fn_body_tail, rewrite_fn_body_tail, Stmt};
 use crate::syntux::session::ParseSess;
+use crate::trace;
 use crate::utils::{
     self, contains_skip, count_newlines, depr_skip_annotation, format_unsafety, inner_attributes,
-    last_line_width, mk_sp, ptr_vec_to_ref_vec, rewrite_ident, stmt_expr,
+    last_line, last_line_width, mk_sp, ptr_vec_to_ref_vec, rewrite_ident, stmt_expr,
 };
 use crate::{ErrorKind, FormatReport, FormattingError};
 
@@ -56,6 +61,16 @@ impl SnippetProvider {
         }
     }
 
+    /// Returns the snippet of `span`, extended back to the start of its first line.
+    pub(crate) fn span_to_snippet_from_line_start(&self, span: Span) -> Option<&str> {
+        let start_index = span.lo().to_usize().checked_sub(self.start_pos)?;
+        let end_index = span.hi().to_usize().checked_sub(self.start_pos)?;
+        let line_start = self.big_snippet[..start_index]
+            .rfind('\n')
+            .map_or(0, |i| i + 1);
+        Some(&self.big_snippet[line_start..end_index])
+    }
+
     pub(crate) fn entire_snippet(&self) -> &str {
         self.big_snippet.as_str()
     }
@@ -86,6 +101,16 @@ pub(crate) struct FmtVisitor<'a> {
     pub(crate) macro_rewrite_failure: bool,
     pub(crate) report: FormatReport,
     pub(crate) skip_context: SkipContext,
+    /// Pairs of 1-based lines of the original file and of `buffer` where the same code starts.
+    pub(crate) line_anchors: Vec<(usize, usize)>,
+    pub(crate) rewrite_hooks: Rc<[Rc<dyn RewriteHook>]>,
+    pub(crate) cancellation: Cancellation,
+    /// The span of the statement ending the current function body which `return_style`
+    /// converts.
+    pub(crate) fn_body_tail: Option<Span>,
+    /// The budget of expression rewrites, shared by the visitor of a file and its nested
+    /// visitors.
+    pub(crate) rewrite_budget: Rc<RewriteBudget>,
 }
 
 impl<'a> Drop for FmtVisitor<'a> {
@@ -128,12 +153,23 @@ impl<'b, 'a: 'b> FmtVisitor<'a> {
         match stmt.as_ast_node().kind {
             ast::StmtKind::Item(ref item) => {
                 self.visit_item(item);
-                // Handle potential `;` after the item.
-                self.format_missing(stmt.span().hi());
+                // Handle potential `;` after the item, which is redundant.
+                let rest = self.snippet(mk_sp(self.last_pos, stmt.span().hi()));
+                if self.config.semicolon_if_nothing_returned()
+                    && rest.chars().all(|c| c.is_whitespace() || c == ';')
+                {
+                    self.last_pos = stmt.span().hi();
+                } else {
+                    self.format_missing(stmt.span().hi());
+                }
             }
             ast::StmtKind::Local(..) | ast::StmtKind::Expr(..) | ast::StmtKind::Semi(..) => {
                 let attrs = get_attrs_from_stmt(stmt.as_ast_node());
-                if contains_skip(attrs) {
+                if contains_skip(attrs)
+                    || self
+                        .parse_sess
+                        .has_recovered_errors_outside(stmt.span(), &[])
+                {
                     self.push_skipped_with_span(
                         attrs,
                         stmt.span(),
@@ -141,7 +177,13 @@ impl<'b, 'a: 'b> FmtVisitor<'a> {
                     );
                 } else {
                     let shape = self.shape();
-                    let rewrite = self.with_context(|ctx| stmt.rewrite(&ctx, shape));
+                    let rewrite = if self.fn_body_tail == Some(stmt.span()) {
+                        self.with_context(|ctx| {
+                            rewrite_fn_body_tail(&ctx, shape, stmt.as_ast_node())
+                        })
+                    } else {
+                        self.with_context(|ctx| stmt.rewrite(&ctx, shape))
+                    };
                     self.push_rewrite(stmt.span(), rewrite)
                 }
             }
@@ -215,9 +257,12 @@ impl<'b, 'a: 'b> FmtVisitor<'a> {
 
         self.walk_block_stmts(b);
 
-        if !b.stmts.is_empty() {
-            if let Some(expr) = stmt_expr(&b.stmts[b.stmts.len() - 1]) {
-                if utils::semicolon_for_expr(&self.get_context(), expr) {
+        if let Some(last_stmt) = b.stmts.last() {
+            if let Some(expr) = stmt_expr(last_stmt) {
+                // A converted `return` doesn't keep its semicolon.
+                if utils::semicolon_for_expr(&self.get_context(), expr)
+                    && self.fn_body_tail != Some(last_stmt.span)
+                {
                     self.push_str(";");
                 }
             }
@@ -371,6 +416,7 @@ impl<'b, 'a: 'b> FmtVisitor<'a> {
             }
             _ => unreachable!(),
         };
+        self.report.count_rewrite(rewrite.is_some());
 
         if let Some((fn_str, fn_brace_style)) = rewrite {
             self.format_missing_with_indent(source!(self, s).lo());
@@ -394,12 +440,72 @@ impl<'b, 'a: 'b> FmtVisitor<'a> {
             self.format_missing(source!(self, block.span).lo());
         }
 
-        self.visit_block(block, inner_attrs, true)
+        let fn_body_tail = match self.config.return_style() {
+            ReturnStyle::Preserve => None,
+            return_style => convertible_fn_body_tail(return_style, fd, block).map(|stmt| stmt.span),
+        };
+        let outer_fn_body_tail = mem::replace(&mut self.fn_body_tail, fn_body_tail);
+        self.visit_block(block, inner_attrs, true);
+        self.fn_body_tail = outer_fn_body_tail;
     }
 
     pub(crate) fn visit_item(&mut self, item: &ast::Item) {
         skip_out_of_file_lines_range_visitor!(self, item.span);
 
+        if self.cancellation.is_cancelled() {
+            self.report.add_cancellation();
+            self.push_skipped_with_span(&item.attrs, item.span(), item.span());
+            return;
+        }
+
+        // Code with parse errors is left as it is, see `recover_parse_errors`.
+        if self
+            .parse_sess
+            .has_recovered_errors_outside(item.span, &item_child_spans(&item.kind))
+        {
+            self.push_skipped_with_span(&item.attrs, item.span(), item.span());
+            return;
+        }
+
+        if self.rewrite_hooks.is_empty() || is_mod_decl(item) || contains_skip(&item.attrs) {
+            self.visit_item_inner(item);
+        } else {
+            self.visit_item_with_hooks(item);
+        }
+    }
+
+    // Rewrites the item in a nested visitor, so that its rewrite can be given to the rewrite
+    // hooks before it is pushed.
+    fn visit_item_with_hooks(&mut self, item: &ast::Item) {
+        let span = item.span();
+        let rewrite = {
+            let context = self.get_context();
+            let original = self.snippet(span);
+            let mut rewrite = context
+                .rewrite_hooks
+                .iter()
+                .find_map(|hook| hook.pre_rewrite_item(item, &context, original));
+            if rewrite.is_none() {
+                let mut visitor = FmtVisitor::from_context(&context);
+                visitor.block_indent = self.block_indent;
+                visitor.last_pos = source!(self, span).lo();
+                visitor.visit_item_inner(item);
+                let mut text = visitor.buffer.trim_start().to_owned();
+                for hook in context.rewrite_hooks.iter() {
+                    if let Some(new_text) = hook.post_rewrite_item(item, &context, &text) {
+                        text = new_text;
+                    }
+                }
+                rewrite = Some(text);
+            }
+            rewrite
+        };
+        self.push_rewrite(span, rewrite);
+    }
+
+    fn visit_item_inner(&mut self, item: &ast::Item) {
+        skip_out_of_file_lines_range_visitor!(self, item.span);
+
         // This is where we bail out if there is a skip attribute. This is only
         // complex in the module case. It is complex because the module could be
         // in a separate file and there might be attributes in both files, but
@@ -589,7 +695,12 @@ impl<'b, 'a: 'b> FmtVisitor<'a> {
     pub(crate) fn visit_trait_item(&mut self, ti: &ast::AssocItem) {
         skip_out_of_file_lines_range_visitor!(self, ti.span);
 
-        if self.visit_attrs(&ti.attrs, ast::AttrStyle::Outer) {
+        // Code with parse errors is left as it is, see `recover_parse_errors`.
+        if self
+            .parse_sess
+            .has_recovered_errors_outside(ti.span, &assoc_item_child_spans(&ti.kind))
+            || self.visit_attrs(&ti.attrs, ast::AttrStyle::Outer)
+        {
             self.push_skipped_with_span(ti.attrs.as_slice(), ti.span(), ti.span());
             return;
         }
@@ -639,7 +750,12 @@ impl<'b, 'a: 'b> FmtVisitor<'a> {
     pub(crate) fn visit_impl_item(&mut self, ii: &ast::AssocItem) {
         skip_out_of_file_lines_range_visitor!(self, ii.span);
 
-        if self.visit_attrs(&ii.attrs, ast::AttrStyle::Outer) {
+        // Code with parse errors is left as it is, see `recover_parse_errors`.
+        if self
+            .parse_sess
+            .has_recovered_errors_outside(ii.span, &assoc_item_child_spans(&ii.kind))
+            || self.visit_attrs(&ii.attrs, ast::AttrStyle::Outer)
+        {
             self.push_skipped_with_span(ii.attrs.as_slice(), ii.span, ii.span);
             return;
         }
@@ -731,6 +847,25 @@ impl<'b, 'a: 'b> FmtVisitor<'a> {
         self.buffer.push_str(s);
     }
 
+    /// Records that the code on the given line of the original file is written to the current
+    /// line of the buffer.
+    pub(crate) fn record_line_anchor(&mut self, original_line: usize) {
+        // The buffers of nested visitors end up within rewrites, which aren't mapped line by line.
+        if self.parent_context.is_none() {
+            self.line_anchors
+                .push((original_line, self.line_number + 1));
+        }
+    }
+
+    /// Records that the code at `pos` in the original file is written to the current line of
+    /// the buffer.
+    pub(crate) fn record_line_anchor_at(&mut self, pos: BytePos) {
+        if self.parent_context.is_none() {
+            let line = self.parse_sess.line_of_byte_pos(pos);
+            self.line_anchors.push((line, self.line_number + 1));
+        }
+    }
+
     #[allow(clippy::needless_pass_by_value)]
     fn push_rewrite_inner(&mut self, span: Span, rewrite: Option<String>) {
         if let Some(ref s) = rewrite {
@@ -744,9 +879,38 @@ impl<'b, 'a: 'b> FmtVisitor<'a> {
 
     pub(crate) fn push_rewrite(&mut self, span: Span, rewrite: Option<String>) {
         self.format_missing_with_indent(source!(self, span).lo());
+        self.report.count_rewrite(rewrite.is_some());
+        if rewrite.is_none() {
+            self.report_rewrite_failure(source!(self, span));
+            trace::trace_decision(
+                self.parse_sess,
+                span,
+                "statement or item",
+                "Original",
+                self.shape(),
+            );
+        }
         self.push_rewrite_inner(span, rewrite);
     }
 
+    // Records the code which is left as is because it couldn't be rewritten.
+    fn report_rewrite_failure(&self, span: Span) {
+        if self.config.failure_report_dir().is_empty() {
+            return;
+        }
+        if let Some(snippet) = self.snippet_provider.span_to_snippet_from_line_start(span) {
+            report_failure(
+                self.config,
+                &Failure {
+                    file: &self.parse_sess.span_to_filename(span),
+                    line: self.parse_sess.line_of_byte_pos(span.lo()),
+                    reason: "failed to rewrite this code, which was left as is",
+                    snippet,
+                },
+            );
+        }
+    }
+
     pub(crate) fn push_skipped_with_span(
         &mut self,
         attrs: &[ast::Attribute],
@@ -778,6 +942,8 @@ impl<'b, 'a: 'b> FmtVisitor<'a> {
             ctx.report.clone(),
         );
         visitor.skip_context.update(ctx.skip_context.clone());
+        visitor.rewrite_hooks = Rc::clone(&ctx.rewrite_hooks);
+        visitor.rewrite_budget = Rc::clone(&ctx.rewrite_budget);
         visitor.set_parent_context(ctx);
         visitor
     }
@@ -802,6 +968,13 @@ impl<'b, 'a: 'b> FmtVisitor<'a> {
             macro_rewrite_failure: false,
             report,
             skip_context: Default::default(),
+            line_anchors: vec![],
+            rewrite_hooks: Rc::from(vec![]),
+            cancellation: Cancellation::default(),
+            fn_body_tail: None,
+            rewrite_budget: Rc::new(RewriteBudget::new(Duration::from_secs(
+                config.file_rewrite_time_limit() as u64,
+            ))),
         }
     }
 
@@ -854,7 +1027,16 @@ impl<'b, 'a: 'b> FmtVisitor<'a> {
             return false;
         }
 
-        let rewrite = attrs.rewrite(&self.get_context(), self.shape());
+        let rewrite = {
+            let context = self.get_context();
+            let shape = self.shape();
+            if style == ast::AttrStyle::Inner && self.config.reorder_inner_attributes() {
+                rewrite_reordered_inner_attrs(&context, &attrs, shape)
+                    .or_else(|| attrs.rewrite(&context, shape))
+            } else {
+                attrs.rewrite(&context, shape)
+            }
+        };
         let span = mk_sp(attrs[0].span.lo(), attrs[attrs.len() - 1].span.hi());
         self.push_rewrite(span, rewrite);
 
@@ -877,6 +1059,13 @@ impl<'b, 'a: 'b> FmtVisitor<'a> {
             return;
         }
 
+        let off_len = self.formatting_off_len(stmts);
+        if off_len > 0 {
+            self.push_formatting_off_stmts(&stmts[..off_len]);
+            self.walk_stmts(&stmts[off_len..]);
+            return;
+        }
+
         // Extract leading `use ...;`.
         let items: Vec<_> = stmts
             .iter()
@@ -893,6 +1082,37 @@ impl<'b, 'a: 'b> FmtVisitor<'a> {
         }
     }
 
+    // Returns the number of statements from the start of `stmts` which are preceded by a
+    // `// rustfmt: off` comment, up to the first one preceded by a `// rustfmt: on` comment.
+    fn formatting_off_len(&self, stmts: &[Stmt<'_>]) -> usize {
+        let before_first = self.snippet(mk_sp(self.last_pos, stmts[0].span().lo()));
+        if !has_directive_comment(before_first, "rustfmt: off") {
+            return 0;
+        }
+        stmts
+            .windows(2)
+            .position(|pair| {
+                let between = mk_sp(pair[0].span().hi(), pair[1].span().lo());
+                has_directive_comment(self.snippet(between), "rustfmt: on")
+            })
+            .map_or(stmts.len(), |i| i + 1)
+    }
+
+    // Pushes the statements between `// rustfmt: off` and `// rustfmt: on` as they are, and
+    // records their lines as skipped so that their width isn't checked either.
+    fn push_formatting_off_stmts(&mut self, stmts: &[Stmt<'_>]) {
+        let lo = stmts[0].span().lo();
+        let hi = stmts[stmts.len() - 1].span().hi();
+        self.format_missing_with_indent(lo);
+        let first_line = self.line_number + 1;
+        let snippet = self.snippet(mk_sp(lo, hi));
+        self.push_str(snippet);
+        self.last_pos = hi;
+        self.skipped_range
+            .borrow_mut()
+            .push((first_line, self.line_number + 1));
+    }
+
     fn walk_block_stmts(&mut self, b: &ast::Block) {
         self.walk_stmts(&Stmt::from_ast_nodes(b.stmts.iter()))
     }
@@ -929,6 +1149,11 @@ impl<'b, 'a: 'b> FmtVisitor<'a> {
                 self.snippet(mk_sp(mod_lo, source!(self, m.inner).hi() - BytePos(1)));
             let body_snippet = body_snippet.trim();
             if body_snippet.is_empty() {
+                if !is_empty_item_single_line(self.config, Indent::empty(), last_line(&self.buffer))
+                {
+                    let indent_str = self.block_indent.to_string_with_newline(self.config);
+                    self.push_str(&indent_str);
+                }
                 self.push_str("}");
             } else {
                 self.last_pos = mod_lo;
@@ -987,6 +1212,7 @@ impl<'b, 'a: 'b> FmtVisitor<'a> {
             config: self.config,
             inside_macro: Rc::new(Cell::new(false)),
             use_block: Cell::new(false),
+            indent_style: Cell::new(None),
             is_if_else_block: Cell::new(false),
             force_one_line_chain: Cell::new(false),
             snippet_provider: self.snippet_provider,
@@ -994,6 +1220,38 @@ impl<'b, 'a: 'b> FmtVisitor<'a> {
             report: self.report.clone(),
             skip_context: self.skip_context.clone(),
             skipped_range: self.skipped_range.clone(),
+            rewrite_hooks: Rc::clone(&self.rewrite_hooks),
+            rewrite_budget: Rc::clone(&self.rewrite_budget),
+        }
+    }
+}
+
+// Returns `true` if `snippet` has a line which only consists of a `//` comment holding the given
+// directive, e.g. `// rustfmt: off`.
+fn has_directive_comment(snippet: &str, directive: &str) -> bool {
+    snippet.lines().map(str::trim).any(|line| {
+        line.starts_with("//") && !line.starts_with("///") && line[2..].trim() == directive
+    })
+}
+
+// Returns the spans of the items or statements of `kind` which are visited one by one, so that a
+// parse error in one of them only leaves that one as it is.
+fn item_child_spans(kind: &ast::ItemKind) -> Vec<Span> {
+    match kind {
+        ast::ItemKind::Fn(_, _, _, Some(ref body)) => body.stmts.iter().map(|s| s.span).collect(),
+        ast::ItemKind::Mod(ref module) => module.items.iter().map(|i| i.span).collect(),
+        ast::ItemKind::Impl { ref items, .. } | ast::ItemKind::Trait(_, _, _, _, ref items) => {
+            items.iter().map(|i| i.span).collect()
+        }
+        _ => vec![],
+    }
+}
+
+fn assoc_item_child_spans(kind: &ast::AssocItemKind) -> Vec<Span> {
+    match kind {
+        ast::AssocItemKind::Fn(_, _, _, Some(ref body)) => {
+            body.stmts.iter().map(|s| s.span).collect()
         }
+        _ => vec![],
     }
 }
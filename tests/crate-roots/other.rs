@@ -0,0 +1,6 @@
+mod common;
+
+#[test]
+fn other() {
+    common::setup();
+}
@@ -5,14 +5,19 @@ use std::fs::remove_file;
 use std::path::Path;
 use std::process::Command;
 
-/// Run the rustfmt executable and return its output.
-fn rustfmt(args: &[&str]) -> (String, String) {
+/// Returns a command running the rustfmt executable of the local target dir.
+fn rustfmt_command() -> Command {
+    local_command("rustfmt")
+}
+
+/// Returns a command running the executable `name` of the local target dir.
+fn local_command(name: &str) -> Command {
     let mut bin_dir = env::current_exe().unwrap();
     bin_dir.pop(); // chop off test exe name
     if bin_dir.ends_with("deps") {
         bin_dir.pop();
     }
-    let cmd = bin_dir.join(format!("rustfmt{}", env::consts::EXE_SUFFIX));
+    let cmd = bin_dir.join(format!("{}{}", name, env::consts::EXE_SUFFIX));
 
     // Ensure the rustfmt binary runs from the local target dir.
     let path = env::var_os("PATH").unwrap_or_default();
@@ -20,12 +25,19 @@ fn rustfmt(args: &[&str]) -> (String, String) {
     paths.insert(0, bin_dir);
     let new_path = env::join_paths(paths).unwrap();
 
-    match Command::new(&cmd).args(args).env("PATH", new_path).output() {
+    let mut command = Command::new(&cmd);
+    command.env("PATH", new_path);
+    command
+}
+
+/// Run the rustfmt executable and return its output.
+fn rustfmt(args: &[&str]) -> (String, String) {
+    match rustfmt_command().args(args).output() {
         Ok(output) => (
             String::from_utf8(output.stdout).expect("utf-8"),
             String::from_utf8(output.stderr).expect("utf-8"),
         ),
-        Err(e) => panic!("failed to run `{:?} {:?}`: {}", cmd, args, e),
+        Err(e) => panic!("failed to run `rustfmt {:?}`: {}", args, e),
     }
 }
 
@@ -55,11 +67,28 @@ fn print_config() {
         starts_with("Unknown print-config option")
     );
     assert_that!(&["--print-config", "default"], contains("max_width = 100"));
+    assert_that!(
+        &["--print-config", "schema"],
+        contains("\"name\": \"max_width\"")
+    );
     assert_that!(&["--print-config", "minimal"], contains("PATH required"));
     assert_that!(
         &["--print-config", "minimal", "minimal-config"],
         contains("doesn't work with standard input.")
     );
+    assert_that!(
+        &["--print-config", "current", "/"],
+        contains("has no parent directory")
+    );
+    assert_that!(
+        &[
+            "--print-config",
+            "minimal",
+            "src/shape.rs",
+            "--config=max_width=80"
+        ],
+        contains("max_width = 80")
+    );
 
     let (stdout, stderr) = rustfmt(&[
         "--print-config",
@@ -106,3 +135,390 @@ fn inline_config() {
             && contains("format_strings = true")
     );
 }
+
+#[ignore]
+#[test]
+fn json_error_format() {
+    assert_that!(
+        &["--error-format=json", "does-not-exist.rs"],
+        contains(
+            "{\"level\":\"error\",\"message\":\"Error: file `does-not-exist.rs` does not exist\"}"
+        )
+    );
+    assert_that!(
+        &["--error-format=xml", "does-not-exist.rs"],
+        contains("Invalid value for `--error-format`")
+    );
+}
+
+#[ignore]
+#[test]
+fn check_quiet_only_prints_file_names() {
+    let (stdout, _) = rustfmt(&["--check", "--quiet", "tests/writemode/source/modified.rs"]);
+    assert_eq!(stdout, "tests/writemode/source/modified.rs\n");
+    assert_that!(
+        &["--check", "--diff-context=x", "src/shape.rs"],
+        contains("Invalid diff context: x")
+    );
+}
+
+#[ignore]
+#[test]
+fn check_diff_summary_prints_changed_line_counts() {
+    assert_that!(
+        &[
+            "--check",
+            "--diff-summary",
+            "tests/writemode/source/modified.rs"
+        ],
+        contains("tests/writemode/source/modified.rs: ") && contains(" lines differ")
+    );
+}
+
+#[ignore]
+#[test]
+fn explain_reports_changed_lines() {
+    assert_that!(
+        &["--explain", "brace_style=AlwaysNextLine", "src/shape.rs"],
+        contains("src/shape.rs: ")
+            && contains("Setting `brace_style` to `AlwaysNextLine` would change 1 files")
+    );
+    assert_that!(
+        &["--explain", "max_width", "src/shape.rs"],
+        contains("invalid key=val pair: `max_width`")
+    );
+}
+
+#[ignore]
+#[test]
+fn trace_decisions() {
+    assert_that!(
+        &["--check", "--trace-decisions", "src/shape.rs"],
+        contains("\"construct\":\"chain\"") && contains("\"tactic\":\"Horizontal\"")
+    );
+}
+
+#[ignore]
+#[test]
+fn out_dir_mirrors_input_tree() {
+    let out_dir = env::temp_dir().join(format!("rustfmt-out-dir-{}", std::process::id()));
+    let out_dir_str = out_dir.to_str().unwrap();
+    rustfmt(&[
+        "--out-dir",
+        out_dir_str,
+        "tests/writemode/source/modified.rs",
+    ]);
+    let written = out_dir.join("tests/writemode/source/modified.rs").exists();
+    let _ = std::fs::remove_dir_all(&out_dir);
+    assert!(written);
+
+    // The files skipped thanks to the format cache are written as they are.
+    let cache = env::temp_dir().join(format!("rustfmt-out-dir-cache-{}", std::process::id()));
+    let config = format!("format_cache={}", cache.to_str().unwrap());
+    let args = [
+        "--out-dir",
+        out_dir_str,
+        "--config",
+        &config,
+        "src/shape.rs",
+    ];
+    rustfmt(&args);
+    let _ = std::fs::remove_dir_all(&out_dir);
+    rustfmt(&args);
+    let written = out_dir.join("src/shape.rs").exists();
+    let _ = std::fs::remove_dir_all(&out_dir);
+    let _ = remove_file(&cache);
+    assert!(written);
+    assert_that!(
+        &["--check", "--out-dir", out_dir_str, "src/shape.rs"],
+        contains("`--out-dir` can only be used with `--emit files`")
+    );
+}
+
+#[ignore]
+#[test]
+fn timings() {
+    assert_that!(
+        &["--check", "--timings=json", "src/shape.rs"],
+        contains("{\"files\":[{\"name\":\"src/shape.rs\",\"format_time\":")
+            && contains("\"rewrite_fallbacks\":0}")
+    );
+    assert_that!(
+        &["--check", "--timings=xml", "src/shape.rs"],
+        contains("Invalid value for `--timings`: xml")
+    );
+}
+
+#[ignore]
+#[test]
+fn stdin_batch() {
+    let mut child = rustfmt_command()
+        .arg("--stdin-batch")
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    {
+        use std::io::Write;
+        let stdin = child.stdin.as_mut().unwrap();
+        stdin
+            .write_all(b"{\"path\":\"a.rs\",\"content\":\"fn main () {}\"}\nnot json\n")
+            .unwrap();
+    }
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].contains("\"formatted\":\"fn main() {}\\n\""));
+    assert!(lines[1].contains("Invalid request"));
+}
+
+#[ignore]
+#[test]
+fn stdin_batch_with_invalid_config() {
+    let dir = env::temp_dir().join(format!("rustfmt-stdin-batch-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("rustfmt.toml"), "max_width = \"wide\"\n").unwrap();
+    let request = format!(
+        "{{\"path\":{:?},\"content\":\"fn main () {{}}\"}}\n",
+        dir.join("a.rs").to_str().unwrap()
+    );
+
+    let mut child = rustfmt_command()
+        .arg("--stdin-batch")
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    {
+        use std::io::Write;
+        let stdin = child.stdin.as_mut().unwrap();
+        stdin.write_all(request.as_bytes()).unwrap();
+        stdin
+            .write_all(b"{\"path\":\"a.rs\",\"content\":\"fn main () {}\"}\n")
+            .unwrap();
+    }
+    let output = child.wait_with_output().unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<_> = stdout.lines().collect();
+    // The request with the invalid config fails on its own, and the batch goes on.
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].contains("\"formatted\":null"));
+    assert!(lines[0].contains("Invalid config"));
+    assert!(lines[1].contains("\"formatted\":\"fn main() {}\\n\""));
+}
+
+#[ignore]
+#[test]
+fn stdin_batch_with_ignored_path() {
+    let dir = env::temp_dir().join(format!("rustfmt-stdin-batch-ignore-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("generated")).unwrap();
+    std::fs::write(dir.join("rustfmt.toml"), "ignore = [\"generated\"]\n").unwrap();
+    let request = |path: &Path| {
+        format!(
+            "{{\"path\":{:?},\"content\":\"fn main () {{}}\"}}\n",
+            path.to_str().unwrap()
+        )
+    };
+
+    let mut child = rustfmt_command()
+        .arg("--stdin-batch")
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    {
+        use std::io::Write;
+        let stdin = child.stdin.as_mut().unwrap();
+        stdin
+            .write_all(request(&dir.join("generated/a.rs")).as_bytes())
+            .unwrap();
+        stdin
+            .write_all(request(&dir.join("b.rs")).as_bytes())
+            .unwrap();
+    }
+    let output = child.wait_with_output().unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<_> = stdout.lines().collect();
+    // The content of the ignored path is given back as it is.
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].contains("\"formatted\":\"fn main () {}\""));
+    assert!(lines[1].contains("\"formatted\":\"fn main() {}\\n\""));
+}
+
+#[ignore]
+#[test]
+fn files_from() {
+    let list = env::temp_dir().join(format!("rustfmt-files-from-{}", std::process::id()));
+    std::fs::write(&list, "src/shape.rs\0tests/writemode/source/modified.rs\0").unwrap();
+    let (stdout, _) = rustfmt(&["--check", "-l", "--files-from", list.to_str().unwrap()]);
+    let _ = remove_file(&list);
+    assert!(stdout.contains("modified.rs"));
+    assert!(!stdout.contains("shape.rs"));
+
+    let mut child = rustfmt_command()
+        .args(&["--check", "-l", "--files-from", "-"])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    {
+        use std::io::Write;
+        let stdin = child.stdin.as_mut().unwrap();
+        stdin
+            .write_all(b"src/shape.rs\ntests/writemode/source/modified.rs\n")
+            .unwrap();
+    }
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("modified.rs"));
+}
+
+#[ignore]
+#[test]
+fn files_reached_through_several_paths() {
+    let (stdout, _) = rustfmt(&[
+        "--check",
+        "-l",
+        "tests/writemode/source/modified.rs",
+        "./tests/writemode/source/../source/modified.rs",
+    ]);
+    assert_eq!(stdout.matches("modified.rs").count(), 1);
+
+    let (stdout, _) = rustfmt(&[
+        "--check",
+        "-l",
+        "--config",
+        "canonicalize_paths=false",
+        "./tests/writemode/source/modified.rs",
+    ]);
+    assert_eq!(stdout.trim_end(), "./tests/writemode/source/modified.rs");
+}
+
+#[ignore]
+#[test]
+fn git_rustfmt_staged_files() {
+    let dir = env::temp_dir().join(format!("rustfmt-git-staged-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let git = |args: &[&str]| {
+        let output = Command::new("git")
+            .args(args)
+            .current_dir(&dir)
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let git_rustfmt = || {
+        local_command("git-rustfmt")
+            .args(&["--staged", "--whole-files"])
+            .current_dir(&dir)
+            .output()
+            .unwrap()
+    };
+
+    git(&["init", "-q"]);
+    std::fs::write(dir.join("lib.rs"), "fn  main( ) {}\n").unwrap();
+    git(&["add", "lib.rs"]);
+    let formatted = git_rustfmt();
+    let staged = git(&["show", ":lib.rs"]);
+
+    // Errors are reported instead of panicking.
+    std::fs::write(dir.join("rustfmt.toml"), "required_version = \"0.0.1\"\n").unwrap();
+    std::fs::write(dir.join("other.rs"), "fn  main( ) {}\n").unwrap();
+    git(&["add", "other.rs"]);
+    let failed = git_rustfmt();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert_eq!(formatted.status.code(), Some(0));
+    assert_eq!(staged, "fn main() {}\n");
+    assert_eq!(failed.status.code(), Some(1));
+    assert!(String::from_utf8(failed.stderr)
+        .unwrap()
+        .contains("Couldn't format `other.rs`"));
+}
+
+#[ignore]
+#[test]
+fn format_workspace_packages() {
+    let (stdout, _) = rustfmt(&[
+        "--check",
+        "-l",
+        "--manifest-path",
+        "tests/workspace/Cargo.toml",
+    ]);
+    assert!(stdout.contains("lorem/src/lib.rs"));
+    assert!(stdout.contains("lorem/src/dolor.rs"));
+    assert!(stdout.contains("ipsum/src/main.rs"));
+
+    let (stdout, stderr) = rustfmt(&[
+        "--check",
+        "-l",
+        "--manifest-path",
+        "tests/workspace/Cargo.toml",
+        "--package",
+        "ipsum",
+    ]);
+    assert!(stdout.contains("ipsum/src/main.rs"));
+    assert!(!stdout.contains("lorem"));
+    // The package is formatted with its own edition.
+    assert!(!stderr.contains("error"));
+
+    assert_that!(
+        &[
+            "--check",
+            "--manifest-path",
+            "tests/workspace/Cargo.toml",
+            "--package",
+            "dolor"
+        ],
+        contains("package `dolor` is not a member of the workspace")
+    );
+
+    // Without a manifest path, the manifest of the current directory is used.
+    let output = rustfmt_command()
+        .args(&["--check", "-l", "--package", "lorem"])
+        .current_dir("tests/workspace/lorem")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("lorem/src/lib.rs"));
+    assert!(!stdout.contains("ipsum"));
+}
+
+#[ignore]
+#[test]
+fn format_directories() {
+    let dir = env::temp_dir().join(format!("rustfmt-directories-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("nested")).unwrap();
+    std::fs::create_dir_all(dir.join(".hidden")).unwrap();
+    // `.gitignore` files are only honoured inside a git repository.
+    Command::new("git")
+        .args(&["init", "-q"])
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    std::fs::write(dir.join(".gitignore"), "ignored.rs\n").unwrap();
+    std::fs::write(dir.join(".rustfmtignore"), "skipped.rs\n").unwrap();
+    for file in &[
+        "lib.rs",
+        "nested/nested.rs",
+        "ignored.rs",
+        "skipped.rs",
+        ".hidden.rs",
+        ".hidden/hidden.rs",
+    ] {
+        std::fs::write(dir.join(file), "fn  main( ) {}\n").unwrap();
+    }
+
+    let (stdout, _) = rustfmt(&["--check", "-l", dir.to_str().unwrap()]);
+    let _ = std::fs::remove_dir_all(&dir);
+    assert!(stdout.contains("lib.rs"));
+    assert!(stdout.contains("nested.rs"));
+    assert!(!stdout.contains("ignored.rs"));
+    assert!(!stdout.contains("skipped.rs"));
+    assert!(!stdout.contains("hidden"));
+}
@@ -0,0 +1,7 @@
+// Associated types with long generics and bounds
+
+pub trait LongGenerics {
+    type LongGenericListTest<'a, 'b, 'c, 'd, LONGPARAMETERNAME, LONGPARAMETERNAME, LONGPARAMETERNAME, A, B, C>: Clone;
+
+    type Item<'a>: Iterator<Item = &'a Self::Element> + DoubleEndedIterator;
+}
@@ -0,0 +1,4 @@
+﻿// A byte order mark is kept
+
+fn   main()  {
+}
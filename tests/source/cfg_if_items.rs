@@ -0,0 +1,31 @@
+// Format the items inside `cfg_if!`.
+
+cfg_if! {
+if #[cfg(unix)] {
+    use  std::os::unix::ffi::OsStrExt ;
+      fn   platform( ) -> &'static str { "unix" }
+} else if #[cfg( windows )] {
+    // Windows needs its own conversion.
+    use std::os::windows::ffi::OsStrExt;
+    fn platform() -> &'static str {"windows"}
+} else {
+    fn platform()->&'static str{ "unknown" }
+}
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(feature = "std")] {
+        pub struct Foo { a : u32, b:u32 }
+    } else {
+    }
+}
+
+// Comments between the branches are kept by leaving the macro as is.
+cfg_if! {
+    if #[cfg(unix)] {
+        fn   foo() {}
+    } // unix
+    else {
+        fn   foo() {}
+    }
+}
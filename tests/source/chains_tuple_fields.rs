@@ -0,0 +1,9 @@
+// rustfmt-chain_tuple_indices_attached: true
+// rustfmt-use_small_heuristics: Off
+// Keep tuple indices next to what they index in vertical chains.
+
+fn main() {
+    let y = a.very .loooooooooooooooooooooooooooooooooooooong()  .chain()
+             .inside()          .weeeeeeeeeeeeeee()? .test()  .0 .1
+        .x;
+}
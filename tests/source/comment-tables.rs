@@ -0,0 +1,20 @@
+// rustfmt-wrap_comments: true
+// rustfmt-max_width: 50
+
+/// Options and their defaults, which are described in a rather long sentence:
+/// | Option              | Default | Stable |
+/// |---------------------|---------|--------|
+/// | max_width           | 100     | Yes    |
+fn foo() {}
+
+// Widths of the various kinds of things that are laid out:
+// kind        min     max     default value
+// fn          0       100     60
+fn bar() {}
+
+// rustfmt: keep-table
+// A long line which does not look like a table row at all, yet is kept
+// as it is.
+//
+// The table ends at the blank line, so this line which is too long gets wrapped.
+fn baz() {}
@@ -0,0 +1,9 @@
+// rustfmt-array_element_per_line_threshold: 8
+// Array element per line threshold
+
+const TABLE: [u8; 20] = [0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10, 0x11, 0x12, 0x13];
+
+fn main() {
+    let small = [1, 2, 3];
+    let bytes = vec![0u8, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22];
+}
@@ -0,0 +1,15 @@
+// rustfmt-closure_multiline_blocks: true
+
+fn main() {
+    result.and_then(|maybe_value| match maybe_value {
+        None => foo(),
+        Some(value) => bar(),
+    });
+
+    match lorem {
+        None => |ipsum| {
+            println!("Hello World");
+        },
+        Some(dolor) => foo(),
+    }
+}
@@ -0,0 +1,9 @@
+// rustfmt-closure_overflow_last_arg: false
+
+fn main() {
+    thread::spawn(|| {
+        foo();
+        bar();
+    });
+    items.iter().map(|x| x + 1);
+}
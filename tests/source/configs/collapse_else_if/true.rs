@@ -0,0 +1,36 @@
+// rustfmt-collapse_else_if: true
+
+fn main() {
+    if lorem {
+        println!("ipsum!");
+    } else {
+        if dolor {
+            println!("dolor!");
+        } else {
+            if sit {
+                println!("sit!");
+            }
+        }
+    }
+
+    if lorem {
+        println!("ipsum!");
+    } else {
+        // Only dolor is left.
+        if let Some(dolor) = dolor {
+            println!("{}", dolor);
+        }
+    }
+
+    // The trailing comment keeps the else block.
+    if lorem {
+        println!("ipsum!");
+    } else {
+        if dolor {
+            println!("dolor!");
+        }
+        // dolor
+    }
+
+    let x = if lorem { 1 } else { if dolor { 2 } else { 3 } };
+}
@@ -0,0 +1,7 @@
+// rustfmt-combine_control_expr_in_closures: false
+
+fn main() {
+    let f = |x| match x { Some(x) => x, None => 0 };
+    let g = |x| loop { break x; };
+    let h = |x| x + 1;
+}
@@ -0,0 +1,9 @@
+// rustfmt-combine_control_expr_in_return: false
+
+fn main() {
+    return match x { Some(x) => x, None => 0 };
+}
+
+fn foo() -> u32 {
+    return bar(x, y);
+}
@@ -0,0 +1,8 @@
+// rustfmt-escape_hex_case: Upper
+// Escape hex case
+
+fn main() {
+    let a = '\x1b';
+    let b = "\u{1f600} \\xab";
+    let c = b"\xfe";
+}
@@ -0,0 +1,8 @@
+// rustfmt-float_exponent_case: Lower
+// Float exponent case
+
+fn main() {
+    let a = 1E10;
+    let b = 2.5E-3f64;
+    let c = 0xE;
+}
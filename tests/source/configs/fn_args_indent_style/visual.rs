@@ -0,0 +1,6 @@
+// rustfmt-fn_args_indent_style: Visual
+// Function parameters are visually indented, the body is block indented
+
+fn lorem(ipsum: usize, dolor: usize, sit: usize, amet: usize, consectetur: usize, adipiscing: usize) -> usize {
+    foo(aaaaaaaaaaaaaaaaaaaaaaaaaaaaa, bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb, ccccccccccccccccccccccccccc)
+}
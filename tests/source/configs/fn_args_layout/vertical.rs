@@ -14,3 +14,7 @@ trait Lorem {
         // body
     }
 }
+
+extern "C" {
+    fn lorem(ipsum: Ipsum, dolor: Dolor, sit: Sit, amet: Amet, consectetur: onsectetur, ...);
+}
@@ -0,0 +1,7 @@
+// rustfmt-fn_call_width: 20
+// Function call width
+
+fn main() {
+    lorem("lorem", "ipsum", "dolor");
+    lorem("lorem", "ipsum");
+}
@@ -0,0 +1,9 @@
+// rustfmt-fn_params_force_multiline_threshold: 2
+
+fn two(a: u8, b: u8) {}
+
+fn three(a: u8, b: u8, c: u8) {}
+
+impl Foo {
+    fn method(&self, a: u8, b: u8) -> u8 { a }
+}
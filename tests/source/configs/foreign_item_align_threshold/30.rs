@@ -0,0 +1,21 @@
+// rustfmt-foreign_item_align_threshold: 30
+// Align foreign items
+
+extern "C" {
+    fn abs(x: c_int) -> c_int;
+    fn strlen(s: *const c_char)  ->  size_t;
+    fn exit(status: c_int) -> !;
+    fn free(ptr: *mut c_void);
+    static errno : c_int;
+    static mut environ: *mut *mut c_char;
+    #[link_name = "stdin"]
+    static mut STDIN: *mut FILE;
+}
+
+// The signatures of the functions differ by more than the threshold.
+extern "C" {
+    fn abs(x: c_int) -> c_int;
+    fn memcpy(dest: *mut c_void, src: *const c_void, n: size_t) -> *mut c_void;
+    static errno : c_int;
+    static mut environ: *mut *mut c_char;
+}
@@ -0,0 +1,6 @@
+// rustfmt-format_doc_attributes: false
+// rustfmt-format_strings: true
+// Format doc attributes
+
+#[doc = "Documentation which is too long to fit on one line written as a doc attribute, unsurprisingly."]
+fn foo() {}
@@ -0,0 +1,6 @@
+// rustfmt-format_doc_attributes: true
+// Format doc attributes
+
+#[doc = "Documentation which is too long to fit on one line written as a doc attribute, unsurprisingly."]
+#[doc = "Short documentation."]
+fn foo() {}
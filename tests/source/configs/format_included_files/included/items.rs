@@ -0,0 +1 @@
+fn  foo( x:u32 )->u32 { x+1 }
@@ -0,0 +1,5 @@
+// rustfmt-format_included_files: true
+
+include!("included/items.rs");
+
+fn main() {}
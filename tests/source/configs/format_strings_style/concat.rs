@@ -0,0 +1,7 @@
+// rustfmt-format_strings: true
+// rustfmt-format_strings_style: Concat
+
+fn main() {
+    let lorem = "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore et dolore magna aliqua.";
+    let ipsum = "short";
+}
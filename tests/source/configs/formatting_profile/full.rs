@@ -0,0 +1,9 @@
+// rustfmt-formatting_profile: Full
+// Full formatting profile
+
+fn main() {
+  let z=1;
+  let x=foo(a,
+            b);
+    let y = some_function_with_a_long_name(first_argument, second_argument, third_argument, fourth_argument);
+}
@@ -0,0 +1,8 @@
+// rustfmt-group_lifetime_params: false
+// Group lifetime parameters
+
+struct Lorem<'a, 'b, 'c, Ipsum: Dolor + Sit + Amet, Consectetur: Adipiscing + Elit, const SIZE: usize> {
+    ipsum: &'a Ipsum,
+}
+
+fn lorem<'a, T>(t: &'a T) {}
@@ -0,0 +1,13 @@
+// rustfmt-group_literal_digits: true
+// Group literal digits
+
+fn main() {
+    let a = 1000;
+    let b = 100;
+    let c = 12_34567u64;
+    let d = 1234567.891e10;
+    let e = 0xdeadbeef;
+    let f = 0b1111000011110000;
+    let g = 0o7777777;
+    let h = 1_000_000_f32;
+}
@@ -0,0 +1,9 @@
+// rustfmt-hex_escape_style: Hex
+// Hex escape style
+
+fn main() {
+    let a = '\u{1b}';
+    let b = "\u{7F}\u{80}\u{1_f}\\u{1b}";
+    let c = b'\x7f';
+    let d = br"\u{1b}";
+}
@@ -0,0 +1,8 @@
+// rustfmt-hex_escape_style: Unicode
+// Unicode escape style
+
+fn main() {
+    let a = '\x1b';
+    let b = "\x1B[0m\\x1b";
+    let c = b"\xff\x1b";
+}
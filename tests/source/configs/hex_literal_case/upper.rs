@@ -0,0 +1,10 @@
+// rustfmt-hex_literal_case: Upper
+// Hexadecimal literal case
+
+fn main() {
+    let a = 0xdeadbeef;
+    let b = 0xAbC_dEfu32;
+    let c = 0x1f32;
+    let d = 0b1010;
+    let e = 12e3;
+}
@@ -0,0 +1,19 @@
+// rustfmt-match_arm_leading_pipes: Always
+// rustfmt-version: Two
+
+fn nested() {
+    match foo {
+        Some(Aaaaaaaaaaaaaaaaaaaaaaaa | Bbbbbbbbbbbbbbbbbbbbbbbbbbbb | Cccccccccccccccccccccccccccccccc) => {}
+        _ => {}
+    }
+
+    let (Ok(aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa) | Err(aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa), b) = foo;
+
+    if let Aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa(x) | Bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb(x) | Ccccccccccccc(x) = foo {
+        bar(x);
+    }
+
+    while let Some(Aaaaaaaaaaaaaaaaaaaaaaaa | Bbbbbbbbbbbbbbbbbbbbbbbbbbbb | Cccccccccccccccccccccccccccccccc) = foo {
+        bar();
+    }
+}
@@ -26,3 +26,22 @@ fn bar() {
         _ => {}
     }
 }
+
+fn nested() {
+    match foo {
+        Some(Aaaaaaaaaaaaaaaaaaaaaaaa | Bbbbbbbbbbbbbbbbbbbbbbbbbbbb | Cccccccccccccccccccccccccccccccc) => {}
+        _ => {}
+    }
+
+    let (Ok(aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa) | Err(aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa), b) = foo;
+}
+
+fn let_patterns() {
+    if let Aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa(x) | Bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb(x) | Ccccccccccccc(x) = foo {
+        bar(x);
+    }
+
+    while let Aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa(x) | Bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb(x) | Ccccccc(x) = foo {
+        bar(x);
+    }
+}
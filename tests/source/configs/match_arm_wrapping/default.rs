@@ -0,0 +1,12 @@
+// rustfmt-match_arm_wrapping: Default
+// Wrap match-arms
+
+fn main() {
+    match lorem {
+        A => foo(),
+        B => some_function_name(argument_number_one, argument_number_two, argument_number_three, argument_number_four),
+        C => { bar() }
+        D => Ipsum { dolor: dolor_value, sit: sit_value, amet: amet_value, consectetur: consectetur_value },
+        E => vec![element_number_one, element_number_two, element_number_three, element_number_four],
+    }
+}
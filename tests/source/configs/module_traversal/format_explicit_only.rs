@@ -0,0 +1,6 @@
+// rustfmt-module_traversal: FormatExplicitOnly
+
+#[path = "mods/unformatted.rs"]
+mod unformatted;
+
+fn  main( ) {}
@@ -0,0 +1,5 @@
+// rustfmt-module_traversal: FormatRoots
+
+mod void;
+
+fn  main( ) {}
@@ -0,0 +1 @@
+fn  skip_formatting_this( ) {}
@@ -0,0 +1,13 @@
+// rustfmt-move_closure_own_line_threshold: 3
+
+fn main() {
+    thread::spawn(move || {
+        foo();
+        bar();
+    });
+    thread::spawn(|| {
+        foo();
+        bar();
+    });
+    thread::spawn(move || foo());
+}
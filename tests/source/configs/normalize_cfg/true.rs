@@ -0,0 +1,15 @@
+// rustfmt-normalize_cfg: true
+// Normalize cfg predicates
+
+#[cfg(any(windows, target_os = "macos", unix, windows))]
+fn foo() {}
+
+#[cfg(all(not(any(unix, target_os = "wasi")), feature = "std"))]
+fn bar() {}
+
+#[cfg_attr(any(b, a), derive(Debug))]
+#[cfg(not(all(test, debug_assertions)))]
+struct Baz;
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd"))]
+fn qux() {}
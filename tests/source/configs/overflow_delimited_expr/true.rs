@@ -0,0 +1,13 @@
+// rustfmt-overflow_delimited_expr: true
+
+fn calls() {
+    foo(ctx, Bar { aaaaaaaaaaaa: 1, bbbbbbbbbbbbbbbb: 2, cccccccccccccccc: 3, dddddddddd: 4 });
+    foo(ctx, &[aaaaaaaaaaaaaaaa, bbbbbbbbbbbbbbbbb, cccccccccccccccc, dddddddddddddd, eeeeeeee]);
+    foo(ctx, |x| { let y = aaaaaaaaaaaaaaaaaaaa(x); bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb(y) });
+}
+
+fn macros() {
+    foo!(ctx, Bar { aaaaaaaaaaaa: 1, bbbbbbbbbbbbbbbb: 2, cccccccccccccccc: 3, dddddddddd: 4 });
+    foo!(ctx, vec![aaaaaaaaaaaaaaaa, bbbbbbbbbbbbbbbbb, cccccccccccccccc, dddddddddddd, eeeeeeee]);
+    foo!(ctx, |x| { let y = aaaaaaaaaaaaaaaaaaaa(x); bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb(y) });
+}
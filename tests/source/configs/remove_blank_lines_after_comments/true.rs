@@ -0,0 +1,22 @@
+// rustfmt-remove_blank_lines_after_comments: true
+// Remove blank lines after comments, but not after the file header or trailing comments
+
+fn foo() {
+    // Compute the answer.
+
+    let x = 42;
+
+
+    // First comment.
+
+    // Second comment.
+
+
+    bar(x);
+    /* block comment */
+
+    baz();
+    qux(x); // A trailing comment.
+
+    quux();
+}
@@ -0,0 +1,13 @@
+// rustfmt-reorder_foreign_items: true
+// Reorder foreign items along with their comments and the blank lines before them
+
+extern "C" {
+    fn write(fd: c_int, buf: *const c_void, count: size_t) -> ssize_t; // Writes.
+    fn close(fd: c_int) -> c_int;   /* Closes. */
+
+    // Reads from a file descriptor.
+    fn read(fd: c_int, buf: *mut c_void, count: size_t) -> ssize_t;
+
+
+    static errno: c_int;
+}
@@ -0,0 +1,10 @@
+// rustfmt-reorder_foreign_items: false
+// Reorder foreign items
+
+extern "C" {
+    fn write(fd: c_int, buf: *const c_void, count: size_t) -> ssize_t;
+
+    // Reads from a file descriptor.
+    fn read(fd: c_int, buf: *mut c_void, count: size_t) -> ssize_t;
+    static  errno : c_int;
+}
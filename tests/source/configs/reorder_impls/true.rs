@@ -0,0 +1,26 @@
+// rustfmt-reorder_impls: true
+// Reorder impls
+
+/// Displays a bar.
+#[cfg(feature = "display")]
+impl fmt::Display for Bar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "bar")
+    }
+}
+
+unsafe impl Send for Foo {}
+
+impl !Sync for Foo {}
+
+impl Foo {
+    fn new() -> Foo {
+        Foo
+    }
+}
+
+impl Clone for Foo {
+    fn clone(&self) -> Foo { Foo }
+}
+
+impl !Send for Bar {}
@@ -0,0 +1,8 @@
+// rustfmt-reorder_imports_in_group: false
+// Reorder imports in groups
+
+use std::io;
+use lorem;
+
+use ipsum;
+use dolor;
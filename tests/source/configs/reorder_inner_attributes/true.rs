@@ -0,0 +1,11 @@
+// rustfmt-reorder_inner_attributes: true
+// Feature gates before other inner attributes
+
+//! Crate docs.
+
+#![deny(missing_docs)]
+#![feature(rustc_private)]
+#![allow(dead_code)]
+#![feature(box_syntax)]
+
+fn main() {}
@@ -0,0 +1,37 @@
+// rustfmt-return_style: Explicit
+// Convert the tail expression ending a function into a return
+
+fn lit() -> u32 {
+    1
+}
+
+fn call(x: u32) -> u32 {
+    foo(x, x + 1)
+}
+
+fn block_like(x: bool) -> u32 {
+    if x {
+        1
+    } else {
+        0
+    }
+}
+
+fn already() -> u32 {
+    return 1;
+}
+
+fn closure() -> u32 {
+    let f = |x: u32| { x + 1 };
+    f(1)
+}
+
+fn unit() {
+    foo()
+}
+
+// The temporary `Ref` would be dropped before `extra` rather than after it.
+fn borrowed(cell: &RefCell<Vec<u32>>) -> usize {
+    let extra = 1;
+    cell.borrow().len() + extra
+}
@@ -0,0 +1,52 @@
+// rustfmt-return_style: Implicit
+// Convert a return ending a function into a tail expression
+
+fn lit() -> u32 {
+    return 1;
+}
+
+fn binary(x: u32) -> u32 {
+    return x * 2 + 1
+}
+
+fn local(x: u32) -> u32 {
+    let y = x + 1;
+    return y;
+}
+
+fn borrow(x: u32) -> usize {
+    let cell = RefCell::new(vec![x]);
+    return cell.borrow().len();
+}
+
+fn block_like(x: bool) -> u32 {
+    return match x {
+        true => 1,
+        false => 0,
+    };
+}
+
+fn starts_with_block_like(x: bool) -> u32 {
+    return match x {
+        true => 1,
+        false => 0,
+    } + 1;
+}
+
+fn nested() -> u32 {
+    fn inner() -> u32 {
+        return 2;
+    }
+    return inner();
+}
+
+fn unit() {
+    return;
+}
+
+fn early(x: u32) -> u32 {
+    if x > 1 {
+        return x;
+    }
+    return 0;
+}
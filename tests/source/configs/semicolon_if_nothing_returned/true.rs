@@ -0,0 +1,40 @@
+// rustfmt-semicolon_if_nothing_returned: true
+// Remove redundant semicolons and add semicolons after assignments
+
+fn foo(x: &mut u32) -> u32 {
+    fn bar() {};
+    struct Baz {};
+    if *x > 1 {
+        bar();
+    } else if *x > 0 {
+        bar();
+    } else {
+        let y = 1;
+    };
+    {
+        bar();
+    };
+    unsafe {
+        bar();
+    };
+    if *x > 1 {
+        *x
+    } else {
+        0
+    };
+    'a: {
+        bar();
+    };
+    *x += 1;
+    *x
+}
+
+fn assign(x: &mut u32, y: u32) {
+    *x = y
+}
+
+fn assign_op(x: &mut u32) {
+    if *x > 1 {
+        *x -= 1
+    }
+}
@@ -0,0 +1,11 @@
+// rustfmt-skip_macro_invocations: html
+
+fn main() {
+    let page = html! {
+        <div   class="page">
+            { title }
+        </div>
+    };
+    let  v = vec![1,2,  3];
+    html!(<p>{  "text"  }</p>);
+}
@@ -0,0 +1,10 @@
+// rustfmt-hard_tabs: true
+// rustfmt-smart_tabs: false
+// No smart tabs
+
+fn main() {
+	foo! {
+		bar(a,
+		    b)
+	}
+}
@@ -0,0 +1,12 @@
+// rustfmt-hard_tabs: true
+// rustfmt-smart_tabs: true
+// Smart tabs
+
+fn main() {
+	let a = 1; // one
+	           // two
+	foo! {
+		bar(a,
+		    b)
+	}
+}
@@ -0,0 +1,10 @@
+// rustfmt-sort_derives: true
+// Sort derives
+
+#[derive(Eq, PartialEq)]
+#[derive(Debug)]
+#[derive(serde::Serialize, Copy, Clone)]
+pub enum Foo {}
+
+#[derive(Serialize, Deserialize, PartialOrd, Ord, PartialEq, Eq, Hash, Default, Debug, Copy, Clone)]
+pub struct Bar;
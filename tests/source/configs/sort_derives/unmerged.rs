@@ -0,0 +1,8 @@
+// rustfmt-sort_derives: true
+// rustfmt-merge_derives: false
+// Sort derives without merging them
+
+#[derive(Eq, PartialEq)]
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+pub enum Foo {}
@@ -0,0 +1,10 @@
+// rustfmt-space_after_colon: false
+// No space after colon
+
+fn lorem<T: Eq, const N: usize>(t: T) {
+    let ipsum: Dolor = sit;
+    let Lorem { ipsum: dolor, sit } = lorem;
+    let amet = consectetur: Adipiscing;
+}
+
+const LOREM: Lorem = Lorem { ipsum: dolor, sit: amet };
@@ -9,3 +9,8 @@ const LOREM : Lorem = Lorem {
     ipsum : dolor,
     sit : amet,
 };
+
+fn dolor<const N: usize>() {
+    let Lorem { ipsum: dolor, sit } = lorem;
+    let amet = consectetur: Adipiscing;
+}
@@ -0,0 +1,10 @@
+// rustfmt-spaces_within_angle_brackets: true
+
+fn lorem<T: Eq, U>(t: T) -> Result<T, U> {
+    let ipsum: Vec<u8> = Vec::<u8>::new();
+    let dolor: Vec<u8> = Vec::new();
+}
+
+struct Lorem<'a, T> {
+    ipsum: &'a T,
+}
@@ -0,0 +1,8 @@
+// rustfmt-spaces_within_brackets: true
+
+fn lorem(ipsum: [u8; 4], dolor: &[u8]) {
+    let sit = [1, 2, 3];
+    let amet = sit[0];
+    let consectetur = [0u8; 16];
+    let empty: [u8; 0] = [];
+}
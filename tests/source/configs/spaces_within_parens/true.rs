@@ -0,0 +1,12 @@
+// rustfmt-spaces_within_parens: true
+
+fn lorem(ipsum: u8) -> (u8, u8) {
+    let dolor = (ipsum + 1) * 2;
+    sit(dolor, ipsum)
+}
+
+fn amet() {
+    let (a, b) = lorem(1);
+    consectetur();
+    adipiscing(|x| x + 1);
+}
@@ -0,0 +1,7 @@
+// rustfmt-spaces_within_struct_lit_braces: false
+
+fn main() {
+    let lorem = Lorem { ipsum: 1, dolor };
+    let Lorem { ipsum, .. } = lorem;
+    let sit = Sit {};
+}
@@ -0,0 +1,5 @@
+﻿// rustfmt-strip_bom: false
+// Keep the byte order mark
+
+fn   main()  {
+}
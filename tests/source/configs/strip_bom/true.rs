@@ -0,0 +1,5 @@
+﻿// rustfmt-strip_bom: true
+// Strip the byte order mark
+
+fn   main()  {
+}
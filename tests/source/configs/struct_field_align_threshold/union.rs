@@ -0,0 +1,9 @@
+// rustfmt-struct_field_align_threshold: 20
+// Align the fields of unions like those of structs
+
+#[repr(C, packed)]
+pub union Lorem {
+    ipsum: u32,
+    dolor_sit: [u8;4],
+    amet  :  f32
+}
@@ -0,0 +1,7 @@
+// rustfmt-struct_lit_force_multiline_fields: 2
+
+fn main() {
+    let a = Point { x: 0, y: 0 };
+    let b = Point3 { x: 0, y: 0, z: 0 };
+    let c = Point3 { x: 0, ..p };
+}
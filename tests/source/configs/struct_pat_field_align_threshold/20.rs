@@ -0,0 +1,9 @@
+// rustfmt-struct_pat_field_align_threshold: 20
+
+fn main() {
+    let Lorem { ipsum: a, dolor_sit: b, amet } = lorem;
+    let Foo { x: a, yy: b } = foo;
+    match lorem {
+        Lorem { ipsum: Some(a), dolor_sit: None, .. } => {}
+    }
+}
@@ -0,0 +1,6 @@
+// rustfmt-struct_pat_force_multiline_fields: 2
+
+fn main() {
+    let Foo { a, b } = foo;
+    let Foo { a, b, c } = foo;
+}
@@ -0,0 +1,6 @@
+// rustfmt-struct_pat_rest_own_line: true
+
+fn main() {
+    let Lorem { ipsum, dolor, sit, amet, consectetur, adipiscing, .. } = lorem;
+    let Foo { a, .. } = foo;
+}
@@ -0,0 +1,18 @@
+// rustfmt-trailing_comment_align_threshold: 20
+
+fn main() {
+    let lorem = 1; // lorem
+    let ipsum_dolor = 2;   // ipsum
+    let sit = ipsum_dolor * 100; // sit
+
+    let amet = "// not a comment"; // amet
+    let consectetur = 3; // consectetur
+    // A comment on its own line ends the run.
+    let adipiscing = 4; // adipiscing
+    let elit_sed_do_eiusmod_tempor_incididunt_ut_labore = 5; // elit
+}
+
+struct Lorem {
+    ipsum: u8, // ipsum
+    dolor_sit: u16, // dolor sit
+}
@@ -0,0 +1,12 @@
+// rustfmt-verify_comments: true
+// rustfmt-normalize_comments: true
+// Comments are kept when they are moved or normalized.
+
+use b; // After b
+use a;
+
+fn main() {
+    /* Normalized */
+    let x = foo( /* first */ a,  b /* second */ );
+    let s = "// not a comment";
+}
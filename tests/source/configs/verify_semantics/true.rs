@@ -0,0 +1,11 @@
+// rustfmt-verify_semantics: true
+// Files are formatted as usual when their meaning doesn't change.
+
+use std::io::{Write, Read};
+use std::{fmt};
+
+fn main() {
+    let x = ((1 + 2)) * 3;
+    let y = foo(x,
+        "text");
+}
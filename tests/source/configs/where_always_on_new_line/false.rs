@@ -0,0 +1,6 @@
+// rustfmt-where_always_on_new_line: false
+// Where on a new line
+
+fn lorem<Ipsum: Eq, Dolor: Eq>(ipsum: Ipsum, dolor: Dolor, sit: Sit, amet: Amet, consectetur: Consectetur) where Ipsum: Dolor {
+    // body
+}
@@ -0,0 +1,6 @@
+// rustfmt-where_compressed: false
+// Compressed where-clauses
+
+fn lorem<Ipsum, Dolor>() -> T where Ipsum: Eq, Dolor: Eq {
+    // body
+}
@@ -0,0 +1,19 @@
+// rustfmt-where_compressed: true
+// Compressed where-clauses
+
+fn lorem<Ipsum, Dolor>() -> T where Ipsum: Eq, Dolor: Eq {
+    // body
+}
+
+fn lorem<Ipsum, Dolor>() -> T where Ipsum: Eqqqqqqqqqqqqqqqqqqqqqqqqq + Cloneeeeeeeeeeeeeeeeeeeeeeeeee + Debuggggggggggggg, Dolor: Eqqqqqqqqqqqqqqqqqqqqqqqqq + Cloneeeeeeeeeeeeeeeeeeeeeeeeee {
+    // body
+}
+
+fn lorem<Ipsum, Dolor>() -> T
+where
+    // comment
+    Ipsum: Eq,
+    Dolor: Eq,
+{
+    // body
+}
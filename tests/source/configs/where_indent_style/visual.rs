@@ -0,0 +1,10 @@
+// rustfmt-where_indent_style: Visual
+// Where-clauses are visually indented, the parameters are block indented
+
+fn lorem<T>(ipsum: T) -> T where T: Aaaaaaaaaaaaaaaaaaaa + Bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb + Cccccccccccccccccccc {
+    ipsum
+}
+
+fn dolor<T>(sit: T, amet: T, consectetur: T, adipiscing: T, elit: T, sed: T, eiusmod: T) where T: Tempor {
+    sit
+}
@@ -24,3 +24,7 @@ fn lorem<Ipsum, Dolor, Sit, Amet>() -> T where Ipsum: Eq {
 }
 
 unsafe impl Sync for Foo where (): Send {}
+
+trait Lorem<T> where T: Ipsum {
+    fn dolor(&self);
+}
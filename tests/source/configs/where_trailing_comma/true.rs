@@ -0,0 +1,10 @@
+// rustfmt-where_trailing_comma: true
+// Trailing comma in where-clauses
+
+fn lorem<Ipsum, Dolor>() -> T where Ipsum: Eq, Dolor: Eq, {
+    // body
+}
+
+struct Lorem<Ipsum, Dolor> where Ipsum: Eq, Dolor: Eq {
+    ipsum: Ipsum,
+}
@@ -0,0 +1,16 @@
+// Blocks in array lengths, repeat counts and const generic arguments
+
+fn foo<const M: usize>() -> [u8; {   M * 2  }] {
+    [0; {
+        M * 2
+    }]
+}
+
+fn main() {
+    let x: [u8; {N+1}] = [0; {N+1}];
+    foo::<{N}>();
+    foo::<{
+        N + 1
+    }>();
+    let y: Matrix<u8, { ROWS*2 }, {COLS}>;
+}
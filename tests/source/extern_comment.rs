@@ -0,0 +1,12 @@
+// Comments between the `extern` keyword and the opening brace
+
+extern "C" /* libc */ {
+    fn abs(x: c_int) -> c_int;
+}
+
+extern   // the platform ABI
+{
+    fn labs(x: c_long) -> c_long;
+}
+
+extern "C"   /* nothing */  {}
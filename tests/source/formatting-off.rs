@@ -0,0 +1,20 @@
+// Statements between `// rustfmt: off` and `// rustfmt: on` are kept as they are.
+
+fn main() {
+    let a  =  1;
+    // rustfmt: off
+    let matrix = [
+        1, 0, 0,
+        0, 1, 0,
+        0, 0, 1,
+    ];
+    foo(  a,b  );
+    // rustfmt: on
+    let b  =  2;
+}
+
+fn no_on() {
+    // rustfmt: off
+    let   x = 1;
+    let   y = 2;
+}
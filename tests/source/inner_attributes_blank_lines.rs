@@ -0,0 +1,7 @@
+// Blank lines between inner attributes are only kept with `version = Two`.
+
+#![feature(rustc_private)]
+
+#![deny(missing_docs)]
+
+fn main() {}
@@ -0,0 +1,20 @@
+// Labeled loops and blocks, and `break`/`continue` referring to them
+
+fn main() {
+    let x = 'a: {
+        if foo() { break 'a 1; }
+        2
+    };
+
+    let result = 'outer: loop {
+        'inner: for i in 0..10 {
+            if i == 5 { continue 'outer; }
+            if i == 7 { break 'inner; }
+        }
+        break   'outer   compute_something_with_a_long_name(first_argument, second_argument, third);
+    };
+
+    'search: while let Some(aaaaaaaaaaaaaaaaaaaaa) = iterator_with_a_very_long_name.next_element() {
+        break 'search;
+    }
+}
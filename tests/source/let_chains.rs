@@ -0,0 +1,18 @@
+// Chains of `let` conditions
+
+fn main() {
+    if let Some(a) = foo()  &&  let Some(b) = bar(a) {
+        baz(a, b);
+    }
+
+    if let Some(aaaaaaaaaaaaaaaaaa) = fooooooooooooooooooooo() && let Some(bbbbbbbbbbbbbbbbbbbbbbb) = baaaaaaaar(aaaaaaaaaaaaaaaaaa) && cccccccccccc {
+        baz(a, b);
+    }
+
+    while let Some(a) = foo() && a>0 {}
+
+    match x {
+        Some(a) if let Some(b)=a.get() => {}
+        _ => {}
+    }
+}
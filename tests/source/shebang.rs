@@ -0,0 +1,3 @@
+#!/usr/bin/env rust-script
+
+fn main(){println!("hello");}
@@ -0,0 +1,11 @@
+#!/usr/bin/env rust-script
+// rustfmt-version: Two
+//! Crate docs.
+
+#![feature(rustc_private)]
+#![feature(box_syntax)]
+
+#![deny(missing_docs)]
+#![allow(dead_code)]
+
+fn main(){}
@@ -0,0 +1,47 @@
+// Skip attributes on statements, match arms, fields and imports, in their path, legacy and
+// `cfg_attr` forms.
+
+#[rustfmt::skip]
+use   c::{f,e};
+#[cfg_attr(rustfmt, rustfmt::skip)]
+use   b::{d,c};
+use   a::{c,b};
+
+struct Foo {
+    #[rustfmt::skip]
+    a:   u32,
+    #[cfg_attr(rustfmt, rustfmt::skip)]
+    b:   u32,
+    #[rustfmt_skip]
+    c:   u32,
+    d:   u32,
+}
+
+fn main() {
+    #[rustfmt::skip]
+    let x   =   1;
+    #[cfg_attr(rustfmt, allow(unused), rustfmt::skip)]
+    let y   =   2;
+    let z   =   3;
+
+    #[::rustfmt::skip]
+    foo(  x,   y  );
+    foo(  y,   z  );
+
+    let foo = Foo {
+        #[rustfmt::skip]
+        a:   x,
+        #[cfg_attr(rustfmt, rustfmt::skip)]
+        b:   y,
+        c:   z,
+        d:   z,
+    };
+
+    match foo.a {
+        #[rustfmt::skip]
+        0   =>   x,
+        #[cfg_attr(rustfmt, rustfmt::skip)]
+        1   =>   {   y   }
+        _   =>   z,
+    };
+}
@@ -0,0 +1,14 @@
+// rustfmt-version: Two
+
+fn main() {
+    match x {
+        [ first , rest @ .. ] => {}
+        [.., last] | [last] => {}
+        box [a,b, ..] => {}
+        &[ref mut head, ref tail @ ..] => {}
+        [aaaaaaaaaaaaaaaaaaaaaaaaa, bbbbbbbbbbbbbbbbbbbbbbbbbbb, cccccccccccccccccccccccccccccc, ..] => {}
+        [] => {}
+    }
+
+    let [first, middle @ .., last] = array;
+}
@@ -0,0 +1,15 @@
+// Packed unions and unions within functions are formatted like structs.
+
+#[repr(C, packed)]
+union   Packed<T: Copy>   {   a: T,b : [u8;  4]   }
+
+#[repr(packed(2))]
+pub(crate) union Packed2<T> where T: Copy {
+    a: T,
+    #[cfg(feature = "b")] b: u16,
+}
+
+fn main() {
+    union Local { a: u8 }
+    let union = Local { a: 1 };
+}
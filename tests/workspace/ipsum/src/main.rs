@@ -0,0 +1,4 @@
+// `async` is only an identifier in the 2015 edition.
+fn main( ) {
+    let async = 1;
+}
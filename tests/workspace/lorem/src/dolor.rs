@@ -0,0 +1,2 @@
+pub fn dolor( ) {
+}
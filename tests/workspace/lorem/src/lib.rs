@@ -0,0 +1,4 @@
+mod dolor;
+
+pub fn lorem( ) {
+}